@@ -1,7 +1,11 @@
 use std::convert::TryFrom;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
 
 use hex::FromHex;
 use num_bigint::BigUint;
+use num_traits::ToPrimitive;
 use sodiumoxide::randombytes::randombytes;
 use thiserror::Error;
 
@@ -61,16 +65,62 @@ impl FromHex for ProofOfWork {
 
 impl ProofOfWork {
     pub fn generate(public_key: &PublicKey, target: f64) -> Self {
+        let target_number = make_target(target);
+        Self::generate_until(public_key, &target_number, &AtomicBool::new(false))
+            .expect("not stopped")
+    }
+
+    /// Generates the proof of work using `threads` threads, each searching from a random nonce.
+    pub fn generate_parallel(public_key: &PublicKey, target: f64, threads: usize) -> Self {
+        if threads <= 1 {
+            return Self::generate(public_key, target);
+        }
+
+        let target_number = Arc::new(make_target(target));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::channel();
+
+        let handles = (0..threads)
+            .map(|_| {
+                let (public_key, target_number, stop) =
+                    (public_key.clone(), target_number.clone(), stop.clone());
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    if let Some(pow) = Self::generate_until(&public_key, &target_number, &stop) {
+                        let _ = tx.send(pow);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        // only the searching threads hold a sender, so `recv` fails if they all panic
+        drop(tx);
+
+        let pow = rx.recv().expect("searching threads are running");
+        stop.store(true, Ordering::Relaxed);
+        for handle in handles {
+            let _ = handle.join();
+        }
+        pow
+    }
+
+    /// Searches for the proof of work, returns `None` if `stop` is set by another searcher.
+    fn generate_until(
+        public_key: &PublicKey,
+        target_number: &BigUint,
+        stop: &AtomicBool,
+    ) -> Option<Self> {
         let mut data = [0; CRYPTO_KEY_SIZE + POW_SIZE];
         data[..CRYPTO_KEY_SIZE].clone_from_slice(public_key.as_ref().as_ref());
         data[CRYPTO_KEY_SIZE..].clone_from_slice(randombytes(POW_SIZE).as_ref());
 
-        let target_number = make_target(target);
         loop {
-            if let Ok(()) = check_proof_of_work_inner(data.as_ref(), &target_number) {
+            if stop.load(Ordering::Relaxed) {
+                return None;
+            }
+            if let Ok(()) = check_proof_of_work_inner(data.as_ref(), target_number) {
                 let mut nonce = [0; POW_SIZE];
                 nonce.clone_from_slice(&data[CRYPTO_KEY_SIZE..]);
-                return ProofOfWork(nonce);
+                return Some(ProofOfWork(nonce));
             } else {
                 // the code might look obscure,
                 // but it just treat `data[CRYPTO_KEY_SIZE..]` as an 192-bit integer and increment it
@@ -105,10 +155,22 @@ impl ProofOfWork {
     }
 
     pub fn check(&self, pk: &PublicKey, target: f64) -> PowResult {
+        check_proof_of_work(self.data(pk).as_ref(), target)
+    }
+
+    /// Returns the difficulty reached by the stamp for `pk`.
+    ///
+    /// The stamp passes the [check](Self::check) of any target below it.
+    pub fn difficulty(&self, pk: &PublicKey) -> Result<f64, PowError> {
+        let hash = blake2b::digest_256(self.data(pk).as_ref())?;
+        Ok(difficulty(&BigUint::from_bytes_le(hash.as_ref())))
+    }
+
+    fn data(&self, pk: &PublicKey) -> [u8; CRYPTO_KEY_SIZE + POW_SIZE] {
         let mut data = [0; CRYPTO_KEY_SIZE + POW_SIZE];
         data[..CRYPTO_KEY_SIZE].clone_from_slice(pk.as_ref().as_ref());
         data[CRYPTO_KEY_SIZE..].clone_from_slice(self.as_ref());
-        check_proof_of_work(data.as_ref(), target)
+        data
     }
 }
 
@@ -131,6 +193,19 @@ fn check_proof_of_work_inner(data: &[u8], target_number: &BigUint) -> PowResult
     }
 }
 
+/// The hash passes the targets below `256 - log2(hash)`, see [make_target].
+fn difficulty(hash_number: &BigUint) -> f64 {
+    let bits = hash_number.bits();
+    if bits == 0 {
+        return 256.0;
+    }
+
+    // the most significant bits are enough for the precision of a f64
+    let shift = bits.saturating_sub(f64::MANTISSA_DIGITS as u64);
+    let mantissa = (hash_number >> shift as usize).to_u64().unwrap_or(u64::MAX) as f64;
+    256.0 - (mantissa.log2() + shift as f64)
+}
+
 fn make_target(target: f64) -> BigUint {
     assert!((0.0..256.0).contains(&target));
     let (frac, shift) = (target.fract(), target.floor() as u64);
@@ -186,6 +261,23 @@ mod tests {
         check_proof_of_work(data.as_ref(), 24.0).unwrap();
     }
 
+    #[test]
+    fn pow_difficulty() {
+        let pk =
+            PublicKey::from_hex("d8246d13d0270cbfff4046b6d94b05ab19920bc5ad9fb77f3e945c40b340e874")
+                .expect("Failed to generate public key");
+        let pow = ProofOfWork::from_hex("d1d0ebd55784bc92852d913dbf0fb5152d505b567d930fb2")
+            .expect("Failed to decode proof of work");
+
+        let difficulty = pow.difficulty(&pk).unwrap();
+        assert!(difficulty > 24.0);
+        assert!(pow.check(&pk, difficulty - 0.01).is_ok());
+        assert!(pow.check(&pk, difficulty + 0.01).is_err());
+
+        assert_eq!(super::difficulty(&BigUint::from(0u32)), 256.0);
+        assert_eq!(super::difficulty(&(BigUint::from(1u32) << 230usize)), 26.0);
+    }
+
     #[test]
     fn simple_generate() {
         let pk =
@@ -194,4 +286,13 @@ mod tests {
         let pow = ProofOfWork::generate(&pk, 3.5);
         assert!(pow.check(&pk, 3.5).is_ok());
     }
+
+    #[test]
+    fn parallel_generate() {
+        let pk =
+            PublicKey::from_hex("d8246d13d0270cbfff4046b6d94b05ab19920bc5ad9fb77f3e945c40b340e874")
+                .expect("Failed to generate public key");
+        let pow = ProofOfWork::generate_parallel(&pk, 8.0, 4);
+        assert!(pow.check(&pk, 8.0).is_ok());
+    }
 }
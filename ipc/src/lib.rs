@@ -1,11 +1,16 @@
 // Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
 // SPDX-License-Identifier: MIT
-#![forbid(unsafe_code)]
+#![deny(unsafe_code)]
 
 //! Provides IPC communication.
 //!
 //! The IPC is implemented as unix domain sockets. Functionality is similar to how network sockets work.
 //!
+//! Each message is sent as its length followed by its bincode serialization. An empty message is
+//! a keep-alive ping, see [`IpcSender::heartbeat`], it is skipped by the receiving end.
+//! Big messages can be passed through a shared memory segment instead, see [`shm`].
+//! The versions of the protocol spoken by both ends can be checked on connect, see [`version`].
+//!
 //! TODO: TE-292 - investigate/reimplement
 
 use std::fs;
@@ -24,6 +29,18 @@ use rand::{thread_rng, Rng};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+pub mod shm;
+pub mod version;
+
+use shm::{ShmMessage, ShmReader, ShmSegment, ShmWriter};
+use version::{IpcProtocol, ProtocolVersion, VersionFrame, MAX_VERSION_FRAME_SIZE};
+
+/// Bit set in the length of a message written in a shared memory segment.
+const SHM_MESSAGE_BIT: u64 = 1 << 63;
+
+/// Time to receive the version frame of the other end.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// IPC communication errors
 #[derive(Debug, Error)]
 pub enum IpcError {
@@ -51,27 +68,108 @@ pub enum IpcError {
     SplitError { reason: io::Error },
     #[error("Socker configuration error: {reason}")]
     SocketConfigurationError { reason: io::Error },
+    #[error("Shared memory error: {reason}")]
+    SharedMemoryError { reason: String },
+    #[error("IPC protocol version mismatch, expected: {expected}, received: {received}")]
+    VersionMismatch { expected: String, received: String },
     #[error("IPC error: {reason}")]
     OtherError { reason: String },
 }
 
 /// Represents sending end of the IPC channel.
-pub struct IpcSender<S>(UnixStream, PhantomData<S>);
+pub struct IpcSender<S> {
+    stream: UnixStream,
+    heartbeat_interval: Option<Duration>,
+    last_sent: Instant,
+    shm: Option<ShmWriter>,
+    protocol_version: Option<ProtocolVersion>,
+    _phantom: PhantomData<S>,
+}
 
 impl<S> IpcSender<S> {
+    fn new(stream: UnixStream) -> Self {
+        Self {
+            stream,
+            heartbeat_interval: None,
+            last_sent: Instant::now(),
+            shm: None,
+            protocol_version: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Version of the protocol spoken on the channel, `None` when no protocol was negotiated.
+    pub fn protocol_version(&self) -> Option<ProtocolVersion> {
+        self.protocol_version
+    }
+
     /// Close IPC channel and release associated resources.
     ///
     /// This closes only the sending part of the IPC channel.
     fn shutdown(&self) -> Result<(), io::Error> {
-        self.0.shutdown(Shutdown::Write)
+        self.stream.shutdown(Shutdown::Write)
     }
 
     pub fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
-        self.0.set_write_timeout(timeout)
+        self.stream.set_write_timeout(timeout)
     }
 
     pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
-        self.0.set_nonblocking(nonblocking)
+        self.stream.set_nonblocking(nonblocking)
+    }
+
+    /// Sets the interval of the keep-alive pings, `None` disables them (the default).
+    pub fn set_heartbeat_interval(&mut self, interval: Option<Duration>) {
+        self.heartbeat_interval = interval;
+    }
+
+    pub fn heartbeat_interval(&self) -> Option<Duration> {
+        self.heartbeat_interval
+    }
+
+    /// Messages of at least `threshold` bytes are written in `segment`, when it has room.
+    ///
+    /// The receiving end must have opened the same segment, see [`IpcReceiver::attach_shm`].
+    pub fn attach_shm(&mut self, segment: ShmSegment, threshold: usize) {
+        self.shm = Some(ShmWriter::new(segment, threshold));
+    }
+
+    /// Sends a keep-alive ping if nothing was sent during the heartbeat interval.
+    ///
+    /// The channel has no thread of its own, so this has to be called regularly by the owner
+    /// of the sender. An error means that the other end of the channel is gone.
+    /// Returns `true` if a ping was sent.
+    pub fn heartbeat(&mut self) -> Result<bool, IpcError> {
+        match self.heartbeat_interval {
+            Some(interval) if self.last_sent.elapsed() >= interval => {
+                self.write_message(&[])?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn write_shm_message(&mut self, message: ShmMessage) -> Result<(), IpcError> {
+        let msg_len = SHM_MESSAGE_BIT | ShmMessage::NBYTES as u64;
+        self.write_frame(msg_len, &message.to_bytes())
+    }
+
+    fn write_message(&mut self, msg_buf: &[u8]) -> Result<(), IpcError> {
+        self.write_frame(msg_buf.len() as u64, msg_buf)
+    }
+
+    fn write_frame(&mut self, msg_len: u64, msg_buf: &[u8]) -> Result<(), IpcError> {
+        self.stream
+            .write_all(&msg_len.to_be_bytes())
+            .map_err(|err| IpcError::SendError { reason: err })?;
+        self.stream
+            .write_all(msg_buf)
+            .map_err(|err| IpcError::SendError { reason: err })?;
+        self.stream
+            .flush()
+            .map_err(|err| IpcError::SendError { reason: err })?;
+        self.last_sent = Instant::now();
+        Ok(())
     }
 }
 
@@ -79,20 +177,28 @@ impl<S: Serialize> IpcSender<S> {
     /// Serialize and sent `value` through IPC channel.
     ///
     /// This is a blocking operation,
+    /// values serialized to nothing would be taken for keep-alive pings and can't be sent.
     pub fn send(&mut self, value: &S) -> Result<(), IpcError> {
+        if let Some(shm) = self.shm.as_mut() {
+            let len =
+                bincode::serialized_size(value).map_err(|err| IpcError::SerializationError {
+                    reason: format!("{:?}", err),
+                })?;
+
+            if let Some(message) = shm.write(value, len as usize)? {
+                return self.write_shm_message(message);
+            }
+        }
+
         let msg_buf = bincode::serialize(value).map_err(|err| IpcError::SerializationError {
             reason: format!("{:?}", err),
         })?;
-        let msg_len_buf = msg_buf.len().to_be_bytes();
-        self.0
-            .write_all(&msg_len_buf)
-            .map_err(|err| IpcError::SendError { reason: err })?;
-        self.0
-            .write_all(&msg_buf)
-            .map_err(|err| IpcError::SendError { reason: err })?;
-        self.0
-            .flush()
-            .map_err(|err| IpcError::SendError { reason: err })
+        if msg_buf.is_empty() {
+            return Err(IpcError::SerializationError {
+                reason: "Empty messages are reserved for keep-alive pings".to_string(),
+            });
+        }
+        self.write_message(&msg_buf)
     }
 }
 
@@ -103,22 +209,79 @@ impl<S> Drop for IpcSender<S> {
 }
 
 /// Represents receiving end of the IPC channel.
-pub struct IpcReceiver<R>(UnixStream, PhantomData<R>);
+pub struct IpcReceiver<R> {
+    stream: UnixStream,
+    shm: Option<ShmReader>,
+    protocol_version: Option<ProtocolVersion>,
+    _phantom: PhantomData<R>,
+}
 
 impl<R> IpcReceiver<R> {
     /// Close IPC channel and release associated resources.
     ///
     /// This closes only the receiving part of the IPC channel.
     fn shutdown(&self) -> Result<(), io::Error> {
-        self.0.shutdown(Shutdown::Read)
+        self.stream.shutdown(Shutdown::Read)
     }
 
     pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
-        self.0.set_read_timeout(timeout)
+        self.stream.set_read_timeout(timeout)
     }
 
     pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
-        self.0.set_nonblocking(nonblocking)
+        self.stream.set_nonblocking(nonblocking)
+    }
+
+    /// Receives the big messages written by the sender in `segment`, see [`IpcSender::attach_shm`].
+    pub fn attach_shm(&mut self, segment: ShmSegment) {
+        self.shm = Some(ShmReader::new(segment));
+    }
+
+    /// Version of the protocol spoken on the channel, `None` when no protocol was negotiated.
+    pub fn protocol_version(&self) -> Option<ProtocolVersion> {
+        self.protocol_version
+    }
+
+    /// Reads the length of the next message, keep-alive pings are skipped.
+    fn read_message_length(&mut self) -> Result<u64, IpcError> {
+        loop {
+            let mut msg_len_buf = [0; 8];
+            self.stream.read_exact(&mut msg_len_buf).map_err(|err| {
+                if err.kind() == io::ErrorKind::WouldBlock {
+                    IpcError::ReceiveMessageTimeout
+                } else {
+                    IpcError::ReceiveMessageLengthError { reason: err }
+                }
+            })?;
+
+            match u64::from_be_bytes(msg_len_buf) {
+                0 => continue,
+                msg_len => return Ok(msg_len),
+            }
+        }
+    }
+
+    fn read_message(&mut self, msg_len: u64) -> Result<Vec<u8>, IpcError> {
+        let mut msg_buf = vec![0u8; msg_len as usize];
+        self.stream
+            .read_exact(&mut msg_buf)
+            .map_err(|err| IpcError::ReceiveMessageError { reason: err })?;
+        Ok(msg_buf)
+    }
+
+    /// Reads the version frame of the other end, `None` if the first message is not one.
+    fn read_version_frame(&mut self) -> Result<Option<VersionFrame>, IpcError> {
+        let msg_len = match self.read_message_length() {
+            Ok(msg_len) => msg_len,
+            Err(IpcError::ReceiveMessageTimeout) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        if msg_len > MAX_VERSION_FRAME_SIZE {
+            return Ok(None);
+        }
+
+        let msg_buf = self.read_message(msg_len)?;
+        Ok(bincode::deserialize(&msg_buf).ok())
     }
 }
 
@@ -145,22 +308,26 @@ where
     }
 
     /// Read bytes from established IPC channel and deserialize into a rust type.
+    ///
+    /// Keep-alive pings are skipped.
     pub fn receive(&mut self) -> Result<R, IpcError> {
-        let mut msg_len_buf = [0; 8];
-        self.0.read_exact(&mut msg_len_buf).map_err(|err| {
-            if err.kind() == io::ErrorKind::WouldBlock {
-                IpcError::ReceiveMessageTimeout
-            } else {
-                IpcError::ReceiveMessageLengthError { reason: err }
-            }
-        })?;
+        let msg_len = self.read_message_length()?;
+
+        if msg_len & SHM_MESSAGE_BIT != 0 {
+            let mut msg_buf = [0; ShmMessage::NBYTES];
+            self.stream
+                .read_exact(&mut msg_buf)
+                .map_err(|err| IpcError::ReceiveMessageError { reason: err })?;
+
+            return match self.shm.as_mut() {
+                Some(shm) => shm.read(ShmMessage::from_bytes(msg_buf)),
+                None => Err(IpcError::SharedMemoryError {
+                    reason: "Received a shared memory message without segment".to_string(),
+                }),
+            };
+        }
 
-        let msg_len = usize::from_be_bytes(msg_len_buf);
-
-        let mut msg_buf = vec![0u8; msg_len];
-        self.0
-            .read_exact(&mut msg_buf)
-            .map_err(|err| IpcError::ReceiveMessageError { reason: err })?;
+        let msg_buf = self.read_message(msg_len)?;
 
         bincode::deserialize(&msg_buf).map_err(|err| IpcError::DeserializationError {
             reason: format!("{:?}", err),
@@ -178,6 +345,7 @@ impl<R> Drop for IpcReceiver<R> {
 pub struct IpcServer<R, S> {
     listener: UnixListener,
     pub path: PathBuf,
+    protocol: Option<IpcProtocol>,
     _phantom_r: PhantomData<R>,
     _phantom_s: PhantomData<S>,
 }
@@ -211,11 +379,18 @@ where
         Ok(IpcServer {
             listener,
             path: path_buf,
+            protocol: None,
             _phantom_r: PhantomData,
             _phantom_s: PhantomData,
         })
     }
 
+    /// Negotiates the version of `protocol` with each client, see [`version`].
+    pub fn with_protocol(mut self, protocol: IpcProtocol) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
     /// Try to accept new connection a return sender/receiver for it
     /// In case of timeout, can be IpcError::AcceptTimeout handled
     ///
@@ -271,7 +446,11 @@ where
         // maybe it is enought to set non_blocking to the [`stream`], but we make sure,
         // also On macOS and FreeBSD new sockets inherit flags from accepting fd,
         // but we expect this to be in blocking by default.
-        split(stream.0, false, false)
+        let (mut receiver, mut sender) = split(stream.0, false, false)?;
+        if let Some(protocol) = self.protocol.as_ref() {
+            handshake(&mut receiver, &mut sender, protocol)?;
+        }
+        Ok((receiver, sender))
     }
 
     /// Accept new connection a return sender/receiver for it
@@ -282,12 +461,19 @@ where
             .map_err(|e| IpcError::ConnectionError { reason: e })?;
 
         // see explaination at `try_accept`.
-        split(stream.0, false, false)
+        let (mut receiver, mut sender) = split(stream.0, false, false)?;
+        if let Some(protocol) = self.protocol.as_ref() {
+            handshake(&mut receiver, &mut sender, protocol)?;
+        }
+        Ok((receiver, sender))
     }
 
     /// Create new IpcClient for this server
     pub fn client(&self) -> IpcClient<R, S> {
-        IpcClient::new(&self.path)
+        IpcClient {
+            protocol: self.protocol.clone(),
+            ..IpcClient::new(&self.path)
+        }
     }
 }
 
@@ -295,6 +481,7 @@ where
 #[derive(Debug)]
 pub struct IpcClient<R, S> {
     path: PathBuf,
+    protocol: Option<IpcProtocol>,
     _phantom_r: PhantomData<R>,
     _phantom_s: PhantomData<S>,
 }
@@ -317,16 +504,27 @@ where
     pub fn new<P: AsRef<Path>>(path: P) -> Self {
         IpcClient {
             path: path.as_ref().into(),
+            protocol: None,
             _phantom_r: PhantomData,
             _phantom_s: PhantomData,
         }
     }
 
+    /// Negotiates the version of `protocol` with the server, see [`version`].
+    pub fn with_protocol(mut self, protocol: IpcProtocol) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
     /// Try to open new connection.
     pub fn connect(&self) -> Result<(IpcReceiver<R>, IpcSender<S>), IpcError> {
         let stream = UnixStream::connect(&self.path)
             .map_err(|err| IpcError::ConnectionError { reason: err })?;
-        split(stream, false, false)
+        let (mut receiver, mut sender) = split(stream, false, false)?;
+        if let Some(protocol) = self.protocol.as_ref() {
+            handshake(&mut receiver, &mut sender, protocol)?;
+        }
+        Ok((receiver, sender))
     }
 }
 
@@ -351,20 +549,58 @@ where
     R: for<'de> Deserialize<'de>,
     S: Serialize,
 {
-    let receiver = IpcReceiver(
-        stream
+    let receiver = IpcReceiver {
+        stream: stream
             .try_clone()
             .map_err(|err| IpcError::SplitError { reason: err })?,
-        PhantomData,
-    );
+        shm: None,
+        protocol_version: None,
+        _phantom: PhantomData,
+    };
     receiver
         .set_nonblocking(receiver_non_blocking)
         .map_err(|err| IpcError::SocketConfigurationError { reason: err })?;
 
-    let sender = IpcSender(stream, PhantomData);
+    let sender = IpcSender::new(stream);
     sender
         .set_nonblocking(sender_non_blocking)
         .map_err(|err| IpcError::SocketConfigurationError { reason: err })?;
 
     Ok((receiver, sender))
 }
+
+/// Exchanges the version frames, and records the version spoken on the channel.
+fn handshake<R, S>(
+    receiver: &mut IpcReceiver<R>,
+    sender: &mut IpcSender<S>,
+    protocol: &IpcProtocol,
+) -> Result<(), IpcError> {
+    let frame = bincode::serialize(&VersionFrame::from(protocol)).map_err(|err| {
+        IpcError::SerializationError {
+            reason: format!("{:?}", err),
+        }
+    })?;
+    sender.write_message(&frame)?;
+
+    receiver
+        .set_read_timeout(Some(HANDSHAKE_TIMEOUT))
+        .map_err(|err| IpcError::SocketConfigurationError { reason: err })?;
+    let remote = receiver.read_version_frame();
+    receiver
+        .set_read_timeout(None)
+        .map_err(|err| IpcError::SocketConfigurationError { reason: err })?;
+
+    let version = match remote? {
+        Some(remote) => protocol.negotiate(&remote)?,
+        None => {
+            return Err(IpcError::VersionMismatch {
+                expected: protocol.to_string(),
+                received: "no version frame".to_string(),
+            })
+        }
+    };
+
+    receiver.protocol_version = Some(version);
+    sender.protocol_version = Some(version);
+    Ok(())
+}
@@ -0,0 +1,293 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+#![allow(unsafe_code)]
+
+//! Shared memory segments, used to pass big messages without copying them through the socket.
+//!
+//! A segment is a file mapped in memory by both ends of a channel, it is a ring buffer written
+//! by an [`IpcSender`](crate::IpcSender) and read by an [`IpcReceiver`](crate::IpcReceiver).
+//! A message bigger than the threshold of the sender is serialized directly in the segment,
+//! and only its position is sent through the socket. When the segment is full, the message is
+//! sent through the socket as usual.
+//!
+//! The first bytes of the segment hold the position up to which the receiver has read the
+//! messages, the sender never overwrites what was not read yet.
+
+use std::fs::{self, OpenOptions};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use memmap::MmapMut;
+use serde::{Deserialize, Serialize};
+
+use crate::{temp_sock, IpcError};
+
+/// Size of the header, containing the read position.
+const HEADER_SIZE: usize = 64;
+
+/// Extension of the files of the segments.
+const SEGMENT_EXTENSION: &str = "shm";
+
+/// Shared memory segment, see the [module documentation](self).
+pub struct ShmSegment {
+    mmap: MmapMut,
+    path: PathBuf,
+    /// The file is removed when the segment is dropped
+    owned: bool,
+}
+
+impl ShmSegment {
+    /// Creates a new segment of `size` bytes, in `/dev/shm` when available.
+    ///
+    /// The file is removed when the segment is dropped, or with [`Self::unlink`]
+    /// once the other end has opened it.
+    pub fn create(size: usize) -> Result<Self, IpcError> {
+        let name = temp_sock().with_extension(SEGMENT_EXTENSION);
+        let name = name.file_name().ok_or_else(|| shm_error("invalid name"))?;
+        let path = shm_dir().join(name);
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(shm_error)?;
+
+        let segment = Self {
+            mmap: map(&file, HEADER_SIZE + size)?,
+            path,
+            owned: true,
+        };
+        segment.read_position().store(0, Ordering::Release);
+
+        Ok(segment)
+    }
+
+    /// Opens the segment created by the other end of the channel.
+    ///
+    /// `path` is received from the other process: only a file named like the segments of
+    /// [`Self::create`], directly in their directory, is opened (and possibly resized).
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, IpcError> {
+        if !is_segment_path(path.as_ref()) {
+            return Err(shm_error(format!(
+                "{} is not a shared memory segment",
+                path.as_ref().display()
+            )));
+        }
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOFOLLOW)
+            .open(path.as_ref())
+            .map_err(shm_error)?;
+        let metadata = file.metadata().map_err(shm_error)?;
+        let len = metadata.len() as usize;
+
+        if !metadata.is_file() {
+            return Err(shm_error("segment is not a regular file"));
+        }
+        if len <= HEADER_SIZE {
+            return Err(shm_error("segment too small"));
+        }
+
+        Ok(Self {
+            mmap: map(&file, len)?,
+            path: path.as_ref().to_path_buf(),
+            owned: false,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Removes the file of the segment, it stays mapped by the processes which opened it.
+    pub fn unlink(&mut self) -> Result<(), IpcError> {
+        if self.owned {
+            self.owned = false;
+            fs::remove_file(&self.path).map_err(shm_error)?;
+        }
+        Ok(())
+    }
+
+    /// Number of bytes available for the messages.
+    pub fn capacity(&self) -> usize {
+        self.mmap.len() - HEADER_SIZE
+    }
+
+    fn read_position(&self) -> &AtomicU64 {
+        // The mapping is page aligned, and the header is never part of `Self::data`
+        unsafe { &*(self.mmap.as_ptr() as *const AtomicU64) }
+    }
+
+    fn data(&self) -> &[u8] {
+        &self.mmap[HEADER_SIZE..]
+    }
+
+    fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.mmap[HEADER_SIZE..]
+    }
+}
+
+impl Drop for ShmSegment {
+    fn drop(&mut self) {
+        let _ = self.unlink();
+    }
+}
+
+/// Directory of the segments, `/dev/shm` when available.
+fn shm_dir() -> PathBuf {
+    let shm_dir = Path::new("/dev/shm");
+    if shm_dir.is_dir() {
+        shm_dir.to_path_buf()
+    } else {
+        std::env::temp_dir()
+    }
+}
+
+/// Returns `true` if `path` is named like the segments of [`ShmSegment::create`].
+fn is_segment_path(path: &Path) -> bool {
+    let is_alphanumeric = |stem: &std::ffi::OsStr| {
+        stem.to_str()
+            .map(|stem| !stem.is_empty() && stem.chars().all(|c| c.is_ascii_alphanumeric()))
+            .unwrap_or(false)
+    };
+
+    path.parent() == Some(shm_dir().as_path())
+        && path.extension() == Some(SEGMENT_EXTENSION.as_ref())
+        && path.file_stem().map(is_alphanumeric).unwrap_or(false)
+}
+
+fn map(file: &fs::File, len: usize) -> Result<MmapMut, IpcError> {
+    file.set_len(len as u64).map_err(shm_error)?;
+    // The file is only modified through the mappings
+    unsafe { MmapMut::map_mut(file) }.map_err(shm_error)
+}
+
+fn shm_error<E: std::fmt::Display>(err: E) -> IpcError {
+    IpcError::SharedMemoryError {
+        reason: err.to_string(),
+    }
+}
+
+/// Position of a message written in a segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ShmMessage {
+    pub position: u64,
+    pub len: u64,
+}
+
+impl ShmMessage {
+    pub const NBYTES: usize = 16;
+
+    pub fn to_bytes(self) -> [u8; Self::NBYTES] {
+        let mut bytes = [0; Self::NBYTES];
+        bytes[..8].copy_from_slice(&self.position.to_be_bytes());
+        bytes[8..].copy_from_slice(&self.len.to_be_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: [u8; Self::NBYTES]) -> Self {
+        let mut position = [0; 8];
+        let mut len = [0; 8];
+        position.copy_from_slice(&bytes[..8]);
+        len.copy_from_slice(&bytes[8..]);
+
+        Self {
+            position: u64::from_be_bytes(position),
+            len: u64::from_be_bytes(len),
+        }
+    }
+}
+
+/// Writing end of a segment, owned by an `IpcSender`.
+pub(crate) struct ShmWriter {
+    segment: ShmSegment,
+    threshold: usize,
+    write_position: u64,
+}
+
+impl ShmWriter {
+    pub fn new(segment: ShmSegment, threshold: usize) -> Self {
+        let write_position = segment.read_position().load(Ordering::Acquire);
+
+        Self {
+            segment,
+            threshold,
+            write_position,
+        }
+    }
+
+    /// Serializes `value` in the segment, if it is bigger than the threshold and there is room.
+    pub fn write<S: Serialize>(
+        &mut self,
+        value: &S,
+        len: usize,
+    ) -> Result<Option<ShmMessage>, IpcError> {
+        let capacity = self.segment.capacity() as u64;
+        let len = len as u64;
+
+        if len < self.threshold as u64 || len > capacity {
+            return Ok(None);
+        }
+
+        // A message is never split, the end of the buffer is skipped if too small
+        let mut position = self.write_position;
+        let offset = position % capacity;
+        if offset + len > capacity {
+            position += capacity - offset;
+        }
+
+        let read_position = self.segment.read_position().load(Ordering::Acquire);
+        if position + len - read_position > capacity {
+            return Ok(None);
+        }
+
+        let offset = (position % capacity) as usize;
+        let buffer = &mut self.segment.data_mut()[offset..offset + len as usize];
+        bincode::serialize_into(buffer, value).map_err(|err| IpcError::SerializationError {
+            reason: format!("{:?}", err),
+        })?;
+        self.write_position = position + len;
+
+        Ok(Some(ShmMessage { position, len }))
+    }
+}
+
+/// Reading end of a segment, owned by an `IpcReceiver`.
+pub(crate) struct ShmReader {
+    segment: ShmSegment,
+}
+
+impl ShmReader {
+    pub fn new(segment: ShmSegment) -> Self {
+        Self { segment }
+    }
+
+    /// Deserializes the message and releases its bytes.
+    pub fn read<R>(&mut self, message: ShmMessage) -> Result<R, IpcError>
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        let capacity = self.segment.capacity() as u64;
+        let offset = message.position % capacity;
+
+        if message.len > capacity - offset {
+            return Err(shm_error("message out of the segment"));
+        }
+
+        let offset = offset as usize;
+        let bytes = &self.segment.data()[offset..offset + message.len as usize];
+        let value = bincode::deserialize(bytes).map_err(|err| IpcError::DeserializationError {
+            reason: format!("{:?}", err),
+        });
+
+        self.segment
+            .read_position()
+            .store(message.position + message.len, Ordering::Release);
+
+        value
+    }
+}
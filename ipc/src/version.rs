@@ -0,0 +1,190 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Version negotiation of the IPC channels.
+//!
+//! When a protocol is set on the server and the client (see [`crate::IpcServer::with_protocol`]
+//! and [`crate::IpcClient::with_protocol`]), both ends send a version frame right after the
+//! connection: a magic number, the name of the protocol, its version and the previous version
+//! still supported. The version spoken on the channel is the highest one supported by both ends,
+//! see [`crate::IpcSender::protocol_version`], so that a new binary can fall back to the messages
+//! of the previous version.
+//!
+//! Binaries speaking incompatible protocols fail with [`crate::IpcError::VersionMismatch`] when
+//! connecting, instead of failing to deserialize the first messages.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::IpcError;
+
+/// First bytes of a version frame.
+pub const VERSION_FRAME_MAGIC: [u8; 4] = *b"TZIP";
+
+/// Maximal size of a version frame, bigger messages are not read during the handshake.
+pub(crate) const MAX_VERSION_FRAME_SIZE: u64 = 1024;
+
+/// Semantic version of an IPC protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ProtocolVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+}
+
+impl ProtocolVersion {
+    pub const fn new(major: u16, minor: u16, patch: u16) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Protocol spoken on an IPC channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IpcProtocol {
+    pub name: &'static str,
+    pub version: ProtocolVersion,
+    /// Previous version still supported, through compatibility shims
+    pub previous: Option<ProtocolVersion>,
+}
+
+impl IpcProtocol {
+    pub const fn new(name: &'static str, version: ProtocolVersion) -> Self {
+        Self {
+            name,
+            version,
+            previous: None,
+        }
+    }
+
+    pub const fn with_previous(mut self, previous: ProtocolVersion) -> Self {
+        self.previous = Some(previous);
+        self
+    }
+
+    fn supports(&self, version: ProtocolVersion) -> bool {
+        self.version == version || self.previous == Some(version)
+    }
+
+    /// Returns the highest version supported by both ends.
+    pub(crate) fn negotiate(&self, remote: &VersionFrame) -> Result<ProtocolVersion, IpcError> {
+        let mismatch = || IpcError::VersionMismatch {
+            expected: self.to_string(),
+            received: remote.to_string(),
+        };
+
+        if remote.magic != VERSION_FRAME_MAGIC || remote.name != self.name {
+            return Err(mismatch());
+        }
+
+        std::iter::once(remote.version)
+            .chain(remote.previous)
+            .filter(|version| self.supports(*version))
+            .max()
+            .ok_or_else(mismatch)
+    }
+}
+
+impl fmt::Display for IpcProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_protocol(f, self.name, self.version, self.previous)
+    }
+}
+
+/// Frame sent by both ends of a channel right after the connection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct VersionFrame {
+    magic: [u8; 4],
+    name: String,
+    version: ProtocolVersion,
+    previous: Option<ProtocolVersion>,
+}
+
+impl From<&IpcProtocol> for VersionFrame {
+    fn from(protocol: &IpcProtocol) -> Self {
+        Self {
+            magic: VERSION_FRAME_MAGIC,
+            name: protocol.name.to_string(),
+            version: protocol.version,
+            previous: protocol.previous,
+        }
+    }
+}
+
+impl fmt::Display for VersionFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_protocol(f, &self.name, self.version, self.previous)
+    }
+}
+
+fn fmt_protocol(
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+    version: ProtocolVersion,
+    previous: Option<ProtocolVersion>,
+) -> fmt::Result {
+    write!(f, "{} {}", name, version)?;
+    if let Some(previous) = previous {
+        write!(f, " (or {})", previous)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V1: ProtocolVersion = ProtocolVersion::new(1, 0, 0);
+    const V2: ProtocolVersion = ProtocolVersion::new(1, 1, 0);
+    const V3: ProtocolVersion = ProtocolVersion::new(2, 0, 0);
+
+    fn frame(protocol: &IpcProtocol) -> VersionFrame {
+        VersionFrame::from(protocol)
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let v1 = IpcProtocol::new("test", V1);
+        let v2 = IpcProtocol::new("test", V2).with_previous(V1);
+        let v3 = IpcProtocol::new("test", V3).with_previous(V2);
+
+        assert_eq!(v2.negotiate(&frame(&v2)).unwrap(), V2);
+        // the previous version is spoken with an older or newer binary
+        assert_eq!(v2.negotiate(&frame(&v1)).unwrap(), V1);
+        assert_eq!(v1.negotiate(&frame(&v2)).unwrap(), V1);
+        assert_eq!(v3.negotiate(&frame(&v2)).unwrap(), V2);
+        assert_eq!(v2.negotiate(&frame(&v3)).unwrap(), V2);
+
+        assert!(matches!(
+            v3.negotiate(&frame(&v1)),
+            Err(IpcError::VersionMismatch { .. })
+        ));
+        assert!(matches!(
+            v1.negotiate(&frame(&IpcProtocol::new("other", V1))),
+            Err(IpcError::VersionMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_mismatch_message() {
+        let local = IpcProtocol::new("test", V2).with_previous(V1);
+        let error = local
+            .negotiate(&frame(&IpcProtocol::new("test", V3)))
+            .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "IPC protocol version mismatch, expected: test 1.1.0 (or 1.0.0), received: test 2.0.0"
+        );
+    }
+}
@@ -154,3 +154,155 @@ fn ipc_accept_timeout() -> Result<(), anyhow::Error> {
         Ok(_) => Err(format_err!("Unexpected result")),
     }
 }
+
+#[test]
+#[serial]
+fn ipc_heartbeat_is_skipped() -> Result<(), anyhow::Error> {
+    let sock_path = temp_sock();
+    let mut server: IpcServer<String, String> = IpcServer::bind_path(&sock_path)?;
+    let (_, mut tx) = server.client().connect()?;
+    let (mut rx, _) = server.accept()?;
+
+    // disabled by default
+    assert!(!tx.heartbeat()?);
+
+    tx.set_heartbeat_interval(Some(Duration::from_millis(0)));
+    assert!(tx.heartbeat()?);
+    assert!(tx.heartbeat()?);
+    tx.send(&String::from("hello"))?;
+
+    assert_eq!(rx.receive()?, "hello");
+
+    tx.set_heartbeat_interval(Some(Duration::from_secs(60)));
+    assert!(!tx.heartbeat()?);
+
+    let (_, mut empty_tx) = IpcClient::<(), ()>::new(&sock_path).connect()?;
+    match empty_tx.send(&()) {
+        Err(IpcError::SerializationError { .. }) => Ok(()),
+        result => Err(format_err!("Unexpected result: {:?}", result)),
+    }
+}
+
+#[test]
+#[serial]
+fn ipc_shared_memory_messages() -> Result<(), anyhow::Error> {
+    let sock_path = temp_sock();
+    let mut server: IpcServer<String, String> = IpcServer::bind_path(&sock_path)?;
+    let (_, mut tx) = server.client().connect()?;
+    let (mut rx, _) = server.accept()?;
+
+    let mut segment = shm::ShmSegment::create(1024)?;
+    let other_end = shm::ShmSegment::open(segment.path())?;
+    segment.unlink()?;
+    assert!(!other_end.path().exists());
+
+    tx.attach_shm(other_end, 64);
+    rx.attach_shm(segment);
+
+    // the 3rd message doesn't fit, it is sent through the socket
+    let messages: Vec<String> = (0..3u8)
+        .map(|i| String::from_utf8(vec![b'a' + i; 400]).unwrap())
+        .collect();
+    for message in &messages {
+        tx.send(message)?;
+    }
+    tx.send(&String::from("small"))?;
+
+    for message in &messages {
+        assert_eq!(&rx.receive()?, message);
+    }
+    assert_eq!(rx.receive()?, "small");
+
+    // wraps around the end of the segment
+    for i in 0..10u8 {
+        let message = String::from_utf8(vec![b'a' + i; 300]).unwrap();
+        tx.send(&message)?;
+        assert_eq!(rx.receive()?, message);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn ipc_shared_memory_open_only_segments() -> Result<(), anyhow::Error> {
+    let segment = shm::ShmSegment::create(1024)?;
+    let shm_dir = segment.path().parent().unwrap();
+    let name = segment.path().file_name().unwrap();
+
+    // the path is sent by the other process, other files are not opened
+    let other_paths = [
+        shm_dir
+            .join("..")
+            .join(shm_dir.file_name().unwrap())
+            .join(name),
+        shm_dir.join("not-a-segment.shm"),
+        segment.path().with_extension("sock"),
+        std::path::PathBuf::from("/etc/passwd"),
+    ];
+    for path in &other_paths {
+        assert!(matches!(
+            shm::ShmSegment::open(path),
+            Err(IpcError::SharedMemoryError { .. })
+        ));
+    }
+
+    assert!(shm::ShmSegment::open(segment.path()).is_ok());
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn ipc_version_handshake() -> Result<(), anyhow::Error> {
+    use ipc::version::{IpcProtocol, ProtocolVersion};
+
+    const V1: ProtocolVersion = ProtocolVersion::new(1, 0, 0);
+    const V2: ProtocolVersion = ProtocolVersion::new(1, 1, 0);
+
+    let sock_path = temp_sock();
+    let mut server: IpcServer<String, String> = IpcServer::bind_path(&sock_path)?
+        .with_protocol(IpcProtocol::new("test", V2).with_previous(V1));
+
+    let connect = |protocol: Option<IpcProtocol>| {
+        let client = IpcClient::<String, String>::new(&sock_path);
+        let client = match protocol {
+            Some(protocol) => client.with_protocol(protocol),
+            None => client,
+        };
+        thread::spawn(move || client.connect())
+    };
+
+    // the previous version is spoken with an older client
+    let client = connect(Some(IpcProtocol::new("test", V1)));
+    let (mut rx, _server_tx) = server.accept()?;
+    let (_client_rx, mut tx) = client.join().unwrap()?;
+    assert_eq!(rx.protocol_version(), Some(V1));
+    assert_eq!(tx.protocol_version(), Some(V1));
+    tx.send(&String::from("hello"))?;
+    assert_eq!(rx.receive()?, "hello");
+
+    let client = connect(Some(IpcProtocol::new(
+        "test",
+        ProtocolVersion::new(2, 0, 0),
+    )));
+    assert!(matches!(
+        server.accept(),
+        Err(IpcError::VersionMismatch { .. })
+    ));
+    assert!(matches!(
+        client.join().unwrap(),
+        Err(IpcError::VersionMismatch { .. })
+    ));
+
+    // a client without version frame
+    let client = connect(None);
+    let (_client_rx, mut tx) = client.join().unwrap()?;
+    tx.send(&String::from("hello"))?;
+    match server.accept() {
+        Err(IpcError::VersionMismatch { received, .. }) => {
+            assert_eq!(received, "no version frame");
+            Ok(())
+        }
+        Err(e) => Err(format_err!("Unexpected result: {:?}", e)),
+        Ok(_) => Err(format_err!("Unexpected result")),
+    }
+}
@@ -19,6 +19,7 @@ use logging::config::{FileLoggerConfig, LogFormat, LoggerType, NoDrainError, Slo
 use shell::peer_manager::P2p;
 use shell::PeerConnectionThreshold;
 use storage::database::tezedge_database::TezedgeDatabaseBackendConfiguration;
+use storage::database::write_policy::{FsyncPolicy, WritePolicy};
 use storage::initializer::{DbsRocksDbTableInitializer, RocksDbConfig};
 use storage::Replay;
 use tezos_api::environment::{self, TezosEnvironmentConfiguration};
@@ -90,6 +91,10 @@ pub struct Storage {
     pub compute_context_action_tree_hashes: bool,
     pub patch_context: Option<PatchContext>,
     pub main_db: TezedgeDatabaseBackendConfiguration,
+    /// If set, main database columns are compacted periodically with this interval
+    pub maindb_compaction_interval: Option<Duration>,
+    /// When the writes of the main database are synced to the disk
+    pub maindb_write_policy: WritePolicy,
 }
 
 impl Storage {
@@ -103,6 +108,8 @@ impl Storage {
     const DEFAULT_CONTEXT_KV_STORE_BACKEND: &'static str = tezos_context::kv_store::INMEM;
 
     const DEFAULT_MAINDB: &'static str = "rocksdb";
+
+    const DEFAULT_MAINDB_FSYNC: &'static str = "never";
 }
 
 #[derive(Debug, Clone)]
@@ -302,6 +309,24 @@ pub fn tezos_app() -> App<'static, 'static> {
             .takes_value(false)
             .conflicts_with("bootstrap-lookup-address")
             .help("Disables dns lookup to get the peers to bootstrap the network from. Default: false"))
+        .arg(Arg::with_name("disable-ipv6")
+            .long("disable-ipv6")
+            .global(true)
+            .takes_value(false)
+            .conflicts_with("prefer-ipv6")
+            .help("Ignores the IPv6 peers and listens for IPv4 connections only. Default: false"))
+        .arg(Arg::with_name("prefer-ipv6")
+            .long("prefer-ipv6")
+            .global(true)
+            .takes_value(false)
+            .help("Connects to the IPv6 peers before the IPv4 ones. Default: false"))
+        .arg(Arg::with_name("p2p-handshake-trace")
+            .long("p2p-handshake-trace")
+            .global(true)
+            .takes_value(true)
+            .value_name("NUM")
+            .help("Keeps the last NUM chunks sent and received during each handshake, and logs them when the handshake fails. Default: disabled")
+            .validator(parse_validator_fn!(usize, "Value must be a valid number")))
         .arg(Arg::with_name("log")
             .long("log")
             .global(true)
@@ -587,6 +612,31 @@ pub fn tezos_app() -> App<'static, 'static> {
             .possible_values(&TezedgeDatabaseBackendConfiguration::possible_values())
             .default_value(Storage::DEFAULT_MAINDB)
             .help("Options fo main database backend"))
+        .arg(Arg::with_name("maindb-compaction-interval")
+            .long("maindb-compaction-interval")
+            .takes_value(true)
+            .value_name("NUM")
+            .help("Interval in seconds for periodic compaction of the main database, which reclaims disk space after large reorgs or mempool churn. Disabled by default")
+            .validator(parse_validator_fn!(u64, "Value must be a valid number")))
+        .arg(Arg::with_name("maindb-fsync")
+            .long("maindb-fsync")
+            .takes_value(true)
+            .value_name("STRING")
+            .possible_values(&FsyncPolicy::possible_values())
+            .default_value(Storage::DEFAULT_MAINDB_FSYNC)
+            .help("When the writes of the main database are synced to the disk: after every write (always), after a number of pending writes or a flush interval (interval), or when the backend decides (never)"))
+        .arg(Arg::with_name("maindb-max-pending-writes")
+            .long("maindb-max-pending-writes")
+            .takes_value(true)
+            .value_name("NUM")
+            .help("Max number of writes of the main database not synced yet, a sync is forced beyond, with '--maindb-fsync=interval'. The writes are not grouped, each one goes to the backend right away")
+            .validator(parse_validator_fn!(usize, "Value must be a valid number")))
+        .arg(Arg::with_name("maindb-flush-interval")
+            .long("maindb-flush-interval")
+            .takes_value(true)
+            .value_name("NUM")
+            .help("Interval in milliseconds between the syncs of the main database, with '--maindb-fsync=interval'")
+            .validator(parse_validator_fn!(u64, "Value must be a valid number")))
         .arg(Arg::with_name("context-kv-store")
             .long("context-kv-store")
             .global(true)
@@ -1002,9 +1052,15 @@ impl Environment {
         Environment {
             p2p: crate::configuration::P2p {
                 listener_port,
-                listener_address: format!("0.0.0.0:{}", listener_port)
-                    .parse::<SocketAddr>()
-                    .expect("Failed to parse listener address"),
+                listener_address: if args.is_present("disable-ipv6") {
+                    format!("0.0.0.0:{}", listener_port)
+                } else {
+                    // the peer manager binds a dual-stack socket, also accepting the IPv4
+                    // connections, or falls back to 0.0.0.0 where it is not supported
+                    format!("[::]:{}", listener_port)
+                }
+                .parse::<SocketAddr>()
+                .expect("Failed to parse listener address"),
                 disable_bootstrap_lookup: args.is_present("disable-bootstrap-lookup"),
                 disable_blacklist: args.is_present("disable-peer-blacklist"),
                 bootstrap_lookup_addresses: args
@@ -1066,6 +1122,12 @@ impl Environment {
                     .parse::<bool>()
                     .expect("Provided value cannot be converted to bool"),
                 disable_mempool: args.is_present("disable-mempool"),
+                disable_ipv6: args.is_present("disable-ipv6"),
+                prefer_ipv6: args.is_present("prefer-ipv6"),
+                handshake_trace: args.value_of("p2p-handshake-trace").map(|v| {
+                    v.parse::<usize>()
+                        .expect("Provided value cannot be converted to number")
+                }),
             },
             rpc: crate::configuration::Rpc {
                 listener_port: args
@@ -1149,6 +1211,42 @@ impl Environment {
                             e
                         )
                     });
+                let maindb_compaction_interval =
+                    args.value_of("maindb-compaction-interval").map(|seconds| {
+                        seconds
+                            .parse::<u64>()
+                            .map(Duration::from_secs)
+                            .expect("Provided value cannot be converted to number")
+                    });
+                let maindb_write_policy = WritePolicy {
+                    max_pending_writes: args
+                        .value_of("maindb-max-pending-writes")
+                        .map(|size| {
+                            size.parse::<usize>()
+                                .expect("Provided value cannot be converted to number")
+                        })
+                        .unwrap_or(WritePolicy::DEFAULT_MAX_PENDING_WRITES),
+                    flush_interval: args
+                        .value_of("maindb-flush-interval")
+                        .map(|millis| {
+                            millis
+                                .parse::<u64>()
+                                .map(Duration::from_millis)
+                                .expect("Provided value cannot be converted to number")
+                        })
+                        .unwrap_or(WritePolicy::DEFAULT_FLUSH_INTERVAL),
+                    fsync: args
+                        .value_of("maindb-fsync")
+                        .unwrap_or(Storage::DEFAULT_MAINDB_FSYNC)
+                        .parse::<FsyncPolicy>()
+                        .unwrap_or_else(|e| {
+                            panic!(
+                                "Expecting one value from {:?}, error: {:?}",
+                                FsyncPolicy::possible_values(),
+                                e
+                            )
+                        }),
+                };
                 let context_kv_store = args
                     .value_of("context-kv-store")
                     .unwrap_or(Storage::DEFAULT_CONTEXT_KV_STORE_BACKEND)
@@ -1212,6 +1310,8 @@ impl Environment {
                     db,
                     context_storage_configuration,
                     main_db: maindb_backend,
+                    maindb_compaction_interval,
+                    maindb_write_policy,
                     db_path,
                     context_stats_db_path,
                     compute_context_action_tree_hashes,
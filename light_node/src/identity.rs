@@ -14,7 +14,7 @@ pub fn ensure_identity(
         load_identity(&identity_cfg.identity_json_file_path)
     } else {
         info!(log, "Generating new tezos identity. This will take a while"; "expected_pow" => identity_cfg.expected_pow);
-        let identity = Identity::generate(identity_cfg.expected_pow)?;
+        let identity = Identity::generate_parallel(identity_cfg.expected_pow, num_cpus::get())?;
         info!(log, "Identity successfully generated");
 
         match store_identity(&identity_cfg.identity_json_file_path, &identity) {
@@ -26,8 +26,9 @@ pub fn ensure_identity(
         }
     };
 
-    identity.and_then(|identity| match identity.check_peer_id() {
-        Ok(_) => Ok(identity),
-        Err(e) => Err(e),
+    identity.and_then(|identity| {
+        identity.check_peer_id()?;
+        identity.check_pow(identity_cfg.expected_pow)?;
+        Ok(identity)
     })
 }
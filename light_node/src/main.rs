@@ -4,7 +4,7 @@
 // #![forbid(unsafe_code)]
 
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use riker::actors::*;
 use slog::{debug, error, info, warn, Logger};
@@ -15,7 +15,7 @@ use networking::p2p::network_channel::NetworkChannel;
 use networking::ShellCompatibilityVersion;
 use rpc::rpc_actor::RpcServer;
 use shell::mempool::{init_mempool_state_storage, MempoolPrevalidatorFactory};
-use shell::peer_manager::PeerManager;
+use shell::peer_manager::{init_ip_blacklist, PeerManager};
 use shell::shell_channel::ShellChannelRef;
 use shell::shell_channel::{ShellChannel, ShellChannelTopic, ShuttingDown};
 use shell::state::head_state::init_current_head_state;
@@ -38,8 +38,9 @@ use tezos_wrapper::TezosApiConnectionPoolError;
 use tezos_wrapper::{TezosApiConnectionPool, TezosApiConnectionPoolConfiguration};
 
 use crate::configuration::Environment;
-use storage::database::tezedge_database::TezedgeDatabaseBackendConfiguration;
-use storage::initializer::initialize_maindb;
+use storage::database::tezedge_database::{TezedgeDatabase, TezedgeDatabaseBackendConfiguration};
+use storage::database::write_policy::FsyncPolicy;
+use storage::initializer::{initialize_maindb, DbsRocksDbTableInitializer};
 
 mod configuration;
 mod identity;
@@ -227,6 +228,7 @@ fn block_on_actors(
     let local_current_head_state = init_current_head_state();
     let remote_current_head_state = init_current_head_state();
     let current_mempool_state_storage = init_mempool_state_storage();
+    let ip_blacklist = init_ip_blacklist();
     let bootstrap_state = init_synchronization_bootstrap_state_storage(
         env.p2p
             .peer_threshold
@@ -330,6 +332,7 @@ fn block_on_actors(
         tokio_runtime.handle().clone(),
         &persistent_storage,
         current_mempool_state_storage,
+        ip_blacklist.clone(),
         tezos_readonly_api_pool.clone(),
         tezos_readonly_prevalidation_api_pool.clone(),
         tezos_without_context_api_pool.clone(),
@@ -364,6 +367,7 @@ fn block_on_actors(
             shell_compatibility_version,
             env.p2p,
             env.identity.expected_pow,
+            ip_blacklist,
         )
         .expect("Failed to create peer manager");
     }
@@ -578,6 +582,84 @@ fn set_gcov_handler() {
     });
 }
 
+/// Periodically compacts all main database columns, logging the reclaimed space.
+fn spawn_maindb_maintenance(maindb: Arc<TezedgeDatabase>, interval: Duration, log: Logger) {
+    let thread_log = log.clone();
+    let spawned = std::thread::Builder::new()
+        .name("maindb-maintenance".to_string())
+        .spawn(move || loop {
+            let log = &thread_log;
+            std::thread::sleep(interval);
+
+            let total_size = |maindb: &TezedgeDatabase| {
+                maindb
+                    .approximate_sizes()
+                    .map(|sizes| sizes.iter().map(|s| s.approximate_bytes).sum::<u64>())
+            };
+
+            let before = total_size(&maindb);
+            for column in DbsRocksDbTableInitializer::column_names() {
+                if let Err(e) = maindb.compact_range(column) {
+                    warn!(log, "Main database compaction failed"; "column" => column, "reason" => format!("{}", e));
+                }
+            }
+            match (before, total_size(&maindb)) {
+                (Ok(before), Ok(after)) => {
+                    info!(log, "Main database compacted"; "size_before" => before, "size_after" => after)
+                }
+                (Err(e), _) | (_, Err(e)) => {
+                    warn!(log, "Failed to read main database size"; "reason" => format!("{}", e))
+                }
+            }
+        });
+
+    if let Err(e) = spawned {
+        warn!(log, "Failed to start main database maintenance thread"; "reason" => format!("{}", e));
+    }
+}
+
+/// Interval of the write statistics of the main database logged by its flusher.
+const MAINDB_WRITE_STATS_LOG_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Syncs the pending writes of the main database every `flush_interval` of its write policy,
+/// and logs its write statistics every [`MAINDB_WRITE_STATS_LOG_INTERVAL`].
+fn spawn_maindb_flusher(maindb: &Arc<TezedgeDatabase>, log: Logger) {
+    let interval = maindb.write_policy().flush_interval;
+    let maindb = Arc::downgrade(maindb);
+    let thread_log = log.clone();
+    let spawned = std::thread::Builder::new()
+        .name("maindb-flusher".to_string())
+        .spawn(move || {
+            let mut last_stats_log = Instant::now();
+
+            loop {
+                std::thread::sleep(interval);
+
+                let maindb = match maindb.upgrade() {
+                    Some(maindb) => maindb,
+                    None => break,
+                };
+                if let Err(e) = maindb.sync_pending() {
+                    warn!(thread_log, "Failed to sync main database"; "reason" => format!("{}", e));
+                }
+
+                if last_stats_log.elapsed() >= MAINDB_WRITE_STATS_LOG_INTERVAL {
+                    last_stats_log = Instant::now();
+                    let stats = maindb.write_stats();
+                    info!(thread_log, "Main database writes";
+                                      "writes" => stats.writes,
+                                      "bytes" => stats.bytes,
+                                      "syncs" => stats.syncs,
+                                      "writes_per_sync" => stats.writes_per_sync().map(|writes| format!("{:.1}", writes)));
+                }
+            }
+        });
+
+    if let Err(e) = spawned {
+        warn!(log, "Failed to start main database flusher thread"; "reason" => format!("{}", e));
+    }
+}
+
 fn main() {
     #[cfg(dyncov)]
     set_gcov_handler();
@@ -675,6 +757,7 @@ fn main() {
             env.storage.db.expected_db_version,
             &main_chain,
             env.storage.main_db,
+            env.storage.maindb_write_policy,
         )
         .expect("Failed to create/initialize MainDB database (db)"),
         TezedgeDatabaseBackendConfiguration::RocksDB => {
@@ -688,11 +771,19 @@ fn main() {
                 env.storage.db.expected_db_version,
                 &main_chain,
                 env.storage.main_db,
+                env.storage.maindb_write_policy,
             )
             .expect("Failed to create/initialize MainDB database (db)")
         }
     };
 
+    if let Some(interval) = env.storage.maindb_compaction_interval {
+        spawn_maindb_maintenance(maindb.clone(), interval, log.clone());
+    }
+    if env.storage.maindb_write_policy.fsync == FsyncPolicy::Interval {
+        spawn_maindb_flusher(&maindb, log.clone());
+    }
+
     let commit_logs = Arc::new(
         open_cl(&env.storage.db_path, vec![BlockStorage::descriptor()])
             .expect("Failed to open plain block_header storage"),
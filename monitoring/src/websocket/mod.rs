@@ -12,7 +12,13 @@ mod ws_server;
 
 pub use ws_manager::{WebsocketHandler, WebsocketHandlerMsg};
 
+use ws_messages::Subscriptions;
+
 // keep the clients in a HashMap
 // not using a HashSet, becouse UnboundedSender does not implement Eq
 type Clients = Arc<RwLock<HashMap<String, Client>>>;
-type Client = Option<mpsc::UnboundedSender<std::result::Result<Message, warp::Error>>>;
+
+struct Client {
+    sender: mpsc::UnboundedSender<std::result::Result<Message, warp::Error>>,
+    subscriptions: Subscriptions,
+}
@@ -121,20 +121,32 @@ impl Receive<WebsocketMessageWrapper> for WebsocketHandler {
 
         self.tokio_executor.spawn(async move {
             let clients = clients.read().await;
-            if !clients.is_empty() {
-                match serde_json::to_string(&msg.messages) {
-                    Ok(serialized) => {
-                        clients.iter().for_each(|(_, client_sender)| {
-                            if let Some(sender) = &client_sender {
-                                let _ = sender.send(Ok(Message::text(serialized.clone())));
-                            }
-                        });
-                    }
+            if clients.is_empty() {
+                return;
+            }
+
+            let mut serialized = Vec::with_capacity(msg.messages.len());
+            for message in &msg.messages {
+                match serde_json::to_string(message) {
+                    Ok(json) => serialized.push((message.topic(), json)),
                     Err(err) => {
-                        warn!(log, "Failed to serialize message"; "message" => msg, "reason" => format!("{:?}", err))
+                        warn!(log, "Failed to serialize message"; "message" => msg.clone(), "reason" => format!("{:?}", err));
+                        return;
                     }
                 }
             }
+
+            clients.values().for_each(|client| {
+                let messages = serialized
+                    .iter()
+                    .filter(|(topic, _)| client.subscriptions.contains(*topic))
+                    .map(|(_, json)| json.as_str())
+                    .collect::<Vec<_>>();
+                if !messages.is_empty() {
+                    let text = format!("[{}]", messages.join(","));
+                    let _ = client.sender.send(Ok(Message::text(text)));
+                }
+            });
         });
     }
 }
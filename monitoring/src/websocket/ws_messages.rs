@@ -1,7 +1,9 @@
 // Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
-use serde::Serialize;
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
 use slog_derive::SerdeValue;
 
 use crate::monitors::ChainMonitor;
@@ -133,6 +135,21 @@ pub enum WebsocketMessage {
     ChainStatus { payload: ChainMonitor },
 }
 
+impl WebsocketMessage {
+    pub fn topic(&self) -> WebsocketTopic {
+        match self {
+            WebsocketMessage::PeersMetrics { .. } => WebsocketTopic::PeersMetrics,
+            WebsocketMessage::PeerStatus { .. } => WebsocketTopic::PeerStatus,
+            WebsocketMessage::IncomingTransfer { .. } => WebsocketTopic::IncomingTransfer,
+            WebsocketMessage::BlockStatus { .. } => WebsocketTopic::BlockStatus,
+            WebsocketMessage::BlockApplicationStatus { .. } => {
+                WebsocketTopic::BlockApplicationStatus
+            }
+            WebsocketMessage::ChainStatus { .. } => WebsocketTopic::ChainStatus,
+        }
+    }
+}
+
 #[derive(SerdeValue, Serialize, Clone, Debug)]
 pub struct WebsocketMessageWrapper {
     pub messages: Vec<WebsocketMessage>,
@@ -165,3 +182,96 @@ pub struct BlockInfo {
     pub(crate) hash: String,
     pub(crate) level: i32,
 }
+
+// -------------------------- SUBSCRIPTIONS -------------------------- //
+/// Topic of a [WebsocketMessage], named as its `type`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum WebsocketTopic {
+    PeersMetrics,
+    PeerStatus,
+    IncomingTransfer,
+    BlockStatus,
+    BlockApplicationStatus,
+    ChainStatus,
+}
+
+/// Message sent by a websocket client, e.g. `{"subscribe": ["peerStatus", "chainStatus"]}`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum WebsocketClientMessage {
+    Subscribe(Vec<WebsocketTopic>),
+    Unsubscribe(Vec<WebsocketTopic>),
+}
+
+/// Topics a client is subscribed to.
+///
+/// A new client receives all the topics, until it subscribes or unsubscribes explicitly.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Subscriptions(Option<HashSet<WebsocketTopic>>);
+
+impl Subscriptions {
+    pub fn contains(&self, topic: WebsocketTopic) -> bool {
+        match &self.0 {
+            Some(topics) => topics.contains(&topic),
+            None => true,
+        }
+    }
+
+    pub fn update(&mut self, message: WebsocketClientMessage) {
+        match message {
+            WebsocketClientMessage::Subscribe(topics) => {
+                self.0.get_or_insert_with(HashSet::new).extend(topics);
+            }
+            WebsocketClientMessage::Unsubscribe(topics) => {
+                let subscribed = self.0.get_or_insert_with(|| {
+                    vec![
+                        WebsocketTopic::PeersMetrics,
+                        WebsocketTopic::PeerStatus,
+                        WebsocketTopic::IncomingTransfer,
+                        WebsocketTopic::BlockStatus,
+                        WebsocketTopic::BlockApplicationStatus,
+                        WebsocketTopic::ChainStatus,
+                    ]
+                    .into_iter()
+                    .collect()
+                });
+                for topic in topics {
+                    subscribed.remove(&topic);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscriptions() {
+        let mut subscriptions = Subscriptions::default();
+        assert!(subscriptions.contains(WebsocketTopic::PeerStatus));
+        assert!(subscriptions.contains(WebsocketTopic::ChainStatus));
+
+        let message: WebsocketClientMessage =
+            serde_json::from_str(r#"{"subscribe": ["peerStatus", "blockStatus"]}"#).unwrap();
+        subscriptions.update(message);
+        assert!(subscriptions.contains(WebsocketTopic::PeerStatus));
+        assert!(subscriptions.contains(WebsocketTopic::BlockStatus));
+        assert!(!subscriptions.contains(WebsocketTopic::ChainStatus));
+
+        subscriptions.update(WebsocketClientMessage::Unsubscribe(vec![
+            WebsocketTopic::PeerStatus,
+        ]));
+        assert!(!subscriptions.contains(WebsocketTopic::PeerStatus));
+        assert!(subscriptions.contains(WebsocketTopic::BlockStatus));
+
+        let mut subscriptions = Subscriptions::default();
+        subscriptions.update(WebsocketClientMessage::Unsubscribe(vec![
+            WebsocketTopic::PeersMetrics,
+        ]));
+        assert!(!subscriptions.contains(WebsocketTopic::PeersMetrics));
+        assert!(subscriptions.contains(WebsocketTopic::IncomingTransfer));
+    }
+}
@@ -4,17 +4,17 @@
 use std::convert::Infallible;
 use std::net::SocketAddr;
 
-use futures::{FutureExt, StreamExt};
+use futures::{SinkExt, StreamExt};
 use slog::{info, warn, Logger};
 use tokio::sync::mpsc;
-use tokio_stream::wrappers::UnboundedReceiverStream;
 use uuid::Uuid;
 use warp::http::StatusCode;
-use warp::ws::WebSocket;
+use warp::ws::{Message, WebSocket};
 use warp::Filter;
 use warp::{reject, Rejection, Reply};
 
-use crate::websocket::Clients;
+use crate::websocket::ws_messages::{Subscriptions, WebsocketClientMessage};
+use crate::websocket::{Client, Clients};
 
 pub async fn run_websocket(
     address: SocketAddr,
@@ -105,29 +105,62 @@ pub async fn ws_handler(
 }
 
 pub async fn client_connection(ws: WebSocket, clients: Clients, log: Logger) {
-    let (client_sender, client_rcv) = mpsc::unbounded_channel();
+    let (client_sender, mut client_rcv) = mpsc::unbounded_channel();
 
     // create an uuid to add to a hashmap
     let id = Uuid::new_v4().to_string();
-    clients
-        .write()
-        .await
-        .insert(id.clone(), Some(client_sender));
+    clients.write().await.insert(
+        id.clone(),
+        Client {
+            sender: client_sender,
+            subscriptions: Subscriptions::default(),
+        },
+    );
     info!(log, "New websocket connection detected"; "id" => &id);
 
-    // redirect channel to websocket
-    {
-        let log = log.clone();
-        let id = id.clone();
-
-        // wrap the reciever into a stream
-        UnboundedReceiverStream::new(client_rcv).forward(ws).map(move |result| {
-            if let Err(e) = result {
-                warn!(log, "Error sending message to connected websocket"; "id" => &id, "reason" => format!("{}", e));
-            }
-        }).await;
+    // redirect channel to websocket and handle the subscriptions sent by the client
+    let mut ws = ws;
+    loop {
+        tokio::select! {
+            outgoing = client_rcv.recv() => match outgoing {
+                Some(Ok(message)) => {
+                    if let Err(e) = ws.send(message).await {
+                        warn!(log, "Error sending message to connected websocket"; "id" => &id, "reason" => format!("{}", e));
+                        break;
+                    }
+                }
+                _ => break,
+            },
+            incoming = ws.next() => match incoming {
+                Some(Ok(message)) => handle_client_message(&message, &clients, &id, &log).await,
+                Some(Err(e)) => {
+                    warn!(log, "Error receiving message from connected websocket"; "id" => &id, "reason" => format!("{}", e));
+                    break;
+                }
+                None => break,
+            },
+        }
     }
 
     info!(log, "Websocket connection removed"; "id" => &id);
     clients.write().await.remove(&id);
 }
+
+/// Updates the subscriptions of the client, other messages are ignored.
+async fn handle_client_message(message: &Message, clients: &Clients, id: &str, log: &Logger) {
+    let text = match message.to_str() {
+        Ok(text) => text,
+        Err(_) => return,
+    };
+
+    match serde_json::from_str::<WebsocketClientMessage>(text) {
+        Ok(client_message) => {
+            if let Some(client) = clients.write().await.get_mut(id) {
+                client.subscriptions.update(client_message);
+            }
+        }
+        Err(e) => {
+            warn!(log, "Invalid message received from connected websocket"; "id" => id, "reason" => format!("{}", e));
+        }
+    }
+}
@@ -54,6 +54,8 @@ pub struct LocalPeerInfo {
     version: Arc<ShellCompatibilityVersion>,
     /// Target number for proof-of-work
     pow_target: f64,
+    /// Number of chunks traced during the handshakes, see [`p2p::chunk_trace`]
+    chunk_trace: Option<usize>,
 }
 
 impl LocalPeerInfo {
@@ -68,9 +70,16 @@ impl LocalPeerInfo {
             identity,
             version,
             pow_target,
+            chunk_trace: None,
         }
     }
 
+    /// Keeps the last `capacity` chunks of each handshake, logged when the handshake fails.
+    pub fn with_chunk_trace(mut self, capacity: usize) -> Self {
+        self.chunk_trace = Some(capacity).filter(|capacity| *capacity > 0);
+        self
+    }
+
     pub fn listener_port(&self) -> u16 {
         self.listener_port
     }
@@ -0,0 +1,202 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Trace of the last chunks exchanged with a peer during the handshake.
+//!
+//! When enabled (see [`crate::LocalPeerInfo::with_chunk_trace`]), every chunk sent or received
+//! by [`crate::p2p::peer::bootstrap`] is recorded as written on the wire, and the encrypted ones
+//! also as their content before the encryption (or after the decryption). Only the last
+//! `capacity` chunks are kept. When the handshake fails, the trace is logged as hex, with the
+//! kind of the message the chunk belongs to, to debug the interoperability with other
+//! implementations.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Max number of bytes of a chunk written in the dump, a peer may send up to 64kB of garbage.
+pub const MAX_DUMP_BYTES: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkDirection {
+    Sent,
+    Received,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkStage {
+    /// Bytes of the chunk on the wire, with the length prefix
+    Wire,
+    /// Content of the chunk before its encryption, or after its decryption
+    Plain,
+}
+
+/// A chunk recorded by the [`ChunkTrace`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TracedChunk {
+    pub direction: ChunkDirection,
+    pub stage: ChunkStage,
+    /// Kind of the message sent, or expected to be received
+    pub kind: &'static str,
+    pub bytes: Vec<u8>,
+}
+
+impl fmt::Display for TracedChunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let direction = match self.direction {
+            ChunkDirection::Sent => "sent",
+            ChunkDirection::Received => "received",
+        };
+        let stage = match self.stage {
+            ChunkStage::Wire => "wire",
+            ChunkStage::Plain => "plain",
+        };
+        write!(
+            f,
+            "{} {} {} ({} bytes): {}",
+            direction,
+            stage,
+            self.kind,
+            self.bytes.len(),
+            hex::encode(&self.bytes[..self.bytes.len().min(MAX_DUMP_BYTES)])
+        )?;
+        if self.bytes.len() > MAX_DUMP_BYTES {
+            write!(f, "...")?;
+        }
+        Ok(())
+    }
+}
+
+/// Ring buffer of the last chunks exchanged with a peer, see the [module documentation](self).
+#[derive(Debug)]
+pub struct ChunkTrace {
+    capacity: usize,
+    chunks: VecDeque<TracedChunk>,
+    /// Number of chunks evicted from the buffer
+    evicted: usize,
+}
+
+/// Trace shared by the reader and the writer of a connection.
+pub type ChunkTraceRef = Arc<Mutex<ChunkTrace>>;
+
+impl ChunkTrace {
+    /// The buffer grows with the chunks recorded, `capacity` is not allocated upfront.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            chunks: VecDeque::new(),
+            evicted: 0,
+        }
+    }
+
+    pub fn new_ref(capacity: usize) -> ChunkTraceRef {
+        Arc::new(Mutex::new(Self::new(capacity)))
+    }
+
+    pub fn record(
+        &mut self,
+        direction: ChunkDirection,
+        stage: ChunkStage,
+        kind: &'static str,
+        bytes: &[u8],
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.chunks.len() == self.capacity {
+            self.chunks.pop_front();
+            self.evicted += 1;
+        }
+        self.chunks.push_back(TracedChunk {
+            direction,
+            stage,
+            kind,
+            bytes: bytes.to_vec(),
+        });
+    }
+
+    /// Recorded chunks, from the oldest to the newest.
+    pub fn chunks(&self) -> impl Iterator<Item = &TracedChunk> {
+        self.chunks.iter()
+    }
+
+    pub fn evicted(&self) -> usize {
+        self.evicted
+    }
+
+    /// One line per recorded chunk, from the oldest to the newest.
+    pub fn dump(&self) -> Vec<String> {
+        self.chunks.iter().map(ToString::to_string).collect()
+    }
+}
+
+/// Records a chunk in an optional trace.
+pub(crate) fn record_chunk(
+    trace: &Option<ChunkTraceRef>,
+    direction: ChunkDirection,
+    stage: ChunkStage,
+    kind: &'static str,
+    bytes: &[u8],
+) {
+    if let Some(trace) = trace {
+        if let Ok(mut trace) = trace.lock() {
+            trace.record(direction, stage, kind, bytes);
+        }
+    }
+}
+
+/// Name of the message type `M`, without its path.
+pub(crate) fn message_kind<M>() -> &'static str {
+    let name = std::any::type_name::<M>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_buffer() {
+        let mut trace = ChunkTrace::new(2);
+        trace.record(ChunkDirection::Sent, ChunkStage::Plain, "A", &[1]);
+        trace.record(ChunkDirection::Sent, ChunkStage::Wire, "A", &[0, 1, 2]);
+        trace.record(ChunkDirection::Received, ChunkStage::Wire, "B", &[0, 1, 3]);
+
+        assert_eq!(trace.evicted(), 1);
+        assert_eq!(
+            trace.dump(),
+            vec![
+                "sent wire A (3 bytes): 000102".to_string(),
+                "received wire B (3 bytes): 000103".to_string(),
+            ]
+        );
+
+        let mut disabled = ChunkTrace::new(0);
+        disabled.record(ChunkDirection::Sent, ChunkStage::Plain, "A", &[1]);
+        assert_eq!(disabled.chunks().count(), 0);
+    }
+
+    #[test]
+    fn test_dump_truncated() {
+        let mut trace = ChunkTrace::new(1);
+        trace.record(
+            ChunkDirection::Received,
+            ChunkStage::Plain,
+            "A",
+            &[0xff; MAX_DUMP_BYTES + 1],
+        );
+
+        let dump = trace.dump();
+        assert!(dump[0].starts_with("received plain A (1025 bytes): ffff"));
+        assert!(dump[0].ends_with("ff..."));
+        assert_eq!(
+            dump[0].len(),
+            "received plain A (1025 bytes): ".len() + 2 * MAX_DUMP_BYTES + 3
+        );
+    }
+
+    #[test]
+    fn test_message_kind() {
+        assert_eq!(message_kind::<ChunkTrace>(), "ChunkTrace");
+    }
+}
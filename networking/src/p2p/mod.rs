@@ -3,6 +3,7 @@
 
 //! This module handles low level p2p communication.
 
+pub mod chunk_trace;
 pub mod network_channel;
 pub mod peer;
 pub mod stream;
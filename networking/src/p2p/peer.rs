@@ -41,6 +41,9 @@ use crate::{LocalPeerInfo, PeerId};
 
 use self::quota::ThrottleQuota;
 
+use super::chunk_trace::{
+    message_kind, record_chunk, ChunkDirection, ChunkStage, ChunkTrace, ChunkTraceRef,
+};
 use super::network_channel::{NetworkChannelRef, NetworkChannelTopic, PeerMessageReceived};
 use super::stream::{EncryptedMessageReader, EncryptedMessageWriter, MessageStream, StreamError};
 
@@ -342,7 +345,7 @@ impl Actor for Peer {
             }, None);
 
             // begin to process incoming messages in a loop
-            begin_process_incoming(net, myself.clone(), network_channel, throttle_quota, log.clone()).await;
+            begin_process_incoming(net, myself.clone(), peer_id, network_channel, throttle_quota, log.clone()).await;
 
             // connection to peer was closed, stop this actor
             system.stop(myself);
@@ -442,6 +445,32 @@ pub async fn bootstrap(
     msg: Bootstrap,
     info: Arc<LocalPeerInfo>,
     log: &Logger,
+) -> Result<BootstrapOutput, PeerError> {
+    let trace = info.chunk_trace.map(ChunkTrace::new_ref);
+    let address = msg.address;
+
+    let result = bootstrap_traced(msg, info, &trace, log).await;
+
+    if let (Err(error), Some(trace)) = (&result, &trace) {
+        if let Ok(trace) = trace.lock() {
+            warn!(log, "Handshake failed, dumping the last chunks exchanged";
+                       "ip" => address,
+                       "reason" => format!("{}", error),
+                       "evicted" => trace.evicted());
+            for chunk in trace.dump() {
+                warn!(log, "Handshake chunk"; "ip" => address, "chunk" => chunk);
+            }
+        }
+    }
+
+    result
+}
+
+async fn bootstrap_traced(
+    msg: Bootstrap,
+    info: Arc<LocalPeerInfo>,
+    trace: &Option<ChunkTraceRef>,
+    log: &Logger,
 ) -> Result<BootstrapOutput, PeerError> {
     let (mut msg_rx, mut msg_tx) = {
         let stream = msg
@@ -466,6 +495,13 @@ pub async fn bootstrap(
     )?;
     let connection_message_sent = {
         let connection_message_bytes = BinaryChunk::from_content(&connection_message.as_bytes()?)?;
+        record_chunk(
+            trace,
+            ChunkDirection::Sent,
+            ChunkStage::Wire,
+            message_kind::<ConnectionMessage>(),
+            connection_message_bytes.raw(),
+        );
         match timeout(IO_TIMEOUT, msg_tx.write_message(&connection_message_bytes)).await? {
             Ok(_) => connection_message_bytes,
             Err(e) => {
@@ -488,6 +524,13 @@ pub async fn bootstrap(
             })
         }
     };
+    record_chunk(
+        trace,
+        ChunkDirection::Received,
+        ChunkStage::Wire,
+        message_kind::<ConnectionMessage>(),
+        received_connection_message_bytes.raw(),
+    );
 
     let connection_message =
         ConnectionMessage::from_bytes(received_connection_message_bytes.content())?;
@@ -534,6 +577,8 @@ pub async fn bootstrap(
     let mut msg_rx =
         EncryptedMessageReader::new(msg_rx, precomputed_key.clone(), nonce_remote, log.clone());
     let mut msg_tx = EncryptedMessageWriter::new(msg_tx, precomputed_key, nonce_local, log.clone());
+    msg_rx.set_trace(trace.clone());
+    msg_tx.set_trace(trace.clone());
 
     // send metadata
     let metadata = MetadataMessage::new(msg.disable_mempool, msg.private_node);
@@ -590,6 +635,9 @@ pub async fn bootstrap(
     match ack_received {
         AckMessage::Ack => {
             debug!(log, "Received ACK");
+            // only the handshake is traced
+            msg_rx.set_trace(None);
+            msg_tx.set_trace(None);
             Ok(BootstrapOutput(
                 Arc::new(Mutex::new(Some(msg_rx))),
                 Arc::new(Mutex::new(Some(msg_tx))),
@@ -627,6 +675,7 @@ fn generate_nonces(
 async fn begin_process_incoming(
     net: Network,
     myself: PeerRef,
+    peer_id: Arc<PeerId>,
     event_channel: NetworkChannelRef,
     throttle_quota: Arc<std::sync::Mutex<ThrottleQuota>>,
     log: Logger,
@@ -668,8 +717,23 @@ async fn begin_process_incoming(
                     BinaryReaderError::UnknownTag(tag) => {
                         warn!(log, "Messages with unsupported tags are ignored"; "tag" => tag);
                     }
+                    error @ BinaryReaderError::TooLarge { .. } => {
+                        warn!(log, "Peer sent too large message - blacklisting peer"; "reason" => format!("{}", error));
+                        event_channel.tell(
+                            Publish {
+                                msg: NetworkChannelMsg::BlacklistPeer(
+                                    peer_id.clone(),
+                                    format!("{}", error),
+                                ),
+                                topic: NetworkChannelTopic::NetworkCommands.into(),
+                            },
+                            None,
+                        );
+                        break;
+                    }
                     error => {
-                        warn!(log, "Failed to read peer message"; "reason" => StreamError::DeserializationError{ error });
+                        let path = error.path().unwrap_or_default().to_string();
+                        warn!(log, "Failed to read peer message"; "path" => path, "reason" => StreamError::DeserializationError{ error });
                         break;
                     }
                 },
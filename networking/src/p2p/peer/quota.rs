@@ -2,30 +2,9 @@
 // SPDX-License-Identifier: MIT
 
 use slog::{warn, Logger};
-use tezos_messages::p2p::encoding::peer::{PeerMessage, PeerMessageResponse};
+use tezos_messages::p2p::encoding::peer::{PeerMessageKind, PeerMessageResponse};
 
-const THROTTLING_QUOTA_NUM: usize = 18;
-
-const THROTTLING_QUOTA_STRS: [&str; THROTTLING_QUOTA_NUM] = [
-    "Disconnect",
-    "Advertise",
-    "SwapRequest",
-    "SwapAck",
-    "Bootstrap",
-    "GetCurrentBranch",
-    "CurrentBranch",
-    "Deactivate",
-    "GetCurrentHead",
-    "CurrentHead",
-    "GetBlockHeaders",
-    "BlockHeader",
-    "GetOperations",
-    "Operation",
-    "GetProtocols",
-    "Protocol",
-    "GetOperationsForBlocks",
-    "OperationsForBlocks",
-];
+const THROTTLING_QUOTA_NUM: usize = PeerMessageKind::ALL.len();
 
 pub const THROTTLING_QUOTA_RESET_MS_DEFAULT: u64 = 5000; // 5 secs
 
@@ -71,13 +50,13 @@ lazy_static::lazy_static! {
             (5000, 5000), // GetOperationsForBlocks
             (10000, 10000), // OperationsForBlocks
         ];
-        for (i, s) in THROTTLING_QUOTA_STRS.iter().enumerate() {
-            let var = "THROTTLING_QUOTA_".to_owned() + &s.to_uppercase();
+        for kind in PeerMessageKind::ALL.iter() {
+            let var = "THROTTLING_QUOTA_".to_owned() + &kind.as_str().to_uppercase();
             if let Ok(val) = std::env::var(var).or_else(|_| std::env::var("THROTTLING_QUOTA_MAX")) {
                 let q = val.split(",").collect::<Vec<_>>();
                 if q.len() == 2 {
                     if let (Ok(tx), Ok(rx)) = (q[0].parse::<isize>(), q[1].parse::<isize>()) {
-                        default[i] = (tx, rx);
+                        default[kind.index()] = (tx, rx);
                     }
                 }
             }
@@ -106,35 +85,14 @@ impl ThrottleQuota {
     }
 
     pub(crate) fn msg_index(msg: &PeerMessageResponse) -> usize {
-        debug_assert!(THROTTLING_QUOTA_NUM == 18);
-        match msg.message() {
-            PeerMessage::Disconnect => 0,
-            PeerMessage::Advertise(_) => 1,
-            PeerMessage::SwapRequest(_) => 2,
-            PeerMessage::SwapAck(_) => 3,
-            PeerMessage::Bootstrap => 4,
-            PeerMessage::GetCurrentBranch(_) => 5,
-            PeerMessage::CurrentBranch(_) => 6,
-            PeerMessage::Deactivate(_) => 7,
-            PeerMessage::GetCurrentHead(_) => 8,
-            PeerMessage::CurrentHead(_) => 9,
-            PeerMessage::GetBlockHeaders(_) => 10,
-            PeerMessage::BlockHeader(_) => 11,
-            PeerMessage::GetOperations(_) => 12,
-            PeerMessage::Operation(_) => 13,
-            PeerMessage::GetProtocols(_) => 14,
-            PeerMessage::Protocol(_) => 15,
-            PeerMessage::GetOperationsForBlocks(_) => 16,
-            PeerMessage::OperationsForBlocks(_) => 17,
-        }
+        msg.message().kind().index()
     }
 
     fn index_to_str(index: usize) -> &'static str {
-        if index < THROTTLING_QUOTA_NUM {
-            THROTTLING_QUOTA_STRS[index]
-        } else {
-            "<invalid index>"
-        }
+        PeerMessageKind::ALL
+            .get(index)
+            .map(|kind| kind.as_str())
+            .unwrap_or("<invalid index>")
     }
 
     pub fn can_send(&mut self, msg: &PeerMessageResponse) -> bool {
@@ -7,6 +7,7 @@
 
 use std::convert::TryInto;
 use std::io;
+use std::task::Poll;
 
 use bytes::Buf;
 use core::time::Duration;
@@ -24,18 +25,21 @@ use tezos_encoding::{binary_reader::BinaryReaderError, binary_writer::BinaryWrit
 use tezos_messages::p2p::binary_message::{
     BinaryChunk, BinaryChunkError, BinaryMessage, SizeFromChunk, CONTENT_LENGTH_FIELD_BYTES,
 };
+use tezos_messages::p2p::chunk_crypto::{ChunkCryptoError, ChunkDecrypter, ChunkEncrypter};
+use tezos_messages::p2p::decoder::MessageDecoder;
+
+use super::chunk_trace::{message_kind, record_chunk, ChunkDirection, ChunkStage, ChunkTraceRef};
 
 /// Max allowed content length in bytes when taking into account extra data added by encryption
-pub const CONTENT_LENGTH_MAX: usize =
-    tezos_messages::p2p::binary_message::CONTENT_LENGTH_MAX - crypto::crypto_box::BOX_ZERO_BYTES;
+pub const CONTENT_LENGTH_MAX: usize = tezos_messages::p2p::chunk_crypto::CHUNK_CONTENT_MAX;
 
 /// This is common error that might happen when communicating with peer over the network.
 #[derive(Debug, Error)]
 pub enum StreamError {
-    #[error("Failed to encrypt message")]
-    FailedToEncryptMessage { error: CryptoError },
-    #[error("Failed to decrypt message")]
-    FailedToDecryptMessage { error: CryptoError },
+    #[error("Failed to encrypt message: {error}")]
+    FailedToEncryptMessage { error: ChunkCryptoError },
+    #[error("Failed to decrypt message: {error}")]
+    FailedToDecryptMessage { error: ChunkCryptoError },
     #[error("Message serialization error: {error}")]
     SerializationError { error: BinaryWriterError },
     #[error("Message de-serialization error: {error}")]
@@ -137,22 +141,20 @@ impl Crypto {
         }
     }
 
-    #[inline]
-    fn nonce_fetch_increment(&mut self) -> Nonce {
-        let nonce = self.nonce.increment();
-        std::mem::replace(&mut self.nonce, nonce)
-    }
-
+    /// Encrypts `data`, the nonce moves forward only on success.
     #[inline]
     pub fn encrypt<T: AsRef<[u8]>>(&mut self, data: &T) -> Result<Vec<u8>, CryptoError> {
-        let nonce = self.nonce_fetch_increment();
-        self.precomputed_key.encrypt(data.as_ref(), &nonce)
+        let encrypted = self.precomputed_key.encrypt(data.as_ref(), &self.nonce)?;
+        self.nonce = self.nonce.increment();
+        Ok(encrypted)
     }
 
+    /// Decrypts `data`, the nonce moves forward only on success.
     #[inline]
     pub fn decrypt<T: AsRef<[u8]>>(&mut self, data: &T) -> Result<Vec<u8>, CryptoError> {
-        let nonce = self.nonce_fetch_increment();
-        self.precomputed_key.decrypt(data.as_ref(), &nonce)
+        let decrypted = self.precomputed_key.decrypt(data.as_ref(), &self.nonce)?;
+        self.nonce = self.nonce.increment();
+        Ok(decrypted)
     }
 }
 
@@ -221,7 +223,9 @@ pub struct EncryptedMessageWriterBase<W> {
     /// Outgoing message writer
     tx: MessageWriterBase<W>,
     /// To encrypt data
-    crypto: Crypto,
+    encrypter: ChunkEncrypter,
+    /// Records the chunks sent, when enabled
+    trace: Option<ChunkTraceRef>,
     /// Logger
     log: Logger,
 }
@@ -235,29 +239,48 @@ impl<W: AsyncWrite + Unpin> EncryptedMessageWriterBase<W> {
     ) -> Self {
         EncryptedMessageWriterBase {
             tx,
-            crypto: Crypto {
-                precomputed_key,
-                nonce: nonce_local,
-            },
+            encrypter: ChunkEncrypter::new(precomputed_key, nonce_local),
+            trace: None,
             log,
         }
     }
 
-    pub async fn write_message<'a>(
+    /// Records the chunks sent in `trace`, or stops recording them.
+    pub fn set_trace(&mut self, trace: Option<ChunkTraceRef>) {
+        self.trace = trace;
+    }
+
+    pub async fn write_message<'a, M: BinaryMessage>(
         &'a mut self,
-        message: &'a impl BinaryMessage,
+        message: &'a M,
     ) -> Result<(), StreamError> {
         let message_bytes = message.as_bytes()?;
         trace!(self.log, "Writing message"; "message" => FnValue(|_| hex::encode(&message_bytes)));
 
         for chunk_content_bytes in message_bytes.chunks(CONTENT_LENGTH_MAX) {
-            let message_bytes_encrypted = match self.crypto.encrypt(&chunk_content_bytes) {
-                Ok(msg) => msg,
-                Err(error) => return Err(StreamError::FailedToEncryptMessage { error }),
-            };
+            let chunk = self
+                .encrypter
+                .encrypt_chunk(chunk_content_bytes)
+                .map_err(|error| StreamError::FailedToEncryptMessage { error })?;
 
             // send
-            let chunk = BinaryChunk::from_content(&message_bytes_encrypted)?;
+            if self.trace.is_some() {
+                let kind = message_kind::<M>();
+                record_chunk(
+                    &self.trace,
+                    ChunkDirection::Sent,
+                    ChunkStage::Plain,
+                    kind,
+                    chunk_content_bytes,
+                );
+                record_chunk(
+                    &self.trace,
+                    ChunkDirection::Sent,
+                    ChunkStage::Wire,
+                    kind,
+                    chunk.raw(),
+                );
+            }
             self.tx.write_message(&chunk).await?;
         }
 
@@ -270,10 +293,12 @@ impl<W: AsyncWrite + Unpin> EncryptedMessageWriterBase<W> {
 pub type EncryptedMessageReader = EncryptedMessageReaderBase<BufReader<ReadHalf<TcpStream>>>;
 
 pub struct EncryptedMessageReaderBase<A> {
-    /// To encrypt data
-    crypto: Crypto,
+    /// To decrypt data
+    decrypter: ChunkDecrypter,
     /// Incoming message reader
     rx: MessageReaderBase<A>,
+    /// Records the chunks received, when enabled
+    trace: Option<ChunkTraceRef>,
     /// Logger
     log: Logger,
 }
@@ -288,41 +313,49 @@ impl<A: AsyncRead + Unpin + Send> EncryptedMessageReaderBase<A> {
     ) -> Self {
         EncryptedMessageReaderBase {
             rx,
-            crypto: Crypto {
-                precomputed_key,
-                nonce: nonce_remote,
-            },
+            decrypter: ChunkDecrypter::new(precomputed_key, nonce_remote),
+            trace: None,
             log,
         }
     }
 
+    /// Records the chunks received in `trace`, or stops recording them.
+    pub fn set_trace(&mut self, trace: Option<ChunkTraceRef>) {
+        self.trace = trace;
+    }
+
     /// Consume content of inner message reader into specific message
     pub async fn read_message<M>(&mut self) -> Result<M, StreamError>
     where
         M: BinaryMessage + SizeFromChunk,
     {
-        let mut input_size = 0;
-        let mut input_data = vec![];
+        let mut decoder = MessageDecoder::<M>::new();
 
         loop {
             // read
             let message_encrypted = self.rx.read_message().await?;
+            record_chunk(
+                &self.trace,
+                ChunkDirection::Received,
+                ChunkStage::Wire,
+                message_kind::<M>(),
+                message_encrypted.raw(),
+            );
 
             // decrypt
-            match self.crypto.decrypt(&message_encrypted.content()) {
-                Ok(mut message_decrypted) => {
+            match self.decrypter.decrypt_chunk(&message_encrypted) {
+                Ok(message_decrypted) => {
                     trace!(self.log, "Message received"; "message" => FnValue(|_| hex::encode(&message_decrypted)));
-
-                    if input_size == 0 {
-                        input_size = M::size_from_chunk(&message_decrypted)?;
-                    }
-                    input_data.append(&mut message_decrypted);
-
-                    if input_size <= input_data.len() {
-                        match M::from_bytes(&input_data) {
-                            Ok(message) => break Ok(message),
-                            Err(e) => break Err(e.into()),
-                        }
+                    record_chunk(
+                        &self.trace,
+                        ChunkDirection::Received,
+                        ChunkStage::Plain,
+                        message_kind::<M>(),
+                        &message_decrypted,
+                    );
+
+                    if let Poll::Ready(result) = decoder.feed(&message_decrypted) {
+                        break result.map_err(StreamError::from);
                     }
                 }
                 Err(error) => {
@@ -27,7 +27,7 @@ pub(crate) fn options() -> ServiceResult {
         .header(hyper::header::ACCESS_CONTROL_ALLOW_HEADERS, "content-type")
         .header(
             hyper::header::ACCESS_CONTROL_ALLOW_METHODS,
-            "GET, POST, OPTIONS, PUT",
+            "GET, POST, OPTIONS, PUT, DELETE",
         )
         .body(Body::empty())?)
 }
@@ -42,7 +42,7 @@ pub fn make_json_response<T: serde::Serialize>(content: &T) -> ServiceResult {
         .header(hyper::header::ACCESS_CONTROL_ALLOW_HEADERS, "content-type")
         .header(
             hyper::header::ACCESS_CONTROL_ALLOW_METHODS,
-            "GET, POST, OPTIONS, PUT",
+            "GET, POST, OPTIONS, PUT, DELETE",
         )
         .body(Body::from(serde_json::to_string(content)?))?)
 }
@@ -56,7 +56,7 @@ pub fn make_raw_response(raw: &'static [u8]) -> ServiceResult {
         .header(hyper::header::ACCESS_CONTROL_ALLOW_HEADERS, "content-type")
         .header(
             hyper::header::ACCESS_CONTROL_ALLOW_METHODS,
-            "GET, POST, OPTIONS, PUT",
+            "GET, POST, OPTIONS, PUT, DELETE",
         )
         .body(Body::from(raw))?)
 }
@@ -71,7 +71,7 @@ pub fn make_response_with_status_and_json_string(status_code: u16, body: &str) -
         .header(hyper::header::ACCESS_CONTROL_ALLOW_HEADERS, "content-type")
         .header(
             hyper::header::ACCESS_CONTROL_ALLOW_METHODS,
-            "GET, POST, OPTIONS, PUT",
+            "GET, POST, OPTIONS, PUT, DELETE",
         )
         .status(status_code)
         .body(Body::from(body.to_owned()))?)
@@ -90,7 +90,7 @@ pub(crate) fn make_json_stream_response<
         .header(hyper::header::ACCESS_CONTROL_ALLOW_HEADERS, "content-type")
         .header(
             hyper::header::ACCESS_CONTROL_ALLOW_METHODS,
-            "GET, POST, OPTIONS, PUT",
+            "GET, POST, OPTIONS, PUT, DELETE",
         )
         .body(Body::wrap_stream(content))?)
 }
@@ -12,6 +12,7 @@ use tokio::runtime::Handle;
 
 use crypto::hash::ChainId;
 use shell::mempool::CurrentMempoolStateStorageRef;
+use shell::peer_manager::IpBlacklistRef;
 use shell::shell_channel::{ShellChannelMsg, ShellChannelRef};
 use shell::subscription::subscribe_to_shell_new_current_head;
 use storage::PersistentStorage;
@@ -56,6 +57,7 @@ impl RpcServer {
         tokio_executor: Handle,
         persistent_storage: &PersistentStorage,
         current_mempool_state_storage: CurrentMempoolStateStorageRef,
+        ip_blacklist: IpBlacklistRef,
         tezos_readonly_api: Arc<TezosApiConnectionPool>,
         tezos_readonly_prevalidation_api: Arc<TezosApiConnectionPool>,
         tezos_without_context_api: Arc<TezosApiConnectionPool>,
@@ -80,6 +82,7 @@ impl RpcServer {
             network_version,
             persistent_storage,
             current_mempool_state_storage,
+            ip_blacklist,
             tezos_readonly_api,
             tezos_readonly_prevalidation_api,
             tezos_without_context_api,
@@ -19,6 +19,7 @@ use tokio::runtime::Handle;
 
 use crypto::hash::{BlockHash, ChainId};
 use shell::mempool::CurrentMempoolStateStorageRef;
+use shell::peer_manager::IpBlacklistRef;
 use shell::shell_channel::ShellChannelRef;
 use storage::PersistentStorage;
 use tezos_api::environment::TezosEnvironmentConfiguration;
@@ -46,6 +47,8 @@ pub struct RpcServiceEnvironment {
     #[get = "pub(crate)"]
     current_mempool_state_storage: CurrentMempoolStateStorageRef,
     #[get = "pub(crate)"]
+    ip_blacklist: IpBlacklistRef,
+    #[get = "pub(crate)"]
     state: RpcCollectedStateRef,
     #[get = "pub(crate)"]
     shell_channel: ShellChannelRef,
@@ -85,6 +88,7 @@ impl RpcServiceEnvironment {
         network_version: Arc<NetworkVersion>,
         persistent_storage: &PersistentStorage,
         current_mempool_state_storage: CurrentMempoolStateStorageRef,
+        ip_blacklist: IpBlacklistRef,
         tezos_readonly_api: Arc<TezosApiConnectionPool>,
         tezos_readonly_prevalidation_api: Arc<TezosApiConnectionPool>,
         tezos_without_context_api: Arc<TezosApiConnectionPool>,
@@ -104,6 +108,7 @@ impl RpcServiceEnvironment {
             network_version,
             persistent_storage: persistent_storage.clone(),
             current_mempool_state_storage,
+            ip_blacklist,
             main_chain_id,
             main_chain_genesis_hash,
             state,
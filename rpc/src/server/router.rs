@@ -107,6 +107,26 @@ pub(crate) fn create_routes(tezedge_is_enabled: bool) -> PathTree<MethodHandler>
         "/chains/:chain_id/mempool/request_operations",
         shell_handler::mempool_request_operations,
     );
+    routes.handle(
+        hash_set![Method::POST],
+        "/chains/:chain_id/mempool/ban_operation",
+        shell_handler::mempool_ban_operation,
+    );
+    routes.handle(
+        hash_set![Method::POST],
+        "/chains/:chain_id/mempool/unban_operation",
+        shell_handler::mempool_unban_operation,
+    );
+    routes.handle(
+        hash_set![Method::POST],
+        "/chains/:chain_id/mempool/unban_all_operations",
+        shell_handler::mempool_unban_all_operations,
+    );
+    routes.handle(
+        hash_set![Method::GET],
+        "/chains/:chain_id/mempool/banned_operations",
+        shell_handler::mempool_banned_operations,
+    );
     routes.handle(
         hash_set![Method::GET],
         "/chains/:chain_id/blocks/:block_id/protocols",
@@ -318,6 +338,21 @@ pub(crate) fn create_routes(tezedge_is_enabled: bool) -> PathTree<MethodHandler>
         "/network/version",
         shell_handler::node_version,
     );
+    routes.handle(
+        hash_set![Method::GET],
+        "/network/greylist/ips",
+        shell_handler::network_blacklisted_ips,
+    );
+    routes.handle(
+        hash_set![Method::DELETE],
+        "/network/greylist/ips/:ip",
+        shell_handler::network_whitelist_ip,
+    );
+    routes.handle(
+        hash_set![Method::DELETE],
+        "/network/greylist",
+        shell_handler::network_whitelist_all_ips,
+    );
 
     routes.handle(
         hash_set![Method::GET],
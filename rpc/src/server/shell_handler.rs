@@ -358,6 +358,98 @@ pub async fn mempool_request_operations(
     )
 }
 
+pub async fn mempool_ban_operation(
+    req: Request<Body>,
+    _: Params,
+    _: Query,
+    env: Arc<RpcServiceEnvironment>,
+) -> ServiceResult {
+    let operation_hash_raw = hyper::body::aggregate(req).await?;
+    let operation_hash: String = serde_json::from_reader(&mut operation_hash_raw.reader())?;
+
+    result_to_empty_json_response(
+        services::mempool_services::ban_operation(&operation_hash, &env),
+        env.log(),
+    )
+}
+
+pub async fn mempool_unban_operation(
+    req: Request<Body>,
+    _: Params,
+    _: Query,
+    env: Arc<RpcServiceEnvironment>,
+) -> ServiceResult {
+    let operation_hash_raw = hyper::body::aggregate(req).await?;
+    let operation_hash: String = serde_json::from_reader(&mut operation_hash_raw.reader())?;
+
+    result_to_empty_json_response(
+        services::mempool_services::unban_operation(&operation_hash, &env),
+        env.log(),
+    )
+}
+
+pub async fn mempool_unban_all_operations(
+    _: Request<Body>,
+    _: Params,
+    _: Query,
+    env: Arc<RpcServiceEnvironment>,
+) -> ServiceResult {
+    result_to_empty_json_response(
+        services::mempool_services::unban_all_operations(&env),
+        env.log(),
+    )
+}
+
+pub async fn mempool_banned_operations(
+    _: Request<Body>,
+    _: Params,
+    _: Query,
+    env: Arc<RpcServiceEnvironment>,
+) -> ServiceResult {
+    result_to_json_response(
+        services::mempool_services::get_banned_operations(&env),
+        env.log(),
+    )
+}
+
+pub async fn network_blacklisted_ips(
+    _: Request<Body>,
+    _: Params,
+    _: Query,
+    env: Arc<RpcServiceEnvironment>,
+) -> ServiceResult {
+    result_to_json_response(
+        services::network_services::get_blacklisted_ips(&env),
+        env.log(),
+    )
+}
+
+pub async fn network_whitelist_ip(
+    _: Request<Body>,
+    params: Params,
+    _: Query,
+    env: Arc<RpcServiceEnvironment>,
+) -> ServiceResult {
+    let ip = required_param!(params, "ip")?;
+
+    result_to_empty_json_response(
+        services::network_services::whitelist_ip(ip, &env),
+        env.log(),
+    )
+}
+
+pub async fn network_whitelist_all_ips(
+    _: Request<Body>,
+    _: Params,
+    _: Query,
+    env: Arc<RpcServiceEnvironment>,
+) -> ServiceResult {
+    result_to_empty_json_response(
+        services::network_services::whitelist_all_ips(&env),
+        env.log(),
+    )
+}
+
 pub async fn get_block_protocols(
     _: Request<Body>,
     params: Params,
@@ -20,8 +20,8 @@ use shell::shell_channel::{
 use shell::validation;
 use storage::mempool_storage::MempoolOperationType;
 use storage::{
-    BlockHeaderWithHash, BlockMetaStorage, BlockMetaStorageReader, BlockStorage,
-    BlockStorageReader, MempoolStorage,
+    BannedOperationsStorage, BlockHeaderWithHash, BlockMetaStorage, BlockMetaStorageReader,
+    BlockStorage, BlockStorageReader, MempoolStorage,
 };
 use tezos_api::ffi::{Applied, Errored};
 use tezos_messages::p2p::binary_message::{BinaryRead, MessageHash};
@@ -464,6 +464,81 @@ pub async fn inject_block(
     Ok(block_hash_b58check_string)
 }
 
+fn parse_operation_hash(operation_hash: &str) -> Result<OperationHash, RpcServiceError> {
+    OperationHash::from_base58_check(operation_hash).map_err(|e| {
+        RpcServiceError::InvalidParameters {
+            reason: format!("Invalid operation hash: {}, reason: {}", operation_hash, e),
+        }
+    })
+}
+
+/// Removes the operation from the mempool, it will not be accepted again until unbanned.
+///
+/// The ban is persisted, so it survives restarts of the node.
+pub fn ban_operation(
+    operation_hash: &str,
+    env: &RpcServiceEnvironment,
+) -> Result<(), RpcServiceError> {
+    let operation_hash = parse_operation_hash(operation_hash)?;
+    info!(env.log(), "Operation ban requested"; "operation_hash" => operation_hash.to_base58_check());
+
+    let persistent_storage = env.persistent_storage();
+    BannedOperationsStorage::new(persistent_storage).ban(&operation_hash)?;
+    MempoolStorage::new(persistent_storage).delete(&operation_hash)?;
+
+    env.current_mempool_state_storage()
+        .write()?
+        .ban_operation(operation_hash);
+    Ok(())
+}
+
+pub fn unban_operation(
+    operation_hash: &str,
+    env: &RpcServiceEnvironment,
+) -> Result<(), RpcServiceError> {
+    let operation_hash = parse_operation_hash(operation_hash)?;
+    info!(env.log(), "Operation unban requested"; "operation_hash" => operation_hash.to_base58_check());
+
+    BannedOperationsStorage::new(env.persistent_storage()).unban(&operation_hash)?;
+    env.current_mempool_state_storage()
+        .write()?
+        .unban_operation(&operation_hash);
+    Ok(())
+}
+
+pub fn unban_all_operations(env: &RpcServiceEnvironment) -> Result<(), RpcServiceError> {
+    info!(env.log(), "Unban of all operations requested");
+
+    let banned_operations_storage = BannedOperationsStorage::new(env.persistent_storage());
+    for (operation_hash, _) in banned_operations_storage.iter()? {
+        banned_operations_storage.unban(&operation_hash)?;
+    }
+    env.current_mempool_state_storage()
+        .write()?
+        .unban_all_operations();
+    Ok(())
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct BannedOperation {
+    pub hash: String,
+    /// Ban time, unix timestamp in seconds
+    pub banned_at: u64,
+}
+
+pub fn get_banned_operations(
+    env: &RpcServiceEnvironment,
+) -> Result<Vec<BannedOperation>, RpcServiceError> {
+    Ok(BannedOperationsStorage::new(env.persistent_storage())
+        .iter()?
+        .into_iter()
+        .map(|(operation_hash, banned_at)| BannedOperation {
+            hash: operation_hash.to_base58_check(),
+            banned_at,
+        })
+        .collect())
+}
+
 pub fn request_operations(shell_channel: ShellChannelRef) {
     // request current head from the peers
     shell_channel.tell(
@@ -7,6 +7,7 @@ pub mod base_services;
 pub mod context;
 pub mod dev_services;
 pub mod mempool_services;
+pub mod network_services;
 pub mod protocol;
 // pub mod stats_services;
 pub mod stream_services;
@@ -0,0 +1,44 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::net::{IpAddr, SocketAddr};
+
+use slog::info;
+
+use shell::peer_manager::canonical_address;
+
+use crate::helpers::RpcServiceError;
+use crate::server::RpcServiceEnvironment;
+
+/// Returns the IP addresses blacklisted by the peer manager, sorted.
+pub fn get_blacklisted_ips(env: &RpcServiceEnvironment) -> Result<Vec<String>, RpcServiceError> {
+    let mut ips: Vec<IpAddr> = env.ip_blacklist().read()?.iter().cloned().collect();
+    ips.sort();
+    Ok(ips.iter().map(|ip| ip.to_string()).collect())
+}
+
+/// Removes the IP address from the blacklist, the peer manager accepts connections with it again.
+pub fn whitelist_ip(ip: &str, env: &RpcServiceEnvironment) -> Result<(), RpcServiceError> {
+    let ip: IpAddr = ip.parse().map_err(|e| RpcServiceError::InvalidParameters {
+        reason: format!("Invalid IP address: {}, reason: {}", ip, e),
+    })?;
+    // the blacklist holds the canonical form of the addresses
+    let ip = canonical_address(SocketAddr::new(ip, 0)).ip();
+    info!(env.log(), "Whitelisting of IP address requested"; "ip" => ip.to_string());
+
+    if env.ip_blacklist().write()?.remove(&ip) {
+        Ok(())
+    } else {
+        Err(RpcServiceError::NoDataFoundError {
+            reason: format!("IP address {} is not blacklisted", ip),
+        })
+    }
+}
+
+/// Clears the blacklist, as the peer manager does periodically.
+pub fn whitelist_all_ips(env: &RpcServiceEnvironment) -> Result<(), RpcServiceError> {
+    info!(env.log(), "Whitelisting of all IP addresses requested");
+
+    env.ip_blacklist().write()?.clear();
+    Ok(())
+}
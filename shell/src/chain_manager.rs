@@ -617,11 +617,25 @@ impl ChainManager {
                                                 let peer_current_mempool =
                                                     message.current_mempool();
 
+                                                let mempool_state = self
+                                                    .current_mempool_state
+                                                    .read()
+                                                    .map_err(|e| {
+                                                        format_err!(
+                                                            "Failed to lock for read, reason: {}",
+                                                            e
+                                                        )
+                                                    })?;
+
                                                 // all operations (known_valid + pending) should be added to pending and validated afterwards
-                                                // enqueue mempool operations for retrieval
+                                                // enqueue mempool operations for retrieval, the banned ones would be dropped anyway
                                                 peer_current_mempool
                                                     .known_valid()
                                                     .iter()
+                                                    .chain(peer_current_mempool.pending())
+                                                    .filter(|operation_hash| {
+                                                        !mempool_state.is_banned(operation_hash)
+                                                    })
                                                     .cloned()
                                                     .for_each(|operation_hash| {
                                                         peer.add_missing_mempool_operations(
@@ -629,16 +643,7 @@ impl ChainManager {
                                                             MempoolOperationType::Pending,
                                                         );
                                                     });
-                                                peer_current_mempool
-                                                    .pending()
-                                                    .iter()
-                                                    .cloned()
-                                                    .for_each(|operation_hash| {
-                                                        peer.add_missing_mempool_operations(
-                                                            operation_hash,
-                                                            MempoolOperationType::Pending,
-                                                        );
-                                                    });
+                                                drop(mempool_state);
 
                                                 // trigger CheckMempoolCompleteness
                                                 ctx.myself().tell(CheckMempoolCompleteness, None);
@@ -751,6 +756,7 @@ impl ChainManager {
                                             Err(e) => match e {
                                                 validation::PrevalidateOperationError::UnknownBranch { .. }
                                                 | validation::PrevalidateOperationError::AlreadyInMempool { .. }
+                                                | validation::PrevalidateOperationError::Banned { .. }
                                                 | validation::PrevalidateOperationError::BranchNotAppliedYet { .. }  => {
                                                     // here we just ignore scenarios
                                                     return Ok(());
@@ -16,6 +16,7 @@ use std::sync::mpsc::{channel, Receiver as QueueReceiver, Sender as QueueSender}
 use std::sync::{Arc, Mutex, PoisonError};
 use std::thread;
 use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use anyhow::{format_err, Error};
 use riker::actors::*;
@@ -25,8 +26,10 @@ use thiserror::Error;
 use crypto::hash::{BlockHash, ChainId, OperationHash};
 use storage::chain_meta_storage::{ChainMetaStorage, ChainMetaStorageReader};
 use storage::mempool_storage::MempoolOperationType;
+use storage::{
+    BannedOperationsStorage, BlockStorage, BlockStorageReader, MempoolStorage, StorageError,
+};
 use storage::{BlockHeaderWithHash, PersistentStorage};
-use storage::{BlockStorage, BlockStorageReader, MempoolStorage, StorageError};
 use tezos_api::ffi::{
     Applied, BeginConstructionRequest, PrevalidatorWrapper, ValidateOperationRequest,
 };
@@ -45,6 +48,10 @@ use crate::utils::{dispatch_oneshot_result, OneshotResultCallback};
 
 type SharedJoinHandle = Arc<Mutex<Option<JoinHandle<Result<(), Error>>>>>;
 
+/// Operations stored in the mempool storage for longer are pruned,
+/// they are far beyond the max operations ttl anyway.
+const MEMPOOL_STORAGE_OPERATION_TTL: Duration = Duration::from_secs(2 * 60 * 60);
+
 #[derive(Clone, Debug)]
 pub struct MempoolOperationReceived {
     pub operation_hash: OperationHash,
@@ -102,6 +109,7 @@ impl MempoolPrevalidator {
                 let block_storage = BlockStorage::new(&persistent_storage);
                 let chain_meta_storage = ChainMetaStorage::new(&persistent_storage);
                 let mempool_storage = MempoolStorage::new(&persistent_storage);
+                let banned_operations_storage = BannedOperationsStorage::new(&persistent_storage);
 
                 while validator_run.load(Ordering::Acquire) {
                     match tezos_readonly_api.pool.get() {
@@ -109,6 +117,7 @@ impl MempoolPrevalidator {
                             &block_storage,
                             &chain_meta_storage,
                             &mempool_storage,
+                            &banned_operations_storage,
                             current_mempool_state_storage.clone(),
                             &chain_id,
                             &validator_run,
@@ -353,6 +362,7 @@ fn process_prevalidation(
     block_storage: &BlockStorage,
     chain_meta_storage: &ChainMetaStorage,
     mempool_storage: &MempoolStorage,
+    banned_operations_storage: &BannedOperationsStorage,
     current_mempool_state_storage: CurrentMempoolStateStorageRef,
     chain_id: &ChainId,
     validator_run: &AtomicBool,
@@ -369,6 +379,7 @@ fn process_prevalidation(
         block_storage,
         chain_meta_storage,
         mempool_storage,
+        banned_operations_storage,
         current_mempool_state_storage.clone(),
         api,
         chain_id,
@@ -412,6 +423,7 @@ fn process_prevalidation(
                                     warn!(log, "Mempool - delete operation failed"; "hash" => oph.to_base58_check(), "error" => format!("{:?}", err))
                                 }
                             });
+                        prune_mempool_storage(mempool_storage, log);
                     } else {
                         debug!(log, "Mempool - new head received, but was ignored"; "received_block_hash" => header.hash.to_base58_check());
                     }
@@ -470,6 +482,7 @@ fn hydrate_state(
     block_storage: &BlockStorage,
     chain_meta_storage: &ChainMetaStorage,
     mempool_storage: &MempoolStorage,
+    banned_operations_storage: &BannedOperationsStorage,
     current_mempool_state_storage: CurrentMempoolStateStorageRef,
     api: &ProtocolController,
     chain_id: &ChainId,
@@ -489,12 +502,35 @@ fn hydrate_state(
         None => (None, None),
     };
 
+    // index operations stored by older versions, before pruning them by their reception time
+    match mempool_storage.reindex() {
+        Ok(0) => (),
+        Ok(deleted) => {
+            debug!(log, "Mempool - deleted operations stored without reception time"; "deleted" => deleted)
+        }
+        Err(err) => {
+            warn!(log, "Mempool - failed to reindex mempool storage"; "error" => format!("{:?}", err))
+        }
+    }
+
+    // drop operations left in Mempool_storage for too long (e.g. before restart)
+    prune_mempool_storage(mempool_storage, log);
+
     // read from Mempool_storage (just pending) -> add to queue for validation -> pending
-    let pending = mempool_storage.iter()?;
+    let pending = mempool_storage.iter_type(MempoolOperationType::Pending)?;
+    let banned = banned_operations_storage.iter()?;
 
     // initialize internal mempool state (write lock)
     let mut state = current_mempool_state_storage.write()?;
 
+    // restore bans before the pendings, so that banned operations are not added back
+    for (oph, _) in banned {
+        if let Err(err) = mempool_storage.delete(&oph) {
+            warn!(log, "Mempool - delete banned operation failed"; "hash" => oph.to_base58_check(), "error" => format!("{:?}", err))
+        }
+        state.ban_operation(oph);
+    }
+
     // reinit + add old unprocessed pendings
     let _ = state.reinit(prevalidator, head);
     for (oph, op) in pending {
@@ -514,6 +550,31 @@ fn hydrate_state(
     Ok(())
 }
 
+/// Deletes operations older than [MEMPOOL_STORAGE_OPERATION_TTL] from mempool storage,
+/// so that it cannot grow without bound.
+fn prune_mempool_storage(mempool_storage: &MempoolStorage, log: &Logger) {
+    let received_before = SystemTime::now()
+        .checked_sub(MEMPOOL_STORAGE_OPERATION_TTL)
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    match mempool_storage.prune_received_before(received_before) {
+        Ok(0) => (),
+        Ok(pruned) => match mempool_storage.total_size() {
+            Ok(size) => {
+                debug!(log, "Mempool - pruned old operations from mempool storage"; "pruned" => pruned, "operations" => size.operations, "total_bytes" => size.total_bytes)
+            }
+            Err(err) => {
+                warn!(log, "Mempool - failed to compute mempool storage size"; "error" => format!("{:?}", err))
+            }
+        },
+        Err(err) => {
+            warn!(log, "Mempool - failed to prune mempool storage"; "error" => format!("{:?}", err))
+        }
+    }
+}
+
 fn begin_construction(
     api: &ProtocolController,
     chain_id: &ChainId,
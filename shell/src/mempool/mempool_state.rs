@@ -18,6 +18,8 @@ use tezos_messages::p2p::encoding::prelude::{Mempool, Operation};
 ///     - are being processed sequentially, after validation, they are moved to `validation_result`
 /// - `operations`
 ///     - kind of cache, contains operation data
+/// - `banned`
+///     - operations banned by the node operator, never added to the mempool again (until unbanned)
 #[derive(Debug, Default)]
 pub struct MempoolState {
     /// Original tezos prevalidator has prevalidator.fitness which is used for set_head comparision
@@ -34,6 +36,9 @@ pub struct MempoolState {
     // TODO: pendings limit
     // TODO: pendings as vec and order
     pending: HashSet<OperationHash>,
+
+    /// Operations banned by the node operator (persisted in `BannedOperationsStorage`)
+    banned: HashSet<OperationHash>,
 }
 
 impl MempoolState {
@@ -69,7 +74,7 @@ impl MempoolState {
         operation_hash: &OperationHash,
         operation: Operation,
     ) -> bool {
-        if self.is_already_validated(&operation_hash) {
+        if self.is_banned(operation_hash) || self.is_already_validated(&operation_hash) {
             return false;
         }
 
@@ -130,6 +135,30 @@ impl MempoolState {
         }
     }
 
+    /// Removes operation from mempool and refuses it from now on
+    pub fn ban_operation(&mut self, oph: OperationHash) {
+        self.remove_operation(oph.clone());
+        self.banned.insert(oph);
+    }
+
+    /// Accepts the banned operation again, returns false if it was not banned
+    pub fn unban_operation(&mut self, oph: &OperationHash) -> bool {
+        self.banned.remove(oph)
+    }
+
+    /// Accepts all the banned operations again
+    pub fn unban_all_operations(&mut self) {
+        self.banned.clear();
+    }
+
+    pub fn is_banned(&self, operation_hash: &OperationHash) -> bool {
+        self.banned.contains(operation_hash)
+    }
+
+    pub fn banned(&self) -> &HashSet<OperationHash> {
+        &self.banned
+    }
+
     /// Indicates, that pending operations can be handled
     /// Returns - None, if nothing can be done, or Some(prevalidator, head, pendings, operations) to handle
     pub(crate) fn can_handle_pending(
@@ -308,4 +337,34 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_state_ban_operation() -> Result<(), anyhow::Error> {
+        let op_hash = "opJ4FdKumPfykAP9ZqwY7rNB8y1SiMupt44RqBDMWL7cmb4xbNr".try_into()?;
+        let operation = Operation::from_bytes(hex::decode("10490b79070cf19175cd7e3b9c1ee66f6e85799980404b119132ea7e58a4a97e000008c387fa065a181d45d47a9b78ddc77e92a881779ff2cbabbf9646eade4bf1405a08e00b725ed849eea46953b10b5cdebc518e6fd47e69b82d2ca18c4cf6d2f312dd08")?)?;
+
+        let mut state = MempoolState::default();
+        assert!(state.add_to_pending(&op_hash, operation.clone()));
+
+        // ban removes the operation and refuses it afterwards
+        state.ban_operation(op_hash.clone());
+        assert!(state.is_banned(&op_hash));
+        assert!(!state.is_already_in_mempool(&op_hash));
+        assert!(state.operations.is_empty());
+        assert!(!state.add_to_pending(&op_hash, operation.clone()));
+
+        // ban survives reinit
+        let _ = state.reinit(None, None);
+        assert!(state.is_banned(&op_hash));
+
+        assert!(state.unban_operation(&op_hash));
+        assert!(!state.unban_operation(&op_hash));
+        assert!(state.add_to_pending(&op_hash, operation));
+
+        state.ban_operation(op_hash.clone());
+        state.unban_all_operations();
+        assert!(state.banned().is_empty());
+
+        Ok(())
+    }
 }
@@ -5,8 +5,9 @@
 
 use std::cmp;
 use std::collections::{HashMap, HashSet};
+use std::io;
 use std::iter::FromIterator;
-use std::net::{IpAddr, SocketAddr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, PoisonError, RwLock};
 use std::time::{Duration, Instant};
@@ -16,6 +17,7 @@ use futures::lock::Mutex;
 use rand::seq::SliceRandom;
 use riker::actors::*;
 use slog::{crit, debug, info, trace, warn, Logger};
+use socket2::{Domain, Protocol, Socket, Type};
 use thiserror::Error;
 use tokio::net::{TcpListener, TcpStream};
 use tokio::runtime::Handle;
@@ -69,6 +71,15 @@ pub struct WhitelistAllIpAddresses;
 
 pub type IncomingConnectionPermit = Arc<OwnedSemaphorePermit>;
 
+/// Blacklisted IP addresses, shared by [`PeerManager`] with the RPC server,
+/// which can inspect and clear them.
+pub type IpBlacklistRef = Arc<RwLock<HashSet<IpAddr>>>;
+
+/// Inits empty IP blacklist
+pub fn init_ip_blacklist() -> IpBlacklistRef {
+    Arc::new(RwLock::new(HashSet::new()))
+}
+
 /// Accept incoming peer connection.
 #[derive(Clone, Debug)]
 pub struct AcceptPeer {
@@ -103,12 +114,62 @@ pub struct P2p {
 
     /// Peers (IP:port) which we try to connect all the time
     pub bootstrap_peers: Vec<SocketAddr>,
+
+    /// IPv6 peers are ignored
+    pub disable_ipv6: bool,
+    /// IPv6 peers are dialed before the IPv4 ones
+    pub prefer_ipv6: bool,
+
+    /// Number of chunks of each handshake logged when it fails, see [`networking::p2p::chunk_trace`]
+    pub handshake_trace: Option<usize>,
 }
 
 impl P2p {
     pub const DEFAULT_P2P_PORT_FOR_LOOKUP: u16 = 9732;
 }
 
+/// Which peer addresses are used, and in which order they are dialed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IpFamilyPolicy {
+    pub disable_ipv6: bool,
+    pub prefer_ipv6: bool,
+}
+
+impl IpFamilyPolicy {
+    /// Returns the canonical form of `address`, or `None` if its family is disabled.
+    pub fn accept(&self, address: SocketAddr) -> Option<SocketAddr> {
+        let address = canonical_address(address);
+        if self.disable_ipv6 && address.is_ipv6() {
+            None
+        } else {
+            Some(address)
+        }
+    }
+
+    /// Puts the addresses of the preferred family first, keeping their order within a family.
+    pub fn sort(&self, addresses: &mut [SocketAddr]) {
+        if self.prefer_ipv6 {
+            addresses.sort_by_key(|address| !address.is_ipv6());
+        }
+    }
+}
+
+/// Returns the IPv4 address of an IPv4-mapped IPv6 address (`[::ffff:a.b.c.d]:port`),
+/// so that a peer is known by a single address whatever the family it was seen with.
+///
+/// An IPv4 address is also dialed with an IPv4 socket, which works without IPv6 support.
+pub fn canonical_address(address: SocketAddr) -> SocketAddr {
+    match address {
+        SocketAddr::V6(v6) => match v6.ip().octets() {
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, a, b, c, d] => {
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::new(a, b, c, d)), v6.port())
+            }
+            _ => address,
+        },
+        SocketAddr::V4(_) => address,
+    }
+}
+
 /// Possible errors for state processing
 #[derive(Debug, Error)]
 pub enum PeerManagerError {
@@ -178,7 +239,9 @@ pub struct PeerManager {
     /// more connections should be accepted from network
     rx_run: Arc<AtomicBool>,
     /// set of blacklisted IP addresses
-    ip_blacklist: HashSet<IpAddr>,
+    ip_blacklist: IpBlacklistRef,
+    /// Families of the addresses of the peers
+    ip_family: IpFamilyPolicy,
     /// Last time we did DNS peer discovery
     discovery_last: Option<Instant>,
     /// Last time we checked peer count
@@ -200,6 +263,7 @@ impl PeerManager {
         shell_compatibility_version: Arc<ShellCompatibilityVersion>,
         p2p_config: P2p,
         pow_target: f64,
+        ip_blacklist: IpBlacklistRef,
     ) -> Result<PeerManagerRef, CreateError> {
         sys.actor_of_props::<PeerManager>(
             PeerManager::name(),
@@ -211,6 +275,7 @@ impl PeerManager {
                 shell_compatibility_version,
                 p2p_config,
                 pow_target,
+                ip_blacklist,
             )),
         )
     }
@@ -273,6 +338,7 @@ impl PeerManager {
         // randomize potential peers as a security measurement
         let mut addresses_to_connect = potential_peers.iter().cloned().collect::<Vec<SocketAddr>>();
         addresses_to_connect.shuffle(&mut rand::thread_rng());
+        self.ip_family.sort(&mut addresses_to_connect);
 
         // drain required count
         addresses_to_connect
@@ -313,7 +379,11 @@ impl PeerManager {
 
     /// Check if given ip address is blacklisted to connect to
     fn is_blacklisted(&self, ip_address: &IpAddr) -> bool {
-        self.ip_blacklist.contains(ip_address)
+        let address = canonical_address(SocketAddr::new(*ip_address, 0));
+        self.ip_blacklist
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains(&address.ip())
     }
 
     fn blacklist_address(&mut self, address: SocketAddr, reason: String, log: &Logger) {
@@ -321,11 +391,15 @@ impl PeerManager {
             return;
         }
 
+        let address = canonical_address(address);
         info!(log, "Blacklisting IP";
                    "ip" => format!("{}", address.ip()),
                    "reason" => reason,
         );
-        self.ip_blacklist.insert(address.ip());
+        self.ip_blacklist
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(address.ip());
 
         // TODO: call firewall
     }
@@ -380,6 +454,7 @@ impl PeerManager {
     ) -> Result<(), PeerManagerError> {
         let sock_addresses = new_potential_peers
             .into_iter()
+            .filter_map(|address| self.ip_family.accept(address))
             .filter(|address: &SocketAddr| !self.is_blacklisted(&address.ip()))
             .collect::<Vec<_>>();
 
@@ -394,6 +469,7 @@ impl PeerManager {
         addresses_to_connect.extend(sock_addresses);
         // randomize peers as a security measurement
         addresses_to_connect.shuffle(&mut rand::thread_rng());
+        self.ip_family.sort(&mut addresses_to_connect);
 
         // try to limit
         if addresses_to_connect.len() > num_of_max_potential_peers {
@@ -524,6 +600,7 @@ impl
         Arc<ShellCompatibilityVersion>,
         P2p,
         f64,
+        IpBlacklistRef,
     )> for PeerManager
 {
     fn create_args(
@@ -535,6 +612,7 @@ impl
             shell_compatibility_version,
             p2p_config,
             pow_target,
+            ip_blacklist,
         ): (
             NetworkChannelRef,
             ShellChannelRef,
@@ -543,6 +621,7 @@ impl
             Arc<ShellCompatibilityVersion>,
             P2p,
             f64,
+            IpBlacklistRef,
         ),
     ) -> Self {
         // resolve all bootstrap addresses
@@ -567,19 +646,29 @@ impl
             tokio_executor,
             bootstrap_addresses,
             threshold: peers_threshold.clone(),
-            local_node_info: Arc::new(LocalPeerInfo::new(
-                p2p_config.listener_port,
-                identity,
-                shell_compatibility_version,
-                pow_target,
-            )),
+            local_node_info: Arc::new({
+                let info = LocalPeerInfo::new(
+                    p2p_config.listener_port,
+                    identity,
+                    shell_compatibility_version,
+                    pow_target,
+                );
+                match p2p_config.handshake_trace {
+                    Some(capacity) => info.with_chunk_trace(capacity),
+                    None => info,
+                }
+            }),
             listener_address: p2p_config.listener_address,
             disable_mempool: p2p_config.disable_mempool,
             disable_blacklist: p2p_config.disable_blacklist,
             private_node: p2p_config.private_node,
             rx_run: Arc::new(AtomicBool::new(true)),
             peers: Arc::new(P2pPeers::new(peers_threshold)),
-            ip_blacklist: HashSet::new(),
+            ip_blacklist,
+            ip_family: IpFamilyPolicy {
+                disable_ipv6: p2p_config.disable_ipv6,
+                prefer_ipv6: p2p_config.prefer_ipv6,
+            },
             discovery_last: None,
             check_peer_count_last: None,
             shutting_down: false,
@@ -721,7 +810,10 @@ impl Receive<LogPeerStats> for PeerManager {
             "connected_peers_count" => connected_peers_count,
             "potential_peers_count" => potential_peers_count,
             "incoming_connection_tickets_available" => self.peers.incoming_connection_tickets.available_permits(),
-            "blacklisted_ip_count" => self.ip_blacklist.len(),
+            "blacklisted_ip_count" => match self.ip_blacklist.read() {
+                Ok(ip_blacklist) => ip_blacklist.len().to_string(),
+                Err(_) => "-failed-to-collect-".to_string(),
+            },
             "check_peer_count_last_elapsed" => match self.check_peer_count_last.as_ref() {
                 Some(time) => format!("{:?}", time.elapsed()),
                 None => "--none--".to_string()
@@ -808,7 +900,10 @@ impl Receive<WhitelistAllIpAddresses> for PeerManager {
         _sender: Sender,
     ) {
         info!(ctx.system.log(), "Whitelisting all IP addresses");
-        self.ip_blacklist.clear();
+        self.ip_blacklist
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
     }
 }
 
@@ -965,18 +1060,47 @@ fn failed_bootstrap_peer(
     );
 }
 
+/// Binds a listener to `address`, an IPv6 one also accepts the IPv4 connections.
+///
+/// Whether an IPv6 socket is dual-stack by default depends on the system (`net.ipv6.bindv6only`
+/// on Linux), so it is requested explicitly. This fails where dual-stack sockets are not supported.
+fn bind_listener(address: SocketAddr) -> io::Result<TcpListener> {
+    let socket = Socket::new(
+        Domain::for_address(address),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    if address.is_ipv6() {
+        socket.set_only_v6(false)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.bind(&address.into())?;
+    socket.listen(1024)?;
+    socket.set_nonblocking(true)?;
+
+    TcpListener::from_std(socket.into())
+}
+
 /// Start to listen for incoming connections indefinitely.
 async fn begin_listen_incoming(
-    listener_address: SocketAddr,
+    mut listener_address: SocketAddr,
     peers: Arc<P2pPeers>,
     peer_manager: PeerManagerRef,
     rx_run: Arc<AtomicBool>,
     log: &Logger,
 ) {
     // TODO: TE-386 - remove expect and handle bind error
-    let listener = TcpListener::bind(&listener_address)
-        .await
-        .expect("Failed to bind to address");
+    let listener = match bind_listener(listener_address) {
+        Ok(listener) => listener,
+        Err(e) if listener_address.ip() == IpAddr::from(Ipv6Addr::UNSPECIFIED) => {
+            // no IPv6 (or no dual-stack) support on the host, listen for IPv4 connections only
+            warn!(log, "Failed to listen on IPv6, listening on IPv4 only"; "reason" => format!("{}", e));
+            listener_address =
+                SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), listener_address.port());
+            bind_listener(listener_address).expect("Failed to bind to address")
+        }
+        Err(e) => panic!("Failed to bind to address: {}", e),
+    };
     info!(log, "Start to listen for incoming p2p connections"; "listener_address" => listener_address);
 
     while rx_run.load(Ordering::Acquire) {
@@ -991,7 +1115,7 @@ async fn begin_listen_incoming(
                                 AcceptPeer {
                                     stream: Arc::new(Mutex::new(Some(stream))),
                                     permit,
-                                    address,
+                                    address: canonical_address(address),
                                 },
                                 None,
                             );
@@ -1064,16 +1188,7 @@ fn resolve_dns_name_to_peer_address(
                 dns_lookup::AddrFamily::Inet.eq(&info.address)
                     || dns_lookup::AddrFamily::Inet6.eq(&info.address)
             })
-            .map(|info: dns_lookup::AddrInfo| {
-                // convert to uniform IPv6 format
-                match &info.sockaddr {
-                    SocketAddr::V4(ipv4) => {
-                        // convert ipv4 to ipv6
-                        SocketAddr::new(IpAddr::V6(ipv4.ip().to_ipv6_mapped()), ipv4.port())
-                    }
-                    SocketAddr::V6(_) => info.sockaddr,
-                }
-            })
+            .map(|info: dns_lookup::AddrInfo| canonical_address(info.sockaddr))
             .collect();
     Ok(addrs)
 }
@@ -1229,6 +1344,83 @@ pub mod tests {
     use networking::p2p::network_channel::NetworkChannel;
     use slog::Level;
 
+    #[test]
+    fn test_canonical_address() {
+        let v4: SocketAddr = "1.2.3.4:9732".parse().unwrap();
+        let mapped: SocketAddr = "[::ffff:1.2.3.4]:9732".parse().unwrap();
+        let v6: SocketAddr = "[2001:db8::1]:9732".parse().unwrap();
+        let loopback: SocketAddr = "[::1]:9732".parse().unwrap();
+
+        assert_eq!(canonical_address(v4), v4);
+        assert_eq!(canonical_address(mapped), v4);
+        assert_eq!(canonical_address(v6), v6);
+        assert_eq!(canonical_address(loopback), loopback);
+    }
+
+    #[test]
+    fn test_ip_family_policy() {
+        // advertised peers, as formatted by octez
+        let advertised = [
+            "1.2.3.4:9732",
+            "[2001:db8::1]:9732",
+            "[::ffff:5.6.7.8]:9733",
+        ]
+        .iter()
+        .filter_map(|address| address.parse::<SocketAddr>().ok())
+        .collect::<Vec<_>>();
+        assert_eq!(advertised.len(), 3);
+
+        let accepted = |policy: IpFamilyPolicy| {
+            advertised
+                .iter()
+                .filter_map(|address| policy.accept(*address))
+                .map(|address| address.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            accepted(IpFamilyPolicy::default()),
+            vec!["1.2.3.4:9732", "[2001:db8::1]:9732", "5.6.7.8:9733"]
+        );
+        assert_eq!(
+            accepted(IpFamilyPolicy {
+                disable_ipv6: true,
+                prefer_ipv6: false,
+            }),
+            vec!["1.2.3.4:9732", "5.6.7.8:9733"]
+        );
+
+        // the advertised addresses are parsed back to the same peers
+        let message = AdvertiseMessage::new(
+            &advertised
+                .iter()
+                .map(|address| canonical_address(*address))
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(
+            message.id(),
+            &vec![
+                "1.2.3.4:9732".to_string(),
+                "[2001:db8::1]:9732".to_string(),
+                "5.6.7.8:9733".to_string(),
+            ]
+        );
+
+        let mut addresses = accepted(IpFamilyPolicy::default())
+            .iter()
+            .map(|address| address.parse::<SocketAddr>().unwrap())
+            .collect::<Vec<_>>();
+        IpFamilyPolicy::default().sort(&mut addresses);
+        assert!(addresses[0].is_ipv4());
+        IpFamilyPolicy {
+            disable_ipv6: false,
+            prefer_ipv6: true,
+        }
+        .sort(&mut addresses);
+        assert!(addresses[0].is_ipv6());
+        assert_eq!(addresses[1].to_string(), "1.2.3.4:9732");
+        assert_eq!(addresses[2].to_string(), "5.6.7.8:9733");
+    }
+
     #[test]
     fn test_peer_actor_name() {
         assert!(P2pPeers::is_peer_actor_name(
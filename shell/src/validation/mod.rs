@@ -194,6 +194,8 @@ pub enum PrevalidateOperationError {
     },
     #[error("Operation ({operation_hash}) is already in mempool, cannot inject the operation.")]
     AlreadyInMempool { operation_hash: String },
+    #[error("Operation ({operation_hash}) is banned, cannot inject the operation.")]
+    Banned { operation_hash: String },
     #[error("Failed to prevalidate operation ({operation_hash}), cannot inject the operation, reason: {reason}")]
     UnexpectedError {
         operation_hash: String,
@@ -245,6 +247,13 @@ pub fn prevalidate_operation(
                 reason: format!("Failed to obtain mempool lock, reason: {}", e),
             })?;
 
+    // check if operation was banned by the node operator
+    if mempool_state.is_banned(operation_hash) {
+        return Err(PrevalidateOperationError::Banned {
+            operation_hash: operation_hash.to_base58_check(),
+        });
+    }
+
     // check if operations is already in mempool
     if mempool_state.is_already_in_mempool(operation_hash) {
         return Err(PrevalidateOperationError::AlreadyInMempool {
@@ -50,6 +50,9 @@ lazy_static! {
             disable_blacklist: false,
             private_node: false,
             bootstrap_peers: vec![],
+            disable_ipv6: false,
+            prefer_ipv6: false,
+            handshake_trace: None,
             peer_threshold: PeerConnectionThreshold::try_new(0, 10, Some(0)).expect("Invalid range"),
         },
         SHELL_COMPATIBILITY_VERSION.clone(),
@@ -26,7 +26,9 @@ use shell::chain_manager::{ChainManager, ChainManagerRef};
 use shell::mempool::{
     init_mempool_state_storage, CurrentMempoolStateStorageRef, MempoolPrevalidatorFactory,
 };
-use shell::peer_manager::{P2p, PeerManager, PeerManagerRef, WhitelistAllIpAddresses};
+use shell::peer_manager::{
+    init_ip_blacklist, P2p, PeerManager, PeerManagerRef, WhitelistAllIpAddresses,
+};
 use shell::shell_channel::{ShellChannel, ShellChannelRef, ShellChannelTopic, ShuttingDown};
 use shell::state::head_state::init_current_head_state;
 use shell::state::synchronization_state::{
@@ -248,6 +250,7 @@ impl NodeInfrastructure {
                 Arc::new(shell_compatibility_version),
                 p2p_config,
                 pow_target,
+                init_ip_blacklist(),
             )
             .expect("Failed to create peer manager");
             Some(peer_manager)
@@ -378,27 +378,7 @@ impl Drop for TestNodePeer {
 }
 
 fn msg_type(msg: &PeerMessageResponse) -> String {
-    match msg.message() {
-        PeerMessage::Disconnect => "Disconnect",
-        PeerMessage::Advertise(_) => "Advertise",
-        PeerMessage::SwapRequest(_) => "SwapRequest",
-        PeerMessage::SwapAck(_) => "SwapAck",
-        PeerMessage::Bootstrap => "Bootstrap",
-        PeerMessage::GetCurrentBranch(_) => "GetCurrentBranch",
-        PeerMessage::CurrentBranch(_) => "CurrentBranch",
-        PeerMessage::Deactivate(_) => "Deactivate",
-        PeerMessage::GetCurrentHead(_) => "GetCurrentHead",
-        PeerMessage::CurrentHead(_) => "CurrentHead",
-        PeerMessage::GetBlockHeaders(_) => "GetBlockHeaders",
-        PeerMessage::BlockHeader(_) => "BlockHeader",
-        PeerMessage::GetOperations(_) => "GetOperations",
-        PeerMessage::Operation(_) => "Operation",
-        PeerMessage::GetProtocols(_) => "GetProtocols",
-        PeerMessage::Protocol(_) => "Protocol",
-        PeerMessage::GetOperationsForBlocks(_) => "GetOperationsForBlocks",
-        PeerMessage::OperationsForBlocks(_) => "OperationsForBlocks",
-    }
-    .to_string()
+    msg.message().kind().to_string()
 }
 
 fn contains_all_keys(set: &HashSet<OperationHash>, keys: &HashSet<OperationHash>) -> bool {
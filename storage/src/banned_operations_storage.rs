@@ -0,0 +1,96 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crypto::hash::OperationHash;
+
+use crate::database::tezedge_database::{KVStoreKeyValueSchema, TezedgeDatabaseWithIterator};
+use crate::persistent::database::RocksDbKeyValueSchema;
+use crate::persistent::{BincodeEncoded, Decoder, KeyValueSchema};
+use crate::{IteratorMode, PersistentStorage, StorageError};
+
+/// Convenience type for banned operations storage database
+pub type BannedOperationsStorageKV =
+    dyn TezedgeDatabaseWithIterator<BannedOperationsStorage> + Sync + Send;
+
+/// Operations banned from the mempool by the node operator, kept across restarts.
+#[derive(Clone)]
+pub struct BannedOperationsStorage {
+    kv: Arc<BannedOperationsStorageKV>,
+}
+
+impl BannedOperationsStorage {
+    pub fn new(persistent_storage: &PersistentStorage) -> Self {
+        Self {
+            kv: persistent_storage.main_db(),
+        }
+    }
+
+    /// Bans the operation, at the current time.
+    pub fn ban(&self, operation_hash: &OperationHash) -> Result<(), StorageError> {
+        let banned_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.kv
+            .put(operation_hash, &BannedOperation { banned_at })
+            .map_err(StorageError::from)
+    }
+
+    /// Unbans the operation, returns `false` if it was not banned.
+    pub fn unban(&self, operation_hash: &OperationHash) -> Result<bool, StorageError> {
+        if !self.kv.contains(operation_hash)? {
+            return Ok(false);
+        }
+        self.kv.delete(operation_hash)?;
+        Ok(true)
+    }
+
+    pub fn is_banned(&self, operation_hash: &OperationHash) -> Result<bool, StorageError> {
+        self.kv.contains(operation_hash).map_err(StorageError::from)
+    }
+
+    /// Returns the banned operations with the time they were banned (unix timestamp in seconds).
+    pub fn iter(&self) -> Result<Vec<(OperationHash, u64)>, StorageError> {
+        let items = self
+            .kv
+            .find(IteratorMode::Start, None, Box::new(|(_, _)| Ok(true)))?;
+        let mut operations = Vec::with_capacity(items.len());
+        for (k, v) in items.iter() {
+            let operation_hash = OperationHash::decode(k)?;
+            let value: BannedOperation = BincodeEncoded::decode(v)?;
+            operations.push((operation_hash, value.banned_at));
+        }
+        Ok(operations)
+    }
+}
+
+impl KeyValueSchema for BannedOperationsStorage {
+    type Key = OperationHash;
+    type Value = BannedOperation;
+}
+
+impl RocksDbKeyValueSchema for BannedOperationsStorage {
+    #[inline]
+    fn name() -> &'static str {
+        "banned_operations_storage"
+    }
+}
+
+impl KVStoreKeyValueSchema for BannedOperationsStorage {
+    fn column_name() -> &'static str {
+        Self::name()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BannedOperation {
+    /// Ban time, unix timestamp in seconds
+    banned_at: u64,
+}
+
+impl BincodeEncoded for BannedOperation {}
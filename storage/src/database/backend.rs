@@ -2,11 +2,21 @@ use crate::database::error::Error;
 use crate::persistent::SchemaError;
 use crate::Direction;
 
+/// Approximate size of a column, as estimated by the backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnSize {
+    pub column: &'static str,
+    pub approximate_keys: u64,
+    pub approximate_bytes: u64,
+}
+
 #[derive(Clone)]
 pub enum BackendIteratorMode {
     Start,
     End,
     From(Vec<u8>, Direction),
+    /// Only the keys starting with the prefix
+    Prefix(Vec<u8>, Direction),
 }
 
 pub trait TezedgeDatabaseBackendStore {
@@ -21,6 +31,11 @@ pub trait TezedgeDatabaseBackendStore {
         batch: Vec<(Vec<u8>, Vec<u8>)>,
     ) -> Result<(), Error>;
     fn flush(&self) -> Result<usize, Error>;
+    /// Forces the writes made so far to the disk.
+    fn sync(&self) -> Result<(), Error>;
+    /// Compacts the whole key range of the column, reclaiming space of deleted/overwritten values.
+    fn compact_range(&self, column: &'static str) -> Result<(), Error>;
+    fn approximate_size(&self, column: &'static str) -> Result<ColumnSize, Error>;
 
     fn find(
         &self,
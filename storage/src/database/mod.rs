@@ -3,3 +3,4 @@ pub mod error;
 pub mod rockdb_backend;
 pub mod sled_backend;
 pub mod tezedge_database;
+pub mod write_policy;
@@ -1,9 +1,10 @@
-use crate::database::backend::{BackendIteratorMode, TezedgeDatabaseBackendStore};
+use crate::database::backend::{BackendIteratorMode, ColumnSize, TezedgeDatabaseBackendStore};
 use crate::database::error::Error;
 use crate::database::tezedge_database::TezdegeDatabaseBackendKV;
 use crate::initializer::{RocksDbColumnFactory, RocksDbConfig};
 use crate::persistent::database::default_kv_options;
 use crate::persistent::{DbConfiguration, SchemaError};
+use crate::Direction;
 use rocksdb::{Cache, ColumnFamilyDescriptor, WriteBatch, WriteOptions, DB};
 use std::path::Path;
 use std::sync::Arc;
@@ -39,6 +40,51 @@ impl RocksDBBackend {
     {
         DB::open_cf_descriptors(&default_kv_options(cfg), path, cfs).map_err(Error::from)
     }
+
+    /// Collects the entries of `column` with the key starting with `prefix`.
+    ///
+    /// The entries are always read forward from the prefix, because iterating out of the prefix
+    /// is undefined for the columns with a prefix extractor.
+    fn find_prefix(
+        &self,
+        column: &'static str,
+        prefix: &[u8],
+        direction: Direction,
+        limit: Option<usize>,
+        filter: Box<dyn Fn((&[u8], &[u8])) -> Result<bool, SchemaError>>,
+    ) -> Result<Vec<(Box<[u8]>, Box<[u8]>)>, Error> {
+        let cf = self
+            .db
+            .cf_handle(column)
+            .ok_or(Error::MissingColumnFamily { name: column })?;
+        let rocks_db_iterator = self.db.iterator_cf(
+            cf,
+            rocksdb::IteratorMode::From(prefix, rocksdb::Direction::Forward),
+        );
+
+        let mut results = Vec::new();
+        for (key, value) in rocks_db_iterator {
+            if !key.starts_with(prefix) {
+                break;
+            }
+            if filter((key.as_ref(), value.as_ref()))? {
+                results.push((key, value));
+            }
+            if let (Direction::Forward, Some(limit)) = (&direction, limit) {
+                if results.len() >= limit {
+                    break;
+                }
+            }
+        }
+
+        if let Direction::Reverse = direction {
+            results.reverse();
+            if let Some(limit) = limit {
+                results.truncate(limit);
+            }
+        }
+        Ok(results)
+    }
 }
 impl TezdegeDatabaseBackendKV for RocksDBBackend {}
 impl TezedgeDatabaseBackendStore for RocksDBBackend {
@@ -113,6 +159,41 @@ impl TezedgeDatabaseBackendStore for RocksDBBackend {
         Ok(0)
     }
 
+    fn sync(&self) -> Result<(), Error> {
+        // A synced write syncs the log of all the previous writes
+        let mut opts = WriteOptions::default();
+        opts.set_sync(true);
+        self.db.write_opt(WriteBatch::default(), &opts)?;
+        Ok(())
+    }
+
+    fn compact_range(&self, column: &'static str) -> Result<(), Error> {
+        let cf = self
+            .db
+            .cf_handle(column)
+            .ok_or(Error::MissingColumnFamily { name: column })?;
+        self.db.compact_range_cf::<&[u8], &[u8]>(cf, None, None);
+        Ok(())
+    }
+
+    fn approximate_size(&self, column: &'static str) -> Result<ColumnSize, Error> {
+        let cf = self
+            .db
+            .cf_handle(column)
+            .ok_or(Error::MissingColumnFamily { name: column })?;
+        Ok(ColumnSize {
+            column,
+            approximate_keys: self
+                .db
+                .property_int_value_cf(cf, "rocksdb.estimate-num-keys")?
+                .unwrap_or(0),
+            approximate_bytes: self
+                .db
+                .property_int_value_cf(cf, "rocksdb.estimate-live-data-size")?
+                .unwrap_or(0),
+        })
+    }
+
     fn find(
         &self,
         column: &'static str,
@@ -130,6 +211,9 @@ impl TezedgeDatabaseBackendStore for RocksDBBackend {
             BackendIteratorMode::From(key, direction) => self
                 .db
                 .iterator_cf(cf, rocksdb::IteratorMode::From(&key, direction.into())),
+            BackendIteratorMode::Prefix(prefix, direction) => {
+                return self.find_prefix(column, &prefix, direction, limit, filter)
+            }
         };
         let mut results = Vec::new();
         if let Some(limit) = limit {
@@ -1,5 +1,5 @@
 use crate::block_meta_storage;
-use crate::database::backend::{BackendIteratorMode, TezedgeDatabaseBackendStore};
+use crate::database::backend::{BackendIteratorMode, ColumnSize, TezedgeDatabaseBackendStore};
 use crate::database::error::Error;
 use crate::database::tezedge_database::{KVStoreKeyValueSchema, TezdegeDatabaseBackendKV};
 use crate::operations_meta_storage;
@@ -93,6 +93,34 @@ impl TezedgeDatabaseBackendStore for SledDBBackend {
         self.db.flush().map_err(Error::from)
     }
 
+    fn sync(&self) -> Result<(), Error> {
+        self.db.flush().map_err(Error::from)?;
+        Ok(())
+    }
+
+    fn compact_range(&self, column: &'static str) -> Result<(), Error> {
+        // sled compacts its segments in the background, we can just make sure
+        // that pending writes are persisted
+        let tree = self.get_tree(column)?;
+        tree.flush().map_err(Error::from)?;
+        Ok(())
+    }
+
+    fn approximate_size(&self, column: &'static str) -> Result<ColumnSize, Error> {
+        let tree = self.get_tree(column)?;
+        let mut size = ColumnSize {
+            column,
+            approximate_keys: 0,
+            approximate_bytes: 0,
+        };
+        for result in tree.iter() {
+            let (key, value) = result.map_err(Error::from)?;
+            size.approximate_keys += 1;
+            size.approximate_bytes += (key.len() + value.len()) as u64;
+        }
+        Ok(size)
+    }
+
     fn find(
         &self,
         column: &'static str,
@@ -108,6 +136,10 @@ impl TezedgeDatabaseBackendStore for SledDBBackend {
             BackendIteratorMode::From(key, direction) => {
                 SledDBIterator::new(SledDBIteratorMode::From(IVec::from(key), direction), tree)
             }
+            BackendIteratorMode::Prefix(prefix, direction) => SledDBIterator::new(
+                SledDBIteratorMode::Prefix(IVec::from(prefix), direction),
+                tree,
+            ),
         };
         let mut results = Vec::new();
         if let Some(limit) = limit {
@@ -145,7 +177,10 @@ impl TezedgeDatabaseBackendStore for SledDBBackend {
     ) -> Result<Vec<(Box<[u8]>, Box<[u8]>)>, Error> {
         let tree = self.get_tree(column)?;
         let prefix_key = key[..max_key_len].to_vec();
-        let iter = SledDBIterator::new(SledDBIteratorMode::Prefix(IVec::from(prefix_key)), tree);
+        let iter = SledDBIterator::new(
+            SledDBIteratorMode::Prefix(IVec::from(prefix_key), Direction::Forward),
+            tree,
+        );
         let mut results = Vec::new();
         for result in iter {
             let (key, value) = result.map_err(Error::from)?;
@@ -167,7 +202,7 @@ pub enum SledDBIteratorMode {
     Start,
     End,
     From(IVec, Direction),
-    Prefix(IVec),
+    Prefix(IVec, Direction),
 }
 
 pub struct SledDBIterator {
@@ -194,7 +229,7 @@ impl SledDBIterator {
 
                 Self { mode, iter }
             }
-            SledDBIteratorMode::Prefix(key) => Self {
+            SledDBIteratorMode::Prefix(key, _) => Self {
                 mode,
                 iter: tree.scan_prefix(key),
             },
@@ -223,7 +258,10 @@ impl Iterator for SledDBIterator {
             SledDBIteratorMode::End => convert_next(self.iter.next_back()),
             SledDBIteratorMode::From(_, Direction::Forward) => convert_next(self.iter.next()),
             SledDBIteratorMode::From(_, Direction::Reverse) => convert_next(self.iter.next_back()),
-            SledDBIteratorMode::Prefix(_) => convert_next(self.iter.next()),
+            SledDBIteratorMode::Prefix(_, Direction::Forward) => convert_next(self.iter.next()),
+            SledDBIteratorMode::Prefix(_, Direction::Reverse) => {
+                convert_next(self.iter.next_back())
+            }
         }
     }
 }
@@ -1,9 +1,11 @@
-use crate::database::backend::{BackendIteratorMode, TezedgeDatabaseBackendStore};
+use crate::database::backend::{BackendIteratorMode, ColumnSize, TezedgeDatabaseBackendStore};
 use crate::database::error::Error;
 use crate::database::rockdb_backend::RocksDBBackend;
 use crate::database::sled_backend::SledDBBackend;
+use crate::database::write_policy::{WritePolicy, WriteStats, WriteTracker};
+use crate::initializer::DbsRocksDbTableInitializer;
 use crate::persistent::{Decoder, Encoder, KeyValueSchema, SchemaError};
-use crate::IteratorMode;
+use crate::{Direction, IteratorMode};
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 use std::sync::Arc;
@@ -69,6 +71,17 @@ pub trait KVStoreWithSchemaIterator<S: KeyValueSchema> {
         max_key_len: usize,
         filter: Box<dyn Fn((&[u8], &[u8])) -> Result<bool, SchemaError>>,
     ) -> Result<Vec<(Box<[u8]>, Box<[u8]>)>, Error>;
+
+    /// Read the entries with the encoded key starting with `key_prefix`, in the order of `direction`.
+    ///
+    /// # Arguments
+    /// * `key_prefix` - Prefix of the encoded keys, e.g. the encoded first field of the key
+    /// * `direction` - Forward (ascending keys) or reverse (descending keys)
+    fn iter_prefix(
+        &self,
+        key_prefix: &[u8],
+        direction: Direction,
+    ) -> Result<Vec<(S::Key, S::Value)>, Error>;
 }
 
 // TODO - TE-498: Todo Change name
@@ -139,6 +152,7 @@ impl<S: KVStoreKeyValueSchema> TezedgeDatabaseWithIterator<S> for TezedgeDatabas
 
 pub struct TezedgeDatabase {
     backend: Arc<TezedgeDatabaseBackend>,
+    writes: WriteTracker,
 }
 
 impl TezedgeDatabase {
@@ -146,16 +160,69 @@ impl TezedgeDatabase {
         match backend_option {
             TezedgeDatabaseBackendOptions::SledDB(backend) => TezedgeDatabase {
                 backend: Arc::new(backend),
+                writes: WriteTracker::default(),
             },
             TezedgeDatabaseBackendOptions::RocksDB(backend) => TezedgeDatabase {
                 backend: Arc::new(backend),
+                writes: WriteTracker::default(),
             },
         }
     }
 
+    /// Persists the writes according to `policy`, see [`crate::database::write_policy`].
+    pub fn with_write_policy(mut self, policy: WritePolicy) -> Self {
+        self.writes = WriteTracker::new(policy);
+        self
+    }
+
+    pub fn write_policy(&self) -> WritePolicy {
+        self.writes.policy()
+    }
+
+    pub fn write_stats(&self) -> WriteStats {
+        self.writes.stats()
+    }
+
     pub fn flush(&self) -> Result<usize, Error> {
         self.backend.flush()
     }
+
+    /// Syncs the writes not synced yet, returns `true` if there were any.
+    ///
+    /// Called periodically by the background flusher of the [`FsyncPolicy::Interval`] policy.
+    ///
+    /// [`FsyncPolicy::Interval`]: crate::database::write_policy::FsyncPolicy::Interval
+    pub fn sync_pending(&self) -> Result<bool, Error> {
+        if !self.writes.take_pending() {
+            return Ok(false);
+        }
+        self.backend.sync()?;
+        self.writes.synced();
+        Ok(true)
+    }
+
+    /// Records `count` writes of `bytes` bytes, and syncs them if the policy requires it.
+    fn written(&self, count: usize, bytes: usize) -> Result<(), Error> {
+        if self.writes.record(count, bytes) {
+            self.writes.take_pending();
+            self.backend.sync()?;
+            self.writes.synced();
+        }
+        Ok(())
+    }
+
+    /// Compacts the whole key range of `column`, see [`TezedgeDatabaseBackendStore::compact_range`].
+    pub fn compact_range(&self, column: &'static str) -> Result<(), Error> {
+        self.backend.compact_range(column)
+    }
+
+    /// Returns approximate sizes of all the columns of the main database.
+    pub fn approximate_sizes(&self) -> Result<Vec<ColumnSize>, Error> {
+        DbsRocksDbTableInitializer::column_names()
+            .into_iter()
+            .map(|column| self.backend.approximate_size(column))
+            .collect()
+    }
 }
 
 impl<S: KVStoreKeyValueSchema> KVStore<S> for TezedgeDatabase {
@@ -163,19 +230,20 @@ impl<S: KVStoreKeyValueSchema> KVStore<S> for TezedgeDatabase {
         let key = key.encode()?;
         let value = value.encode()?;
         self.backend.put(S::column_name(), &key, &value)?;
-        Ok(())
+        self.written(1, key.len() + value.len())
     }
 
     fn delete(&self, key: &S::Key) -> Result<(), Error> {
         let key = key.encode()?;
-        self.backend.delete(S::column_name(), &key)
+        self.backend.delete(S::column_name(), &key)?;
+        self.written(1, key.len())
     }
 
     fn merge(&self, key: &S::Key, value: &S::Value) -> Result<(), Error> {
         let key = key.encode()?;
         let value = value.encode()?;
         self.backend.merge(S::column_name(), &key, &value)?;
-        Ok(())
+        self.written(1, key.len() + value.len())
     }
 
     fn get(&self, key: &S::Key) -> Result<Option<S::Value>, Error> {
@@ -197,13 +265,17 @@ impl<S: KVStoreKeyValueSchema> KVStore<S> for TezedgeDatabase {
 
     fn write_batch(&self, batch: Vec<(S::Key, S::Value)>) -> Result<(), Error> {
         let mut generic_batch = Vec::new();
+        let mut bytes = 0;
 
         for (k, v) in batch.iter() {
-            generic_batch.push((k.encode()?, v.encode()?))
+            let (k, v) = (k.encode()?, v.encode()?);
+            bytes += k.len() + v.len();
+            generic_batch.push((k, v))
         }
 
+        let count = generic_batch.len();
         self.backend.write_batch(S::column_name(), generic_batch)?;
-        Ok(())
+        self.written(count, bytes)
     }
 }
 
@@ -234,6 +306,19 @@ impl<S: KVStoreKeyValueSchema> KVStoreWithSchemaIterator<S> for TezedgeDatabase
         self.backend
             .find_by_prefix(S::column_name(), &key, max_key_len, filter)
     }
+
+    fn iter_prefix(
+        &self,
+        key_prefix: &[u8],
+        direction: Direction,
+    ) -> Result<Vec<(S::Key, S::Value)>, Error> {
+        let mode = BackendIteratorMode::Prefix(key_prefix.to_vec(), direction);
+        self.backend
+            .find(S::column_name(), mode, None, Box::new(|(_, _)| Ok(true)))?
+            .iter()
+            .map(|(k, v)| Ok((S::Key::decode(k)?, S::Value::decode(v)?)))
+            .collect()
+    }
 }
 
 #[cfg(test)]
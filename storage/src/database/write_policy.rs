@@ -0,0 +1,229 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Durability of the writes of the main database.
+//!
+//! The backends buffer the writes and persist them lazily, a [`WritePolicy`] decides when the
+//! [`crate::database::tezedge_database::TezedgeDatabase`] forces them to the disk:
+//! - [`FsyncPolicy::Always`]: after every write, nothing is lost on a crash,
+//! - [`FsyncPolicy::Interval`]: once `max_pending_writes` writes are pending, and every
+//!   `flush_interval` by a background flusher, at most these writes (or an interval of writes)
+//!   are lost,
+//! - [`FsyncPolicy::Never`]: when the backend decides, the fastest one during the initial sync.
+//!
+//! The writes are not grouped: each one still goes to the backend right away, the policy only
+//! decides when they are synced. [`WriteStats`] measure how many writes each sync persists.
+
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, Hash, EnumIter)]
+pub enum FsyncPolicy {
+    Always,
+    Interval,
+    Never,
+}
+
+impl FsyncPolicy {
+    pub fn possible_values() -> Vec<&'static str> {
+        FsyncPolicy::iter()
+            .map(|policy| policy.supported_value())
+            .collect()
+    }
+
+    pub fn supported_value(&self) -> &'static str {
+        match self {
+            Self::Always => "always",
+            Self::Interval => "interval",
+            Self::Never => "never",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FsyncPolicyError(String);
+
+impl FromStr for FsyncPolicy {
+    type Err = FsyncPolicyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_ascii_lowercase();
+        FsyncPolicy::iter()
+            .find(|policy| policy.supported_value() == s)
+            .ok_or_else(|| FsyncPolicyError(format!("Invalid fsync policy: {}", s)))
+    }
+}
+
+/// When the writes of the main database are persisted, see the [module documentation](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WritePolicy {
+    /// Max number of writes pending before a sync, with [`FsyncPolicy::Interval`]
+    pub max_pending_writes: usize,
+    /// Interval of the background flusher, with [`FsyncPolicy::Interval`]
+    pub flush_interval: Duration,
+    pub fsync: FsyncPolicy,
+}
+
+impl WritePolicy {
+    pub const DEFAULT_MAX_PENDING_WRITES: usize = 1024;
+    pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+}
+
+impl Default for WritePolicy {
+    fn default() -> Self {
+        Self {
+            max_pending_writes: Self::DEFAULT_MAX_PENDING_WRITES,
+            flush_interval: Self::DEFAULT_FLUSH_INTERVAL,
+            fsync: FsyncPolicy::Never,
+        }
+    }
+}
+
+/// Writes made through the database since it was opened.
+///
+/// Every sync rewrites the last, partially filled, page of the log of the backend: the fewer
+/// writes per sync, the higher the write amplification.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WriteStats {
+    /// Number of keys written or deleted
+    pub writes: u64,
+    /// Bytes of the keys and values written
+    pub bytes: u64,
+    /// Number of syncs forced by the policy
+    pub syncs: u64,
+}
+
+impl WriteStats {
+    /// Average number of writes persisted by a sync, `None` before the first sync.
+    pub fn writes_per_sync(&self) -> Option<f64> {
+        if self.syncs == 0 {
+            return None;
+        }
+        Some(self.writes as f64 / self.syncs as f64)
+    }
+}
+
+/// Applies a [`WritePolicy`] to the writes of a database.
+#[derive(Debug, Default)]
+pub(crate) struct WriteTracker {
+    policy: WritePolicy,
+    /// Writes not synced yet
+    pending: AtomicUsize,
+    writes: AtomicU64,
+    bytes: AtomicU64,
+    syncs: AtomicU64,
+}
+
+impl WriteTracker {
+    pub fn new(policy: WritePolicy) -> Self {
+        Self {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    pub fn policy(&self) -> WritePolicy {
+        self.policy
+    }
+
+    /// Records `count` writes of `bytes` bytes, returns `true` when they must be synced now.
+    pub fn record(&self, count: usize, bytes: usize) -> bool {
+        self.writes.fetch_add(count as u64, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+
+        match self.policy.fsync {
+            FsyncPolicy::Always => true,
+            FsyncPolicy::Interval => {
+                let pending = self.pending.fetch_add(count, Ordering::AcqRel) + count;
+                pending >= self.policy.max_pending_writes
+            }
+            FsyncPolicy::Never => false,
+        }
+    }
+
+    /// Takes the pending writes, returns `true` if there were any.
+    pub fn take_pending(&self) -> bool {
+        self.pending.swap(0, Ordering::AcqRel) > 0
+    }
+
+    pub fn synced(&self) {
+        self.syncs.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> WriteStats {
+        WriteStats {
+            writes: self.writes.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            syncs: self.syncs.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fsync_policy_from_str() {
+        assert_eq!(
+            "always".parse::<FsyncPolicy>().unwrap(),
+            FsyncPolicy::Always
+        );
+        assert_eq!(
+            "Interval".parse::<FsyncPolicy>().unwrap(),
+            FsyncPolicy::Interval
+        );
+        assert_eq!("never".parse::<FsyncPolicy>().unwrap(), FsyncPolicy::Never);
+        assert!("sometimes".parse::<FsyncPolicy>().is_err());
+        assert_eq!(
+            FsyncPolicy::possible_values(),
+            vec!["always", "interval", "never"]
+        );
+    }
+
+    #[test]
+    fn test_write_tracker() {
+        let always = WriteTracker::new(WritePolicy {
+            fsync: FsyncPolicy::Always,
+            ..Default::default()
+        });
+        assert!(always.record(1, 10));
+
+        let never = WriteTracker::new(WritePolicy::default());
+        assert!(!never.record(1_000_000, 10));
+        assert!(!never.take_pending());
+
+        let interval = WriteTracker::new(WritePolicy {
+            max_pending_writes: 3,
+            fsync: FsyncPolicy::Interval,
+            ..Default::default()
+        });
+        assert!(!interval.record(1, 10));
+        assert!(!interval.record(1, 10));
+        assert!(interval.record(1, 10));
+        assert!(interval.take_pending());
+        interval.synced();
+        assert!(!interval.take_pending());
+
+        // the flusher syncs the writes below the threshold
+        assert!(!interval.record(2, 20));
+        assert!(interval.take_pending());
+        interval.synced();
+
+        let stats = interval.stats();
+        assert_eq!(
+            stats,
+            WriteStats {
+                writes: 5,
+                bytes: 50,
+                syncs: 2,
+            }
+        );
+        assert_eq!(stats.writes_per_sync(), Some(2.5));
+    }
+}
@@ -31,6 +31,7 @@ use tezos_messages::p2p::binary_message::{BinaryRead, BinaryWrite, MessageHash,
 use tezos_messages::p2p::encoding::prelude::BlockHeader;
 use tezos_messages::Head;
 
+pub use crate::banned_operations_storage::BannedOperationsStorage;
 pub use crate::block_meta_storage::{
     BlockAdditionalData, BlockMetaStorage, BlockMetaStorageKV, BlockMetaStorageReader,
 };
@@ -52,6 +53,7 @@ use crate::persistent::{DBError, Decoder, Encoder, SchemaError};
 pub use crate::predecessor_storage::PredecessorStorage;
 pub use crate::system_storage::SystemStorage;
 
+pub mod banned_operations_storage;
 pub mod block_meta_storage;
 pub mod block_storage;
 pub mod chain_meta_storage;
@@ -474,6 +476,7 @@ pub mod initializer {
 
     use crate::database::error::Error as DatabaseError;
     use crate::database::tezedge_database::{TezedgeDatabase, TezedgeDatabaseBackendConfiguration};
+    use crate::database::write_policy::WritePolicy;
     use crate::persistent::database::{open_kv, RocksDbKeyValueSchema};
     use crate::persistent::{open_main_db, DBError, DbConfiguration};
     use crate::{StorageError, SystemStorage};
@@ -496,25 +499,59 @@ pub mod initializer {
     #[derive(Debug, Clone)]
     pub struct ContextActionsRocksDbTableInitializer;
 
+    /// Column of the main database, with the functions of its [`RocksDbKeyValueSchema`]
+    struct MainDbColumn {
+        name: fn() -> &'static str,
+        descriptor: fn(&RocksDbCache) -> ColumnFamilyDescriptor,
+    }
+
+    macro_rules! main_db_columns {
+        ($($schema:ty),* $(,)?) => {
+            &[$(MainDbColumn {
+                name: <$schema as RocksDbKeyValueSchema>::name,
+                descriptor: <$schema as RocksDbKeyValueSchema>::descriptor,
+            }),*]
+        };
+    }
+
+    /// All the columns of the main database, [`DbsRocksDbTableInitializer::create`] and
+    /// [`DbsRocksDbTableInitializer::column_names`] are both derived from this list.
+    static MAIN_DB_COLUMNS: &[MainDbColumn] = main_db_columns![
+        crate::block_storage::BlockPrimaryIndex,
+        crate::block_storage::BlockByLevelIndex,
+        crate::block_storage::BlockByContextHashIndex,
+        crate::BlockMetaStorage,
+        crate::OperationsStorage,
+        crate::OperationsMetaStorage,
+        crate::SystemStorage,
+        crate::persistent::sequence::Sequences,
+        crate::MempoolStorage,
+        crate::mempool_storage::MempoolByReceptionIndex,
+        crate::ChainMetaStorage,
+        crate::PredecessorStorage,
+        crate::BlockAdditionalData,
+        crate::CycleMetaStorage,
+        crate::CycleErasStorage,
+        crate::ConstantsStorage,
+        crate::BannedOperationsStorage,
+    ];
+
     impl RocksDbColumnFactory for DbsRocksDbTableInitializer {
         fn create(&self, cache: &RocksDbCache) -> Vec<ColumnFamilyDescriptor> {
-            vec![
-                crate::block_storage::BlockPrimaryIndex::descriptor(cache),
-                crate::block_storage::BlockByLevelIndex::descriptor(cache),
-                crate::block_storage::BlockByContextHashIndex::descriptor(cache),
-                crate::BlockMetaStorage::descriptor(cache),
-                crate::OperationsStorage::descriptor(cache),
-                crate::OperationsMetaStorage::descriptor(cache),
-                crate::SystemStorage::descriptor(cache),
-                crate::persistent::sequence::Sequences::descriptor(cache),
-                crate::MempoolStorage::descriptor(cache),
-                crate::ChainMetaStorage::descriptor(cache),
-                crate::PredecessorStorage::descriptor(cache),
-                crate::BlockAdditionalData::descriptor(&cache),
-                crate::CycleMetaStorage::descriptor(cache),
-                crate::CycleErasStorage::descriptor(cache),
-                crate::ConstantsStorage::descriptor(cache),
-            ]
+            MAIN_DB_COLUMNS
+                .iter()
+                .map(|column| (column.descriptor)(cache))
+                .collect()
+        }
+    }
+
+    impl DbsRocksDbTableInitializer {
+        /// Names of all the columns created by [`DbsRocksDbTableInitializer::create`]
+        pub fn column_names() -> Vec<&'static str> {
+            MAIN_DB_COLUMNS
+                .iter()
+                .map(|column| (column.name)())
+                .collect()
         }
     }
 
@@ -569,8 +606,10 @@ pub mod initializer {
         db_version: i64,
         expected_main_chain: &MainChain,
         backend_config: TezedgeDatabaseBackendConfiguration,
+        write_policy: WritePolicy,
     ) -> Result<Arc<TezedgeDatabase>, DatabaseError> {
-        let db = Arc::new(open_main_db(kv, config, backend_config)?);
+        let db =
+            Arc::new(open_main_db(kv, config, backend_config)?.with_write_policy(write_policy));
 
         match check_database_compatibility(db.clone(), db_version, expected_main_chain, &log) {
             Ok(false) => Err(DatabaseError::DatabaseIncompatibility {
@@ -701,10 +740,8 @@ pub mod tests_common {
 
     use anyhow::Error;
 
-    use crate::block_storage;
-    use crate::chain_meta_storage::ChainMetaStorage;
-    use crate::mempool_storage::MempoolStorage;
-    use crate::persistent::database::{open_kv, RocksDbKeyValueSchema};
+    use crate::initializer::{DbsRocksDbTableInitializer, RocksDbColumnFactory};
+    use crate::persistent::database::open_kv;
     use crate::persistent::sequence::Sequences;
     use crate::persistent::{open_cl, CommitLogSchema, DbConfiguration};
 
@@ -746,23 +783,7 @@ pub mod tests_common {
             let backend = if cfg!(feature = "maindb-backend-rocksdb") {
                 let kv = Arc::new(open_kv(
                     path.join("db"),
-                    vec![
-                        block_storage::BlockPrimaryIndex::descriptor(&db_cache),
-                        block_storage::BlockByLevelIndex::descriptor(&db_cache),
-                        block_storage::BlockByContextHashIndex::descriptor(&db_cache),
-                        BlockMetaStorage::descriptor(&db_cache),
-                        OperationsStorage::descriptor(&db_cache),
-                        OperationsMetaStorage::descriptor(&db_cache),
-                        SystemStorage::descriptor(&db_cache),
-                        Sequences::descriptor(&db_cache),
-                        MempoolStorage::descriptor(&db_cache),
-                        ChainMetaStorage::descriptor(&db_cache),
-                        PredecessorStorage::descriptor(&db_cache),
-                        BlockAdditionalData::descriptor(&db_cache),
-                        CycleErasStorage::descriptor(&db_cache),
-                        CycleMetaStorage::descriptor(&db_cache),
-                        ConstantsStorage::descriptor(&db_cache),
-                    ],
+                    DbsRocksDbTableInitializer.create(&db_cache),
                     &cfg,
                 )?);
                 TezedgeDatabaseBackendOptions::RocksDB(
@@ -775,23 +796,7 @@ pub mod tests_common {
             } else {
                 let kv = Arc::new(open_kv(
                     path.join("db"),
-                    vec![
-                        block_storage::BlockPrimaryIndex::descriptor(&db_cache),
-                        block_storage::BlockByLevelIndex::descriptor(&db_cache),
-                        block_storage::BlockByContextHashIndex::descriptor(&db_cache),
-                        BlockMetaStorage::descriptor(&db_cache),
-                        OperationsStorage::descriptor(&db_cache),
-                        OperationsMetaStorage::descriptor(&db_cache),
-                        SystemStorage::descriptor(&db_cache),
-                        Sequences::descriptor(&db_cache),
-                        MempoolStorage::descriptor(&db_cache),
-                        ChainMetaStorage::descriptor(&db_cache),
-                        PredecessorStorage::descriptor(&db_cache),
-                        BlockAdditionalData::descriptor(&db_cache),
-                        CycleErasStorage::descriptor(&db_cache),
-                        CycleMetaStorage::descriptor(&db_cache),
-                        ConstantsStorage::descriptor(&db_cache),
-                    ],
+                    DbsRocksDbTableInitializer.create(&db_cache),
                     &cfg,
                 )?);
                 TezedgeDatabaseBackendOptions::RocksDB(
@@ -5,6 +5,7 @@ use std::convert::TryFrom;
 use std::fmt;
 use std::fmt::Formatter;
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use serde::{Deserialize, Serialize};
 
@@ -15,7 +16,7 @@ use tezos_messages::p2p::encoding::operation::OperationMessage;
 use crate::database::tezedge_database::{KVStoreKeyValueSchema, TezedgeDatabaseWithIterator};
 use crate::persistent::database::RocksDbKeyValueSchema;
 use crate::persistent::{BincodeEncoded, Decoder, Encoder, KeyValueSchema, SchemaError};
-use crate::{num_from_slice, IteratorMode, PersistentStorage, StorageError};
+use crate::{num_from_slice, Direction, IteratorMode, PersistentStorage, StorageError};
 
 /// Convenience type for operation meta storage database
 pub type MempoolStorageKV = dyn TezedgeDatabaseWithIterator<MempoolStorage> + Sync + Send;
@@ -58,12 +59,14 @@ impl fmt::Display for MempoolOperationTypeParseError {
 #[derive(Clone)]
 pub struct MempoolStorage {
     kv: Arc<MempoolStorageKV>,
+    by_reception_index: MempoolByReceptionIndex,
 }
 
 impl MempoolStorage {
     pub fn new(persistent_storage: &PersistentStorage) -> Self {
         Self {
             kv: persistent_storage.main_db(),
+            by_reception_index: MempoolByReceptionIndex::new(persistent_storage.main_db()),
         }
     }
 
@@ -82,14 +85,40 @@ impl MempoolStorage {
         &mut self,
         operation_type: MempoolOperationType,
         operation: OperationMessage,
+    ) -> Result<(), StorageError> {
+        let received_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.put_received_at(operation_type, operation, received_at)
+    }
+
+    /// Stores the operation with an explicit reception time (unix timestamp in seconds),
+    /// which is used by [`MempoolStorage::prune_received_before`].
+    #[inline]
+    pub fn put_received_at(
+        &mut self,
+        operation_type: MempoolOperationType,
+        operation: OperationMessage,
+        received_at: u64,
     ) -> Result<(), StorageError> {
         let key = MempoolKey {
             operation_type,
             operation_hash: OperationHash::try_from(operation.message_hash()?)?,
         };
-        let value = MempoolValue { operation };
+        let value = MempoolValue {
+            operation,
+            received_at,
+        };
+        let size = MempoolKey::LEN_KEY as u64
+            + bincode::serialized_size(&value).map_err(|_| SchemaError::EncodeError)?;
 
-        self.kv.put(&key, &value).map_err(StorageError::from)
+        // an operation stored again is indexed by its last reception time only
+        if let Some(previous) = self.kv.get(&key)? {
+            self.by_reception_index.delete(previous.received_at, &key)?;
+        }
+        self.kv.put(&key, &value)?;
+        self.by_reception_index.put(received_at, &key, size)
     }
 
     #[inline]
@@ -116,15 +145,21 @@ impl MempoolStorage {
             operation_type: MempoolOperationType::Pending,
             operation_hash: operation_hash.clone(),
         };
-        self.kv.delete(&key).map_err(StorageError::from)?;
+        self.delete_key(&key)?;
 
         let key = MempoolKey {
             operation_type: MempoolOperationType::KnownValid,
             operation_hash: operation_hash.clone(),
         };
-        self.kv.delete(&key).map_err(StorageError::from)?;
+        self.delete_key(&key)
+    }
 
-        Ok(())
+    fn delete_key(&self, key: &MempoolKey) -> Result<(), StorageError> {
+        // the index entry goes first, an operation left without one is indexed again by `reindex`
+        if let Some(value) = self.kv.get(key)? {
+            self.by_reception_index.delete(value.received_at, key)?;
+        }
+        self.kv.delete(key).map_err(StorageError::from)
     }
 
     #[inline]
@@ -160,6 +195,86 @@ impl MempoolStorage {
         }
         Ok(operations)
     }
+
+    /// Returns the operations stored with `operation_type`.
+    pub fn iter_type(
+        &self,
+        operation_type: MempoolOperationType,
+    ) -> Result<Vec<(OperationHash, OperationMessage)>, StorageError> {
+        Ok(self
+            .kv
+            .iter_prefix(&[operation_type.to_u8()], Direction::Forward)?
+            .into_iter()
+            .map(|(key, value)| (key.operation_hash, value.operation))
+            .collect())
+    }
+
+    /// Deletes all operations received before `timestamp` (unix timestamp in seconds).
+    ///
+    /// Only the reception time index is read, see [`MempoolStorage::reindex`] for the operations
+    /// stored by older versions. Returns the number of deleted operations.
+    pub fn prune_received_before(&self, timestamp: u64) -> Result<usize, StorageError> {
+        let entries = self.by_reception_index.find_received_before(timestamp)?;
+
+        for (key, _) in entries.iter() {
+            self.by_reception_index.kv.delete(key)?;
+            self.kv.delete(&key.key)?;
+        }
+        Ok(entries.len())
+    }
+
+    /// Returns the number of stored operations and the size of their encoded values,
+    /// computed from the reception time index.
+    pub fn total_size(&self) -> Result<MempoolStorageSize, StorageError> {
+        let entries = self.by_reception_index.kv.find(
+            IteratorMode::Start,
+            None,
+            Box::new(|(_, _)| Ok(true)),
+        )?;
+
+        let mut total_bytes = 0;
+        for (_, v) in entries.iter() {
+            total_bytes += u64::decode(v)? as usize;
+        }
+        Ok(MempoolStorageSize {
+            operations: entries.len(),
+            total_bytes,
+        })
+    }
+
+    /// Adds the operations missing from the reception time index, e.g. stored by older
+    /// versions or left by an interrupted write, the ones without a reception time are deleted.
+    ///
+    /// It reads the whole mempool, so it is meant to run once on startup.
+    /// Returns the number of deleted operations.
+    pub fn reindex(&self) -> Result<usize, StorageError> {
+        let items = self
+            .kv
+            .find(IteratorMode::Start, None, Box::new(|(_, _)| Ok(true)))?;
+
+        let mut deleted = 0;
+        for (k, v) in items.iter() {
+            let key: MempoolKey = <Self as KeyValueSchema>::Key::decode(k)?;
+            let value: MempoolValue = BincodeEncoded::decode(v)?;
+            if value.received_at == 0 {
+                self.kv.delete(&key)?;
+                deleted += 1;
+            } else {
+                self.by_reception_index
+                    .put(value.received_at, &key, (k.len() + v.len()) as u64)?;
+            }
+        }
+        Ok(deleted)
+    }
+}
+
+/// Size accounting of the [`MempoolStorage`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MempoolStorageSize {
+    /// Number of stored operations (pending and known valid)
+    pub operations: usize,
+    /// Total size of the stored keys and values in bytes
+    pub total_bytes: usize,
 }
 
 impl KeyValueSchema for MempoolStorage {
@@ -180,7 +295,7 @@ impl KVStoreKeyValueSchema for MempoolStorage {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct MempoolKey {
     operation_type: MempoolOperationType,
     operation_hash: OperationHash,
@@ -229,6 +344,132 @@ impl Decoder for MempoolKey {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct MempoolValue {
     operation: OperationMessage,
+    /// Reception time, unix timestamp in seconds
+    received_at: u64,
+}
+
+impl BincodeEncoded for MempoolValue {
+    fn decode(bytes: &[u8]) -> Result<Self, SchemaError> {
+        bincode::deserialize(bytes)
+            .or_else(|_| {
+                // values stored before the reception time was added
+                bincode::deserialize(bytes).map(|operation| MempoolValue {
+                    operation,
+                    received_at: 0,
+                })
+            })
+            .map_err(|_| SchemaError::DecodeError)
+    }
+}
+
+/// Index of the mempool operations by their reception time, with the size of their stored
+/// key and value, so that pruning and size accounting don't read the operations.
+#[derive(Clone)]
+pub struct MempoolByReceptionIndex {
+    kv: Arc<MempoolByReceptionIndexKV>,
 }
 
-impl BincodeEncoded for MempoolValue {}
+pub type MempoolByReceptionIndexKV =
+    dyn TezedgeDatabaseWithIterator<MempoolByReceptionIndex> + Sync + Send;
+
+impl MempoolByReceptionIndex {
+    fn new(kv: Arc<MempoolByReceptionIndexKV>) -> Self {
+        Self { kv }
+    }
+
+    fn put(&self, received_at: u64, key: &MempoolKey, size: u64) -> Result<(), StorageError> {
+        let key = MempoolByReceptionKey {
+            received_at,
+            key: key.clone(),
+        };
+        self.kv.put(&key, &size).map_err(StorageError::from)
+    }
+
+    fn delete(&self, received_at: u64, key: &MempoolKey) -> Result<(), StorageError> {
+        let key = MempoolByReceptionKey {
+            received_at,
+            key: key.clone(),
+        };
+        self.kv.delete(&key).map_err(StorageError::from)
+    }
+
+    /// Returns the entries received before `timestamp`, decoding only their keys.
+    fn find_received_before(
+        &self,
+        timestamp: u64,
+    ) -> Result<Vec<(MempoolByReceptionKey, u64)>, StorageError> {
+        let items = self.kv.find(
+            IteratorMode::Start,
+            None,
+            Box::new(move |(k, _)| {
+                if k.len() < MempoolByReceptionKey::LEN_RECEIVED_AT {
+                    return Err(SchemaError::DecodeError);
+                }
+                Ok(num_from_slice!(k, MempoolByReceptionKey::IDX_RECEIVED_AT, u64) < timestamp)
+            }),
+        )?;
+
+        let mut entries = Vec::with_capacity(items.len());
+        for (k, v) in items.iter() {
+            entries.push((MempoolByReceptionKey::decode(k)?, u64::decode(v)?));
+        }
+        Ok(entries)
+    }
+}
+
+impl KeyValueSchema for MempoolByReceptionIndex {
+    type Key = MempoolByReceptionKey;
+    /// Size of the stored key and value of the operation
+    type Value = u64;
+}
+
+impl RocksDbKeyValueSchema for MempoolByReceptionIndex {
+    #[inline]
+    fn name() -> &'static str {
+        "mempool_by_reception_storage"
+    }
+}
+
+impl KVStoreKeyValueSchema for MempoolByReceptionIndex {
+    fn column_name() -> &'static str {
+        Self::name()
+    }
+}
+
+/// Key of [`MempoolByReceptionIndex`], the big endian reception time goes first,
+/// so the entries are ordered by it.
+#[derive(Debug)]
+pub struct MempoolByReceptionKey {
+    received_at: u64,
+    key: MempoolKey,
+}
+
+impl MempoolByReceptionKey {
+    const LEN_RECEIVED_AT: usize = std::mem::size_of::<u64>();
+    const LEN_KEY: usize = Self::LEN_RECEIVED_AT + MempoolKey::LEN_KEY;
+
+    const IDX_RECEIVED_AT: usize = 0;
+    const IDX_KEY: usize = Self::IDX_RECEIVED_AT + Self::LEN_RECEIVED_AT;
+}
+
+impl Encoder for MempoolByReceptionKey {
+    fn encode(&self) -> Result<Vec<u8>, SchemaError> {
+        let mut bytes = Vec::with_capacity(Self::LEN_KEY);
+        bytes.extend(&self.received_at.to_be_bytes());
+        bytes.extend(self.key.encode()?);
+        Ok(bytes)
+    }
+}
+
+impl Decoder for MempoolByReceptionKey {
+    fn decode(bytes: &[u8]) -> Result<Self, SchemaError> {
+        if bytes.len() == Self::LEN_KEY {
+            Ok(MempoolByReceptionKey {
+                received_at: num_from_slice!(bytes, Self::IDX_RECEIVED_AT, u64),
+                key: MempoolKey::decode(&bytes[Self::IDX_KEY..])?,
+            })
+        } else {
+            Err(SchemaError::DecodeError)
+        }
+    }
+}
@@ -12,7 +12,7 @@ use tezos_messages::p2p::encoding::prelude::*;
 use crate::database::tezedge_database::{KVStoreKeyValueSchema, TezedgeDatabaseWithIterator};
 use crate::persistent::database::{default_table_options, RocksDbKeyValueSchema};
 use crate::persistent::{BincodeEncoded, Decoder, Encoder, KeyValueSchema, SchemaError};
-use crate::{PersistentStorage, StorageError};
+use crate::{Direction, PersistentStorage, StorageError};
 
 pub type OperationsStorageKV = dyn TezedgeDatabaseWithIterator<OperationsStorage> + Sync + Send;
 
@@ -67,19 +67,12 @@ impl OperationsStorageReader for OperationsStorage {
         &self,
         block_hash: &BlockHash,
     ) -> Result<Vec<OperationsForBlocksMessage>, StorageError> {
-        let key = OperationKey {
-            block_hash: block_hash.clone(),
-            validation_pass: 0,
-        };
-
-        let mut operations: Vec<OperationsForBlocksMessage> = vec![];
-        for (_key, value) in self.kv.find_by_prefix(
-            &key,
-            HashType::BlockHash.size(),
-            Box::new(|(_, _)| Ok(true)),
-        )? {
-            operations.push(BincodeEncoded::decode(value.as_ref())?);
-        }
+        let mut operations: Vec<OperationsForBlocksMessage> = self
+            .kv
+            .iter_prefix(block_hash.as_ref(), Direction::Forward)?
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect();
         operations.sort_by_key(|v| v.operations_for_block().validation_pass());
         Ok(operations)
     }
@@ -4,9 +4,10 @@
 use anyhow::Error;
 use crypto::hash::OperationHash;
 
-use storage::mempool_storage::MempoolOperationType;
+use storage::initializer::DbsRocksDbTableInitializer;
+use storage::mempool_storage::{MempoolOperationType, MempoolStorageSize};
 use storage::tests_common::TmpStorage;
-use storage::MempoolStorage;
+use storage::{BannedOperationsStorage, MempoolStorage};
 use tezos_messages::p2p::binary_message::BinaryRead;
 use tezos_messages::p2p::binary_message::MessageHash;
 use tezos_messages::p2p::encoding::prelude::*;
@@ -32,6 +33,138 @@ fn mempool_storage_read_write() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn mempool_storage_prune_and_size() -> Result<(), Error> {
+    let tmp_storage = TmpStorage::create("__mempool_storage_prune_and_size")?;
+    let mut storage = MempoolStorage::new(tmp_storage.storage());
+    assert_eq!(storage.total_size()?.operations, 0);
+
+    let operation = make_test_operation_message()?;
+    let operation_hash = operation.message_typed_hash::<OperationHash>()?;
+
+    storage.put_received_at(MempoolOperationType::Pending, operation.clone(), 100)?;
+    storage.put_received_at(MempoolOperationType::KnownValid, operation, 200)?;
+
+    let size = storage.total_size()?;
+    assert_eq!(size.operations, 2);
+    assert!(size.total_bytes > 0);
+
+    assert_eq!(storage.prune_received_before(150)?, 1);
+    assert!(storage
+        .get(MempoolOperationType::Pending, operation_hash.clone())?
+        .is_none());
+    assert!(storage
+        .get(MempoolOperationType::KnownValid, operation_hash.clone())?
+        .is_some());
+    assert_eq!(storage.total_size()?.operations, 1);
+
+    assert_eq!(storage.prune_received_before(150)?, 0);
+    assert_eq!(storage.prune_received_before(201)?, 1);
+    assert!(storage.find(&operation_hash)?.is_none());
+    assert_eq!(storage.total_size()?, MempoolStorageSize::default());
+
+    Ok(())
+}
+
+#[test]
+fn mempool_storage_reception_index() -> Result<(), Error> {
+    let tmp_storage = TmpStorage::create("__mempool_storage_reception_index")?;
+    let mut storage = MempoolStorage::new(tmp_storage.storage());
+
+    let operation = make_test_operation_message()?;
+    let operation_hash = operation.message_typed_hash::<OperationHash>()?;
+
+    // stored again, the operation is indexed by its last reception time only
+    storage.put_received_at(MempoolOperationType::Pending, operation.clone(), 100)?;
+    storage.put_received_at(MempoolOperationType::Pending, operation, 300)?;
+    let size = storage.total_size()?;
+    assert_eq!(size.operations, 1);
+    assert_eq!(storage.prune_received_before(150)?, 0);
+
+    // reindexing an indexed mempool changes nothing
+    assert_eq!(storage.reindex()?, 0);
+    assert_eq!(storage.total_size()?, size);
+
+    storage.delete(&operation_hash)?;
+    assert_eq!(storage.total_size()?, MempoolStorageSize::default());
+    assert_eq!(storage.prune_received_before(301)?, 0);
+
+    Ok(())
+}
+
+#[test]
+fn mempool_storage_iter_type() -> Result<(), Error> {
+    let tmp_storage = TmpStorage::create("__mempool_storage_iter_type")?;
+    let mut storage = MempoolStorage::new(tmp_storage.storage());
+
+    let operation = make_test_operation_message()?;
+    let operation_hash = operation.message_typed_hash::<OperationHash>()?;
+    assert!(storage.iter_type(MempoolOperationType::Pending)?.is_empty());
+
+    storage.put_pending(operation.clone())?;
+    assert_eq!(
+        storage.iter_type(MempoolOperationType::Pending)?,
+        vec![(operation_hash.clone(), operation.clone())]
+    );
+    assert!(storage
+        .iter_type(MempoolOperationType::KnownValid)?
+        .is_empty());
+
+    storage.put_known_valid(operation.clone())?;
+    assert_eq!(storage.iter_type(MempoolOperationType::Pending)?.len(), 1);
+    assert_eq!(
+        storage.iter_type(MempoolOperationType::KnownValid)?,
+        vec![(operation_hash, operation)]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn mempool_storage_compaction() -> Result<(), Error> {
+    let tmp_storage = TmpStorage::create("__mempool_storage_compaction")?;
+    let mut storage = MempoolStorage::new(tmp_storage.storage());
+    let main_db = tmp_storage.storage().main_db();
+
+    storage.put_known_valid(make_test_operation_message()?)?;
+    main_db.flush()?;
+
+    let sizes = main_db.approximate_sizes()?;
+    assert_eq!(
+        sizes.len(),
+        DbsRocksDbTableInitializer::column_names().len()
+    );
+    assert!(sizes.iter().any(|size| size.column == "mempool_storage"));
+
+    main_db.compact_range("mempool_storage")?;
+    assert_eq!(storage.total_size()?.operations, 1);
+
+    Ok(())
+}
+
+#[test]
+fn banned_operations_storage_ban_unban() -> Result<(), Error> {
+    let tmp_storage = TmpStorage::create("__banned_operations_storage_ban_unban")?;
+    let storage = BannedOperationsStorage::new(tmp_storage.storage());
+
+    let operation_hash = make_test_operation_message()?.message_typed_hash::<OperationHash>()?;
+    assert!(!storage.is_banned(&operation_hash)?);
+    assert!(storage.iter()?.is_empty());
+
+    storage.ban(&operation_hash)?;
+    assert!(storage.is_banned(&operation_hash)?);
+    let banned = storage.iter()?;
+    assert_eq!(banned.len(), 1);
+    assert_eq!(banned[0].0, operation_hash);
+    assert!(banned[0].1 > 0);
+
+    assert!(storage.unban(&operation_hash)?);
+    assert!(!storage.unban(&operation_hash)?);
+    assert!(!storage.is_banned(&operation_hash)?);
+
+    Ok(())
+}
+
 fn make_test_operation_message() -> Result<OperationMessage, Error> {
     let message_bytes = hex::decode("10490b79070cf19175cd7e3b9c1ee66f6e85799980404b119132ea7e58a4a97e000008c387fa065a181d45d47a9b78ddc77e92a881779ff2cbabbf9646eade4bf1405a08e00b725ed849eea46953b10b5cdebc518e6fd47e69b82d2ca18c4cf6d2f312dd08")?;
     let operation = Operation::from_bytes(message_bytes)?;
@@ -0,0 +1,268 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Checkpoints: speculative changes on top of a commit.
+//!
+//! The prevalidator of the mempool and the construction of a block apply operations that may
+//! be thrown away. Applied on the working tree of the index, their objects would stay in the
+//! shared working storage until the next checkout. A [`ContextOverlay`] instead:
+//! - owns a private working storage, the objects of the commit are loaded in it on demand and
+//!   the changes are allocated in it, the shared one is untouched,
+//! - interns its strings in a private [`StringInterner`]: nothing is copied from the working
+//!   tree of the index, the strings of the directory shapes are resolved through the repository,
+//! - pins its commit in the index like a [`crate::read_transaction::ReadTransaction`],
+//! - is either committed into the repository with [`ContextOverlay::commit`], or dropped with
+//!   [`ContextOverlay::discard`], which frees its storage.
+//!
+//! ```no_compile
+//! let mut overlay = context.checkpoint()?;
+//! overlay.apply(|context| context.add(&context_key!("data/contracts/index/a"), &value))?;
+//! let context_hash = overlay.commit(author, message, date)?;
+//! ```
+//!
+//! The `StringId`s of a private interner never collide with the ones of the working tree or the
+//! repository, and the directories using them are serialized without shape: an overlay can be
+//! committed whatever the working tree of the index interned meanwhile.
+
+use std::{cell::RefCell, rc::Rc};
+
+use crypto::hash::ContextHash;
+
+use crate::{
+    kv_store::HashId,
+    read_transaction::release_pin,
+    working_tree::{storage::Storage, string_interner::StringInterner},
+    ContextError, ShellContextApi, TezedgeContext, TezedgeIndex,
+};
+
+/// Speculative changes on top of a commit, see the [module documentation](self).
+pub struct ContextOverlay {
+    /// Index of the working tree forked
+    index: TezedgeIndex,
+    /// Commit forked
+    hash_id: HashId,
+    /// Current state of the overlay, in its private storage
+    context: TezedgeContext,
+}
+
+impl ContextOverlay {
+    /// Forks the commit `hash_id` of `index`.
+    ///
+    /// Returns `None` if the commit doesn't exist.
+    pub(crate) fn fork(
+        index: &TezedgeIndex,
+        hash_id: HashId,
+    ) -> Result<Option<Self>, ContextError> {
+        let mut storage = Storage::default();
+        storage.strings = StringInterner::new_private();
+
+        let private_index = TezedgeIndex {
+            storage: Rc::new(RefCell::new(storage)),
+            ..index.clone()
+        };
+
+        let context = match private_index.checkout_commit(hash_id)? {
+            Some(context) => context,
+            None => return Ok(None),
+        };
+
+        index.pinned_commits.pin(hash_id);
+
+        Ok(Some(Self {
+            index: index.clone(),
+            hash_id,
+            context,
+        }))
+    }
+
+    /// The current state of the overlay.
+    pub fn context(&self) -> &TezedgeContext {
+        &self.context
+    }
+
+    /// Applies `f` to the overlay, which is left unchanged when `f` fails.
+    ///
+    /// `f` must return a context derived from the one it receives.
+    pub fn apply<F>(&mut self, f: F) -> Result<(), ContextError>
+    where
+        F: FnOnce(&TezedgeContext) -> Result<TezedgeContext, ContextError>,
+    {
+        let context = f(&self.context)?;
+        debug_assert!(Rc::ptr_eq(
+            &context.index.storage,
+            &self.context.index.storage
+        ));
+
+        self.context = context;
+        Ok(())
+    }
+
+    /// Commits the overlay into the repository, the new commit can then be checked out from the
+    /// index.
+    pub fn commit(
+        self,
+        author: String,
+        message: String,
+        date: i64,
+    ) -> Result<ContextHash, ContextError> {
+        self.context.commit(author, message, date)
+    }
+
+    /// Drops the changes of the overlay.
+    pub fn discard(self) {}
+}
+
+impl Drop for ContextOverlay {
+    fn drop(&mut self) {
+        release_pin(&self.index, self.hash_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tezos_api::ffi::{ContextKvStoreConfiguration, TezosContextTezEdgeStorageConfiguration};
+
+    use super::*;
+    use crate::{initializer::initialize_tezedge_context, IndexApi, ProtocolContextApi};
+
+    fn context() -> TezedgeContext {
+        initialize_tezedge_context(&TezosContextTezEdgeStorageConfiguration {
+            backend: ContextKvStoreConfiguration::InMem,
+            ipc_socket_path: None,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let context = context();
+        let context = context.add(&["a", "b"], &[1]).unwrap();
+        let base = context
+            .commit("Tezos".to_string(), "".to_string(), 0)
+            .unwrap();
+        let index = context.index.clone();
+
+        assert!(matches!(
+            context.checkpoint(),
+            Err(ContextError::CheckpointWithoutCommit)
+        ));
+        let context = index.checkout(&base).unwrap().unwrap();
+
+        // discarded changes are not visible anywhere
+        let mut overlay = context.checkpoint().unwrap();
+        assert_eq!(index.pinned_commits.count(), 1);
+        overlay
+            .apply(|context| context.add(&["a", "b"], &[2]))
+            .unwrap();
+        overlay
+            .apply(|context| context.add(&["a", "speculative"], &[3]))
+            .unwrap();
+        assert_eq!(overlay.context().find(&["a", "b"]).unwrap(), Some(vec![2]));
+        assert_eq!(context.find(&["a", "b"]).unwrap(), Some(vec![1]));
+        overlay.discard();
+        assert_eq!(index.pinned_commits.count(), 0);
+
+        let context = index.checkout(&base).unwrap().unwrap();
+        assert_eq!(context.find(&["a", "speculative"]).unwrap(), None);
+
+        // a failed application leaves the overlay unchanged
+        let mut overlay = index.checkpoint(&base).unwrap().unwrap();
+        overlay
+            .apply(|context| context.add(&["a", "c"], &[4]))
+            .unwrap();
+        assert!(overlay
+            .apply(|_| Err(ContextError::CheckpointWithoutCommit))
+            .is_err());
+        assert_eq!(overlay.context().find(&["a", "c"]).unwrap(), Some(vec![4]));
+
+        let committed = overlay
+            .commit("Tezos".to_string(), "".to_string(), 1)
+            .unwrap();
+        assert_eq!(index.pinned_commits.count(), 0);
+
+        // same commit as the one made on the working tree of the index
+        let expected = context
+            .add(&["a", "c"], &[4])
+            .unwrap()
+            .hash("Tezos".to_string(), "".to_string(), 1)
+            .unwrap();
+        assert_eq!(committed, expected);
+
+        let context = index.checkout(&committed).unwrap().unwrap();
+        assert_eq!(context.find(&["a", "b"]).unwrap(), Some(vec![1]));
+        assert_eq!(context.find(&["a", "c"]).unwrap(), Some(vec![4]));
+    }
+
+    #[test]
+    fn test_checkpoint_strings() {
+        let context = context();
+        let context = context.add(&["a", "b"], &[1]).unwrap();
+        let base = context
+            .commit("Tezos".to_string(), "".to_string(), 0)
+            .unwrap();
+        let index = context.index.clone();
+        let context = index.checkout(&base).unwrap().unwrap();
+        let strings_length = index.storage.borrow().strings.cursor();
+
+        let mut overlay = context.checkpoint().unwrap();
+        overlay
+            .apply(|context| context.add(&["a", "overlay"], &[2]))
+            .unwrap();
+        // nothing is interned in the working tree of the index by the overlay
+        assert_eq!(index.storage.borrow().strings.cursor(), strings_length);
+
+        // the working tree of the index interns a new string meanwhile
+        let shared = context.add(&["a", "shared"], &[3]).unwrap();
+
+        let committed = overlay
+            .commit("Tezos".to_string(), "".to_string(), 1)
+            .unwrap();
+        let shared = shared
+            .commit("Tezos".to_string(), "".to_string(), 1)
+            .unwrap();
+
+        let context = index.checkout(&committed).unwrap().unwrap();
+        assert_eq!(context.find(&["a", "b"]).unwrap(), Some(vec![1]));
+        assert_eq!(context.find(&["a", "overlay"]).unwrap(), Some(vec![2]));
+        assert_eq!(context.find(&["a", "shared"]).unwrap(), None);
+
+        let context = index.checkout(&shared).unwrap().unwrap();
+        assert_eq!(context.find(&["a", "shared"]).unwrap(), Some(vec![3]));
+        assert_eq!(context.find(&["a", "overlay"]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_checkpoint_no_compaction() {
+        let context = context();
+        let context = context.add(&["a", "b"], &[1]).unwrap();
+        let base = context
+            .commit("Tezos".to_string(), "".to_string(), 0)
+            .unwrap();
+        let index = context.index.clone();
+
+        let mut overlay = index.checkpoint(&base).unwrap().unwrap();
+        let generation = index.storage.borrow().strings.generation();
+
+        // enough strings to trigger a compaction at the next checkout
+        {
+            let mut storage = index.storage.borrow_mut();
+            let mut i = 0;
+            while !storage.strings.should_compact() {
+                storage.strings.get_string_id(&format!("{:029}", i));
+                i += 1;
+            }
+        }
+
+        index.checkout(&base).unwrap().unwrap();
+        assert_eq!(index.storage.borrow().strings.generation(), generation);
+        overlay
+            .apply(|context| context.add(&["a", "c"], &[2]))
+            .unwrap();
+        assert_eq!(overlay.context().find(&["a", "b"]).unwrap(), Some(vec![1]));
+        overlay.discard();
+
+        let context = index.checkout(&base).unwrap().unwrap();
+        assert_ne!(index.storage.borrow().strings.generation(), generation);
+        assert_eq!(context.find(&["a", "b"]).unwrap(), Some(vec![1]));
+    }
+}
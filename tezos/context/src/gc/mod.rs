@@ -14,6 +14,8 @@ use crypto::hash::FromBytesError;
 use crate::persistent::DBError;
 use crate::{hash::HashingError, kv_store::HashId};
 
+pub mod rolling;
+pub mod watermark;
 pub(crate) mod worker;
 
 pub trait GarbageCollector {
@@ -0,0 +1,359 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Rolling garbage collection: only the trees of the last commits are kept.
+//!
+//! Every commit is recorded by [`RollingGc`], once twice the number of commits to keep have been
+//! applied, the objects reachable from the last ones (and from the pinned commits) are collected
+//! and everything else is dropped from the repository with `KeyValueStoreBackend::retain`.
+//!
+//! The parents of the commits kept are not followed, but they are still referenced: they are
+//! kept as tombstones, their hash remains and their id is not reused until no commit kept
+//! references them anymore. They cannot be checked out.
+//!
+//! The mode is enabled with the `CONTEXT_ROLLING_GC_KEEP` environment variable, set to the
+//! number of commits to keep. It replaces the cycle garbage collection of the in-memory repository.
+//!
+//! The collection can also be triggered by the memory used by the process, see
+//! [`crate::gc::watermark`].
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::{
+    gc::watermark::MemoryPressure,
+    kv_store::HashId,
+    persistent::DBError,
+    working_tree::serializer::{commit_parent_hash_id, iter_hash_ids},
+    ContextKeyValueStore,
+};
+
+/// Environment variable containing the number of commits to keep.
+pub const ROLLING_GC_KEEP_ENV: &str = "CONTEXT_ROLLING_GC_KEEP";
+
+/// Returns the number of commits to keep, when the rolling mode is enabled.
+pub fn rolling_gc_from_env() -> Option<usize> {
+    let keep = std::env::var(ROLLING_GC_KEEP_ENV).ok()?;
+
+    Some(
+        keep.parse::<usize>()
+            .expect("Provided `CONTEXT_ROLLING_GC_KEEP` value cannot be converted to usize"),
+    )
+}
+
+/// Last commits applied, see the [module documentation](self).
+#[derive(Debug)]
+pub struct RollingGc {
+    keep: usize,
+    commits: VecDeque<HashId>,
+    /// The last collection ran under a hard pressure, which didn't drop since
+    after_hard_collection: bool,
+}
+
+impl RollingGc {
+    pub fn new(keep: usize) -> Self {
+        let keep = keep.max(1);
+
+        Self {
+            keep,
+            commits: VecDeque::with_capacity(keep * 2),
+            after_hard_collection: false,
+        }
+    }
+
+    /// Number of commits kept by a collection.
+    pub fn keep(&self) -> usize {
+        self.keep
+    }
+
+    /// Records a new commit, returns `true` when a collection is due.
+    pub fn commit_applied(&mut self, commit_hash_id: HashId) -> bool {
+        self.commit_applied_under(commit_hash_id, MemoryPressure::Normal)
+    }
+
+    /// Records a new commit, returns `true` when a collection is due under the memory `pressure`.
+    ///
+    /// See [`crate::gc::watermark`].
+    pub fn commit_applied_under(
+        &mut self,
+        commit_hash_id: HashId,
+        pressure: MemoryPressure,
+    ) -> bool {
+        self.commits.push_back(commit_hash_id);
+
+        let due = match pressure {
+            MemoryPressure::Normal => self.keep * 2,
+            MemoryPressure::Soft => self.keep + (self.keep / 2).max(1),
+            // The memory is rarely given back to the system, the pressure may remain
+            // hard after a collection: the next one waits for `keep` commits
+            MemoryPressure::Hard if self.after_hard_collection => self.keep.max(2),
+            MemoryPressure::Hard => 2,
+        };
+        if pressure != MemoryPressure::Hard {
+            self.after_hard_collection = false;
+        }
+        self.commits.len() >= due
+    }
+
+    /// Forgets the older commits, and returns the ones to keep.
+    pub fn take_roots(&mut self) -> Vec<HashId> {
+        self.take_roots_under(MemoryPressure::Normal)
+    }
+
+    /// Forgets the older commits, and returns the ones to keep under the memory `pressure`.
+    ///
+    /// Only the last commit is kept under a hard pressure.
+    pub fn take_roots_under(&mut self, pressure: MemoryPressure) -> Vec<HashId> {
+        let keep = match pressure {
+            MemoryPressure::Hard => 1,
+            _ => self.keep,
+        };
+        self.after_hard_collection = pressure == MemoryPressure::Hard;
+
+        while self.commits.len() > keep {
+            self.commits.pop_front();
+        }
+        self.commits.iter().copied().collect()
+    }
+}
+
+/// Objects to keep after a collection, see [`collect_reachable`].
+#[derive(Debug, Default)]
+pub struct Reachable {
+    pub objects: HashSet<HashId>,
+    /// Parents of the commits kept, not reachable themselves
+    pub tombstones: HashSet<HashId>,
+}
+
+/// Returns the `HashId` of all the objects reachable from the commits `roots`.
+///
+/// The parents of the commits are not followed, they are returned as tombstones.
+pub fn collect_reachable(
+    repository: &ContextKeyValueStore,
+    roots: &[HashId],
+) -> Result<Reachable, DBError> {
+    let mut reachable = Reachable::default();
+    let mut stack: Vec<HashId> = roots.to_vec();
+
+    while let Some(hash_id) = stack.pop() {
+        if !reachable.objects.insert(hash_id) {
+            continue;
+        }
+
+        let value = match repository.get_value(hash_id)? {
+            Some(value) => value,
+            None => continue,
+        };
+
+        if let Some(parent) = commit_parent_hash_id(&value) {
+            reachable.tombstones.insert(parent);
+        }
+        stack.extend(iter_hash_ids(&value).filter(|id| !reachable.objects.contains(id)));
+    }
+
+    let objects = &reachable.objects;
+    reachable
+        .tombstones
+        .retain(|hash_id| !objects.contains(hash_id));
+
+    Ok(reachable)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use std::sync::{Arc, RwLock};
+
+    use crypto::hash::ContextHash;
+
+    use super::*;
+    use crate::{
+        gc::watermark::{process_rss, MemoryWatermarks},
+        kv_store::in_memory::InMemory,
+        IndexApi, ProtocolContextApi, ShellContextApi, TezedgeContext, TezedgeIndex,
+    };
+
+    #[test]
+    fn test_rolling_gc_roots() {
+        let mut gc = RollingGc::new(2);
+        let ids: Vec<HashId> = (1..=5).map(|id| HashId::new(id).unwrap()).collect();
+
+        assert!(!gc.commit_applied(ids[0]));
+        assert!(!gc.commit_applied(ids[1]));
+        assert!(!gc.commit_applied(ids[2]));
+        assert!(gc.commit_applied(ids[3]));
+        assert_eq!(gc.take_roots(), vec![ids[2], ids[3]]);
+
+        assert!(!gc.commit_applied(ids[4]));
+        assert_eq!(RollingGc::new(0).keep(), 1);
+    }
+
+    #[test]
+    fn test_rolling_gc_under_pressure() {
+        let mut gc = RollingGc::new(4);
+        let ids: Vec<HashId> = (1..=12).map(|id| HashId::new(id).unwrap()).collect();
+
+        for id in &ids[..5] {
+            assert!(!gc.commit_applied_under(*id, MemoryPressure::Normal));
+        }
+        // the collection is due earlier above the soft limit
+        assert!(gc.commit_applied_under(ids[5], MemoryPressure::Soft));
+        assert_eq!(
+            gc.take_roots_under(MemoryPressure::Soft),
+            vec![ids[2], ids[3], ids[4], ids[5]]
+        );
+
+        // above the hard limit, only the last one is kept
+        assert!(gc.commit_applied_under(ids[6], MemoryPressure::Hard));
+        assert_eq!(gc.take_roots_under(MemoryPressure::Hard), vec![ids[6]]);
+
+        // the pressure remains hard, the next collection waits for `keep` commits
+        for id in &ids[7..9] {
+            assert!(!gc.commit_applied_under(*id, MemoryPressure::Hard));
+        }
+        assert!(gc.commit_applied_under(ids[9], MemoryPressure::Hard));
+        assert_eq!(gc.take_roots_under(MemoryPressure::Hard), vec![ids[9]]);
+
+        // once the pressure dropped, crossing the hard limit collects right away
+        assert!(!gc.commit_applied_under(ids[10], MemoryPressure::Normal));
+        assert!(gc.commit_applied_under(ids[11], MemoryPressure::Hard));
+    }
+
+    #[test]
+    fn test_rolling_gc_in_memory() {
+        let repository = InMemory::try_new_with_gc(false).unwrap();
+        let index = TezedgeIndex::new(Arc::new(RwLock::new(repository)), None).with_rolling_gc(2);
+        let mut context = TezedgeContext::new(index.clone(), None, None);
+        let mut commits = Vec::new();
+        let mut pinned = None;
+
+        for (i, value) in [1u8, 2, 3].iter().enumerate() {
+            context = context.add(&["a", "b"], &[*value; 40]).unwrap();
+            context = context.add(&["c"], &[*value; 40]).unwrap();
+            let commit = context
+                .commit("Tezos".to_string(), "".to_string(), i as i64)
+                .unwrap();
+            commits.push(commit);
+
+            if i == 0 {
+                // pinned commits are kept
+                pinned = index.begin_read(&commits[0]).unwrap();
+            }
+        }
+        let values_before = index.repository.read().unwrap().memory_usage().values_bytes;
+
+        // 4th commit, the collection keeps the 2 last commits and the pinned one
+        context = context.add(&["a", "d"], &[4; 40]).unwrap();
+        commits.push(
+            context
+                .commit("Tezos".to_string(), "".to_string(), 3)
+                .unwrap(),
+        );
+
+        let values_after = index.repository.read().unwrap().memory_usage().values_bytes;
+        assert!(values_after < values_before);
+
+        assert!(index.checkout(&commits[1]).unwrap().is_none());
+
+        assert!(pinned.is_some());
+        let first = index.checkout(&commits[0]).unwrap().unwrap();
+        assert_eq!(first.find(&["a", "b"]).unwrap(), Some(vec![1; 40]));
+
+        let third = index.checkout(&commits[2]).unwrap().unwrap();
+        assert_eq!(third.find(&["c"]).unwrap(), Some(vec![3; 40]));
+
+        let last = index.checkout(&commits[3]).unwrap().unwrap();
+        assert_eq!(last.find(&["a", "b"]).unwrap(), Some(vec![3; 40]));
+        assert_eq!(last.find(&["a", "d"]).unwrap(), Some(vec![4; 40]));
+
+        // released ids are reused by the next commits
+        let context = last.add(&["e"], &[5; 40]).unwrap();
+        let commit = context
+            .commit("Tezos".to_string(), "".to_string(), 4)
+            .unwrap();
+        let context = index.checkout(&commit).unwrap().unwrap();
+        assert_eq!(context.find(&["e"]).unwrap(), Some(vec![5; 40]));
+        assert_eq!(context.find(&["a", "d"]).unwrap(), Some(vec![4; 40]));
+    }
+
+    #[test]
+    fn test_rolling_gc_commit_parents() {
+        let repository = InMemory::try_new_with_gc(false).unwrap();
+        let index = TezedgeIndex::new(Arc::new(RwLock::new(repository)), None).with_rolling_gc(2);
+        let mut context = TezedgeContext::new(index.clone(), None, None);
+        let mut commits = Vec::new();
+
+        // the 4th commit drops the 2 first ones, the 5th reuses their ids
+        for value in 1u8..=5 {
+            context = context.add(&["a"], &[value; 40]).unwrap();
+            commits.push(
+                context
+                    .commit("Tezos".to_string(), "".to_string(), value as i64)
+                    .unwrap(),
+            );
+            context = index.checkout(commits.last().unwrap()).unwrap().unwrap();
+        }
+        assert!(index.checkout(&commits[1]).unwrap().is_none());
+
+        // the parent of a commit kept cannot be checked out, but its id was not reused
+        let third = index.checkout(&commits[2]).unwrap().unwrap();
+        let parent_id = {
+            let mut storage = index.storage.borrow_mut();
+            let commit = index
+                .fetch_commit(third.parent_commit_hash.unwrap(), &mut storage)
+                .unwrap()
+                .unwrap();
+            commit.parent_commit_hash.unwrap()
+        };
+        let parent_hash = index.fetch_hash(parent_id).unwrap().unwrap();
+        assert_eq!(ContextHash::try_from(&parent_hash[..]).unwrap(), commits[1]);
+    }
+
+    #[test]
+    fn test_rolling_gc_hard_watermark() {
+        let repository = InMemory::try_new_with_gc(false).unwrap();
+        let index = TezedgeIndex::new(Arc::new(RwLock::new(repository)), None)
+            .with_rolling_gc(10)
+            .with_memory_watermarks(MemoryWatermarks {
+                soft: None,
+                hard: Some(0),
+            });
+        let mut context = TezedgeContext::new(index.clone(), None, None);
+        let mut commits = Vec::new();
+
+        for (i, value) in [1u8, 2].iter().enumerate() {
+            context = context.add(&["a"], &[*value; 40]).unwrap();
+            commits.push(
+                context
+                    .commit("Tezos".to_string(), "".to_string(), i as i64)
+                    .unwrap(),
+            );
+        }
+
+        // the process exceeds the hard limit, only the last commit is kept
+        if process_rss().is_some() {
+            assert!(index.checkout(&commits[0]).unwrap().is_none());
+        }
+        let last = index.checkout(&commits[1]).unwrap().unwrap();
+        assert_eq!(last.find(&["a"]).unwrap(), Some(vec![2; 40]));
+
+        let usage = context.get_memory_usage().unwrap().breakdown();
+        assert!(usage.hashes > 0);
+        assert!(usage.values > 0);
+        assert!(usage.shapes > 0 || usage.strings > 0);
+        assert_eq!(
+            usage.total,
+            usage.hashes + usage.values + usage.shapes + usage.strings + usage.working_tree
+        );
+    }
+
+    #[test]
+    fn test_retain_with_cycle_gc() {
+        let repository = InMemory::try_new_with_gc(true).unwrap();
+        let index = TezedgeIndex::new(Arc::new(RwLock::new(repository)), None);
+
+        assert!(matches!(
+            index.retain_commits(&[]),
+            Err(DBError::RetainNotSupported { .. })
+        ));
+    }
+}
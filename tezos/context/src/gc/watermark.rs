@@ -0,0 +1,143 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Memory watermarks: collections triggered by the memory used by the process.
+//!
+//! In rolling mode (see [`crate::gc::rolling`]), the resident set size of the process is read
+//! after every commit and compared to two limits:
+//! - above the soft limit, the collection is due once `keep + keep / 2` commits are recorded,
+//!   instead of `keep * 2`,
+//! - above the hard limit, the collection keeps only the last commit (and the pinned ones), it
+//!   runs as soon as two commits are recorded.
+//!
+//! The allocator rarely gives the freed memory back to the system, so the resident set size
+//! may stay above the hard limit after a collection. While it does, the next collections
+//! wait for `keep` commits, instead of walking the whole tree after every commit.
+//!
+//! The limits are set in megabytes with the `CONTEXT_MEMORY_SOFT_LIMIT_MB` and
+//! `CONTEXT_MEMORY_HARD_LIMIT_MB` environment variables.
+
+use std::fs;
+
+/// Environment variable containing the soft limit, in megabytes.
+pub const MEMORY_SOFT_LIMIT_ENV: &str = "CONTEXT_MEMORY_SOFT_LIMIT_MB";
+/// Environment variable containing the hard limit, in megabytes.
+pub const MEMORY_HARD_LIMIT_ENV: &str = "CONTEXT_MEMORY_HARD_LIMIT_MB";
+
+const MEGABYTE: usize = 1024 * 1024;
+
+/// Returns the limits set in the environment, `None` when none is set.
+pub fn memory_watermarks_from_env() -> Option<MemoryWatermarks> {
+    let limit_from_env = |name: &str| {
+        std::env::var(name).ok().map(|limit| {
+            limit
+                .parse::<usize>()
+                .unwrap_or_else(|_| {
+                    panic!("Provided `{}` value cannot be converted to usize", name)
+                })
+                .saturating_mul(MEGABYTE)
+        })
+    };
+
+    let watermarks = MemoryWatermarks {
+        soft: limit_from_env(MEMORY_SOFT_LIMIT_ENV),
+        hard: limit_from_env(MEMORY_HARD_LIMIT_ENV),
+    };
+
+    if watermarks.soft.is_none() && watermarks.hard.is_none() {
+        return None;
+    }
+    Some(watermarks)
+}
+
+/// Memory limits of the process, in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryWatermarks {
+    pub soft: Option<usize>,
+    pub hard: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MemoryPressure {
+    Normal,
+    /// The soft limit is exceeded
+    Soft,
+    /// The hard limit is exceeded
+    Hard,
+}
+
+impl MemoryWatermarks {
+    /// Returns the pressure when the process uses `rss` bytes.
+    pub fn pressure(&self, rss: usize) -> MemoryPressure {
+        let exceeds = |limit: Option<usize>| matches!(limit, Some(limit) if rss > limit);
+
+        if exceeds(self.hard) {
+            MemoryPressure::Hard
+        } else if exceeds(self.soft) {
+            MemoryPressure::Soft
+        } else {
+            MemoryPressure::Normal
+        }
+    }
+
+    /// Returns the current pressure, `Normal` when the memory of the process cannot be read.
+    pub fn current_pressure(&self) -> MemoryPressure {
+        match process_rss() {
+            Some(rss) => self.pressure(rss),
+            None => MemoryPressure::Normal,
+        }
+    }
+}
+
+/// Returns the resident set size of the process in bytes, from `/proc/self/status`.
+///
+/// Returns `None` when it's not available (on other systems than Linux).
+pub fn process_rss() -> Option<usize> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    parse_rss(&status)
+}
+
+fn parse_rss(status: &str) -> Option<usize> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes = line
+        .trim_start_matches("VmRSS:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse::<usize>()
+        .ok()?;
+
+    Some(kilobytes.saturating_mul(1024))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_pressure() {
+        let watermarks = MemoryWatermarks {
+            soft: Some(100),
+            hard: Some(200),
+        };
+
+        assert_eq!(watermarks.pressure(50), MemoryPressure::Normal);
+        assert_eq!(watermarks.pressure(100), MemoryPressure::Normal);
+        assert_eq!(watermarks.pressure(150), MemoryPressure::Soft);
+        assert_eq!(watermarks.pressure(250), MemoryPressure::Hard);
+
+        let hard_only = MemoryWatermarks {
+            soft: None,
+            hard: Some(200),
+        };
+        assert_eq!(hard_only.pressure(150), MemoryPressure::Normal);
+        assert_eq!(hard_only.pressure(250), MemoryPressure::Hard);
+    }
+
+    #[test]
+    fn test_parse_rss() {
+        let status = "Name:\tlight-node\nVmPeak:\t  204800 kB\nVmRSS:\t   10240 kB\n";
+        assert_eq!(parse_rss(status), Some(10240 * 1024));
+        assert_eq!(parse_rss("Name:\tlight-node\n"), None);
+    }
+}
@@ -17,8 +17,8 @@ use crate::{
     kv_store::HashId,
     persistent::DBError,
     working_tree::{
-        storage::{Blob, BlobId, DirectoryId, Inode, Storage, StorageError},
-        Commit, DirEntryKind, Object,
+        storage::{Blob, BlobId, DirectoryId, Inode, PointerToInode, Storage, StorageError},
+        Commit, DirEntry, DirEntryKind, Object,
     },
     ContextKeyValueStore,
 };
@@ -102,11 +102,50 @@ pub(crate) fn index(depth: u32, name: &str) -> u32 {
     ocaml_hash_string(depth, name.as_bytes()) % 32
 }
 
+/// Child of a directory or of an inode, its hash is part of the hash of its parent.
+pub(crate) enum Child<'a> {
+    DirEntry(&'a DirEntry),
+    Pointer(&'a PointerToInode),
+}
+
 fn hash_long_inode(
     inode: &Inode,
     store: &mut ContextKeyValueStore,
     storage: &Storage,
 ) -> Result<HashId, HashingError> {
+    let object_hash = long_inode_hash(inode, storage, &mut |child| match child {
+        Child::DirEntry(dir_entry) => Ok(*dir_entry.object_hash(store, storage)?),
+        Child::Pointer(pointer) => {
+            let hash_id = match pointer.hash_id() {
+                Some(hash_id) => hash_id,
+                None => {
+                    let inode_id = pointer.inode_id();
+                    let inode = storage.get_inode(inode_id)?;
+                    let hash_id = hash_long_inode(inode, store, storage)?;
+                    pointer.set_hash_id(Some(hash_id));
+                    hash_id
+                }
+            };
+
+            store
+                .get_hash(hash_id)?
+                .map(|hash| *hash)
+                .ok_or(HashingError::HashIdNotFound { hash_id })
+        }
+    })?;
+
+    store_object_hash(store, object_hash)
+}
+
+/// Computes the hash of an inode, the hashes of its children are given by `child_hash`.
+pub(crate) fn long_inode_hash<F>(
+    inode: &Inode,
+    storage: &Storage,
+    child_hash: &mut F,
+) -> Result<ObjectHash, HashingError>
+where
+    F: FnMut(Child) -> Result<ObjectHash, HashingError>,
+{
     let mut hasher = VarBlake2b::new(OBJECT_HASH_LEN)?;
 
     match inode {
@@ -143,16 +182,7 @@ fn hash_long_inode(
                     DirEntryKind::Directory => hasher.update(&[0u8]),
                 };
 
-                let blob_inlined = dir_entry.get_object().and_then(|object| match object {
-                    Object::Blob(blob_id) if blob_id.is_inline() => storage.get_blob(blob_id).ok(),
-                    _ => None,
-                });
-
-                if let Some(blob) = blob_inlined {
-                    hasher.update(&hash_inlined_blob(blob)?);
-                } else {
-                    hasher.update(dir_entry.object_hash(store, storage)?.as_ref());
-                }
+                hasher.update(&dir_entry_hash(dir_entry, storage, child_hash)?);
             }
         }
         Inode::Pointers {
@@ -188,33 +218,13 @@ fn hash_long_inode(
                     let index: u8 = index as u8;
 
                     hasher.update(&[index]);
-
-                    let hash_id = match pointer.hash_id() {
-                        Some(hash_id) => hash_id,
-                        None => {
-                            let inode_id = pointer.inode_id();
-                            let inode = storage.get_inode(inode_id)?;
-                            let hash_id = hash_long_inode(inode, store, storage)?;
-                            pointer.set_hash_id(Some(hash_id));
-                            hash_id
-                        }
-                    };
-
-                    let hash = store
-                        .get_hash(hash_id)?
-                        .ok_or(HashingError::HashIdNotFound { hash_id })?;
-
-                    hasher.update(hash.as_ref());
+                    hasher.update(&child_hash(Child::Pointer(pointer))?);
                 };
             }
         }
     }
 
-    let hash_id = store
-        .get_vacant_object_hash()?
-        .write_with(|object| hasher.finalize_variable(|r| object.copy_from_slice(r)));
-
-    Ok(hash_id)
+    Ok(finalize(hasher))
 }
 
 // hash is calculated as:
@@ -227,6 +237,25 @@ fn hash_short_inode(
     store: &mut ContextKeyValueStore,
     storage: &Storage,
 ) -> Result<HashId, HashingError> {
+    let object_hash = short_inode_hash(dir_id, storage, &mut |child| match child {
+        Child::DirEntry(dir_entry) => Ok(*dir_entry.object_hash(store, storage)?),
+        // A directory without inode has no pointers
+        Child::Pointer(_) => Err(HashingError::DirectoryNotFound),
+    })?;
+
+    store_object_hash(store, object_hash)
+}
+
+/// Computes the hash of a directory with at most `DIRECTORY_INODE_THRESHOLD` entries,
+/// the hashes of its children are given by `child_hash`.
+pub(crate) fn short_inode_hash<F>(
+    dir_id: DirectoryId,
+    storage: &Storage,
+    child_hash: &mut F,
+) -> Result<ObjectHash, HashingError>
+where
+    F: FnMut(Child) -> Result<ObjectHash, HashingError>,
+{
     let mut hasher = VarBlake2b::new(OBJECT_HASH_LEN)?;
 
     // DirEntry list:
@@ -254,21 +283,45 @@ fn hash_short_inode(
         hasher.update(k.as_bytes());
         hasher.update(&(OBJECT_HASH_LEN as u64).to_be_bytes());
 
-        let blob_inlined = v.get_object().and_then(|object| match object {
-            Object::Blob(blob_id) if blob_id.is_inline() => storage.get_blob(blob_id).ok(),
-            _ => None,
-        });
+        hasher.update(&dir_entry_hash(v, storage, child_hash)?);
+    }
 
-        if let Some(blob) = blob_inlined {
-            hasher.update(&hash_inlined_blob(blob)?);
-        } else {
-            hasher.update(v.object_hash(store, storage)?.as_ref());
-        }
+    Ok(finalize(hasher))
+}
+
+/// Hash of the object of `dir_entry`, inlined blobs are hashed on the fly.
+fn dir_entry_hash<F>(
+    dir_entry: &DirEntry,
+    storage: &Storage,
+    child_hash: &mut F,
+) -> Result<ObjectHash, HashingError>
+where
+    F: FnMut(Child) -> Result<ObjectHash, HashingError>,
+{
+    let blob_inlined = dir_entry.get_object().and_then(|object| match object {
+        Object::Blob(blob_id) if blob_id.is_inline() => storage.get_blob(blob_id).ok(),
+        _ => None,
+    });
+
+    match blob_inlined {
+        Some(blob) => hash_inlined_blob(blob),
+        None => child_hash(Child::DirEntry(dir_entry)),
     }
+}
+
+fn finalize(hasher: VarBlake2b) -> ObjectHash {
+    let mut object_hash: ObjectHash = Default::default();
+    hasher.finalize_variable(|r| object_hash.copy_from_slice(r));
+    object_hash
+}
 
+fn store_object_hash(
+    store: &mut ContextKeyValueStore,
+    object_hash: ObjectHash,
+) -> Result<HashId, HashingError> {
     let hash_id = store
         .get_vacant_object_hash()?
-        .write_with(|object| hasher.finalize_variable(|r| object.copy_from_slice(r)));
+        .write_with(|object| object.copy_from_slice(&object_hash));
 
     Ok(hash_id)
 }
@@ -300,17 +353,10 @@ pub(crate) fn hash_blob(
         return Ok(None);
     }
 
-    let mut hasher = VarBlake2b::new(OBJECT_HASH_LEN)?;
-
     let blob = storage.get_blob(blob_id)?;
-    hasher.update(&(blob.len() as u64).to_be_bytes());
-    hasher.update(blob);
-
-    let hash_id = store
-        .get_vacant_object_hash()?
-        .write_with(|object| hasher.finalize_variable(|r| object.copy_from_slice(r)));
+    let object_hash = hash_inlined_blob(blob)?;
 
-    Ok(Some(hash_id))
+    store_object_hash(store, object_hash).map(Some)
 }
 
 // Calculates hash of BLOB
@@ -322,10 +368,7 @@ pub(crate) fn hash_inlined_blob(blob: Blob) -> Result<ObjectHash, HashingError>
     hasher.update(&(blob.len() as u64).to_be_bytes());
     hasher.update(blob);
 
-    let mut object_hash: ObjectHash = Default::default();
-    hasher.finalize_variable(|r| object_hash.copy_from_slice(r));
-
-    Ok(object_hash)
+    Ok(finalize(hasher))
 }
 
 // Calculates hash of commit
@@ -340,6 +383,15 @@ pub(crate) fn hash_commit(
     commit: &Commit,
     store: &mut ContextKeyValueStore,
 ) -> Result<HashId, HashingError> {
+    let object_hash = commit_hash(commit, store)?;
+    store_object_hash(store, object_hash)
+}
+
+/// Computes the hash of `commit`, from the hashes of its root and parent in `store`.
+pub(crate) fn commit_hash(
+    commit: &Commit,
+    store: &ContextKeyValueStore,
+) -> Result<ObjectHash, HashingError> {
     let mut hasher = VarBlake2b::new(OBJECT_HASH_LEN)?;
     hasher.update(&(OBJECT_HASH_LEN as u64).to_be_bytes());
 
@@ -365,11 +417,45 @@ pub(crate) fn hash_commit(
     hasher.update(&(commit.message.len() as u64).to_be_bytes());
     hasher.update(&commit.message.clone().into_bytes());
 
-    let hash_id = store
-        .get_vacant_object_hash()?
-        .write_with(|object| hasher.finalize_variable(|r| object.copy_from_slice(r)));
+    Ok(finalize(hasher))
+}
 
-    Ok(hash_id)
+/// Recomputes the hash of the directory `dir_id` from the hashes of its entries in `store`.
+///
+/// The hashes of its inodes are recomputed as well, nothing is written to `store`.
+pub(crate) fn recompute_directory_hash(
+    dir_id: DirectoryId,
+    store: &ContextKeyValueStore,
+    storage: &Storage,
+) -> Result<ObjectHash, HashingError> {
+    let mut child_hash = |child: Child| stored_child_hash(child, store, storage);
+
+    match dir_id.get_inode_id() {
+        Some(inode_id) => long_inode_hash(storage.get_inode(inode_id)?, storage, &mut child_hash),
+        None => short_inode_hash(dir_id, storage, &mut child_hash),
+    }
+}
+
+fn stored_child_hash(
+    child: Child,
+    store: &ContextKeyValueStore,
+    storage: &Storage,
+) -> Result<ObjectHash, HashingError> {
+    match child {
+        Child::DirEntry(dir_entry) => {
+            let hash_id = dir_entry.hash_id().ok_or(HashingError::HashIdEmpty)?;
+            store
+                .get_hash(hash_id)?
+                .map(|hash| *hash)
+                .ok_or(HashingError::HashIdNotFound { hash_id })
+        }
+        Child::Pointer(pointer) => {
+            let inode = storage.get_inode(pointer.inode_id())?;
+            long_inode_hash(inode, storage, &mut |child| {
+                stored_child_hash(child, store, storage)
+            })
+        }
+    }
 }
 
 pub(crate) fn hash_object(
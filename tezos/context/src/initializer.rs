@@ -9,7 +9,13 @@ pub use tezos_api::ffi::ContextKvStoreConfiguration;
 use tezos_api::ffi::TezosContextTezEdgeStorageConfiguration;
 use thiserror::Error;
 
-use crate::{kv_store::in_memory::InMemory, kv_store::readonly_ipc::ReadonlyIpcBackend};
+use crate::gc::{rolling::rolling_gc_from_env, watermark::memory_watermarks_from_env};
+use crate::persistent::DBError;
+use crate::{
+    kv_store::in_memory::InMemory,
+    kv_store::persistent::{persistent_path_from_env, Persistent},
+    kv_store::readonly_ipc::ReadonlyIpcBackend,
+};
 use crate::{PatchContextFunction, TezedgeContext, TezedgeIndex};
 
 /// IPC communication errors
@@ -21,6 +27,8 @@ pub enum IndexInitializationError {
     IpcSocketPathMissing,
     #[error("Unexpected IO error occurred, {reason}")]
     IoError { reason: std::io::Error },
+    #[error("Failed to open the context repository, {reason}")]
+    RepositoryError { reason: DBError },
 }
 
 impl From<IpcError> for IndexInitializationError {
@@ -35,11 +43,17 @@ impl From<std::io::Error> for IndexInitializationError {
     }
 }
 
+impl From<DBError> for IndexInitializationError {
+    fn from(error: DBError) -> Self {
+        Self::RepositoryError { reason: error }
+    }
+}
+
 pub fn initialize_tezedge_index(
     configuration: &TezosContextTezEdgeStorageConfiguration,
     patch_context: Option<BoxRoot<PatchContextFunction>>,
 ) -> Result<TezedgeIndex, IndexInitializationError> {
-    Ok(TezedgeIndex::new(
+    let index = TezedgeIndex::new(
         match configuration.backend {
             ContextKvStoreConfiguration::ReadOnlyIpc => {
                 match configuration.ipc_socket_path.clone() {
@@ -49,10 +63,23 @@ pub fn initialize_tezedge_index(
                     )),
                 }
             }
-            ContextKvStoreConfiguration::InMem => Arc::new(RwLock::new(InMemory::try_new()?)),
+            ContextKvStoreConfiguration::InMem => match persistent_path_from_env() {
+                Some(db_path) => Arc::new(RwLock::new(Persistent::try_new(db_path)?)),
+                None => Arc::new(RwLock::new(InMemory::try_new()?)),
+            },
         },
         patch_context,
-    ))
+    );
+
+    let index = match (&configuration.backend, rolling_gc_from_env()) {
+        (ContextKvStoreConfiguration::InMem, Some(keep)) => index.with_rolling_gc(keep),
+        _ => return Ok(index),
+    };
+
+    match memory_watermarks_from_env() {
+        Some(watermarks) => Ok(index.with_memory_watermarks(watermarks)),
+        None => Ok(index),
+    }
 }
 
 pub fn initialize_tezedge_context(
@@ -6,6 +6,7 @@
 use std::{
     borrow::Cow,
     collections::{hash_map::DefaultHasher, BTreeMap, VecDeque},
+    convert::{TryFrom, TryInto},
     hash::Hasher,
     mem::size_of,
     sync::{atomic::Ordering, Arc},
@@ -18,6 +19,7 @@ use tezos_timing::RepositoryMemoryUsage;
 
 use crate::{
     gc::{
+        rolling::rolling_gc_from_env,
         worker::{Command, Cycles, GCThread, GC_PENDING_HASHIDS, PRESERVE_CYCLE_COUNT},
         GarbageCollectionError, GarbageCollector,
     },
@@ -26,7 +28,7 @@ use crate::{
     working_tree::{
         shape::{DirectoryShapeId, DirectoryShapes, ShapeStrings},
         storage::DirEntryId,
-        string_interner::{StringId, StringInterner},
+        string_interner::{StringId, StringInterner, StringsUpdate},
     },
     Map,
 };
@@ -42,9 +44,25 @@ pub struct HashValueStore {
     values: IndexMap<HashId, Option<Arc<[u8]>>>,
     free_ids: Option<Consumer<HashId>>,
     new_ids: Vec<HashId>,
+    /// Ids released by `Self::retain`, reused before the ones of the garbage collector
+    released_ids: Vec<HashId>,
+    /// Ids without value kept by `Self::retain`, they are still referenced
+    tombstones: Vec<HashId>,
+    /// Number of ids given to another object, see `KeyValueStoreBackend::reused_hash_ids`
+    reused_hash_ids: u64,
     values_bytes: usize,
 }
 
+/// State of an object after `HashValueStore::retain`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Retained {
+    /// The object is dropped and its id reused
+    No,
+    Object,
+    /// The value is dropped, the hash is kept and the id is not reused
+    Tombstone,
+}
+
 impl HashValueStore {
     pub(crate) fn new<T>(consumer: T) -> Self
     where
@@ -55,6 +73,9 @@ impl HashValueStore {
             values: IndexMap::new(),
             free_ids: consumer.into(),
             new_ids: Vec::with_capacity(1024),
+            released_ids: Vec::new(),
+            tombstones: Vec::new(),
+            reused_hash_ids: 0,
             values_bytes: 0,
         }
     }
@@ -63,10 +84,11 @@ impl HashValueStore {
         let values_bytes = self.values_bytes;
         let values_capacity = self.values.capacity();
         let hashes_capacity = self.hashes.capacity();
+        let hashes_bytes = hashes_capacity * size_of::<ObjectHash>();
         let total_bytes = values_bytes
             .saturating_add(values_capacity * size_of::<Option<Arc<[u8]>>>())
             .saturating_add(values_capacity * 16) // Each `Arc` has 16 extra bytes for the counters
-            .saturating_add(hashes_capacity * size_of::<ObjectHash>());
+            .saturating_add(hashes_bytes);
 
         RepositoryMemoryUsage {
             values_bytes,
@@ -75,9 +97,13 @@ impl HashValueStore {
             hashes_capacity,
             hashes_length: self.hashes.len(),
             total_bytes,
-            npending_free_ids: self.free_ids.as_ref().map(|c| c.len()).unwrap_or(0),
+            npending_free_ids: self.free_ids.as_ref().map(|c| c.len()).unwrap_or(0)
+                + self.released_ids.len(),
             gc_npending_free_ids: GC_PENDING_HASHIDS.load(Ordering::Acquire),
             nshapes: 0,
+            hashes_bytes,
+            shapes_bytes: 0,
+            strings_bytes: 0,
         }
     }
 
@@ -87,15 +113,24 @@ impl HashValueStore {
             values: IndexMap::new(),
             free_ids: self.free_ids.take(),
             new_ids: Vec::new(),
+            released_ids: Vec::new(),
+            tombstones: Vec::new(),
+            // The ids start again from the first one
+            reused_hash_ids: self.reused_hash_ids.wrapping_add(1),
             values_bytes: 0,
         }
     }
 
+    pub(crate) fn reused_hash_ids(&self) -> u64 {
+        self.reused_hash_ids
+    }
+
     pub(crate) fn get_vacant_object_hash(&mut self) -> Result<VacantObjectHash, HashIdError> {
         let (hash_id, entry) = if let Some(free_id) = self.get_free_id() {
             if let Some(old_value) = self.values.set(free_id, None)? {
                 self.values_bytes = self.values_bytes.saturating_sub(old_value.len());
             }
+            self.reused_hash_ids = self.reused_hash_ids.wrapping_add(1);
             (free_id, self.hashes.get_mut(free_id)?.ok_or(HashIdError)?)
         } else {
             self.hashes.get_vacant_entry()?
@@ -109,9 +144,51 @@ impl HashValueStore {
     }
 
     fn get_free_id(&mut self) -> Option<HashId> {
+        if let Some(hash_id) = self.released_ids.pop() {
+            return Some(hash_id);
+        }
         self.free_ids.as_mut()?.pop().ok()
     }
 
+    /// Removes the values not marked as `Retained::Object`, the ids of the objects not marked
+    /// at all will be reused.
+    ///
+    /// `marks` is indexed by `HashId`, see `HashId::try_into::<usize>`.
+    fn retain(&mut self, marks: &[Retained]) -> Result<(), HashIdError> {
+        let mark = |index: usize| marks.get(index).copied().unwrap_or(Retained::No);
+        let previous_tombstones = std::mem::take(&mut self.tombstones);
+
+        for index in 0..self.values.len() {
+            let hash_id = HashId::try_from(index)?;
+
+            match mark(index) {
+                Retained::Object => continue,
+                Retained::Tombstone => {
+                    if let Some(old_value) = self.values.set(hash_id, None)? {
+                        self.values_bytes = self.values_bytes.saturating_sub(old_value.len());
+                    }
+                    self.tombstones.push(hash_id);
+                }
+                Retained::No => {
+                    if let Some(old_value) = self.values.set(hash_id, None)? {
+                        self.values_bytes = self.values_bytes.saturating_sub(old_value.len());
+                        self.released_ids.push(hash_id);
+                    }
+                }
+            }
+        }
+
+        // The tombstones not referenced anymore are released
+        for hash_id in previous_tombstones {
+            let index: usize = hash_id.try_into()?;
+            if mark(index) == Retained::No {
+                self.released_ids.push(hash_id);
+            }
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn insert_value_at(
         &mut self,
         hash_id: HashId,
@@ -218,9 +295,19 @@ impl KeyValueStoreBackend for InMemory {
         Ok(())
     }
 
+    fn reused_hash_ids(&self) -> u64 {
+        self.hashes.reused_hash_ids()
+    }
+
     fn memory_usage(&self) -> RepositoryMemoryUsage {
         let mut mem = self.hashes.get_memory_usage();
         mem.nshapes = self.shapes.nshapes();
+        mem.shapes_bytes = self.shapes.total_bytes();
+        mem.strings_bytes = self.string_interner.memory_usage().total_bytes;
+        mem.total_bytes = mem
+            .total_bytes
+            .saturating_add(mem.shapes_bytes)
+            .saturating_add(mem.strings_bytes);
         mem
     }
 
@@ -244,6 +331,51 @@ impl KeyValueStoreBackend for InMemory {
         Ok(())
     }
 
+    fn compact_strings(&mut self, string_interner: &mut StringInterner) -> Result<(), DBError> {
+        // The shapes are the only `StringId`s remaining after a checkout
+        let remap = string_interner.compact(&mut self.shapes.string_ids());
+        self.shapes.remap_strings(&remap)?;
+        self.string_interner.extend_from(string_interner);
+
+        Ok(())
+    }
+
+    fn get_strings_update(&self, generation: u32, offset: usize) -> Option<StringsUpdate> {
+        Some(self.string_interner.update_since(generation, offset))
+    }
+
+    fn retain(
+        &mut self,
+        reachable: &mut dyn Iterator<Item = HashId>,
+        tombstones: &mut dyn Iterator<Item = HashId>,
+    ) -> Result<(), DBError> {
+        if self.sender.is_some() {
+            // Both collectors would release the same ids
+            return Err(DBError::RetainNotSupported {
+                reason: "the cycle garbage collector is running",
+            });
+        }
+
+        let mut marked = vec![Retained::No; self.hashes.hashes.len()];
+        for (hash_id, retained) in tombstones
+            .map(|hash_id| (hash_id, Retained::Tombstone))
+            .chain(reachable.map(|hash_id| (hash_id, Retained::Object)))
+        {
+            let index: usize = hash_id.try_into()?;
+            if let Some(mark) = marked.get_mut(index) {
+                *mark = retained;
+            }
+        }
+
+        self.hashes.retain(&marked)?;
+        self.context_hashes.retain(|_, hash_id| {
+            let index: usize = (*hash_id).try_into().unwrap_or(usize::MAX);
+            marked.get(index).copied() == Some(Retained::Object)
+        });
+
+        Ok(())
+    }
+
     fn get_str(&self, string_id: StringId) -> Option<&str> {
         self.string_interner.get(string_id)
     }
@@ -255,9 +387,16 @@ impl InMemory {
         let garbage_collector_disabled = std::env::var("DISABLE_INMEM_CONTEXT_GC")
             .unwrap_or_else(|_| "false".to_string())
             .parse::<bool>()
-            .expect("Provided `DISABLE_INMEM_CONTEXT_GC` value cannot be converted to bool");
+            .expect("Provided `DISABLE_INMEM_CONTEXT_GC` value cannot be converted to bool")
+            // The rolling garbage collection replaces the cycles
+            || rolling_gc_from_env().is_some();
+
+        Self::try_new_with_gc(!garbage_collector_disabled)
+    }
 
-        let (sender, cons, thread_handle) = if garbage_collector_disabled {
+    /// Creates the repository, with or without the cycle garbage collector.
+    pub fn try_new_with_gc(garbage_collector: bool) -> Result<Self, std::io::Error> {
+        let (sender, cons, thread_handle) = if !garbage_collector {
             (None, None, None)
         } else {
             let (sender, recv) = crossbeam_channel::unbounded();
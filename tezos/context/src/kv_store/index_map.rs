@@ -63,6 +63,14 @@ impl<K, V> IndexMap<K, V> {
     pub fn clear(&mut self) {
         self.entries.clear();
     }
+
+    /// Drops the entries after `len`, and the memory they used.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.entries.len() {
+            self.entries.truncate(len);
+            self.entries.shrink_to_fit();
+        }
+    }
 }
 
 impl<K, V> IndexMap<K, V>
@@ -0,0 +1,158 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Least recently used cache of objects, bounded by the size of their values.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use super::HashId;
+
+struct Entry {
+    value: Arc<[u8]>,
+    last_used: u64,
+}
+
+/// Cache of values, evicting the least recently used ones once `capacity_bytes` is reached.
+///
+/// The garbage collector of the writer reuses the `HashId` of collected objects: the owner
+/// must clear the cache when that happens, a cached value must not outlive its object.
+pub struct LruCache {
+    capacity_bytes: usize,
+    nbytes: usize,
+    clock: u64,
+    entries: HashMap<HashId, Entry>,
+    /// `Entry::last_used` -> `HashId`, the first one is the least recently used
+    usage: BTreeMap<u64, HashId>,
+}
+
+impl LruCache {
+    pub fn new(capacity_bytes: usize) -> Self {
+        Self {
+            capacity_bytes,
+            nbytes: 0,
+            clock: 0,
+            entries: HashMap::new(),
+            usage: BTreeMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Total size of the values in the cache.
+    pub fn nbytes(&self) -> usize {
+        self.nbytes
+    }
+
+    pub fn contains(&self, hash_id: HashId) -> bool {
+        self.entries.contains_key(&hash_id)
+    }
+
+    /// Returns the value of `hash_id`, and marks it as the most recently used.
+    pub fn get(&mut self, hash_id: HashId) -> Option<Arc<[u8]>> {
+        let clock = self.tick();
+        let entry = self.entries.get_mut(&hash_id)?;
+
+        self.usage.remove(&entry.last_used);
+        self.usage.insert(clock, hash_id);
+        entry.last_used = clock;
+
+        Some(Arc::clone(&entry.value))
+    }
+
+    /// Inserts the value of `hash_id`, evicting the least recently used values when full.
+    ///
+    /// A value bigger than the whole cache is not inserted.
+    pub fn insert(&mut self, hash_id: HashId, value: Arc<[u8]>) {
+        self.remove(hash_id);
+
+        if value.len() > self.capacity_bytes {
+            return;
+        }
+
+        while self.nbytes + value.len() > self.capacity_bytes {
+            let oldest = match self.usage.values().next() {
+                Some(oldest) => *oldest,
+                None => break,
+            };
+            self.remove(oldest);
+        }
+
+        let clock = self.tick();
+        self.nbytes += value.len();
+        self.usage.insert(clock, hash_id);
+        self.entries.insert(
+            hash_id,
+            Entry {
+                value,
+                last_used: clock,
+            },
+        );
+    }
+
+    pub fn remove(&mut self, hash_id: HashId) {
+        if let Some(entry) = self.entries.remove(&hash_id) {
+            self.usage.remove(&entry.last_used);
+            self.nbytes -= entry.value.len();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.usage.clear();
+        self.nbytes = 0;
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(id: u32) -> HashId {
+        HashId::new(id).unwrap()
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let mut cache = LruCache::new(10);
+
+        cache.insert(id(1), Arc::from(&[1; 4][..]));
+        cache.insert(id(2), Arc::from(&[2; 4][..]));
+        assert_eq!(cache.nbytes(), 8);
+
+        // 1 becomes the most recently used, 2 is evicted
+        assert!(cache.get(id(1)).is_some());
+        cache.insert(id(3), Arc::from(&[3; 4][..]));
+        assert!(cache.contains(id(1)));
+        assert!(!cache.contains(id(2)));
+        assert!(cache.contains(id(3)));
+        assert_eq!(cache.nbytes(), 8);
+
+        // too big for the cache
+        cache.insert(id(4), Arc::from(&[4; 11][..]));
+        assert!(!cache.contains(id(4)));
+        assert_eq!(cache.len(), 2);
+
+        // replacing a value updates the size
+        cache.insert(id(3), Arc::from(&[3; 2][..]));
+        assert_eq!(cache.nbytes(), 6);
+        assert_eq!(cache.get(id(3)).unwrap().as_ref(), &[3, 3]);
+
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.nbytes(), 0);
+    }
+}
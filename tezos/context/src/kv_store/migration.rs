@@ -0,0 +1,391 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Versioning and migrations of the on-disk format of the repositories.
+//!
+//! A persistent repository keeps a small [`FormatHeader`] file in its directory.
+//! When the repository is opened, [`MigrationRegistry::open`] compares the version found
+//! in the header with the version supported by this build:
+//! - an empty directory is initialized with the current version,
+//! - an older version is upgraded step-by-step by the registered [`Migration`]s,
+//! - a newer version is refused, so that an older node never misreads (and corrupts)
+//!   data written by a newer release.
+
+use std::{
+    collections::BTreeMap,
+    convert::TryInto,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+/// Name of the file containing the [`FormatHeader`].
+pub const FORMAT_HEADER_FILENAME: &str = "FORMAT";
+
+const FORMAT_MAGIC: &[u8; 8] = b"TZEDGECX";
+
+#[derive(Debug, Error)]
+pub enum MigrationError {
+    #[error("I/O error {error}")]
+    IOError {
+        #[from]
+        error: io::Error,
+    },
+    #[error("Invalid format header in {path:?}")]
+    InvalidHeader { path: PathBuf },
+    #[error("Missing format header in non empty directory {path:?}")]
+    MissingHeader { path: PathBuf },
+    #[error("Repository format version {found} is newer than the supported version {supported}")]
+    NewerFormat { found: u32, supported: u32 },
+    #[error("No migration registered from format version {from}")]
+    MissingMigration { from: u32 },
+    #[error("Migration from format version {from} failed: {reason}")]
+    MigrationFailed { from: u32, reason: String },
+}
+
+/// Header identifying the on-disk format version of a repository.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatHeader {
+    pub version: u32,
+}
+
+impl FormatHeader {
+    const SIZE: usize = FORMAT_MAGIC.len() + 4;
+
+    /// Reads the header from the repository directory `db_path`.
+    ///
+    /// Returns `None` when there is no header file.
+    pub fn read(db_path: &Path) -> Result<Option<Self>, MigrationError> {
+        let path = db_path.join(FORMAT_HEADER_FILENAME);
+
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        if bytes.len() != Self::SIZE || &bytes[..FORMAT_MAGIC.len()] != FORMAT_MAGIC {
+            return Err(MigrationError::InvalidHeader { path });
+        }
+
+        let version = bytes[FORMAT_MAGIC.len()..]
+            .try_into()
+            .map(u32::from_be_bytes)
+            .map_err(|_| MigrationError::InvalidHeader { path })?;
+
+        Ok(Some(Self { version }))
+    }
+
+    /// Writes the header into the repository directory `db_path`.
+    ///
+    /// The header is written to a temporary file first and then renamed, so that
+    /// an interrupted write never leaves a truncated header behind.
+    pub fn write(&self, db_path: &Path) -> Result<(), MigrationError> {
+        let mut bytes = Vec::with_capacity(Self::SIZE);
+        bytes.extend_from_slice(FORMAT_MAGIC);
+        bytes.extend_from_slice(&self.version.to_be_bytes());
+
+        let tmp_path = db_path.join(format!("{}.tmp", FORMAT_HEADER_FILENAME));
+        fs::write(&tmp_path, &bytes)?;
+        fs::rename(&tmp_path, db_path.join(FORMAT_HEADER_FILENAME))?;
+
+        Ok(())
+    }
+}
+
+/// Progress of a migration, reported to the callback given to [`MigrationRegistry::open`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MigrationProgress {
+    /// Migration `step` (1-based) out of `steps` is starting.
+    Started {
+        step: usize,
+        steps: usize,
+        from: u32,
+        description: &'static str,
+    },
+    /// Migration `step` has processed `done` out of `total` units of work.
+    Progress { step: usize, done: u64, total: u64 },
+    /// Migration `step` completed, the repository is now at version `to`.
+    Finished { step: usize, to: u32 },
+}
+
+/// A migration upgrading a repository from the format version `source_version()` to the next one.
+pub trait Migration {
+    /// The version this migration upgrades from.
+    fn source_version(&self) -> u32;
+
+    /// Short human readable description of the migration.
+    fn description(&self) -> &'static str;
+
+    /// Upgrades the repository in `db_path`.
+    ///
+    /// `progress` may be called with the amount of work done so far, as `(done, total)`.
+    fn migrate(
+        &self,
+        db_path: &Path,
+        progress: &mut dyn FnMut(u64, u64),
+    ) -> Result<(), MigrationError>;
+}
+
+/// Outcome of [`MigrationRegistry::open`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationReport {
+    /// Version found in the repository, `None` if it was initialized.
+    pub found: Option<u32>,
+    /// Version of the repository once opened (unchanged in dry-run mode).
+    pub version: u32,
+    /// Descriptions of the migrations that were applied (or would be, in dry-run mode).
+    pub migrations: Vec<&'static str>,
+}
+
+/// Set of migrations leading to the format `current_version`.
+pub struct MigrationRegistry {
+    current_version: u32,
+    migrations: BTreeMap<u32, Box<dyn Migration + Send + Sync>>,
+}
+
+impl MigrationRegistry {
+    pub fn new(current_version: u32) -> Self {
+        Self {
+            current_version,
+            migrations: BTreeMap::new(),
+        }
+    }
+
+    pub fn current_version(&self) -> u32 {
+        self.current_version
+    }
+
+    /// Registers `migration`, replacing any other migration starting from the same version.
+    pub fn register<M>(&mut self, migration: M) -> &mut Self
+    where
+        M: Migration + Send + Sync + 'static,
+    {
+        self.migrations
+            .insert(migration.source_version(), Box::new(migration));
+        self
+    }
+
+    /// Returns the migrations needed to upgrade from the version `from`, in order.
+    pub fn plan(&self, from: u32) -> Result<Vec<&(dyn Migration + Send + Sync)>, MigrationError> {
+        if from > self.current_version {
+            return Err(MigrationError::NewerFormat {
+                found: from,
+                supported: self.current_version,
+            });
+        }
+
+        (from..self.current_version)
+            .map(|version| {
+                self.migrations
+                    .get(&version)
+                    .map(|m| m.as_ref())
+                    .ok_or(MigrationError::MissingMigration { from: version })
+            })
+            .collect()
+    }
+
+    /// Prepares the repository directory `db_path` to be opened by this build.
+    ///
+    /// - When the directory is missing or empty, it is initialized with the current version.
+    /// - When the repository has an older version, the migrations are applied one by one,
+    ///   and the header is updated after each of them, so that an interrupted upgrade
+    ///   resumes from the last completed step.
+    /// - In `dry_run` mode, nothing is written, the report lists the migrations that would run.
+    pub fn open<F>(
+        &self,
+        db_path: &Path,
+        dry_run: bool,
+        mut progress: F,
+    ) -> Result<MigrationReport, MigrationError>
+    where
+        F: FnMut(MigrationProgress),
+    {
+        let found = match FormatHeader::read(db_path)? {
+            Some(header) => header.version,
+            None => {
+                if !is_empty_dir(db_path)? {
+                    return Err(MigrationError::MissingHeader {
+                        path: db_path.to_path_buf(),
+                    });
+                }
+                if !dry_run {
+                    fs::create_dir_all(db_path)?;
+                    FormatHeader {
+                        version: self.current_version,
+                    }
+                    .write(db_path)?;
+                }
+                return Ok(MigrationReport {
+                    found: None,
+                    version: self.current_version,
+                    migrations: Vec::new(),
+                });
+            }
+        };
+
+        let plan = self.plan(found)?;
+        let migrations = plan.iter().map(|m| m.description()).collect();
+
+        if dry_run {
+            return Ok(MigrationReport {
+                found: Some(found),
+                version: found,
+                migrations,
+            });
+        }
+
+        let steps = plan.len();
+        for (index, migration) in plan.into_iter().enumerate() {
+            let step = index + 1;
+            let from = migration.source_version();
+
+            progress(MigrationProgress::Started {
+                step,
+                steps,
+                from,
+                description: migration.description(),
+            });
+            migration.migrate(db_path, &mut |done, total| {
+                progress(MigrationProgress::Progress { step, done, total })
+            })?;
+            FormatHeader { version: from + 1 }.write(db_path)?;
+            progress(MigrationProgress::Finished { step, to: from + 1 });
+        }
+
+        Ok(MigrationReport {
+            found: Some(found),
+            version: self.current_version,
+            migrations,
+        })
+    }
+}
+
+fn is_empty_dir(path: &Path) -> Result<bool, MigrationError> {
+    match fs::read_dir(path) {
+        Ok(mut entries) => Ok(entries.next().is_none()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(true),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct AppendVersion(u32);
+
+    impl Migration for AppendVersion {
+        fn source_version(&self) -> u32 {
+            self.0
+        }
+
+        fn description(&self) -> &'static str {
+            "append version"
+        }
+
+        fn migrate(
+            &self,
+            db_path: &Path,
+            progress: &mut dyn FnMut(u64, u64),
+        ) -> Result<(), MigrationError> {
+            let path = db_path.join("data");
+            let mut data = fs::read(&path)?;
+            data.push(self.0 as u8);
+            fs::write(&path, data)?;
+            progress(1, 1);
+            Ok(())
+        }
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "tezedge_context_migration_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&path);
+        path
+    }
+
+    #[test]
+    fn test_initialize_empty() {
+        let path = test_dir("empty");
+        let registry = MigrationRegistry::new(3);
+
+        let report = registry.open(&path, false, |_| {}).unwrap();
+        assert_eq!(report.found, None);
+        assert_eq!(report.version, 3);
+        assert_eq!(
+            FormatHeader::read(&path).unwrap(),
+            Some(FormatHeader { version: 3 })
+        );
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_step_by_step() {
+        let path = test_dir("steps");
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join("data"), b"").unwrap();
+        FormatHeader { version: 1 }.write(&path).unwrap();
+
+        let mut registry = MigrationRegistry::new(3);
+        registry
+            .register(AppendVersion(2))
+            .register(AppendVersion(1));
+
+        // dry run doesn't touch anything
+        let report = registry.open(&path, true, |_| {}).unwrap();
+        assert_eq!(report.version, 1);
+        assert_eq!(report.migrations.len(), 2);
+        assert_eq!(fs::read(path.join("data")).unwrap(), Vec::<u8>::new());
+
+        let mut events = Vec::new();
+        let report = registry.open(&path, false, |p| events.push(p)).unwrap();
+        assert_eq!(report.found, Some(1));
+        assert_eq!(report.version, 3);
+        assert_eq!(fs::read(path.join("data")).unwrap(), vec![1, 2]);
+        assert_eq!(events.len(), 6);
+        assert_eq!(events[5], MigrationProgress::Finished { step: 2, to: 3 });
+        assert_eq!(
+            FormatHeader::read(&path).unwrap(),
+            Some(FormatHeader { version: 3 })
+        );
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_refuse_newer_or_unknown() {
+        let path = test_dir("newer");
+        fs::create_dir_all(&path).unwrap();
+        FormatHeader { version: 5 }.write(&path).unwrap();
+
+        let registry = MigrationRegistry::new(3);
+        assert!(matches!(
+            registry.open(&path, false, |_| {}),
+            Err(MigrationError::NewerFormat {
+                found: 5,
+                supported: 3
+            })
+        ));
+
+        FormatHeader { version: 1 }.write(&path).unwrap();
+        assert!(matches!(
+            registry.open(&path, false, |_| {}),
+            Err(MigrationError::MissingMigration { from: 1 })
+        ));
+
+        fs::remove_file(path.join(FORMAT_HEADER_FILENAME)).unwrap();
+        fs::write(path.join("data"), b"").unwrap();
+        assert!(matches!(
+            registry.open(&path, false, |_| {}),
+            Err(MigrationError::MissingHeader { .. })
+        ));
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -14,6 +14,9 @@ use crate::ObjectHash;
 
 pub mod in_memory;
 pub mod index_map;
+pub mod lru;
+pub mod migration;
+pub mod persistent;
 pub mod readonly_ipc;
 
 pub const INMEM: &str = "inmem";
@@ -0,0 +1,1090 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Implementation of a persistent, file-backed repository.
+//!
+//! The repository replaces the in-memory one when the `CONTEXT_PERSISTENT_PATH` environment
+//! variable is set to its directory. It is not a variant of `ContextKvStoreConfiguration`,
+//! which has to stay in sync with the OCaml side.
+//!
+//! The files of the repository directory are append-only, except `hashes.db`:
+//! - `hashes.db`: the `ObjectHash` of every `HashId`, at offset `(hash_id - 1) * 32`,
+//!   overwritten when the id is reused,
+//! - `data.db`: the values, as `hash_id (u32) | length (u32) | value` records,
+//! - `commits.db`: the `HashId` (u32) of every commit,
+//! - `shapes.db`: the directory shapes, as `length (u32) | string_length (u32) | string ...` records,
+//!   where the position of the record is the `DirectoryShapeId`.
+//! - `strings.db`: the small strings of the `StringInterner`, in the same layout as in memory.
+//!   It is rewritten when the `StringInterner` is compacted.
+//! - `released.db` and `tombstones.db`: the `HashId` (u32) released and kept as tombstones by
+//!   the last `KeyValueStoreBackend::retain`.
+//!
+//! `data.db` and `commits.db` are rewritten by `KeyValueStoreBackend::retain`. The ids of the
+//! objects it drops are reused, and the released ids at the end of `hashes.db` are truncated,
+//! so `hashes.db` and the indexes in memory don't grow beyond the objects alive.
+//!
+//! The offset index of the values is rebuilt when the repository is opened, a record truncated
+//! by a crash is dropped. The format of the directory is versioned with a
+//! [`FormatHeader`](super::migration::FormatHeader).
+//!
+//! The shapes are stored (and returned) as strings rather than `StringId`, so that they don't depend
+//! on the state of the `StringInterner` of a previous run.
+//! The `StringInterner` of the working tree is still initialized from `strings.db`, the strings of
+//! a previous run keep their `StringId`.
+
+use std::{
+    borrow::Cow,
+    collections::{hash_map::DefaultHasher, HashMap},
+    convert::{TryFrom, TryInto},
+    fs::{self, File, OpenOptions},
+    hash::Hasher,
+    io::{self, BufReader, BufWriter, Read, Write},
+    mem::size_of,
+    os::unix::fs::FileExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crypto::hash::ContextHash;
+use tezos_timing::RepositoryMemoryUsage;
+
+use crate::{
+    gc::NotGarbageCollected,
+    hash::{ObjectHash, OBJECT_HASH_LEN},
+    persistent::{DBError, Flushable, KeyValueStoreBackend, Persistable},
+    working_tree::{
+        shape::{DirectoryShapeError, DirectoryShapeId, ShapeStrings},
+        storage::DirEntryId,
+        string_interner::{StringId, StringInterner, StringsUpdate},
+    },
+    Map,
+};
+
+use super::{
+    in_memory::Retained, index_map::IndexMap, migration::MigrationRegistry, HashId,
+    VacantObjectHash,
+};
+
+/// Version of the on-disk format written by [`Persistent`]
+pub const PERSISTENT_FORMAT_VERSION: u32 = 1;
+
+const HASHES_FILENAME: &str = "hashes.db";
+const DATA_FILENAME: &str = "data.db";
+const COMMITS_FILENAME: &str = "commits.db";
+const SHAPES_FILENAME: &str = "shapes.db";
+const STRINGS_FILENAME: &str = "strings.db";
+const RELEASED_FILENAME: &str = "released.db";
+const TOMBSTONES_FILENAME: &str = "tombstones.db";
+
+/// Environment variable containing the directory of the persistent repository.
+pub const PERSISTENT_PATH_ENV: &str = "CONTEXT_PERSISTENT_PATH";
+
+/// Returns the directory of the persistent repository, when it replaces the in-memory one.
+pub fn persistent_path_from_env() -> Option<PathBuf> {
+    std::env::var_os(PERSISTENT_PATH_ENV).map(PathBuf::from)
+}
+
+/// Size of the `hash_id | length` header of a record in `data.db`
+const DATA_HEADER_LEN: u64 = 8;
+
+/// Keys of a directory shape
+type ShapeKeys = Box<[String]>;
+
+/// Location of a value in `data.db`
+#[derive(Debug, Clone, Copy)]
+struct ValueLocation {
+    offset: u64,
+    length: u32,
+}
+
+pub struct Persistent {
+    db_path: PathBuf,
+    hashes_file: File,
+    data_file: File,
+    commits_file: File,
+    shapes_file: File,
+    strings_file: File,
+    /// Current length of `data.db`
+    data_file_length: u64,
+    hashes: IndexMap<HashId, ObjectHash>,
+    /// Number of hashes written in `hashes.db`
+    hashes_persisted: usize,
+    /// Reused ids, their hash has to be overwritten in `hashes.db`
+    rewritten_hashes: Vec<HashId>,
+    /// Ids released by `Self::retain`, reused before creating new ones
+    released_ids: Vec<HashId>,
+    /// Ids without value kept by `Self::retain`, they are still referenced
+    tombstones: Vec<HashId>,
+    /// Number of ids given to another object, see `KeyValueStoreBackend::reused_hash_ids`
+    reused_hash_ids: u64,
+    values: IndexMap<HashId, Option<ValueLocation>>,
+    values_bytes: usize,
+    context_hashes: Map<u64, HashId>,
+    shapes: IndexMap<DirectoryShapeId, ShapeKeys>,
+    shapes_ids: HashMap<ShapeKeys, DirectoryShapeId>,
+    string_interner: StringInterner,
+    /// Cursor of `string_interner` written in `strings.db`
+    strings_persisted: (u32, usize),
+}
+
+impl NotGarbageCollected for Persistent {}
+
+impl Flushable for Persistent {
+    fn flush(&self) -> Result<(), anyhow::Error> {
+        self.hashes_file.sync_data()?;
+        self.data_file.sync_data()?;
+        self.commits_file.sync_data()?;
+        self.shapes_file.sync_data()?;
+        self.strings_file.sync_data()?;
+        Ok(())
+    }
+}
+
+impl Persistable for Persistent {
+    fn is_persistent(&self) -> bool {
+        true
+    }
+}
+
+impl KeyValueStoreBackend for Persistent {
+    fn write_batch(&mut self, batch: Vec<(HashId, Arc<[u8]>)>) -> Result<(), DBError> {
+        // Hashes are written first, so that a value on disk always has its hash
+        self.persist_hashes()?;
+
+        let mut output = Vec::with_capacity(batch.iter().map(|(_, v)| v.len() + 8).sum());
+
+        for (hash_id, value) in batch {
+            let length: u32 = value
+                .len()
+                .try_into()
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Value too big"))?;
+
+            output.extend_from_slice(&hash_id.as_u32().to_be_bytes());
+            output.extend_from_slice(&length.to_be_bytes());
+            let offset = self.data_file_length + output.len() as u64;
+            output.extend_from_slice(&value);
+
+            self.insert_value_location(hash_id, ValueLocation { offset, length })?;
+        }
+
+        (&self.data_file).write_all(&output)?;
+        self.data_file_length += output.len() as u64;
+
+        Ok(())
+    }
+
+    fn contains(&self, hash_id: HashId) -> Result<bool, DBError> {
+        Ok(self.get_value_location(hash_id)?.is_some())
+    }
+
+    fn put_context_hash(&mut self, hash_id: HashId) -> Result<(), DBError> {
+        self.persist_hashes()?;
+        self.insert_context_hash(hash_id)?;
+        (&self.commits_file).write_all(&hash_id.as_u32().to_be_bytes())?;
+        Ok(())
+    }
+
+    fn get_context_hash(&self, context_hash: &ContextHash) -> Result<Option<HashId>, DBError> {
+        Ok(self
+            .context_hashes
+            .get(&hash_context_hash(context_hash.as_ref()))
+            .cloned())
+    }
+
+    fn get_hash(&self, hash_id: HashId) -> Result<Option<Cow<ObjectHash>>, DBError> {
+        Ok(self.hashes.get(hash_id)?.map(Cow::Borrowed))
+    }
+
+    fn get_value(&self, hash_id: HashId) -> Result<Option<Cow<[u8]>>, DBError> {
+        let location = match self.get_value_location(hash_id)? {
+            Some(location) => location,
+            None => return Ok(None),
+        };
+
+        let mut value = vec![0; location.length as usize];
+        self.data_file.read_exact_at(&mut value, location.offset)?;
+
+        Ok(Some(Cow::Owned(value)))
+    }
+
+    fn get_vacant_object_hash(&mut self) -> Result<VacantObjectHash, DBError> {
+        let (hash_id, entry) = match self.released_ids.pop() {
+            Some(hash_id) => {
+                self.reused_hash_ids = self.reused_hash_ids.wrapping_add(1);
+                self.rewritten_hashes.push(hash_id);
+                (
+                    hash_id,
+                    self.hashes.get_mut(hash_id)?.ok_or(DBError::HashIdFailed)?,
+                )
+            }
+            None => self.hashes.get_vacant_entry()?,
+        };
+
+        Ok(VacantObjectHash {
+            entry: Some(entry),
+            hash_id,
+        })
+    }
+
+    fn clear_objects(&mut self) -> Result<(), DBError> {
+        // Objects are on disk, there is nothing to clear
+        Ok(())
+    }
+
+    fn reused_hash_ids(&self) -> u64 {
+        self.reused_hash_ids
+    }
+
+    fn memory_usage(&self) -> RepositoryMemoryUsage {
+        let values_capacity = self.values.capacity();
+        let hashes_capacity = self.hashes.capacity();
+        let hashes_bytes = hashes_capacity * size_of::<ObjectHash>();
+        let shapes_bytes = (self.shapes.capacity() * size_of::<ShapeKeys>()).saturating_add(
+            self.shapes_ids.capacity() * size_of::<(ShapeKeys, DirectoryShapeId)>(),
+        );
+        let strings_bytes = self.string_interner.memory_usage().total_bytes;
+        let total_bytes = (values_capacity * size_of::<Option<ValueLocation>>())
+            .saturating_add(hashes_bytes)
+            .saturating_add(shapes_bytes)
+            .saturating_add(strings_bytes);
+
+        RepositoryMemoryUsage {
+            values_bytes: self.values_bytes,
+            values_capacity,
+            values_length: self.values.len(),
+            hashes_capacity,
+            hashes_length: self.hashes.len(),
+            total_bytes,
+            npending_free_ids: self.released_ids.len(),
+            gc_npending_free_ids: 0,
+            nshapes: self.shapes.len(),
+            hashes_bytes,
+            shapes_bytes,
+            strings_bytes,
+        }
+    }
+
+    fn get_shape(&self, shape_id: DirectoryShapeId) -> Result<ShapeStrings, DBError> {
+        self.shapes
+            .get(shape_id)?
+            .map(|strings| ShapeStrings::Owned(strings.to_vec()))
+            .ok_or_else(|| DirectoryShapeError::ShapeIdNotFound.into())
+    }
+
+    fn make_shape(
+        &mut self,
+        dir: &[(StringId, DirEntryId)],
+    ) -> Result<Option<DirectoryShapeId>, DBError> {
+        let mut strings = Vec::with_capacity(dir.len());
+
+        for (key_id, _) in dir {
+            if key_id.is_big() {
+                return Ok(None);
+            }
+
+            let key = self
+                .string_interner
+                .get(*key_id)
+                .ok_or(DirectoryShapeError::CannotFindKey)?;
+            strings.push(key.to_string());
+        }
+
+        let strings = strings.into_boxed_slice();
+        if let Some(shape_id) = self.shapes_ids.get(&strings) {
+            return Ok(Some(*shape_id));
+        }
+
+        let mut output = Vec::new();
+        write_shape(&mut output, &strings)?;
+        (&self.shapes_file).write_all(&output)?;
+
+        self.insert_shape(strings).map(Some)
+    }
+
+    fn synchronize_strings(&mut self, string_interner: &StringInterner) -> Result<(), DBError> {
+        self.string_interner.extend_from(string_interner);
+        self.persist_strings()
+    }
+
+    fn compact_strings(&mut self, string_interner: &mut StringInterner) -> Result<(), DBError> {
+        // The shapes are stored as strings, they don't reference any `StringId`
+        string_interner.compact(&mut std::iter::empty());
+        self.synchronize_strings(string_interner)
+    }
+
+    fn get_strings_update(&self, generation: u32, offset: usize) -> Option<StringsUpdate> {
+        Some(self.string_interner.update_since(generation, offset))
+    }
+
+    fn interned_strings(&self) -> Option<StringInterner> {
+        Some(self.string_interner.clone())
+    }
+
+    fn get_str(&self, string_id: StringId) -> Option<&str> {
+        self.string_interner.get(string_id)
+    }
+
+    fn retain(
+        &mut self,
+        reachable: &mut dyn Iterator<Item = HashId>,
+        tombstones: &mut dyn Iterator<Item = HashId>,
+    ) -> Result<(), DBError> {
+        let mut marked = vec![Retained::No; self.hashes.len()];
+        for (hash_id, retained) in tombstones
+            .map(|hash_id| (hash_id, Retained::Tombstone))
+            .chain(reachable.map(|hash_id| (hash_id, Retained::Object)))
+        {
+            let index: usize = hash_id.try_into()?;
+            if let Some(mark) = marked.get_mut(index) {
+                *mark = retained;
+            }
+        }
+        let mark = |hash_id: HashId| -> Result<Retained, DBError> {
+            let index: usize = hash_id.try_into()?;
+            Ok(marked.get(index).copied().unwrap_or(Retained::No))
+        };
+
+        // Commits are rewritten first: after a crash, the data file may contain
+        // unreachable values, but never miss the value of a commit
+        let mut commits = Vec::with_capacity(self.context_hashes.len());
+        for hash_id in self.context_hashes.values() {
+            if mark(*hash_id)? == Retained::Object {
+                commits.push(*hash_id);
+            }
+        }
+        self.context_hashes.clear();
+        for hash_id in commits.iter() {
+            self.insert_context_hash(*hash_id)?;
+        }
+        self.commits_file = rewrite_file(&self.db_path, COMMITS_FILENAME, |output| {
+            for hash_id in commits.iter() {
+                output.write_all(&hash_id.as_u32().to_be_bytes())?;
+            }
+            Ok(())
+        })?;
+
+        let mut values = IndexMap::with_capacity(self.values.len());
+        let mut values_bytes = 0;
+        let mut data_file_length = 0;
+        let mut value = Vec::new();
+        let mut released = Vec::new();
+        let mut new_tombstones = Vec::new();
+
+        let data_file = rewrite_file(&self.db_path, DATA_FILENAME, |output| {
+            for (index, location) in self.values.as_slice().iter().enumerate() {
+                let hash_id = HashId::try_from(index)?;
+                let location = match (location, mark(hash_id)?) {
+                    (None, _) => continue,
+                    (Some(location), Retained::Object) => location,
+                    (Some(_), Retained::Tombstone) => {
+                        new_tombstones.push(hash_id);
+                        continue;
+                    }
+                    (Some(_), Retained::No) => {
+                        released.push(hash_id);
+                        continue;
+                    }
+                };
+
+                value.resize(location.length as usize, 0);
+                self.data_file.read_exact_at(&mut value, location.offset)?;
+
+                output.write_all(&hash_id.as_u32().to_be_bytes())?;
+                output.write_all(&location.length.to_be_bytes())?;
+                output.write_all(&value)?;
+
+                let offset = data_file_length + DATA_HEADER_LEN;
+                values.insert_at(
+                    hash_id,
+                    Some(ValueLocation {
+                        offset,
+                        length: location.length,
+                    }),
+                )?;
+                values_bytes += value.len();
+                data_file_length = offset + location.length as u64;
+            }
+            Ok(())
+        })?;
+
+        self.data_file = data_file;
+        self.data_file_length = data_file_length;
+        self.values = values;
+        self.values_bytes = values_bytes;
+
+        // The tombstones not referenced anymore are released
+        for hash_id in std::mem::take(&mut self.tombstones) {
+            match mark(hash_id)? {
+                Retained::No => released.push(hash_id),
+                Retained::Tombstone => new_tombstones.push(hash_id),
+                Retained::Object => {}
+            }
+        }
+        released.append(&mut self.released_ids);
+
+        // The released ids at the end are dropped instead of being reused
+        released.sort_unstable();
+        released.dedup();
+        let mut hashes_length = self.hashes.len();
+        while let Some(hash_id) = released.last() {
+            let index: usize = (*hash_id).try_into()?;
+            if index + 1 != hashes_length {
+                break;
+            }
+            released.pop();
+            hashes_length -= 1;
+        }
+
+        if hashes_length < self.hashes.len() {
+            // These ids will be given again to new objects
+            self.reused_hash_ids = self.reused_hash_ids.wrapping_add(1);
+        }
+        self.hashes.truncate(hashes_length);
+        self.values.truncate(hashes_length);
+        self.hashes_persisted = self.hashes_persisted.min(hashes_length);
+        truncate(
+            &self.hashes_file,
+            (self.hashes_persisted * OBJECT_HASH_LEN) as u64,
+        )?;
+        self.rewritten_hashes.retain(|hash_id| {
+            let index: usize = (*hash_id).try_into().unwrap_or(usize::MAX);
+            index < hashes_length
+        });
+
+        rewrite_hash_ids(&self.db_path, RELEASED_FILENAME, &released)?;
+        rewrite_hash_ids(&self.db_path, TOMBSTONES_FILENAME, &new_tombstones)?;
+
+        // Lowest ids are reused first
+        released.reverse();
+        self.released_ids = released;
+        self.tombstones = new_tombstones;
+
+        Ok(())
+    }
+}
+
+impl Persistent {
+    /// Opens (or creates) the repository in the directory `db_path`.
+    pub fn try_new<P: AsRef<Path>>(db_path: P) -> Result<Self, DBError> {
+        let db_path = db_path.as_ref();
+
+        MigrationRegistry::new(PERSISTENT_FORMAT_VERSION).open(db_path, false, |_| {})?;
+
+        let mut repository = Self {
+            db_path: db_path.to_path_buf(),
+            hashes_file: open_file_at(db_path, HASHES_FILENAME)?,
+            data_file: open_file(db_path, DATA_FILENAME)?,
+            commits_file: open_file(db_path, COMMITS_FILENAME)?,
+            shapes_file: open_file(db_path, SHAPES_FILENAME)?,
+            strings_file: open_file(db_path, STRINGS_FILENAME)?,
+            data_file_length: 0,
+            hashes: IndexMap::new(),
+            hashes_persisted: 0,
+            rewritten_hashes: Vec::new(),
+            released_ids: Vec::new(),
+            tombstones: Vec::new(),
+            reused_hash_ids: 0,
+            values: IndexMap::new(),
+            values_bytes: 0,
+            context_hashes: Default::default(),
+            shapes: IndexMap::new(),
+            shapes_ids: HashMap::new(),
+            string_interner: StringInterner::default(),
+            strings_persisted: (0, 0),
+        };
+
+        repository.load_hashes()?;
+        repository.load_values()?;
+        repository.load_commits()?;
+        repository.load_shapes()?;
+        repository.load_strings()?;
+        repository.load_free_ids()?;
+
+        Ok(repository)
+    }
+
+    fn load_hashes(&mut self) -> Result<(), DBError> {
+        let mut reader = BufReader::new(&self.hashes_file);
+        let mut hash: ObjectHash = Default::default();
+
+        while read_exact_or_eof(&mut reader, &mut hash)? {
+            self.hashes.push(hash)?;
+        }
+
+        self.hashes_persisted = self.hashes.len();
+        truncate(
+            &self.hashes_file,
+            (self.hashes.len() * OBJECT_HASH_LEN) as u64,
+        )
+    }
+
+    fn load_values(&mut self) -> Result<(), DBError> {
+        let mut reader = BufReader::new(self.data_file.try_clone()?);
+        let file_length = self.data_file.metadata()?.len();
+        let mut offset = 0;
+        let mut header = [0; DATA_HEADER_LEN as usize];
+
+        while read_exact_or_eof(&mut reader, &mut header)? {
+            let hash_id = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+            let length = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+            let end = offset + DATA_HEADER_LEN + length as u64;
+
+            if end > file_length {
+                break;
+            }
+
+            let hash_id = HashId::new(hash_id).ok_or(DBError::HashIdFailed)?;
+            let location = ValueLocation {
+                offset: offset + DATA_HEADER_LEN,
+                length,
+            };
+            self.insert_value_location(hash_id, location)?;
+
+            reader.seek_relative(length as i64)?;
+            offset = end;
+        }
+
+        self.data_file_length = offset;
+        truncate(&self.data_file, offset)
+    }
+
+    fn load_commits(&mut self) -> Result<(), DBError> {
+        let mut reader = BufReader::new(&self.commits_file);
+        let mut bytes = [0; 4];
+        let mut length = 0;
+        let mut hash_ids = Vec::new();
+
+        while read_exact_or_eof(&mut reader, &mut bytes)? {
+            hash_ids.push(HashId::new(u32::from_be_bytes(bytes)).ok_or(DBError::HashIdFailed)?);
+            length += bytes.len() as u64;
+        }
+
+        for hash_id in hash_ids {
+            self.insert_context_hash(hash_id)?;
+        }
+
+        truncate(&self.commits_file, length)
+    }
+
+    fn load_shapes(&mut self) -> Result<(), DBError> {
+        let mut reader = BufReader::new(&self.shapes_file);
+        let file_length = self.shapes_file.metadata()?.len();
+        let mut length = 0;
+        let mut shapes = Vec::new();
+
+        while let Some((strings, record_length)) = read_shape(&mut reader, file_length - length)? {
+            shapes.push(strings);
+            length += record_length;
+        }
+
+        for strings in shapes {
+            self.insert_shape(strings)?;
+        }
+
+        truncate(&self.shapes_file, length)
+    }
+
+    fn load_strings(&mut self) -> Result<(), DBError> {
+        let mut bytes = Vec::new();
+        (&self.strings_file).read_to_end(&mut bytes)?;
+
+        let all_strings = match std::str::from_utf8(&bytes) {
+            Ok(all_strings) => all_strings,
+            // A truncated record may end with an incomplete character
+            Err(e) => std::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or_default(),
+        };
+
+        let (string_interner, length) = StringInterner::from_all_strings(all_strings);
+        self.string_interner = string_interner;
+        self.strings_persisted = self.string_interner.cursor();
+
+        truncate(&self.strings_file, length as u64)
+    }
+
+    fn load_free_ids(&mut self) -> Result<(), DBError> {
+        let hashes_length = self.hashes.len();
+
+        for hash_id in read_hash_ids(&self.db_path, RELEASED_FILENAME)? {
+            let index: usize = hash_id.try_into()?;
+            // The id was reused before the restart when it has a value
+            if index < hashes_length && !self.contains(hash_id)? {
+                self.released_ids.push(hash_id);
+            }
+        }
+        self.released_ids.reverse();
+        self.tombstones = read_hash_ids(&self.db_path, TOMBSTONES_FILENAME)?;
+
+        Ok(())
+    }
+
+    /// Writes to `strings.db` the strings interned since the last call.
+    fn persist_strings(&mut self) -> Result<(), DBError> {
+        let (generation, offset) = self.strings_persisted;
+        let update = self.string_interner.update_since(generation, offset);
+
+        if update.offset != offset {
+            // The `StringInterner` was compacted
+            self.strings_file = rewrite_file(&self.db_path, STRINGS_FILENAME, |output| {
+                output.write_all(update.as_str().as_bytes())?;
+                Ok(())
+            })?;
+        } else if !update.as_str().is_empty() {
+            (&self.strings_file).write_all(update.as_str().as_bytes())?;
+        }
+
+        self.strings_persisted = self.string_interner.cursor();
+        Ok(())
+    }
+
+    /// Writes to `hashes.db` the hashes created or reused since the last call.
+    fn persist_hashes(&mut self) -> Result<(), DBError> {
+        for hash_id in std::mem::take(&mut self.rewritten_hashes) {
+            let index: usize = hash_id.try_into()?;
+            // Not written yet, it is appended below
+            if index >= self.hashes_persisted {
+                continue;
+            }
+            let hash = self.hashes.get(hash_id)?.ok_or(DBError::HashIdFailed)?;
+            self.hashes_file
+                .write_all_at(hash, (index * OBJECT_HASH_LEN) as u64)?;
+        }
+
+        let new_hashes = match self.hashes.get_index(self.hashes_persisted..) {
+            Some(new_hashes) if !new_hashes.is_empty() => new_hashes,
+            _ => return Ok(()),
+        };
+
+        let bytes: Vec<u8> = new_hashes.iter().flatten().copied().collect();
+        self.hashes_file
+            .write_all_at(&bytes, (self.hashes_persisted * OBJECT_HASH_LEN) as u64)?;
+        self.hashes_persisted = self.hashes.len();
+
+        Ok(())
+    }
+
+    fn get_value_location(&self, hash_id: HashId) -> Result<Option<ValueLocation>, DBError> {
+        Ok(self.values.get(hash_id)?.copied().flatten())
+    }
+
+    fn insert_value_location(
+        &mut self,
+        hash_id: HashId,
+        location: ValueLocation,
+    ) -> Result<(), DBError> {
+        self.values_bytes = self.values_bytes.saturating_add(location.length as usize);
+        if let Some(old) = self.values.insert_at(hash_id, Some(location))? {
+            self.values_bytes = self.values_bytes.saturating_sub(old.length as usize);
+        }
+        Ok(())
+    }
+
+    fn insert_context_hash(&mut self, hash_id: HashId) -> Result<(), DBError> {
+        let commit_hash = self
+            .hashes
+            .get(hash_id)?
+            .ok_or(DBError::MissingObject { hash_id })?;
+
+        self.context_hashes
+            .insert(hash_context_hash(commit_hash), hash_id);
+        Ok(())
+    }
+
+    fn insert_shape(&mut self, strings: ShapeKeys) -> Result<DirectoryShapeId, DBError> {
+        let shape_id = self.shapes.push(strings.clone())?;
+        self.shapes_ids.insert(strings, shape_id);
+        Ok(shape_id)
+    }
+}
+
+fn hash_context_hash(context_hash: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(context_hash);
+    hasher.finish()
+}
+
+fn open_file(db_path: &Path, name: &str) -> Result<File, DBError> {
+    fs::create_dir_all(db_path)?;
+
+    OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(db_path.join(name))
+        .map_err(Into::into)
+}
+
+/// Same as `open_file`, but not in append mode: the file is written at explicit offsets.
+fn open_file_at(db_path: &Path, name: &str) -> Result<File, DBError> {
+    fs::create_dir_all(db_path)?;
+
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(db_path.join(name))
+        .map_err(Into::into)
+}
+
+/// Replaces the file `name` with the content written by `fun`, and returns it opened.
+///
+/// The content is written into a temporary file renamed once complete.
+fn rewrite_file<F>(db_path: &Path, name: &str, fun: F) -> Result<File, DBError>
+where
+    F: FnOnce(&mut BufWriter<File>) -> Result<(), DBError>,
+{
+    let tmp_path = db_path.join(format!("{}.tmp", name));
+    let mut output = BufWriter::new(File::create(&tmp_path)?);
+
+    fun(&mut output)?;
+
+    let output = output.into_inner().map_err(|e| e.into_error())?;
+    output.sync_all()?;
+    fs::rename(&tmp_path, db_path.join(name))?;
+
+    open_file(db_path, name)
+}
+
+/// Replaces the file `name` with the list of `hash_ids`.
+fn rewrite_hash_ids(db_path: &Path, name: &str, hash_ids: &[HashId]) -> Result<(), DBError> {
+    rewrite_file(db_path, name, |output| {
+        for hash_id in hash_ids {
+            output.write_all(&hash_id.as_u32().to_be_bytes())?;
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
+
+/// Reads the list of `HashId` of the file `name`, empty when the file doesn't exist.
+fn read_hash_ids(db_path: &Path, name: &str) -> Result<Vec<HashId>, DBError> {
+    let bytes = match fs::read(db_path.join(name)) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    bytes
+        .chunks_exact(4)
+        .map(|bytes| {
+            HashId::new(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                .ok_or(DBError::HashIdFailed)
+        })
+        .collect()
+}
+
+/// Drops the (partially written) bytes after `length`.
+fn truncate(file: &File, length: u64) -> Result<(), DBError> {
+    if file.metadata()?.len() != length {
+        file.set_len(length)?;
+    }
+    Ok(())
+}
+
+/// Same as `Read::read_exact`, but returns `false` when the end of file is reached first.
+fn read_exact_or_eof<R: Read>(reader: &mut R, buffer: &mut [u8]) -> Result<bool, DBError> {
+    match reader.read_exact(buffer) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_shape(output: &mut Vec<u8>, strings: &[String]) -> Result<(), DBError> {
+    let length = u32::try_from(strings.len()).map_err(|_| DBError::HashIdFailed)?;
+    output.extend_from_slice(&length.to_be_bytes());
+
+    for string in strings {
+        // keys of a shape are small strings, their length fits in a `u32`
+        output.extend_from_slice(&(string.len() as u32).to_be_bytes());
+        output.extend_from_slice(string.as_bytes());
+    }
+    Ok(())
+}
+
+/// Reads a shape record, returns it with its length in bytes,
+/// or `None` at the end of file or on a truncated record.
+///
+/// `remaining` is the number of bytes left in the file: the lengths read are checked against
+/// it before allocating, a corrupted length must not make us allocate gigabytes.
+fn read_shape<R: Read>(
+    reader: &mut R,
+    remaining: u64,
+) -> Result<Option<(ShapeKeys, u64)>, DBError> {
+    let mut bytes = [0; 4];
+
+    if !read_exact_or_eof(reader, &mut bytes)? {
+        return Ok(None);
+    }
+    let nstrings = u32::from_be_bytes(bytes);
+    let mut record_length = 4;
+
+    // Each string has at least its 4 bytes of length
+    if 4 + nstrings as u64 * 4 > remaining {
+        return Ok(None);
+    }
+    let mut strings = Vec::with_capacity(nstrings as usize);
+
+    for _ in 0..nstrings {
+        if !read_exact_or_eof(reader, &mut bytes)? {
+            return Ok(None);
+        }
+        let string_length = u32::from_be_bytes(bytes) as u64;
+        if record_length + 4 + string_length > remaining {
+            return Ok(None);
+        }
+        let mut string = vec![0; string_length as usize];
+        if !read_exact_or_eof(reader, &mut string)? {
+            return Ok(None);
+        }
+        record_length += 4 + string.len() as u64;
+
+        match String::from_utf8(string) {
+            Ok(string) => strings.push(string),
+            Err(_) => return Ok(None),
+        }
+    }
+
+    Ok(Some((strings.into_boxed_slice(), record_length)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, sync::RwLock};
+
+    use super::*;
+    use crate::{IndexApi, ProtocolContextApi, ShellContextApi, TezedgeContext, TezedgeIndex};
+
+    fn test_dir(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "tezedge_context_persistent_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&path);
+        path
+    }
+
+    fn open_context(path: &Path) -> TezedgeContext {
+        let repository = Persistent::try_new(path).unwrap();
+        let index = TezedgeIndex::new(Arc::new(RwLock::new(repository)), None);
+        TezedgeContext::new(index, None, None)
+    }
+
+    #[test]
+    fn test_persistent_reopen() {
+        let path = test_dir("reopen");
+
+        let (first, second) = {
+            let context = open_context(&path);
+            let context = context.add(&["a", "b", "c"], &[1, 2, 3]).unwrap();
+            let context = context.add(&["a", "d"], &[4]).unwrap();
+            let first = context
+                .commit("Tezos".to_string(), "".to_string(), 0)
+                .unwrap();
+
+            let context = context.add(&["a", "b", "c"], &[5; 100]).unwrap();
+            let second = context
+                .commit("Tezos".to_string(), "".to_string(), 1)
+                .unwrap();
+
+            context.index.repository.read().unwrap().flush().unwrap();
+            (first, second)
+        };
+
+        let context = open_context(&path);
+        assert!(context.index.repository.read().unwrap().is_persistent());
+
+        let first = context.index.checkout(&first).unwrap().unwrap();
+        assert_eq!(first.find(&["a", "b", "c"]).unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(first.find(&["a", "d"]).unwrap(), Some(vec![4]));
+
+        let second = context.index.checkout(&second).unwrap().unwrap();
+        assert_eq!(second.find(&["a", "b", "c"]).unwrap(), Some(vec![5; 100]));
+
+        // new commits on top of the reopened repository
+        let context = second.add(&["e"], &[6]).unwrap();
+        let third = context
+            .commit("Tezos".to_string(), "".to_string(), 2)
+            .unwrap();
+        let third = context.index.checkout(&third).unwrap().unwrap();
+        assert_eq!(third.find(&["a", "d"]).unwrap(), Some(vec![4]));
+        assert_eq!(third.find(&["e"]).unwrap(), Some(vec![6]));
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_persistent_strings() {
+        let path = test_dir("strings");
+
+        let string_id = {
+            let context = open_context(&path);
+            let context = context.add(&["a", "b"], &[1]).unwrap();
+            context
+                .commit("Tezos".to_string(), "".to_string(), 0)
+                .unwrap();
+
+            let index = &context.index;
+            let mut storage = index.storage.borrow_mut();
+            let string_id = storage.strings.get_string_id("b");
+            let length_before = fs::metadata(path.join(STRINGS_FILENAME)).unwrap().len();
+
+            let mut repository = index.repository.write().unwrap();
+            storage.clear();
+            repository.compact_strings(&mut storage.strings).unwrap();
+            assert!(fs::metadata(path.join(STRINGS_FILENAME)).unwrap().len() <= length_before);
+
+            let string_id = storage.strings.get_string_id("b");
+            repository.synchronize_strings(&storage.strings).unwrap();
+            string_id
+        };
+
+        // The working tree starts from the strings of the previous run
+        let context = open_context(&path);
+        let mut storage = context.index.storage.borrow_mut();
+        assert_eq!(storage.strings.get(string_id), Some("b"));
+        assert_eq!(storage.strings.get_string_id("b"), string_id);
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_persistent_truncated_record() {
+        let path = test_dir("truncated");
+
+        let hash_id = {
+            let mut repository = Persistent::try_new(&path).unwrap();
+            let hash_id = repository
+                .get_vacant_object_hash()
+                .unwrap()
+                .write_with(|hash| *hash = [1; 32]);
+            repository
+                .write_batch(vec![(hash_id, Arc::from(&[1, 2, 3][..]))])
+                .unwrap();
+            hash_id
+        };
+
+        // simulates a crash in the middle of a write
+        let mut data = OpenOptions::new()
+            .append(true)
+            .open(path.join(DATA_FILENAME))
+            .unwrap();
+        data.write_all(&[0, 0, 0, 2, 0, 0, 0, 10, 1]).unwrap();
+
+        // the lengths of a truncated shape are not allocated
+        let mut shapes = OpenOptions::new()
+            .append(true)
+            .open(path.join(SHAPES_FILENAME))
+            .unwrap();
+        shapes
+            .write_all(&[0xff, 0xff, 0xff, 0xff, 0, 0, 0, 1])
+            .unwrap();
+
+        let mut repository = Persistent::try_new(&path).unwrap();
+        assert_eq!(
+            repository.get_value(hash_id).unwrap().unwrap().as_ref(),
+            &[1, 2, 3]
+        );
+        assert_eq!(
+            repository.get_hash(hash_id).unwrap().unwrap().as_ref(),
+            &[1; 32]
+        );
+
+        let hash_id = repository
+            .get_vacant_object_hash()
+            .unwrap()
+            .write_with(|hash| *hash = [2; 32]);
+        repository
+            .write_batch(vec![(hash_id, Arc::from(&[4][..]))])
+            .unwrap();
+
+        let repository = Persistent::try_new(&path).unwrap();
+        assert_eq!(
+            repository.get_value(hash_id).unwrap().unwrap().as_ref(),
+            &[4]
+        );
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_persistent_retain() {
+        let path = test_dir("retain");
+
+        let (first, second) = {
+            let context = open_context(&path);
+            let context = context.add(&["a"], &[1; 40]).unwrap();
+            let first = context
+                .commit("Tezos".to_string(), "".to_string(), 0)
+                .unwrap();
+            let context = context.add(&["a"], &[2; 40]).unwrap();
+            let second = context
+                .commit("Tezos".to_string(), "".to_string(), 1)
+                .unwrap();
+
+            let index = &context.index;
+            let second_id = index.fetch_context_hash_id(&second).unwrap().unwrap();
+            let length_before = fs::metadata(path.join(DATA_FILENAME)).unwrap().len();
+
+            index.retain_commits(&[second_id]).unwrap();
+
+            assert!(fs::metadata(path.join(DATA_FILENAME)).unwrap().len() < length_before);
+            assert!(index.checkout(&first).unwrap().is_none());
+            (first, second)
+        };
+
+        let context = open_context(&path);
+        assert!(context.index.checkout(&first).unwrap().is_none());
+        let second = context.index.checkout(&second).unwrap().unwrap();
+        assert_eq!(second.find(&["a"]).unwrap(), Some(vec![2; 40]));
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_persistent_retain_bounded() {
+        let path = test_dir("retain_bounded");
+
+        let (last, hashes_length) = {
+            let mut context = open_context(&path);
+            let mut hashes_length = None;
+            let mut last = None;
+
+            for level in 0..10u8 {
+                context = context.add(&["a", "b"], &[level; 40]).unwrap();
+                context = context.add(&["a", "c"], &[level; 40]).unwrap();
+                let commit_hash = context
+                    .commit("Tezos".to_string(), "".to_string(), level as i64)
+                    .unwrap();
+
+                let index = &context.index;
+                let hash_id = index.fetch_context_hash_id(&commit_hash).unwrap().unwrap();
+                index.retain_commits(&[hash_id]).unwrap();
+                context.index.repository.read().unwrap().flush().unwrap();
+
+                // The ids dropped by the previous retain are reused, `hashes.db` stops growing
+                let length = fs::metadata(path.join(HASHES_FILENAME)).unwrap().len();
+                if level > 0 {
+                    assert!(length <= *hashes_length.get_or_insert(length));
+                }
+                last = Some(commit_hash);
+            }
+
+            (last.unwrap(), hashes_length.unwrap())
+        };
+
+        let context = open_context(&path);
+        let last = context.index.checkout(&last).unwrap().unwrap();
+        assert_eq!(last.find(&["a", "b"]).unwrap(), Some(vec![9; 40]));
+
+        // The released ids are still reused after a restart
+        let context = last.add(&["a", "b"], &[10; 40]).unwrap();
+        context
+            .commit("Tezos".to_string(), "".to_string(), 10)
+            .unwrap();
+        assert!(fs::metadata(path.join(HASHES_FILENAME)).unwrap().len() <= hashes_length);
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -5,7 +5,11 @@
 //! It is used by read-only protocol runners to be able to access the in-memory context
 //! owned by the writable protocol runner.
 
-use std::{borrow::Cow, path::Path, sync::Arc};
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
 
 use crypto::hash::ContextHash;
 use slog::{error, info};
@@ -13,17 +17,30 @@ use tezos_timing::RepositoryMemoryUsage;
 use thiserror::Error;
 
 use crate::persistent::{DBError, Flushable, Persistable};
-use crate::working_tree::shape::{DirectoryShapeId, ShapeStrings};
+use crate::working_tree::shape::{DirectoryShapeError, DirectoryShapeId, ShapeStrings};
 use crate::working_tree::storage::DirEntryId;
-use crate::working_tree::string_interner::{StringId, StringInterner};
-use crate::ContextValue;
+use crate::working_tree::string_interner::{StringId, StringInterner, StringsUpdate};
 use crate::{
     ffi::TezedgeIndexError, gc::NotGarbageCollected, persistent::KeyValueStoreBackend, ObjectHash,
 };
+use crate::{
+    tezedge_context::{KeyDiff, TreeStats, TreeVerification, VerifyMode},
+    ContextKey, ContextKeyOwned, ContextValue, TezedgeIndex,
+};
 
 pub struct ReadonlyIpcBackend {
-    client: IpcContextClient,
+    client: ContextClient,
+    /// Objects of this protocol runner, they may reference the objects of the writable one
     hashes: HashValueStore,
+    /// Values fetched from the writable protocol runner
+    cache: RefCell<LruCache>,
+    /// Number of `HashId`s reused by the writable protocol runner when the cache was filled,
+    /// `None` when it is unknown: the values are then not cached
+    reused_hash_ids: Cell<Option<u64>>,
+    /// Events of the blocking client
+    events: Option<Receiver<IpcClientEvent>>,
+    /// Copy of the strings of the writable protocol runner, used to resolve the shapes
+    strings: RefCell<StringInterner>,
 }
 
 // TODO - TE-261: quick hack to make the initializer happy, but must be fixed.
@@ -37,13 +54,199 @@ impl ReadonlyIpcBackend {
     /// Will wait for a few seconds if the socket file is not found yet.
     pub fn try_connect<P: AsRef<Path>>(socket_path: P) -> Result<Self, IpcError> {
         let client = IpcContextClient::try_connect(socket_path)?;
+        let events = client.subscribe();
+        // Without shared memory, the responses are received through the socket
+        let _ = client.enable_shared_memory(SHARED_MEMORY_SIZE);
+
         Ok(Self {
+            events: Some(events),
+            ..Self::with_client(ContextClient::Blocking(client))
+        })
+    }
+
+    /// Same as `Self::try_connect`, but the requests are made through an [`AsyncIpcContextClient`],
+    /// with a timeout for each kind of request.
+    pub fn try_connect_async<P: AsRef<Path>>(
+        socket_path: P,
+        timeouts: IpcRequestTimeouts,
+    ) -> Result<Self, IpcError> {
+        let client = IpcContextClient::try_connect(socket_path)?;
+        // Without shared memory, the responses are received through the socket
+        let _ = client.enable_shared_memory(SHARED_MEMORY_SIZE);
+
+        let client = AsyncIpcContextClient::from_client(client, timeouts)?;
+        Ok(Self::with_client(ContextClient::Async(client)))
+    }
+
+    fn with_client(client: ContextClient) -> Self {
+        Self {
             client,
             hashes: HashValueStore::new(None),
-        })
+            cache: RefCell::new(LruCache::new(CACHE_CAPACITY_BYTES)),
+            reused_hash_ids: Cell::new(None),
+            events: None,
+            strings: RefCell::new(StringInterner::default()),
+        }
+    }
+
+    /// Drops the cached values and the strings when the client reconnected to a new writable
+    /// protocol runner, the `HashId`s of its objects are different.
+    ///
+    /// The local objects are kept, the client refuses the `HashId`s they reference until a
+    /// context hash is resolved again, see [`ContextServiceError::Reconnected`].
+    fn handle_client_events(&self) {
+        let events = match self.events.as_ref() {
+            Some(events) => events,
+            None => return,
+        };
+
+        for event in events.try_iter() {
+            match event {
+                IpcClientEvent::Reconnected => {
+                    self.cache.borrow_mut().clear();
+                    self.reused_hash_ids.set(None);
+                    *self.strings.borrow_mut() = StringInterner::default();
+                }
+            }
+        }
+    }
+
+    /// Returns the keys of the shape, resolved with the copy of the strings of the writable
+    /// protocol runner.
+    ///
+    /// Only the strings interned since the last call are received. This returns `None` when
+    /// the shapes of the writable protocol runner are not made of `StringId`s.
+    fn get_shape_keys(&self, shape_id: DirectoryShapeId) -> Result<Option<Vec<String>>, DBError> {
+        self.handle_client_events();
+        let (generation, offset) = self.strings.borrow().cursor();
+
+        let ShapeIds {
+            string_ids,
+            strings: update,
+        } = match self
+            .client
+            .get_shape_ids(shape_id, generation, offset)
+            .map_err(|reason| DBError::IpcAccessError { reason })?
+        {
+            Some(shape_ids) => shape_ids,
+            None => return Ok(None),
+        };
+
+        let mut strings = self.strings.borrow_mut();
+        if !strings.apply_update(update) {
+            return Ok(None);
+        }
+
+        string_ids
+            .iter()
+            .map(|string_id| {
+                strings
+                    .get(*string_id)
+                    .map(str::to_string)
+                    .ok_or_else(|| DirectoryShapeError::CannotFindKey.into())
+            })
+            .collect::<Result<Vec<_>, DBError>>()
+            .map(Some)
+    }
+
+    /// Returns the value of `hash_id` from the cache, or fetches it from the writable protocol runner.
+    fn get_remote_value(&self, hash_id: HashId) -> Result<Option<Arc<[u8]>>, DBError> {
+        if let Some(value) = self.cache.borrow_mut().get(hash_id) {
+            return Ok(Some(value));
+        }
+
+        let value = self
+            .client
+            .get_value(hash_id)
+            .map_err(|reason| DBError::IpcAccessError { reason })?
+            .map(|value| Arc::<[u8]>::from(value.as_ref()));
+        self.handle_client_events();
+
+        if let (Some(value), Some(_)) = (value.as_ref(), self.reused_hash_ids.get()) {
+            self.cache.borrow_mut().insert(hash_id, Arc::clone(value));
+        }
+
+        Ok(value)
+    }
+}
+
+/// Client used by `ReadonlyIpcBackend`.
+enum ContextClient {
+    Blocking(IpcContextClient),
+    Async(AsyncIpcContextClient),
+}
+
+impl ContextClient {
+    fn get_value(&self, hash_id: HashId) -> Result<Option<Cow<[u8]>>, ContextServiceError> {
+        match self {
+            Self::Blocking(client) => client.get_value(hash_id),
+            Self::Async(client) => Ok(client.get_value(hash_id).wait()?.map(Cow::Owned)),
+        }
+    }
+
+    fn get_many(
+        &self,
+        hash_ids: &[HashId],
+    ) -> Result<Vec<(HashId, Option<ContextValue>)>, ContextServiceError> {
+        match self {
+            Self::Blocking(client) => client.get_many(hash_ids),
+            Self::Async(client) => client.get_many(hash_ids).wait(),
+        }
+    }
+
+    fn contains_object(&self, hash_id: HashId) -> Result<bool, ContextServiceError> {
+        match self {
+            Self::Blocking(client) => client.contains_object(hash_id),
+            Self::Async(client) => client.contains_object(hash_id).wait(),
+        }
+    }
+
+    fn get_context_hash_id_and_reuses(
+        &self,
+        context_hash: &ContextHash,
+    ) -> Result<(Option<HashId>, Option<u64>), ContextServiceError> {
+        match self {
+            Self::Blocking(client) => client.get_context_hash_id_and_reuses(context_hash),
+            Self::Async(client) => client.get_context_hash_id_and_reuses(context_hash).wait(),
+        }
+    }
+
+    fn get_hash(&self, hash_id: HashId) -> Result<Option<Cow<ObjectHash>>, ContextServiceError> {
+        match self {
+            Self::Blocking(client) => client.get_hash(hash_id),
+            Self::Async(client) => Ok(client.get_hash(hash_id).wait()?.map(Cow::Owned)),
+        }
+    }
+
+    fn get_shape(&self, shape_id: DirectoryShapeId) -> Result<Vec<String>, ContextServiceError> {
+        match self {
+            Self::Blocking(client) => client.get_shape(shape_id),
+            Self::Async(client) => client.get_shape(shape_id).wait(),
+        }
+    }
+
+    fn get_shape_ids(
+        &self,
+        shape_id: DirectoryShapeId,
+        generation: u32,
+        offset: usize,
+    ) -> Result<Option<ShapeIds>, ContextServiceError> {
+        match self {
+            Self::Blocking(client) => client.get_shape_ids(shape_id, generation, offset),
+            Self::Async(client) => client.get_shape_ids(shape_id, generation, offset).wait(),
+        }
     }
 }
 
+/// Maximum size of the values kept in the cache of `ReadonlyIpcBackend`.
+const CACHE_CAPACITY_BYTES: usize = 64 * 1024 * 1024;
+
+/// Maximum number of objects requested with a single `ContextRequest::GetMany`.
+const GET_MANY_MAX_OBJECTS: usize = 4096;
+
+/// Size of the shared memory segment receiving the big responses of the writable protocol runner.
+const SHARED_MEMORY_SIZE: usize = 64 * 1024 * 1024;
+
 impl NotGarbageCollected for ReadonlyIpcBackend {}
 
 impl KeyValueStoreBackend for ReadonlyIpcBackend {
@@ -68,9 +271,20 @@ impl KeyValueStoreBackend for ReadonlyIpcBackend {
     }
 
     fn get_context_hash(&self, context_hash: &ContextHash) -> Result<Option<HashId>, DBError> {
-        self.client
-            .get_context_hash_id(context_hash)
-            .map_err(|reason| DBError::IpcAccessError { reason })
+        let (hash_id, reused_hash_ids) = self
+            .client
+            .get_context_hash_id_and_reuses(context_hash)
+            .map_err(|reason| DBError::IpcAccessError { reason })?;
+        self.handle_client_events();
+
+        // The writable protocol runner gave some `HashId`s to other objects,
+        // the cached values may belong to the previous ones
+        if reused_hash_ids != self.reused_hash_ids.get() {
+            self.cache.borrow_mut().clear();
+            self.reused_hash_ids.set(reused_hash_ids);
+        }
+
+        Ok(hash_id)
     }
 
     fn get_hash(&self, hash_id: HashId) -> Result<Option<Cow<ObjectHash>>, DBError> {
@@ -87,9 +301,9 @@ impl KeyValueStoreBackend for ReadonlyIpcBackend {
         if let Some(hash_id) = hash_id.get_readonly_id()? {
             Ok(self.hashes.get_value(hash_id)?.map(Cow::Borrowed))
         } else {
-            self.client
-                .get_value(hash_id)
-                .map_err(|reason| DBError::IpcAccessError { reason })
+            Ok(self
+                .get_remote_value(hash_id)?
+                .map(|value| Cow::Owned(value.to_vec())))
         }
     }
 
@@ -106,10 +320,17 @@ impl KeyValueStoreBackend for ReadonlyIpcBackend {
     }
 
     fn memory_usage(&self) -> RepositoryMemoryUsage {
-        self.hashes.get_memory_usage()
+        let mut mem = self.hashes.get_memory_usage();
+        mem.strings_bytes = self.strings.borrow().memory_usage().total_bytes;
+        mem.total_bytes = mem.total_bytes.saturating_add(mem.strings_bytes);
+        mem
     }
 
     fn get_shape(&self, shape_id: DirectoryShapeId) -> Result<ShapeStrings, DBError> {
+        if let Some(keys) = self.get_shape_keys(shape_id)? {
+            return Ok(ShapeStrings::Owned(keys));
+        }
+
         self.client
             .get_shape(shape_id)
             .map(ShapeStrings::Owned)
@@ -125,7 +346,8 @@ impl KeyValueStoreBackend for ReadonlyIpcBackend {
     }
 
     fn get_str(&self, _: StringId) -> Option<&str> {
-        // Readonly protocol runner doesn't have the `StringInterner`.
+        // Readonly protocol runner doesn't have the `StringInterner`, only a copy
+        // of the strings of the writable protocol runner.
         None
     }
 
@@ -133,6 +355,55 @@ impl KeyValueStoreBackend for ReadonlyIpcBackend {
         // Readonly protocol runner doesn't update strings.
         Ok(())
     }
+
+    fn compact_strings(&mut self, string_interner: &mut StringInterner) -> Result<(), DBError> {
+        // The shapes are received as strings, no `StringId` is kept
+        string_interner.compact(&mut std::iter::empty());
+        Ok(())
+    }
+
+    fn get_strings_update(&self, _generation: u32, _offset: usize) -> Option<StringsUpdate> {
+        None
+    }
+
+    fn retain(
+        &mut self,
+        _reachable: &mut dyn Iterator<Item = HashId>,
+        _tombstones: &mut dyn Iterator<Item = HashId>,
+    ) -> Result<(), DBError> {
+        Err(DBError::RetainNotSupported {
+            reason: "the objects are owned by the main process",
+        })
+    }
+
+    fn prefetch(&self, hash_ids: &[HashId]) -> Result<(), DBError> {
+        let mut missing = Vec::with_capacity(hash_ids.len());
+        {
+            let cache = self.cache.borrow();
+            for hash_id in hash_ids {
+                if hash_id.get_readonly_id()?.is_none() && !cache.contains(*hash_id) {
+                    missing.push(*hash_id);
+                }
+            }
+        }
+
+        for hash_ids in missing.chunks(GET_MANY_MAX_OBJECTS) {
+            let values = self
+                .client
+                .get_many(hash_ids)
+                .map_err(|reason| DBError::IpcAccessError { reason })?;
+            self.handle_client_events();
+
+            let mut cache = self.cache.borrow_mut();
+            for (hash_id, value) in values {
+                if let Some(value) = value {
+                    cache.insert(hash_id, Arc::from(value));
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl Flushable for ReadonlyIpcBackend {
@@ -149,37 +420,133 @@ impl Persistable for ReadonlyIpcBackend {
 
 // IPC communication
 
-use std::{cell::RefCell, time::Duration};
+use std::{
+    cell::{Cell, RefCell},
+    time::{Duration, Instant},
+};
 
-use ipc::{IpcClient, IpcError, IpcReceiver, IpcSender, IpcServer};
+use crossbeam_channel::{Receiver, Sender};
+use ipc::{
+    shm::ShmSegment,
+    version::{IpcProtocol, ProtocolVersion},
+    IpcClient, IpcError, IpcReceiver, IpcSender, IpcServer,
+};
 use serde::{Deserialize, Serialize};
 use slog::{warn, Logger};
 use strum_macros::IntoStaticStr;
 
-use super::{in_memory::HashValueStore, HashId, VacantObjectHash};
+use super::{in_memory::HashValueStore, lru::LruCache, HashId, VacantObjectHash};
+
+mod async_client;
+
+pub use async_client::{AsyncIpcContextClient, IpcRequestTimeouts, IpcResponse};
 
 /// This request is generated by a readonly protool runner and is received by the writable protocol runner.
-#[derive(Serialize, Deserialize, Debug, IntoStaticStr)]
+#[derive(Serialize, Deserialize, Debug, Clone, IntoStaticStr)]
 enum ContextRequest {
     GetContextHashId(ContextHash),
+    /// Same as `GetContextHashId`, with the number of `HashId`s reused by the writable
+    /// protocol runner, since protocol 1.2.0
+    GetContextHashIdAndReuses(ContextHash),
     GetHash(HashId),
     GetValue(HashId),
+    GetMany(Vec<HashId>),
     GetShape(DirectoryShapeId),
+    /// `StringId`s of the shape, with the strings interned since the cursor `(generation, offset)`
+    GetShapeIds(DirectoryShapeId, u32, usize),
     ContainsObject(HashId),
+    GetTreeStats(ContextHash, ContextKeyOwned),
+    /// Diff of the keys under the prefix, between the first and the second commit
+    Diff(ContextHash, ContextHash, ContextKeyOwned),
+    VerifyTree(ContextHash, VerifyMode),
+    /// Big responses are written in the shared memory segment at this path,
+    /// must be sent before any other request of the connection
+    AttachSharedMemory(PathBuf),
     ShutdownCall, // TODO: is this required?
 }
 
+impl ContextRequest {
+    /// Returns `true` if the request can be sent again to a new writable protocol runner.
+    ///
+    /// Only the requests made of context hashes can: the `HashId`s and the shape ids belong
+    /// to the runner they were received from.
+    fn can_retry_after_reconnect(&self) -> bool {
+        matches!(
+            self,
+            Self::GetContextHashId(_)
+                | Self::GetContextHashIdAndReuses(_)
+                | Self::GetTreeStats(..)
+                | Self::Diff(..)
+                | Self::VerifyTree(..)
+        )
+    }
+
+    /// Returns `true` if the request is made of `HashId`s or shape ids received from the
+    /// writable protocol runner.
+    fn references_ids(&self) -> bool {
+        matches!(
+            self,
+            Self::GetHash(_)
+                | Self::GetValue(_)
+                | Self::GetMany(_)
+                | Self::GetShape(_)
+                | Self::GetShapeIds(..)
+                | Self::ContainsObject(_)
+        )
+    }
+
+    /// Returns `true` if the response to the request gives the ids of a new context.
+    fn resolves_ids(&self) -> bool {
+        matches!(
+            self,
+            Self::GetContextHashId(_) | Self::GetContextHashIdAndReuses(_)
+        )
+    }
+}
+
 /// This is generated as a response to the `ContextRequest` command.
 #[derive(Serialize, Deserialize, Debug, IntoStaticStr)]
 enum ContextResponse {
     GetContextHashResponse(Result<Option<ObjectHash>, String>),
     GetContextHashIdResponse(Result<Option<HashId>, String>),
+    GetContextHashIdAndReusesResponse(Result<(Option<HashId>, u64), String>),
     GetValueResponse(Result<Option<ContextValue>, String>),
+    GetManyResponse(Result<Vec<(HashId, Option<ContextValue>)>, String>),
     GetShapeResponse(Result<Vec<String>, String>),
+    GetShapeIdsResponse(Result<Option<ShapeIds>, String>),
     ContainsObjectResponse(Result<bool, String>),
+    GetTreeStatsResponse(Result<Option<TreeStats>, String>),
+    DiffResponse(Result<Option<Vec<KeyDiff>>, String>),
+    VerifyTreeResponse(Result<Option<TreeVerification>, String>),
+    AttachSharedMemoryResponse(Result<(), String>),
     ShutdownResult,
 }
 
+/// The `StringId`s of a directory shape, see `ContextRequest::GetShapeIds`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShapeIds {
+    pub string_ids: Vec<StringId>,
+    /// Strings interned by the writable protocol runner since the cursor of the request
+    pub strings: StringsUpdate,
+}
+
+/// A `ContextRequest` sent on the socket.
+///
+/// Several requests can be in flight on the same connection, `id` is used to
+/// match each of them with its `ContextResponseFrame`.
+#[derive(Serialize, Deserialize, Debug)]
+struct ContextRequestFrame {
+    id: u64,
+    request: ContextRequest,
+}
+
+/// The response to the `ContextRequestFrame` with the same `id`.
+#[derive(Serialize, Deserialize, Debug)]
+struct ContextResponseFrame {
+    id: u64,
+    response: ContextResponse,
+}
+
 #[derive(Error, Debug)]
 pub enum ContextError {
     #[error("Context get object error: {reason}")]
@@ -192,6 +559,12 @@ pub enum ContextError {
     GetContextHashIdError { reason: String },
     #[error("Context get hash error: {reason}")]
     GetContextHashError { reason: String },
+    #[error("Context get tree stats error: {reason}")]
+    GetTreeStatsError { reason: String },
+    #[error("Context diff error: {reason}")]
+    DiffError { reason: String },
+    #[error("Context verify tree error: {reason}")]
+    VerifyTreeError { reason: String },
 }
 
 #[derive(Error, Debug)]
@@ -229,6 +602,18 @@ pub enum ContextServiceError {
     /// Lock error
     #[error("Lock error: {message:?}")]
     LockPoisonError { message: String },
+    /// No response received in time
+    #[error("Timeout waiting for the response to {request}")]
+    Timeout { request: &'static str },
+    /// The connection was closed
+    #[error("IPC context client is disconnected")]
+    Disconnected,
+    /// The request references ids of the previous writable protocol runner: the connection was
+    /// reopened, and no context hash was resolved on the new connection since
+    #[error(
+        "IPC context client reconnected, the ids of the {request} request are not valid anymore"
+    )]
+    Reconnected { request: &'static str },
 }
 
 impl<T> From<std::sync::PoisonError<T>> for ContextServiceError {
@@ -262,26 +647,60 @@ impl From<ContextError> for ContextServiceError {
     }
 }
 
+/// Version of the context IPC protocol without `ContextRequest::GetContextHashIdAndReuses`.
+const CONTEXT_IPC_VERSION_WITHOUT_REUSES: ProtocolVersion = ProtocolVersion::new(1, 1, 0);
+
+/// Protocol of the context IPC channel.
+///
+/// Version 1.1.0 added the shared memory segment, version 1.2.0 added
+/// `ContextRequest::GetContextHashIdAndReuses`: with a 1.1.0 server, the values are
+/// not cached by `ReadonlyIpcBackend`. 1.0.0 is not supported anymore.
+pub const CONTEXT_IPC_PROTOCOL: IpcProtocol =
+    IpcProtocol::new("tezedge-context", ProtocolVersion::new(1, 2, 0))
+        .with_previous(CONTEXT_IPC_VERSION_WITHOUT_REUSES);
+
 /// IPC context server that listens for new connections.
-pub struct IpcContextListener(IpcServer<ContextRequest, ContextResponse>);
+pub struct IpcContextListener(IpcServer<ContextRequestFrame, ContextResponseFrame>);
 
 pub struct ContextIncoming<'a> {
     listener: &'a mut IpcContextListener,
 }
 
 struct IpcClientIO {
-    rx: IpcReceiver<ContextResponse>,
-    tx: IpcSender<ContextRequest>,
+    rx: IpcReceiver<ContextResponseFrame>,
+    tx: IpcSender<ContextRequestFrame>,
+    last_id: u64,
 }
 
 struct IpcServerIO {
-    rx: IpcReceiver<ContextRequest>,
-    tx: IpcSender<ContextResponse>,
+    rx: IpcReceiver<ContextRequestFrame>,
+    /// Shared by the threads handling the requests
+    tx: Arc<Mutex<IpcSender<ContextResponseFrame>>>,
+}
+
+/// Events of the connection of an [`IpcContextClient`], see [`IpcContextClient::subscribe`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IpcClientEvent {
+    /// The connection was lost and a new one was opened, likely to a restarted writable
+    /// protocol runner: the `HashId`s received before are not valid anymore.
+    Reconnected,
 }
 
 /// Encapsulate IPC communication.
+///
+/// When the connection is lost, the client reconnects to the same socket and retries the
+/// request if it is made of context hashes only, see [`IpcClientEvent::Reconnected`].
 pub struct IpcContextClient {
     io: RefCell<IpcClientIO>,
+    socket_path: PathBuf,
+    subscribers: RefCell<Vec<Sender<IpcClientEvent>>>,
+    /// Size of the shared memory segment, when enabled
+    shared_memory_size: Cell<Option<usize>>,
+    /// Number of reconnections
+    epoch: Cell<u64>,
+    /// Value of `epoch` when a context hash was last resolved, the requests referencing ids
+    /// are refused when it is older
+    ids_epoch: Cell<u64>,
 }
 
 pub struct IpcContextServer {
@@ -291,6 +710,8 @@ pub struct IpcContextServer {
 /// IPC context client for readers.
 impl IpcContextClient {
     const TIMEOUT: Duration = Duration::from_secs(180);
+    const RECONNECT_ATTEMPTS: usize = 5;
+    const RECONNECT_DELAY: Duration = Duration::from_secs(1);
 
     pub fn try_connect<P: AsRef<Path>>(socket_path: P) -> Result<Self, IpcError> {
         // TODO - TE-261: do this in a better way
@@ -300,22 +721,178 @@ impl IpcContextClient {
             }
             std::thread::sleep(Duration::from_secs(1));
         }
-        let ipc_client: IpcClient<ContextResponse, ContextRequest> = IpcClient::new(socket_path);
+        let io = RefCell::new(Self::connect(socket_path.as_ref())?);
+        Ok(Self {
+            io,
+            socket_path: socket_path.as_ref().to_path_buf(),
+            subscribers: RefCell::new(Vec::new()),
+            shared_memory_size: Cell::new(None),
+            epoch: Cell::new(0),
+            ids_epoch: Cell::new(0),
+        })
+    }
+
+    /// Receives the big responses through a shared memory segment of `size` bytes,
+    /// instead of the socket.
+    ///
+    /// Must be called before any request. Returns `false` if the server couldn't open
+    /// the segment, the responses are then received through the socket.
+    pub fn enable_shared_memory(&self, size: usize) -> Result<bool, ContextServiceError> {
+        let enabled = self.attach_shared_memory(size)?;
+        if enabled {
+            self.shared_memory_size.set(Some(size));
+        }
+        Ok(enabled)
+    }
+
+    fn attach_shared_memory(&self, size: usize) -> Result<bool, ContextServiceError> {
+        let mut segment = ShmSegment::create(size)?;
+        let request = ContextRequest::AttachSharedMemory(segment.path().to_path_buf());
+
+        match self.try_call(request)? {
+            ContextResponse::AttachSharedMemoryResponse(Ok(())) => {
+                // Both ends have mapped the segment, the file is not needed anymore
+                segment.unlink()?;
+                self.io.borrow_mut().rx.attach_shm(segment);
+                Ok(true)
+            }
+            ContextResponse::AttachSharedMemoryResponse(Err(_)) => Ok(false),
+            message => Err(ContextServiceError::UnexpectedMessage {
+                message: message.into(),
+            }),
+        }
+    }
+
+    fn connect(socket_path: &Path) -> Result<IpcClientIO, IpcError> {
+        let ipc_client: IpcClient<ContextResponseFrame, ContextRequestFrame> =
+            IpcClient::new(socket_path).with_protocol(CONTEXT_IPC_PROTOCOL);
         let (rx, tx) = ipc_client.connect()?;
-        let io = RefCell::new(IpcClientIO { rx, tx });
-        Ok(Self { io })
+        Ok(IpcClientIO { rx, tx, last_id: 0 })
     }
 
-    /// Get object by hash id
-    pub fn get_value(&self, hash_id: HashId) -> Result<Option<Cow<[u8]>>, ContextServiceError> {
+    /// Returns a receiver of the events of the connection.
+    pub fn subscribe(&self) -> Receiver<IpcClientEvent> {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        self.subscribers.borrow_mut().push(sender);
+        receiver
+    }
+
+    /// Sets the interval of the keep-alive pings, `None` disables them (the default).
+    ///
+    /// The pings are sent by [`Self::heartbeat`], when the client is idle.
+    pub fn set_heartbeat_interval(&self, interval: Option<Duration>) {
+        self.io.borrow_mut().tx.set_heartbeat_interval(interval);
+    }
+
+    /// Sends a keep-alive ping if no request was sent during the heartbeat interval,
+    /// reconnects if the connection is lost.
+    pub fn heartbeat(&self) -> Result<(), ContextServiceError> {
+        let result = self.io.borrow_mut().tx.heartbeat();
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(_) => self.reconnect().map_err(Into::into),
+        }
+    }
+
+    /// Opens a new connection, after the previous one was lost.
+    fn reconnect(&self) -> Result<(), IpcError> {
+        let mut attempt = 0;
+
+        let io = loop {
+            attempt += 1;
+            match Self::connect(&self.socket_path) {
+                Ok(io) => break io,
+                Err(error) if attempt >= Self::RECONNECT_ATTEMPTS => return Err(error),
+                Err(_) => std::thread::sleep(Self::RECONNECT_DELAY),
+            }
+        };
+
+        {
+            let mut current = self.io.borrow_mut();
+            let heartbeat_interval = current.tx.heartbeat_interval();
+            *current = io;
+            current.tx.set_heartbeat_interval(heartbeat_interval);
+        }
+        self.epoch.set(self.epoch.get() + 1);
+
+        if let Some(size) = self.shared_memory_size.get() {
+            if !matches!(self.attach_shared_memory(size), Ok(true)) {
+                self.shared_memory_size.set(None);
+            }
+        }
+
+        self.subscribers
+            .borrow_mut()
+            .retain(|subscriber| subscriber.send(IpcClientEvent::Reconnected).is_ok());
+
+        Ok(())
+    }
+
+    /// Sends `request` and waits for its response.
+    ///
+    /// If the connection is lost, reconnects and sends the request again when possible.
+    /// After a reconnection, the requests referencing ids fail until a context hash is
+    /// resolved on the new connection.
+    fn call(&self, request: ContextRequest) -> Result<ContextResponse, ContextServiceError> {
+        if request.references_ids() && self.ids_epoch.get() != self.epoch.get() {
+            return Err(ContextServiceError::Reconnected {
+                request: (&request).into(),
+            });
+        }
+
+        let resolves_ids = request.resolves_ids();
+        let retry = if request.can_retry_after_reconnect() {
+            Ok(request.clone())
+        } else {
+            Err(<&'static str>::from(&request))
+        };
+
+        let result = match self.try_call(request) {
+            Err(ContextServiceError::IpcError { reason }) if is_connection_lost(&reason) => {
+                self.reconnect()?;
+                match retry {
+                    Ok(request) => self.try_call(request),
+                    Err(request) => Err(ContextServiceError::Reconnected { request }),
+                }
+            }
+            result => result,
+        };
+
+        if resolves_ids && result.is_ok() {
+            self.ids_epoch.set(self.epoch.get());
+        }
+        result
+    }
+
+    fn try_call(&self, request: ContextRequest) -> Result<ContextResponse, ContextServiceError> {
         let mut io = self.io.borrow_mut();
-        io.tx.send(&ContextRequest::GetValue(hash_id))?;
+        io.last_id += 1;
+        let id = io.last_id;
+        io.tx.send(&ContextRequestFrame { id, request })?;
 
         // this might take a while, so we will use unusually long timeout
-        match io
-            .rx
-            .try_receive(Some(Self::TIMEOUT), Some(IpcContextListener::IO_TIMEOUT))?
-        {
+        let deadline = Instant::now() + Self::TIMEOUT;
+        loop {
+            let timeout = match deadline.checked_duration_since(Instant::now()) {
+                Some(timeout) => timeout,
+                None => return Err(IpcError::ReceiveMessageTimeout.into()),
+            };
+
+            let frame = io
+                .rx
+                .try_receive(Some(timeout), Some(IpcContextListener::IO_TIMEOUT))?;
+
+            // Responses to previous requests that timed out are discarded
+            if frame.id == id {
+                return Ok(frame.response);
+            }
+        }
+    }
+
+    /// Get object by hash id
+    pub fn get_value(&self, hash_id: HashId) -> Result<Option<Cow<[u8]>>, ContextServiceError> {
+        match self.call(ContextRequest::GetValue(hash_id))? {
             ContextResponse::GetValueResponse(result) => result
                 .map(|h| h.map(Cow::Owned))
                 .map_err(|err| ContextError::GetValueError { reason: err }.into()),
@@ -325,16 +902,24 @@ impl IpcContextClient {
         }
     }
 
+    /// Get objects by hash ids, in a single request
+    pub fn get_many(
+        &self,
+        hash_ids: &[HashId],
+    ) -> Result<Vec<(HashId, Option<ContextValue>)>, ContextServiceError> {
+        match self.call(ContextRequest::GetMany(hash_ids.to_vec()))? {
+            ContextResponse::GetManyResponse(result) => {
+                result.map_err(|err| ContextError::GetValueError { reason: err }.into())
+            }
+            message => Err(ContextServiceError::UnexpectedMessage {
+                message: message.into(),
+            }),
+        }
+    }
+
     /// Check if object with hash id exists
     pub fn contains_object(&self, hash_id: HashId) -> Result<bool, ContextServiceError> {
-        let mut io = self.io.borrow_mut();
-        io.tx.send(&ContextRequest::ContainsObject(hash_id))?;
-
-        // this might take a while, so we will use unusually long timeout
-        match io
-            .rx
-            .try_receive(Some(Self::TIMEOUT), Some(IpcContextListener::IO_TIMEOUT))?
-        {
+        match self.call(ContextRequest::ContainsObject(hash_id))? {
             ContextResponse::ContainsObjectResponse(result) => {
                 result.map_err(|err| ContextError::ContainsObjectError { reason: err }.into())
             }
@@ -349,15 +934,7 @@ impl IpcContextClient {
         &self,
         context_hash: &ContextHash,
     ) -> Result<Option<HashId>, ContextServiceError> {
-        let mut io = self.io.borrow_mut();
-        io.tx
-            .send(&ContextRequest::GetContextHashId(context_hash.clone()))?;
-
-        // this might take a while, so we will use unusually long timeout
-        match io
-            .rx
-            .try_receive(Some(Self::TIMEOUT), Some(IpcContextListener::IO_TIMEOUT))?
-        {
+        match self.call(ContextRequest::GetContextHashId(context_hash.clone()))? {
             ContextResponse::GetContextHashIdResponse(result) => {
                 result.map_err(|err| ContextError::GetContextHashIdError { reason: err }.into())
             }
@@ -367,19 +944,36 @@ impl IpcContextClient {
         }
     }
 
+    /// Same as `Self::get_context_hash_id`, with the number of `HashId`s reused by the
+    /// server so far, see `KeyValueStoreBackend::reused_hash_ids`.
+    ///
+    /// The number is `None` when the server doesn't provide it (protocol 1.1.0).
+    pub fn get_context_hash_id_and_reuses(
+        &self,
+        context_hash: &ContextHash,
+    ) -> Result<(Option<HashId>, Option<u64>), ContextServiceError> {
+        if self.io.borrow().tx.protocol_version() == Some(CONTEXT_IPC_VERSION_WITHOUT_REUSES) {
+            return Ok((self.get_context_hash_id(context_hash)?, None));
+        }
+
+        match self.call(ContextRequest::GetContextHashIdAndReuses(
+            context_hash.clone(),
+        ))? {
+            ContextResponse::GetContextHashIdAndReusesResponse(result) => result
+                .map(|(hash_id, reuses)| (hash_id, Some(reuses)))
+                .map_err(|err| ContextError::GetContextHashIdError { reason: err }.into()),
+            message => Err(ContextServiceError::UnexpectedMessage {
+                message: message.into(),
+            }),
+        }
+    }
+
     /// Check if object with hash id exists
     pub fn get_hash(
         &self,
         hash_id: HashId,
     ) -> Result<Option<Cow<ObjectHash>>, ContextServiceError> {
-        let mut io = self.io.borrow_mut();
-        io.tx.send(&ContextRequest::GetHash(hash_id))?;
-
-        // this might take a while, so we will use unusually long timeout
-        match io
-            .rx
-            .try_receive(Some(Self::TIMEOUT), Some(IpcContextListener::IO_TIMEOUT))?
-        {
+        match self.call(ContextRequest::GetHash(hash_id))? {
             ContextResponse::GetContextHashResponse(result) => result
                 .map(|h| h.map(Cow::Owned))
                 .map_err(|err| ContextError::GetContextHashError { reason: err }.into()),
@@ -394,14 +988,7 @@ impl IpcContextClient {
         &self,
         shape_id: DirectoryShapeId,
     ) -> Result<Vec<String>, ContextServiceError> {
-        let mut io = self.io.borrow_mut();
-        io.tx.send(&ContextRequest::GetShape(shape_id))?;
-
-        // this might take a while, so we will use unusually long timeout
-        match io
-            .rx
-            .try_receive(Some(Self::TIMEOUT), Some(IpcContextListener::IO_TIMEOUT))?
-        {
+        match self.call(ContextRequest::GetShape(shape_id))? {
             ContextResponse::GetShapeResponse(result) => {
                 result.map_err(|err| ContextError::GetShapeError { reason: err }.into())
             }
@@ -410,6 +997,89 @@ impl IpcContextClient {
             }),
         }
     }
+
+    /// Get the `StringId`s of the shape and the strings interned since `(generation, offset)`
+    ///
+    /// Returns `None` when the shapes of the server are not made of `StringId`s.
+    pub fn get_shape_ids(
+        &self,
+        shape_id: DirectoryShapeId,
+        generation: u32,
+        offset: usize,
+    ) -> Result<Option<ShapeIds>, ContextServiceError> {
+        match self.call(ContextRequest::GetShapeIds(shape_id, generation, offset))? {
+            ContextResponse::GetShapeIdsResponse(result) => {
+                result.map_err(|err| ContextError::GetShapeError { reason: err }.into())
+            }
+            message => Err(ContextServiceError::UnexpectedMessage {
+                message: message.into(),
+            }),
+        }
+    }
+
+    /// Get the statistics of the tree under `prefix`, computed by the server
+    pub fn get_tree_stats(
+        &self,
+        context_hash: &ContextHash,
+        prefix: &ContextKey,
+    ) -> Result<Option<TreeStats>, ContextServiceError> {
+        let prefix = prefix.iter().map(|s| s.to_string()).collect();
+
+        match self.call(ContextRequest::GetTreeStats(context_hash.clone(), prefix))? {
+            ContextResponse::GetTreeStatsResponse(result) => {
+                result.map_err(|err| ContextError::GetTreeStatsError { reason: err }.into())
+            }
+            message => Err(ContextServiceError::UnexpectedMessage {
+                message: message.into(),
+            }),
+        }
+    }
+
+    /// Get the keys changed under `prefix` between the commits `from` and `to`,
+    /// computed by the server
+    pub fn diff(
+        &self,
+        from: &ContextHash,
+        to: &ContextHash,
+        prefix: &ContextKey,
+    ) -> Result<Option<Vec<KeyDiff>>, ContextServiceError> {
+        let prefix = prefix.iter().map(|s| s.to_string()).collect();
+
+        match self.call(ContextRequest::Diff(from.clone(), to.clone(), prefix))? {
+            ContextResponse::DiffResponse(result) => {
+                result.map_err(|err| ContextError::DiffError { reason: err }.into())
+            }
+            message => Err(ContextServiceError::UnexpectedMessage {
+                message: message.into(),
+            }),
+        }
+    }
+
+    /// Verify the hashes of the commit `context_hash` and of its tree, computed by the server
+    pub fn verify_tree(
+        &self,
+        context_hash: &ContextHash,
+        mode: VerifyMode,
+    ) -> Result<Option<TreeVerification>, ContextServiceError> {
+        match self.call(ContextRequest::VerifyTree(context_hash.clone(), mode))? {
+            ContextResponse::VerifyTreeResponse(result) => {
+                result.map_err(|err| ContextError::VerifyTreeError { reason: err }.into())
+            }
+            message => Err(ContextServiceError::UnexpectedMessage {
+                message: message.into(),
+            }),
+        }
+    }
+}
+
+/// Returns `true` if `error` means that the other end of the connection is gone.
+fn is_connection_lost(error: &IpcError) -> bool {
+    matches!(
+        error,
+        IpcError::SendError { .. }
+            | IpcError::ReceiveMessageLengthError { .. }
+            | IpcError::ReceiveMessageError { .. }
+    )
 }
 
 impl<'a> Iterator for ContextIncoming<'a> {
@@ -427,7 +1097,9 @@ impl IpcContextListener {
         // Remove file first, otherwise bind will fail.
         std::fs::remove_file(&socket_path).ok();
 
-        Ok(IpcContextListener(IpcServer::bind_path(socket_path)?))
+        Ok(IpcContextListener(
+            IpcServer::bind_path(socket_path)?.with_protocol(CONTEXT_IPC_PROTOCOL),
+        ))
     }
 
     /// Start accepting incoming IPC connections.
@@ -438,7 +1110,10 @@ impl IpcContextListener {
         let (rx, tx) = self.0.accept()?;
 
         Ok(IpcContextServer {
-            io: RefCell::new(IpcServerIO { rx, tx }),
+            io: RefCell::new(IpcServerIO {
+                rx,
+                tx: Arc::new(Mutex::new(tx)),
+            }),
         })
     }
 
@@ -487,116 +1162,429 @@ impl IpcContextListener {
 }
 
 impl IpcContextServer {
+    /// Number of threads handling the requests of a connection.
+    const WORKERS: usize = 4;
+    /// Responses of at least this size are sent through shared memory, when the client enabled it.
+    const SHARED_MEMORY_THRESHOLD: usize = 64 * 1024;
+
     /// Listen to new connections from context readers.
     /// Begin receiving commands from context readers until `ShutdownCall` command is received.
+    ///
+    /// Requests are handled concurrently, their responses are sent back as soon as they are
+    /// ready, so a slow request doesn't block the following ones.
     pub fn process_context_requests(&self, log: &Logger) -> Result<(), IpcContextError> {
         let mut io = self.io.borrow_mut();
-        loop {
-            let cmd = io.rx.receive()?;
-
-            match cmd {
-                ContextRequest::GetValue(hash) => match crate::ffi::get_context_index()? {
-                    None => io.tx.send(&ContextResponse::GetValueResponse(Err(
-                        "Context index unavailable".to_owned(),
-                    )))?,
-                    Some(index) => {
-                        let res = index
-                            .fetch_object_bytes(hash)
-                            .map_err(|err| format!("Context error: {:?}", err));
-                        io.tx.send(&ContextResponse::GetValueResponse(res))?;
+        let (jobs, jobs_recv) = crossbeam_channel::unbounded::<ContextRequestFrame>();
+
+        let mut workers = Vec::with_capacity(Self::WORKERS);
+        for _ in 0..Self::WORKERS {
+            let jobs_recv = jobs_recv.clone();
+            let tx = Arc::clone(&io.tx);
+            let log = log.clone();
+
+            let worker = std::thread::Builder::new()
+                .name("ctx-ipc-worker-thread".to_string())
+                .spawn(move || {
+                    for ContextRequestFrame { id, request } in jobs_recv.iter() {
+                        let response = handle_request(request);
+                        let mut tx = tx.lock().unwrap_or_else(|e| e.into_inner());
+
+                        if let Err(e) = tx.send(&ContextResponseFrame { id, response }) {
+                            warn!(log, "Failed to send context IPC response"; "reason" => format!("{}", e));
+                        }
+                    }
+                })
+                .map_err(|reason| IpcError::ThreadError { reason })?;
+            workers.push(worker);
+        }
+
+        let result = loop {
+            let frame = match io.rx.receive() {
+                Ok(frame) => frame,
+                Err(e) => break Err(e),
+            };
+
+            let ContextRequestFrame { id, request } = frame;
+
+            match request {
+                ContextRequest::ShutdownCall => break Ok(id),
+                ContextRequest::AttachSharedMemory(path) => {
+                    // Handled here, no other request was received yet
+                    let mut tx = io.tx.lock().unwrap_or_else(|e| e.into_inner());
+                    let result = ShmSegment::open(path)
+                        .map(|segment| tx.attach_shm(segment, Self::SHARED_MEMORY_THRESHOLD))
+                        .map_err(|err| format!("Shared memory error: {:?}", err));
+                    let response = ContextResponse::AttachSharedMemoryResponse(result);
+
+                    if let Err(e) = tx.send(&ContextResponseFrame { id, response }) {
+                        warn!(log, "Failed to send context IPC response"; "reason" => format!("{}", e));
                     }
-                },
-                ContextRequest::GetShape(shape_id) => match crate::ffi::get_context_index()? {
-                    None => io.tx.send(&ContextResponse::GetShapeResponse(Err(
-                        "Context index unavailable".to_owned(),
-                    )))?,
-                    Some(index) => {
-                        let res = index
-                            .repository
-                            .read()
-                            .map_err(|_| ContextError::GetShapeError {
-                                reason: "Fail to get repo".to_string(),
-                            })
-                            .and_then(|repo| {
-                                let shape = repo.get_shape(shape_id).map_err(|_| {
-                                    ContextError::GetShapeError {
-                                        reason: "Fail to get shape".to_string(),
-                                    }
+                }
+                request => {
+                    // The workers only stop once `jobs` is dropped
+                    let _ = jobs.send(ContextRequestFrame { id, request });
+                }
+            }
+        };
+
+        // Finish the pending requests before replying to the shutdown
+        drop(jobs);
+        for worker in workers {
+            let _ = worker.join();
+        }
+
+        let id = result?;
+        let mut tx = io.tx.lock().unwrap_or_else(|e| e.into_inner());
+        let response = ContextResponse::ShutdownResult;
+
+        if let Err(e) = tx.send(&ContextResponseFrame { id, response }) {
+            warn!(log, "Failed to send shutdown response"; "reason" => format!("{}", e));
+        }
+
+        Ok(())
+    }
+}
+
+fn context_index() -> Result<TezedgeIndex, String> {
+    match crate::ffi::get_context_index() {
+        Ok(Some(index)) => Ok(index),
+        Ok(None) => Err("Context index unavailable".to_owned()),
+        Err(err) => Err(format!("Context index error: {:?}", err)),
+    }
+}
+
+/// Returns the context index, with a copy of the strings of the repository.
+///
+/// The shapes of the repository are made of its `StringId`s: the requests walking a tree
+/// resolve them through the `StringInterner` of the index, which starts empty.
+fn context_index_with_strings() -> Result<TezedgeIndex, String> {
+    let index = context_index()?;
+    let (generation, offset) = index.storage.borrow().strings.cursor();
+
+    let update = index
+        .repository
+        .read()
+        .map_err(|err| format!("Context error: {:?}", err))?
+        .get_strings_update(generation, offset);
+
+    if let Some(update) = update {
+        index.storage.borrow_mut().strings.apply_update(update);
+    }
+    Ok(index)
+}
+
+fn handle_request(request: ContextRequest) -> ContextResponse {
+    match request {
+        ContextRequest::GetValue(hash) => {
+            let res = context_index().and_then(|index| {
+                index
+                    .fetch_object_bytes(hash)
+                    .map_err(|err| format!("Context error: {:?}", err))
+            });
+            ContextResponse::GetValueResponse(res)
+        }
+        ContextRequest::GetMany(hash_ids) => {
+            let res = context_index().and_then(|index| {
+                hash_ids
+                    .into_iter()
+                    .map(|hash_id| Ok((hash_id, index.fetch_object_bytes(hash_id)?)))
+                    .collect::<Result<Vec<_>, DBError>>()
+                    .map_err(|err| format!("Context error: {:?}", err))
+            });
+            ContextResponse::GetManyResponse(res)
+        }
+        ContextRequest::GetShape(shape_id) => {
+            let res = context_index().and_then(|index| {
+                index
+                    .repository
+                    .read()
+                    .map_err(|_| ContextError::GetShapeError {
+                        reason: "Fail to get repo".to_string(),
+                    })
+                    .and_then(|repo| {
+                        let shape =
+                            repo.get_shape(shape_id)
+                                .map_err(|_| ContextError::GetShapeError {
+                                    reason: "Fail to get shape".to_string(),
                                 })?;
 
-                                // We send the owned `String` to the read only protocol runner.
-                                // We do not send the `StringId`s because the read only protocol
-                                // runner doesn't have access to the same `StringInterner`.
-                                match shape {
-                                    ShapeStrings::SliceIds(slice_ids) => slice_ids
-                                        .iter()
-                                        .map(|s| {
-                                            repo.get_str(*s)
-                                                .ok_or_else(|| ContextError::GetShapeError {
-                                                    reason: format!("String not found"),
-                                                })
-                                                .map(|s| s.to_string())
+                        // We send the owned `String` to the read only protocol runner.
+                        // We do not send the `StringId`s because the read only protocol
+                        // runner doesn't have access to the same `StringInterner`.
+                        match shape {
+                            ShapeStrings::SliceIds(slice_ids) => slice_ids
+                                .iter()
+                                .map(|s| {
+                                    repo.get_str(*s)
+                                        .ok_or_else(|| ContextError::GetShapeError {
+                                            reason: "String not found".to_string(),
                                         })
-                                        .collect(),
-                                    ShapeStrings::Owned(_) => Err(ContextError::GetShapeError {
-                                        reason: "Should receive a slice of StringId".to_string(),
-                                    }),
-                                }
-                            })
-                            .map_err(|err| format!("Context error: {:?}", err));
-
-                        io.tx.send(&ContextResponse::GetShapeResponse(res))?;
-                    }
-                },
-                ContextRequest::ContainsObject(hash) => match crate::ffi::get_context_index()? {
-                    None => io.tx.send(&ContextResponse::GetValueResponse(Err(
-                        "Context index unavailable".to_owned(),
-                    )))?,
-                    Some(index) => {
-                        let res = index
-                            .contains(hash)
-                            .map_err(|err| format!("Context error: {:?}", err));
-                        io.tx.send(&ContextResponse::ContainsObjectResponse(res))?;
-                    }
-                },
+                                        .map(|s| s.to_string())
+                                })
+                                .collect(),
+                            ShapeStrings::Owned(strings) => Ok(strings),
+                        }
+                    })
+                    .map_err(|err| format!("Context error: {:?}", err))
+            });
+            ContextResponse::GetShapeResponse(res)
+        }
+        ContextRequest::GetShapeIds(shape_id, generation, offset) => {
+            let res = context_index().and_then(|index| {
+                let repo = index
+                    .repository
+                    .read()
+                    .map_err(|err| format!("Context error: {:?}", err))?;
+
+                let string_ids = match repo
+                    .get_shape(shape_id)
+                    .map_err(|err| format!("Context error: {:?}", err))?
+                {
+                    ShapeStrings::SliceIds(string_ids) => string_ids.to_vec(),
+                    ShapeStrings::Owned(_) => return Ok(None),
+                };
+
+                Ok(repo
+                    .get_strings_update(generation, offset)
+                    .map(|strings| ShapeIds {
+                        string_ids,
+                        strings,
+                    }))
+            });
+            ContextResponse::GetShapeIdsResponse(res)
+        }
+        ContextRequest::ContainsObject(hash) => {
+            let res = context_index().and_then(|index| {
+                index
+                    .contains(hash)
+                    .map_err(|err| format!("Context error: {:?}", err))
+            });
+            ContextResponse::ContainsObjectResponse(res)
+        }
+        ContextRequest::GetContextHashId(context_hash) => {
+            let res = context_index().and_then(|index| {
+                index
+                    .fetch_context_hash_id(&context_hash)
+                    .map_err(|err| format!("Context error: {:?}", err))
+            });
+            ContextResponse::GetContextHashIdResponse(res)
+        }
+        ContextRequest::GetContextHashIdAndReuses(context_hash) => {
+            let res = context_index().and_then(|index| {
+                index
+                    .fetch_context_hash_id_and_reuses(&context_hash)
+                    .map_err(|err| format!("Context error: {:?}", err))
+            });
+            ContextResponse::GetContextHashIdAndReusesResponse(res)
+        }
+        ContextRequest::GetHash(hash_id) => {
+            let res = context_index().and_then(|index| {
+                index
+                    .fetch_hash(hash_id)
+                    .map_err(|err| format!("Context error: {:?}", err))
+            });
+            ContextResponse::GetContextHashResponse(res)
+        }
+        ContextRequest::GetTreeStats(context_hash, prefix) => {
+            let res = context_index_with_strings().and_then(|index| {
+                let prefix: Vec<&str> = prefix.iter().map(|s| s.as_str()).collect();
+
+                index
+                    .get_tree_stats(&context_hash, &prefix)
+                    .map_err(|err| format!("Context error: {:?}", err))
+            });
+            ContextResponse::GetTreeStatsResponse(res)
+        }
+        ContextRequest::Diff(from, to, prefix) => {
+            let res = context_index_with_strings().and_then(|index| {
+                let prefix: Vec<&str> = prefix.iter().map(|s| s.as_str()).collect();
+
+                index
+                    .diff(&from, &to, &prefix)
+                    .map_err(|err| format!("Context error: {:?}", err))
+            });
+            ContextResponse::DiffResponse(res)
+        }
+        ContextRequest::VerifyTree(context_hash, mode) => {
+            let res = context_index_with_strings().and_then(|index| {
+                index
+                    .verify_tree(&context_hash, mode)
+                    .map_err(|err| format!("Context error: {:?}", err))
+            });
+            ContextResponse::VerifyTreeResponse(res)
+        }
+        ContextRequest::AttachSharedMemory(_) => ContextResponse::AttachSharedMemoryResponse(Err(
+            "Shared memory must be attached before any other request".to_owned(),
+        )),
+        ContextRequest::ShutdownCall => ContextResponse::ShutdownResult,
+    }
+}
 
-                ContextRequest::ShutdownCall => {
-                    if let Err(e) = io.tx.send(&ContextResponse::ShutdownResult) {
-                        warn!(log, "Failed to send shutdown response"; "reason" => format!("{}", e));
-                    }
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use std::thread;
+
+    use super::*;
 
-                    break;
+    /// Fake server, answering `nrequests` `GetValue` requests with `value` (and `GetContextHashId`
+    /// requests with the id `value`) and closing the connection.
+    fn serve(listener: &mut IpcContextListener, nrequests: usize, value: u8) {
+        let server = listener.accept().unwrap();
+        let mut io = server.io.borrow_mut();
+
+        for _ in 0..nrequests {
+            let ContextRequestFrame { id, request } = io.rx.receive().unwrap();
+
+            let response = match request {
+                ContextRequest::GetValue(_) => {
+                    ContextResponse::GetValueResponse(Ok(Some(vec![value])))
                 }
-                ContextRequest::GetContextHashId(context_hash) => {
-                    match crate::ffi::get_context_index()? {
-                        None => io.tx.send(&ContextResponse::GetContextHashIdResponse(Err(
-                            "Context index unavailable".to_owned(),
-                        )))?,
-                        Some(index) => {
-                            let res = index
-                                .fetch_context_hash_id(&context_hash)
-                                .map_err(|err| format!("Context error: {:?}", err));
-
-                            io.tx
-                                .send(&ContextResponse::GetContextHashIdResponse(res))?;
-                        }
-                    }
+                ContextRequest::GetContextHashId(_) => {
+                    ContextResponse::GetContextHashIdResponse(Ok(HashId::new(value as u32)))
                 }
-                ContextRequest::GetHash(hash_id) => match crate::ffi::get_context_index()? {
-                    None => io.tx.send(&ContextResponse::GetContextHashResponse(Err(
-                        "Context index unavailable".to_owned(),
-                    )))?,
-                    Some(index) => {
-                        let res = index
-                            .fetch_hash(hash_id)
-                            .map_err(|err| format!("Context error: {:?}", err));
-
-                        io.tx.send(&ContextResponse::GetContextHashResponse(res))?;
-                    }
-                },
-            }
+                request => panic!("Unexpected request {:?}", request),
+            };
+            io.tx
+                .lock()
+                .unwrap()
+                .send(&ContextResponseFrame { id, response })
+                .unwrap();
         }
+    }
 
-        Ok(())
+    #[test]
+    fn test_client_reconnect() {
+        let path =
+            std::env::temp_dir().join(format!("tezedge_ctx_reconnect_{}.sock", std::process::id()));
+        let mut listener = IpcContextListener::try_new(&path).unwrap();
+        let first_server = thread::spawn(move || serve(&mut listener, 1, 1));
+
+        let client = IpcContextClient::try_connect(&path).unwrap();
+        let events = client.subscribe();
+        let hash_id = HashId::new(1).unwrap();
+
+        assert_eq!(client.get_value(hash_id).unwrap().unwrap().as_ref(), &[1]);
+        first_server.join().unwrap();
+        assert!(events.try_recv().is_err());
+
+        // the writable protocol runner restarted, the `HashId` belongs to the previous one
+        let mut listener = IpcContextListener::try_new(&path).unwrap();
+        let second_server = thread::spawn(move || serve(&mut listener, 2, 2));
+
+        for _ in 0..2 {
+            assert!(matches!(
+                client.get_value(hash_id),
+                Err(ContextServiceError::Reconnected {
+                    request: "GetValue"
+                })
+            ));
+        }
+        assert_eq!(events.try_recv(), Ok(IpcClientEvent::Reconnected));
+
+        // the ids are valid again once a context hash is resolved by the new runner
+        let context_hash = ContextHash::try_from(&[0; 32][..]).unwrap();
+        let hash_id = client.get_context_hash_id(&context_hash).unwrap().unwrap();
+        assert_eq!(hash_id, HashId::new(2).unwrap());
+        assert_eq!(client.get_value(hash_id).unwrap().unwrap().as_ref(), &[2]);
+        second_server.join().unwrap();
+
+        // requests made of context hashes are sent again
+        let mut listener = IpcContextListener::try_new(&path).unwrap();
+        let third_server = thread::spawn(move || serve(&mut listener, 1, 3));
+
+        assert_eq!(
+            client.get_context_hash_id(&context_hash).unwrap(),
+            HashId::new(3)
+        );
+        assert_eq!(events.try_recv(), Ok(IpcClientEvent::Reconnected));
+        third_server.join().unwrap();
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_cache_cleared_on_reuse() {
+        let path =
+            std::env::temp_dir().join(format!("tezedge_ctx_reuse_{}.sock", std::process::id()));
+        let mut listener = IpcContextListener::try_new(&path).unwrap();
+        let hash_id = HashId::new(1).unwrap();
+
+        // a request for a cached value would receive the wrong response
+        let responses = vec![
+            ContextResponse::GetContextHashIdAndReusesResponse(Ok((Some(hash_id), 0))),
+            ContextResponse::GetValueResponse(Ok(Some(vec![1]))),
+            ContextResponse::GetContextHashIdAndReusesResponse(Ok((Some(hash_id), 0))),
+            ContextResponse::GetContextHashIdAndReusesResponse(Ok((Some(hash_id), 1))),
+            ContextResponse::GetValueResponse(Ok(Some(vec![2]))),
+        ];
+        let server = thread::spawn(move || {
+            let server = listener.accept().unwrap();
+            let mut io = server.io.borrow_mut();
+
+            for response in responses {
+                let ContextRequestFrame { id, .. } = io.rx.receive().unwrap();
+                io.tx
+                    .lock()
+                    .unwrap()
+                    .send(&ContextResponseFrame { id, response })
+                    .unwrap();
+            }
+        });
+
+        let client = IpcContextClient::try_connect(&path).unwrap();
+        let backend = ReadonlyIpcBackend::with_client(ContextClient::Blocking(client));
+        let context_hash = ContextHash::try_from(&[0; 32][..]).unwrap();
+
+        assert_eq!(
+            backend.get_context_hash(&context_hash).unwrap(),
+            Some(hash_id)
+        );
+        assert_eq!(backend.get_value(hash_id).unwrap().unwrap().as_ref(), &[1]);
+        assert_eq!(backend.get_value(hash_id).unwrap().unwrap().as_ref(), &[1]);
+
+        // no id was reused, the value is still cached
+        assert_eq!(
+            backend.get_context_hash(&context_hash).unwrap(),
+            Some(hash_id)
+        );
+        assert_eq!(backend.get_value(hash_id).unwrap().unwrap().as_ref(), &[1]);
+
+        // the id may now refer to another object
+        assert_eq!(
+            backend.get_context_hash(&context_hash).unwrap(),
+            Some(hash_id)
+        );
+        assert_eq!(backend.get_value(hash_id).unwrap().unwrap().as_ref(), &[2]);
+        server.join().unwrap();
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_client_shared_memory() {
+        let path = std::env::temp_dir().join(format!(
+            "tezedge_ctx_shared_memory_{}.sock",
+            std::process::id()
+        ));
+        let mut listener = IpcContextListener::try_new(&path).unwrap();
+        thread::spawn(move || {
+            let server = listener.accept().unwrap();
+            let log = slog::Logger::root(slog::Discard, slog::o!());
+            let _ = server.process_context_requests(&log);
+        });
+
+        let client = IpcContextClient::try_connect(&path).unwrap();
+        assert!(client.enable_shared_memory(1024 * 1024).unwrap());
+
+        // there is no context index in this process
+        assert!(matches!(
+            client.get_value(HashId::new(1).unwrap()),
+            Err(ContextServiceError::ContextError {
+                reason: ContextError::GetValueError { .. }
+            })
+        ));
+
+        std::fs::remove_file(&path).ok();
     }
 }
@@ -0,0 +1,825 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Non-blocking IPC context client.
+//!
+//! Requests are sent through a channel to a writer thread, which tags each of them with an id,
+//! and a reader thread dispatches the responses by id, so several requests are in flight on
+//! the same connection. Each request gets its response through a oneshot [`IpcResponse`]:
+//! - it is a `Future`, so it can be awaited from async code (with any executor),
+//! - or it can be waited synchronously with [`IpcResponse::wait`],
+//! - dropping it cancels the request, if it has not been sent yet.
+//!
+//! Each kind of request has its own timeout, see [`IpcRequestTimeouts`]. A request that
+//! timed out fails alone, its response is discarded if it arrives later.
+//!
+//! The client can be cloned, the clones share the same connection. It doesn't reconnect: once
+//! the connection is lost, every request fails with `ContextServiceError::Disconnected`.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    path::Path,
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use crypto::hash::ContextHash;
+use ipc::{version::ProtocolVersion, IpcError, IpcReceiver, IpcSender};
+
+use super::{
+    ContextError, ContextRequest, ContextRequestFrame, ContextResponse, ContextResponseFrame,
+    ContextServiceError, IpcClientIO, IpcContextClient, ShapeIds,
+    CONTEXT_IPC_VERSION_WITHOUT_REUSES,
+};
+use crate::{
+    kv_store::HashId,
+    tezedge_context::{KeyDiff, TreeStats, TreeVerification, VerifyMode},
+    working_tree::shape::DirectoryShapeId,
+    ContextKey, ContextValue, ObjectHash,
+};
+
+/// Timeouts of each kind of request.
+#[derive(Debug, Clone)]
+pub struct IpcRequestTimeouts {
+    pub get_value: Duration,
+    pub get_many: Duration,
+    pub get_hash: Duration,
+    pub get_shape: Duration,
+    pub contains_object: Duration,
+    pub get_context_hash_id: Duration,
+    pub get_tree_stats: Duration,
+    pub diff: Duration,
+    pub verify_tree: Duration,
+    /// Interval of the keep-alive pings sent while no request is sent, `None` disables them
+    pub heartbeat_interval: Option<Duration>,
+}
+
+impl Default for IpcRequestTimeouts {
+    fn default() -> Self {
+        Self {
+            get_value: IpcContextClient::TIMEOUT,
+            get_many: IpcContextClient::TIMEOUT,
+            get_hash: IpcContextClient::TIMEOUT,
+            get_shape: IpcContextClient::TIMEOUT,
+            contains_object: IpcContextClient::TIMEOUT,
+            get_context_hash_id: IpcContextClient::TIMEOUT,
+            get_tree_stats: IpcContextClient::TIMEOUT,
+            diff: IpcContextClient::TIMEOUT,
+            verify_tree: IpcContextClient::TIMEOUT,
+            heartbeat_interval: None,
+        }
+    }
+}
+
+struct OneshotState<T> {
+    value: Option<T>,
+    waker: Option<Waker>,
+    cancelled: bool,
+}
+
+struct Oneshot<T> {
+    state: Mutex<OneshotState<T>>,
+    ready: Condvar,
+}
+
+impl<T> Oneshot<T> {
+    fn lock(&self) -> MutexGuard<'_, OneshotState<T>> {
+        // The state stays consistent even if a holder panicked
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Sending half of the oneshot channel, owned by the IO thread.
+struct Responder<T> {
+    oneshot: Option<Arc<Oneshot<T>>>,
+}
+
+type Response = Result<ContextResponse, ContextServiceError>;
+
+impl Responder<Response> {
+    fn is_cancelled(&self) -> bool {
+        self.oneshot
+            .as_ref()
+            .map(|oneshot| oneshot.lock().cancelled)
+            .unwrap_or(true)
+    }
+
+    fn send(mut self, value: Response) {
+        if let Some(oneshot) = self.oneshot.take() {
+            let mut state = oneshot.lock();
+            state.value = Some(value);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+            oneshot.ready.notify_all();
+        }
+    }
+}
+
+impl<T> Drop for Responder<T> {
+    fn drop(&mut self) {
+        // The request was dropped without response, the IO thread is gone
+        if let Some(oneshot) = self.oneshot.take() {
+            let mut state = oneshot.lock();
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+            state.cancelled = true;
+            oneshot.ready.notify_all();
+        }
+    }
+}
+
+/// Response to a request of [`AsyncIpcContextClient`].
+pub struct IpcResponse<T> {
+    oneshot: Arc<Oneshot<Response>>,
+    map: fn(ContextResponse) -> Result<T, ContextServiceError>,
+}
+
+impl<T> IpcResponse<T> {
+    fn take(&self, state: &mut OneshotState<Response>) -> Option<Result<T, ContextServiceError>> {
+        match state.value.take() {
+            Some(response) => Some(response.and_then(self.map)),
+            None if state.cancelled => Some(Err(ContextServiceError::Disconnected)),
+            None => None,
+        }
+    }
+
+    /// Blocks the current thread until the response is received.
+    pub fn wait(self) -> Result<T, ContextServiceError> {
+        let mut state = self.oneshot.lock();
+
+        loop {
+            if let Some(result) = self.take(&mut state) {
+                return result;
+            }
+            state = self
+                .oneshot
+                .ready
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+impl<T> Future for IpcResponse<T> {
+    type Output = Result<T, ContextServiceError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.oneshot.lock();
+
+        match self.take(&mut state) {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> Drop for IpcResponse<T> {
+    fn drop(&mut self) {
+        self.oneshot.lock().cancelled = true;
+    }
+}
+
+struct PendingRequest {
+    request: ContextRequest,
+    timeout: Duration,
+    responder: Responder<Response>,
+}
+
+/// Request sent and waiting for its response.
+struct InFlightRequest {
+    request: &'static str,
+    deadline: Instant,
+    responder: Responder<Response>,
+}
+
+#[derive(Default)]
+struct InFlightRequests {
+    requests: HashMap<u64, InFlightRequest>,
+    disconnected: bool,
+}
+
+type SharedInFlightRequests = Arc<Mutex<InFlightRequests>>;
+
+fn lock(in_flight: &SharedInFlightRequests) -> MutexGuard<'_, InFlightRequests> {
+    // The requests stay consistent even if a holder panicked
+    in_flight.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// IPC context client usable from async code, see the [module documentation](self).
+#[derive(Clone)]
+pub struct AsyncIpcContextClient {
+    requests: Sender<PendingRequest>,
+    timeouts: IpcRequestTimeouts,
+    /// Version of the protocol negotiated with the server
+    protocol_version: Option<ProtocolVersion>,
+}
+
+impl AsyncIpcContextClient {
+    /// Interval at which the writer thread checks the timeouts of the requests in flight.
+    const TIMEOUT_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+    /// Connects to the socket in `socket_path` and starts the IO threads.
+    ///
+    /// This operation is blocking, see [`IpcContextClient::try_connect`].
+    pub fn try_connect<P: AsRef<Path>>(
+        socket_path: P,
+        timeouts: IpcRequestTimeouts,
+    ) -> Result<Self, IpcError> {
+        Self::from_client(IpcContextClient::try_connect(socket_path)?, timeouts)
+    }
+
+    /// Takes over the connection of `client`, and starts the IO threads.
+    pub fn from_client(
+        client: IpcContextClient,
+        timeouts: IpcRequestTimeouts,
+    ) -> Result<Self, IpcError> {
+        let IpcClientIO { rx, mut tx, .. } = client.io.into_inner();
+        tx.set_heartbeat_interval(timeouts.heartbeat_interval);
+        let protocol_version = tx.protocol_version();
+        let (requests, recv) = crossbeam_channel::unbounded();
+        let in_flight = SharedInFlightRequests::default();
+
+        let reader_in_flight = Arc::clone(&in_flight);
+        std::thread::Builder::new()
+            .name("ctx-ipc-reader-thread".to_string())
+            .spawn(move || run_reader(rx, reader_in_flight))
+            .map_err(|reason| IpcError::ThreadError { reason })?;
+
+        std::thread::Builder::new()
+            .name("ctx-ipc-client-thread".to_string())
+            .spawn(move || run_writer(tx, recv, in_flight))
+            .map_err(|reason| IpcError::ThreadError { reason })?;
+
+        Ok(Self {
+            requests,
+            timeouts,
+            protocol_version,
+        })
+    }
+
+    pub fn timeouts(&self) -> &IpcRequestTimeouts {
+        &self.timeouts
+    }
+
+    /// Get object by hash id
+    pub fn get_value(&self, hash_id: HashId) -> IpcResponse<Option<ContextValue>> {
+        self.request(
+            ContextRequest::GetValue(hash_id),
+            self.timeouts.get_value,
+            |response| match response {
+                ContextResponse::GetValueResponse(result) => {
+                    result.map_err(|err| ContextError::GetValueError { reason: err }.into())
+                }
+                message => Err(unexpected(message)),
+            },
+        )
+    }
+
+    /// Get objects by hash ids, in a single request
+    pub fn get_many(
+        &self,
+        hash_ids: &[HashId],
+    ) -> IpcResponse<Vec<(HashId, Option<ContextValue>)>> {
+        self.request(
+            ContextRequest::GetMany(hash_ids.to_vec()),
+            self.timeouts.get_many,
+            |response| match response {
+                ContextResponse::GetManyResponse(result) => {
+                    result.map_err(|err| ContextError::GetValueError { reason: err }.into())
+                }
+                message => Err(unexpected(message)),
+            },
+        )
+    }
+
+    /// Check if object with hash id exists
+    pub fn contains_object(&self, hash_id: HashId) -> IpcResponse<bool> {
+        self.request(
+            ContextRequest::ContainsObject(hash_id),
+            self.timeouts.contains_object,
+            |response| match response {
+                ContextResponse::ContainsObjectResponse(result) => {
+                    result.map_err(|err| ContextError::ContainsObjectError { reason: err }.into())
+                }
+                message => Err(unexpected(message)),
+            },
+        )
+    }
+
+    /// Get the hash id of a context hash
+    pub fn get_context_hash_id(&self, context_hash: &ContextHash) -> IpcResponse<Option<HashId>> {
+        self.request(
+            ContextRequest::GetContextHashId(context_hash.clone()),
+            self.timeouts.get_context_hash_id,
+            |response| match response {
+                ContextResponse::GetContextHashIdResponse(result) => {
+                    result.map_err(|err| ContextError::GetContextHashIdError { reason: err }.into())
+                }
+                message => Err(unexpected(message)),
+            },
+        )
+    }
+
+    /// Same as [`Self::get_context_hash_id`], with the number of `HashId`s reused by the
+    /// server so far, see [`IpcContextClient::get_context_hash_id_and_reuses`].
+    pub fn get_context_hash_id_and_reuses(
+        &self,
+        context_hash: &ContextHash,
+    ) -> IpcResponse<(Option<HashId>, Option<u64>)> {
+        if self.protocol_version == Some(CONTEXT_IPC_VERSION_WITHOUT_REUSES) {
+            return self.request(
+                ContextRequest::GetContextHashId(context_hash.clone()),
+                self.timeouts.get_context_hash_id,
+                |response| match response {
+                    ContextResponse::GetContextHashIdResponse(result) => result
+                        .map(|hash_id| (hash_id, None))
+                        .map_err(|err| ContextError::GetContextHashIdError { reason: err }.into()),
+                    message => Err(unexpected(message)),
+                },
+            );
+        }
+
+        self.request(
+            ContextRequest::GetContextHashIdAndReuses(context_hash.clone()),
+            self.timeouts.get_context_hash_id,
+            |response| match response {
+                ContextResponse::GetContextHashIdAndReusesResponse(result) => result
+                    .map(|(hash_id, reuses)| (hash_id, Some(reuses)))
+                    .map_err(|err| ContextError::GetContextHashIdError { reason: err }.into()),
+                message => Err(unexpected(message)),
+            },
+        )
+    }
+
+    /// Get the hash of an object
+    pub fn get_hash(&self, hash_id: HashId) -> IpcResponse<Option<ObjectHash>> {
+        self.request(
+            ContextRequest::GetHash(hash_id),
+            self.timeouts.get_hash,
+            |response| match response {
+                ContextResponse::GetContextHashResponse(result) => {
+                    result.map_err(|err| ContextError::GetContextHashError { reason: err }.into())
+                }
+                message => Err(unexpected(message)),
+            },
+        )
+    }
+
+    /// Get the strings of a directory shape
+    pub fn get_shape(&self, shape_id: DirectoryShapeId) -> IpcResponse<Vec<String>> {
+        self.request(
+            ContextRequest::GetShape(shape_id),
+            self.timeouts.get_shape,
+            |response| match response {
+                ContextResponse::GetShapeResponse(result) => {
+                    result.map_err(|err| ContextError::GetShapeError { reason: err }.into())
+                }
+                message => Err(unexpected(message)),
+            },
+        )
+    }
+
+    /// Get the `StringId`s of a directory shape and the strings interned since `(generation, offset)`,
+    /// with the timeout of `Self::get_shape`
+    pub fn get_shape_ids(
+        &self,
+        shape_id: DirectoryShapeId,
+        generation: u32,
+        offset: usize,
+    ) -> IpcResponse<Option<ShapeIds>> {
+        self.request(
+            ContextRequest::GetShapeIds(shape_id, generation, offset),
+            self.timeouts.get_shape,
+            |response| match response {
+                ContextResponse::GetShapeIdsResponse(result) => {
+                    result.map_err(|err| ContextError::GetShapeError { reason: err }.into())
+                }
+                message => Err(unexpected(message)),
+            },
+        )
+    }
+
+    /// Get the statistics of the tree under `prefix`, computed by the server
+    pub fn get_tree_stats(
+        &self,
+        context_hash: &ContextHash,
+        prefix: &ContextKey,
+    ) -> IpcResponse<Option<TreeStats>> {
+        let prefix = prefix.iter().map(|s| s.to_string()).collect();
+
+        self.request(
+            ContextRequest::GetTreeStats(context_hash.clone(), prefix),
+            self.timeouts.get_tree_stats,
+            |response| match response {
+                ContextResponse::GetTreeStatsResponse(result) => {
+                    result.map_err(|err| ContextError::GetTreeStatsError { reason: err }.into())
+                }
+                message => Err(unexpected(message)),
+            },
+        )
+    }
+
+    /// Get the keys changed under `prefix` between the commits `from` and `to`,
+    /// computed by the server
+    pub fn diff(
+        &self,
+        from: &ContextHash,
+        to: &ContextHash,
+        prefix: &ContextKey,
+    ) -> IpcResponse<Option<Vec<KeyDiff>>> {
+        let prefix = prefix.iter().map(|s| s.to_string()).collect();
+
+        self.request(
+            ContextRequest::Diff(from.clone(), to.clone(), prefix),
+            self.timeouts.diff,
+            |response| match response {
+                ContextResponse::DiffResponse(result) => {
+                    result.map_err(|err| ContextError::DiffError { reason: err }.into())
+                }
+                message => Err(unexpected(message)),
+            },
+        )
+    }
+
+    /// Verify the hashes of the commit `context_hash` and of its tree, computed by the server
+    pub fn verify_tree(
+        &self,
+        context_hash: &ContextHash,
+        mode: VerifyMode,
+    ) -> IpcResponse<Option<TreeVerification>> {
+        self.request(
+            ContextRequest::VerifyTree(context_hash.clone(), mode),
+            self.timeouts.verify_tree,
+            |response| match response {
+                ContextResponse::VerifyTreeResponse(result) => {
+                    result.map_err(|err| ContextError::VerifyTreeError { reason: err }.into())
+                }
+                message => Err(unexpected(message)),
+            },
+        )
+    }
+
+    fn request<T>(
+        &self,
+        request: ContextRequest,
+        timeout: Duration,
+        map: fn(ContextResponse) -> Result<T, ContextServiceError>,
+    ) -> IpcResponse<T> {
+        let oneshot = Arc::new(Oneshot {
+            state: Mutex::new(OneshotState {
+                value: None,
+                waker: None,
+                cancelled: false,
+            }),
+            ready: Condvar::new(),
+        });
+
+        // When the IO threads are gone, the responder is dropped with the request
+        // and the response resolves to `ContextServiceError::Disconnected`
+        let _ = self.requests.send(PendingRequest {
+            request,
+            timeout,
+            responder: Responder {
+                oneshot: Some(Arc::clone(&oneshot)),
+            },
+        });
+
+        IpcResponse { oneshot, map }
+    }
+}
+
+fn unexpected(message: ContextResponse) -> ContextServiceError {
+    ContextServiceError::UnexpectedMessage {
+        message: message.into(),
+    }
+}
+
+/// Loop of the writer thread, until all the clients are dropped.
+fn run_writer(
+    mut tx: IpcSender<ContextRequestFrame>,
+    recv: Receiver<PendingRequest>,
+    in_flight: SharedInFlightRequests,
+) {
+    let mut last_id: u64 = 0;
+
+    loop {
+        match recv.recv_timeout(AsyncIpcContextClient::TIMEOUT_CHECK_INTERVAL) {
+            Ok(PendingRequest {
+                request,
+                timeout,
+                responder,
+            }) => {
+                if responder.is_cancelled() {
+                    continue;
+                }
+
+                last_id += 1;
+                let id = last_id;
+
+                {
+                    let mut in_flight = lock(&in_flight);
+                    if in_flight.disconnected {
+                        responder.send(Err(ContextServiceError::Disconnected));
+                        continue;
+                    }
+                    // Registered before sending, the response may arrive right after
+                    in_flight.requests.insert(
+                        id,
+                        InFlightRequest {
+                            request: (&request).into(),
+                            deadline: Instant::now() + timeout,
+                            responder,
+                        },
+                    );
+                }
+
+                if let Err(error) = tx.send(&ContextRequestFrame { id, request }) {
+                    let mut in_flight = lock(&in_flight);
+                    if let Some(request) = in_flight.requests.remove(&id) {
+                        request.responder.send(Err(error.into()));
+                    }
+                    disconnect(&mut in_flight);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                if tx.heartbeat().is_err() {
+                    disconnect(&mut lock(&in_flight));
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        let now = Instant::now();
+        let mut in_flight = lock(&in_flight);
+        let expired: Vec<u64> = in_flight
+            .requests
+            .iter()
+            .filter(|(_, request)| request.deadline <= now)
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in expired {
+            if let Some(InFlightRequest {
+                request, responder, ..
+            }) = in_flight.requests.remove(&id)
+            {
+                responder.send(Err(ContextServiceError::Timeout { request }));
+            }
+        }
+    }
+}
+
+/// Loop of the reader thread, until the connection is closed.
+fn run_reader(mut rx: IpcReceiver<ContextResponseFrame>, in_flight: SharedInFlightRequests) {
+    loop {
+        match rx.receive() {
+            Ok(ContextResponseFrame { id, response }) => {
+                // Unknown ids are responses to requests that timed out
+                if let Some(request) = lock(&in_flight).requests.remove(&id) {
+                    request.responder.send(Ok(response));
+                }
+            }
+            Err(_) => {
+                disconnect(&mut lock(&in_flight));
+                return;
+            }
+        }
+    }
+}
+
+/// Fails all the requests in flight, and the following ones.
+fn disconnect(in_flight: &mut InFlightRequests) {
+    in_flight.disconnected = true;
+    for (_, request) in in_flight.requests.drain() {
+        request
+            .responder
+            .send(Err(ContextServiceError::Disconnected));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::TryFrom,
+        path::PathBuf,
+        task::Wake,
+        thread::{self, Thread},
+    };
+
+    use super::*;
+    use crate::kv_store::readonly_ipc::{IpcContextListener, IpcServerIO};
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    fn socket_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "tezedge_ctx_async_{}_{}.sock",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn test_async_client_requests() {
+        let path = socket_path("requests");
+        let mut listener = IpcContextListener::try_new(&path).unwrap();
+        thread::spawn(move || {
+            let server = listener.accept().unwrap();
+            let log = slog::Logger::root(slog::Discard, slog::o!());
+            let _ = server.process_context_requests(&log);
+        });
+
+        let client = AsyncIpcContextClient::try_connect(&path, Default::default()).unwrap();
+        let hash_id = HashId::new(1).unwrap();
+
+        // there is no context index in this process
+        let cancelled = client.get_hash(hash_id);
+        drop(cancelled);
+        assert!(matches!(
+            client.get_value(hash_id).wait(),
+            Err(ContextServiceError::ContextError {
+                reason: ContextError::GetValueError { .. }
+            })
+        ));
+        assert!(matches!(
+            block_on(client.get_many(&[hash_id])),
+            Err(ContextServiceError::ContextError { .. })
+        ));
+        let context_hash = ContextHash::try_from(&[0; 32][..]).unwrap();
+        assert!(matches!(
+            client.get_tree_stats(&context_hash, &["a"]).wait(),
+            Err(ContextServiceError::ContextError {
+                reason: ContextError::GetTreeStatsError { .. }
+            })
+        ));
+        assert!(matches!(
+            client.diff(&context_hash, &context_hash, &[]).wait(),
+            Err(ContextServiceError::ContextError {
+                reason: ContextError::DiffError { .. }
+            })
+        ));
+        assert!(matches!(
+            client.get_shape_ids(DirectoryShapeId::from(0), 0, 0).wait(),
+            Err(ContextServiceError::ContextError {
+                reason: ContextError::GetShapeError { .. }
+            })
+        ));
+        assert!(matches!(
+            client.verify_tree(&context_hash, VerifyMode::Irmin).wait(),
+            Err(ContextServiceError::ContextError {
+                reason: ContextError::VerifyTreeError { .. }
+            })
+        ));
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    /// Fake server, replying with the responses given by `reply` in its own order.
+    fn fake_server(
+        name: &str,
+        reply: impl FnOnce(&mut IpcServerIO) + Send + 'static,
+    ) -> (PathBuf, thread::JoinHandle<()>) {
+        let path = socket_path(name);
+        let mut listener = IpcContextListener::try_new(&path).unwrap();
+        let server = thread::spawn(move || {
+            let server = listener.accept().unwrap();
+            reply(&mut server.io.borrow_mut());
+        });
+        (path, server)
+    }
+
+    fn respond(io: &mut IpcServerIO, id: u64, response: ContextResponse) {
+        io.tx
+            .lock()
+            .unwrap()
+            .send(&ContextResponseFrame { id, response })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_async_client_multiplexing() {
+        let (path, server) = fake_server("multiplexing", |io| {
+            let first = io.rx.receive().unwrap();
+            let second = io.rx.receive().unwrap();
+            assert!(matches!(first.request, ContextRequest::GetValue(_)));
+            assert!(matches!(second.request, ContextRequest::ContainsObject(_)));
+
+            // replies in the reverse order
+            respond(
+                io,
+                second.id,
+                ContextResponse::ContainsObjectResponse(Ok(true)),
+            );
+            respond(
+                io,
+                first.id,
+                ContextResponse::GetValueResponse(Ok(Some(vec![1]))),
+            );
+        });
+
+        let client = AsyncIpcContextClient::try_connect(&path, Default::default()).unwrap();
+        let hash_id = HashId::new(1).unwrap();
+
+        let value = client.get_value(hash_id);
+        // clones share the connection
+        let contains = client.clone().contains_object(hash_id);
+
+        assert!(contains.wait().unwrap());
+        assert_eq!(value.wait().unwrap(), Some(vec![1]));
+
+        server.join().unwrap();
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_async_client_timeout() {
+        let (timed_out, timed_out_recv) = crossbeam_channel::bounded(1);
+        let (path, server) = fake_server("timeout", move |io| {
+            let first = io.rx.receive().unwrap();
+
+            // too late, the response is discarded by the client
+            timed_out_recv.recv().unwrap();
+            respond(io, first.id, ContextResponse::GetValueResponse(Ok(None)));
+
+            let second = io.rx.receive().unwrap();
+            respond(
+                io,
+                second.id,
+                ContextResponse::ContainsObjectResponse(Ok(true)),
+            );
+        });
+
+        let timeouts = IpcRequestTimeouts {
+            get_value: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let client = AsyncIpcContextClient::try_connect(&path, timeouts).unwrap();
+        let hash_id = HashId::new(1).unwrap();
+
+        assert!(matches!(
+            client.get_value(hash_id).wait(),
+            Err(ContextServiceError::Timeout {
+                request: "GetValue"
+            })
+        ));
+        timed_out.send(()).unwrap();
+
+        // the connection is still usable
+        assert!(client.contains_object(hash_id).wait().unwrap());
+
+        server.join().unwrap();
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_async_client_disconnected() {
+        let (path, server) = fake_server("disconnected", |io| {
+            io.rx.receive().unwrap();
+        });
+
+        let client = AsyncIpcContextClient::try_connect(&path, Default::default()).unwrap();
+        let hash_id = HashId::new(1).unwrap();
+
+        // the connection is closed without response
+        let value = client.get_value(hash_id);
+        server.join().unwrap();
+        assert!(matches!(
+            value.wait(),
+            Err(ContextServiceError::Disconnected)
+        ));
+        assert!(matches!(
+            client.contains_object(hash_id).wait(),
+            Err(ContextServiceError::Disconnected)
+        ));
+
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -154,8 +154,11 @@ mod persistent;
 
 use crate::persistent::{Flushable, Persistable};
 
+pub mod checkpoint;
 pub mod kv_store;
+pub mod read_transaction;
 pub mod tezedge_context;
+pub mod virtual_fs;
 
 pub type ContextKey<'a> = [&'a str];
 pub type ContextKeyOwned = Vec<String>;
@@ -286,6 +289,8 @@ pub enum ContextError {
     FoundUnexpectedStructure { sought: String, found: String },
     #[error("Mutex/lock error, reason: {reason:?}")]
     LockError { reason: String },
+    #[error("Cannot checkpoint a context without commit")]
+    CheckpointWithoutCommit,
 }
 
 impl From<MerkleError> for ContextError {
@@ -13,12 +13,15 @@ use thiserror::Error;
 use tezos_timing::RepositoryMemoryUsage;
 
 use crate::{
-    kv_store::{readonly_ipc::ContextServiceError, HashId, HashIdError, VacantObjectHash},
+    kv_store::{
+        migration::MigrationError, readonly_ipc::ContextServiceError, HashId, HashIdError,
+        VacantObjectHash,
+    },
     working_tree::{
         serializer::DeserializationError,
         shape::{DirectoryShapeError, DirectoryShapeId, ShapeStrings},
         storage::DirEntryId,
-        string_interner::{StringId, StringInterner},
+        string_interner::{StringId, StringInterner, StringsUpdate},
     },
     ObjectHash,
 };
@@ -87,6 +90,49 @@ pub trait KeyValueStoreBackend {
     fn get_str(&self, string_id: StringId) -> Option<&str>;
     /// Update the `StringInterner`.
     fn synchronize_strings(&mut self, string_interner: &StringInterner) -> Result<(), DBError>;
+    /// Compacts the `StringInterner` of the working tree, see `StringInterner::compact`
+    ///
+    /// The strings referenced by the repository are kept, and the repository is synchronized
+    /// with the compacted `StringInterner`.
+    fn compact_strings(&mut self, string_interner: &mut StringInterner) -> Result<(), DBError>;
+    /// Returns the strings of the repository since the cursor `(generation, offset)`,
+    /// see `StringInterner::update_since`
+    fn get_strings_update(&self, generation: u32, offset: usize) -> Option<StringsUpdate>;
+    /// Returns the `StringInterner` persisted by a previous run
+    ///
+    /// The `StringInterner` of the working tree starts from it.
+    fn interned_strings(&self) -> Option<StringInterner> {
+        None
+    }
+    /// Drop every object not listed in `reachable`, and the context hashes pointing to them
+    ///
+    /// The ids in `tombstones` are still referenced by objects kept: their values are dropped,
+    /// but their hashes are kept and the ids are not reused.
+    ///
+    /// # Arguments
+    /// * `reachable` - HashIds of all the objects to keep
+    /// * `tombstones` - HashIds of the objects dropped, still referenced
+    fn retain(
+        &mut self,
+        reachable: &mut dyn Iterator<Item = HashId>,
+        tombstones: &mut dyn Iterator<Item = HashId>,
+    ) -> Result<(), DBError>;
+    /// Fetch the values of `hash_ids` in advance, so that the next `get_value` are cheap
+    ///
+    /// This is a no-operation for the repositories storing the values locally.
+    ///
+    /// # Arguments
+    /// * `hash_ids` - HashIds of the values to fetch
+    fn prefetch(&self, _hash_ids: &[HashId]) -> Result<(), DBError> {
+        Ok(())
+    }
+    /// Number of times a `HashId` was given to another object
+    ///
+    /// A `HashId` read before this number changed may now refer to another object.
+    /// This is always 0 for the repositories never reusing their ids.
+    fn reused_hash_ids(&self) -> u64 {
+        0
+    }
 }
 
 /// Possible errors for schema
@@ -127,6 +173,13 @@ pub enum DBError {
         #[from]
         error: DirectoryShapeError,
     },
+    #[error("Retain is not supported: {reason}")]
+    RetainNotSupported { reason: &'static str },
+    #[error("Migration error: {error}")]
+    MigrationError {
+        #[from]
+        error: MigrationError,
+    },
 }
 
 impl From<HashIdError> for DBError {
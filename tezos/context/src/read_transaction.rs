@@ -0,0 +1,328 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Read transactions: a consistent view of one commit across several queries.
+//!
+//! Queries made directly on a [`TezedgeIndex`] share its working storage, which is
+//! cleared by every checkout, so a sequence of reads may observe objects of another commit
+//! checked out in between. A [`ReadTransaction`] instead:
+//! - owns a private working storage, so other checkouts don't disturb it,
+//! - pins its commit in the index, starting a new garbage collection cycle and compacting
+//!   the interned strings are postponed until every transaction is released,
+//! - is released automatically when dropped.
+//!
+//! ```no_compile
+//! let tx = index.begin_read(&context_hash)?.unwrap();
+//! let protocol = tx.find(&context_key!("protocol"))?;
+//! let listings = tx.get_key_values_by_prefix(&context_key!("data/votes/listings"))?;
+//! ```
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::Mutex};
+
+use crypto::hash::ContextHash;
+
+use crate::{
+    kv_store::HashId,
+    working_tree::{storage::Storage, working_tree::WorkingTree, Object},
+    ContextError, ContextKey, ContextKeyOwned, ContextValue, ProtocolContextApi, StringTreeObject,
+    TezedgeContext, TezedgeIndex,
+};
+
+#[derive(Debug, Default)]
+struct PinnedCommitsInner {
+    pins: HashMap<HashId, usize>,
+    cycle_pending: bool,
+}
+
+/// Commits pinned by the open [`ReadTransaction`]s of an index.
+#[derive(Debug, Default)]
+pub struct PinnedCommits {
+    inner: Mutex<PinnedCommitsInner>,
+}
+
+impl PinnedCommits {
+    /// Returns `true` if at least one read transaction is open on `hash_id`.
+    pub fn is_pinned(&self, hash_id: HashId) -> bool {
+        self.lock().pins.contains_key(&hash_id)
+    }
+
+    /// Returns the number of open read transactions.
+    pub fn count(&self) -> usize {
+        self.lock().pins.values().sum()
+    }
+
+    /// Returns the commits pinned by at least one read transaction.
+    pub(crate) fn pinned(&self) -> Vec<HashId> {
+        self.lock().pins.keys().copied().collect()
+    }
+
+    /// Returns `true` if no read transaction is open, otherwise remembers that
+    /// a new garbage collection cycle has to be started once they are all released.
+    pub(crate) fn try_start_cycle(&self) -> bool {
+        let mut inner = self.lock();
+
+        if inner.pins.is_empty() {
+            true
+        } else {
+            inner.cycle_pending = true;
+            false
+        }
+    }
+
+    pub(crate) fn pin(&self, hash_id: HashId) {
+        *self.lock().pins.entry(hash_id).or_insert(0) += 1;
+    }
+
+    /// Returns `true` if this was the last pin and a new cycle was postponed meanwhile.
+    fn unpin(&self, hash_id: HashId) -> bool {
+        let mut inner = self.lock();
+
+        if let Some(count) = inner.pins.get_mut(&hash_id) {
+            *count -= 1;
+            if *count == 0 {
+                inner.pins.remove(&hash_id);
+            }
+        }
+
+        if inner.pins.is_empty() {
+            std::mem::take(&mut inner.cycle_pending)
+        } else {
+            false
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, PinnedCommitsInner> {
+        // The data stays consistent even if a holder panicked
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Consistent read-only view of a commit, see the [module documentation](self).
+pub struct ReadTransaction {
+    context_hash: ContextHash,
+    hash_id: HashId,
+    context: TezedgeContext,
+}
+
+impl ReadTransaction {
+    /// Opens a read transaction on `context_hash`.
+    ///
+    /// Returns `None` if the commit doesn't exist.
+    pub fn begin(
+        index: &TezedgeIndex,
+        context_hash: &ContextHash,
+    ) -> Result<Option<Self>, ContextError> {
+        let hash_id = match index.repository.read()?.get_context_hash(context_hash)? {
+            Some(hash_id) => hash_id,
+            None => return Ok(None),
+        };
+
+        // Private working storage, shared by nothing but this transaction.
+        // The copy of the interner keeps the ids used by the repository: the index doesn't
+        // compact its strings while a transaction is open, and the checkout below doesn't
+        // compact the copy either.
+        let mut storage = Storage::default();
+        storage.strings = index.storage.borrow().strings.clone();
+        let index = TezedgeIndex {
+            storage: Rc::new(RefCell::new(storage)),
+            ..index.clone()
+        };
+
+        let context = match index.checkout_commit(hash_id)? {
+            Some(context) => context,
+            None => return Ok(None),
+        };
+
+        index.pinned_commits.pin(hash_id);
+
+        Ok(Some(Self {
+            context_hash: context_hash.clone(),
+            hash_id,
+            context,
+        }))
+    }
+
+    pub fn context_hash(&self) -> &ContextHash {
+        &self.context_hash
+    }
+
+    /// The checked out context, to run queries not exposed by the transaction itself.
+    pub fn context(&self) -> &TezedgeContext {
+        &self.context
+    }
+
+    pub fn find(&self, key: &ContextKey) -> Result<Option<ContextValue>, ContextError> {
+        self.context.find(key)
+    }
+
+    pub fn mem(&self, key: &ContextKey) -> Result<bool, ContextError> {
+        self.context.mem(key)
+    }
+
+    pub fn find_tree(&self, key: &ContextKey) -> Result<Option<WorkingTree>, ContextError> {
+        self.context.find_tree(key)
+    }
+
+    pub fn list(
+        &self,
+        offset: Option<usize>,
+        length: Option<usize>,
+        key: &ContextKey,
+    ) -> Result<Vec<(String, WorkingTree)>, ContextError> {
+        self.context.list(offset, length, key)
+    }
+
+    /// Same as [`IndexApi::get_key_values_by_prefix`], on the commit of this transaction.
+    pub fn get_key_values_by_prefix(
+        &self,
+        prefix: &ContextKey,
+    ) -> Result<Option<Vec<(ContextKeyOwned, ContextValue)>>, ContextError> {
+        self.context
+            .index
+            .get_context_key_values_by_prefix(self.hash_id, prefix)
+            .map_err(Into::into)
+    }
+
+    /// Same as [`IndexApi::get_context_tree_by_prefix`], on the commit of this transaction.
+    pub fn get_context_tree_by_prefix(
+        &self,
+        prefix: &ContextKey,
+        depth: Option<usize>,
+    ) -> Result<StringTreeObject, ContextError> {
+        let index = &self.context.index;
+        let mut storage = index.storage.borrow_mut();
+
+        index
+            ._get_context_tree_by_prefix(self.hash_id, prefix, depth, &mut storage)
+            .map_err(Into::into)
+    }
+
+    /// Returns `true` if the commit of this transaction is still a commit in the repository.
+    pub fn is_valid(&self) -> Result<bool, ContextError> {
+        let index = &self.context.index;
+        let mut storage = index.storage.borrow_mut();
+
+        Ok(matches!(
+            index.fetch_object(self.hash_id, &mut storage)?,
+            Some(Object::Commit(_))
+        ))
+    }
+}
+
+impl Drop for ReadTransaction {
+    fn drop(&mut self) {
+        release_pin(&self.context.index, self.hash_id);
+    }
+}
+
+/// Unpins `hash_id`, and starts the postponed GC cycle if it was the last pin.
+pub(crate) fn release_pin(index: &TezedgeIndex, hash_id: HashId) {
+    if index.pinned_commits.unpin(hash_id) {
+        match index.repository.write() {
+            Ok(mut repository) => {
+                if let Err(e) = repository.new_cycle_started() {
+                    eprintln!("Failed to start postponed GC cycle: {:?}", e);
+                }
+            }
+            Err(e) => eprintln!("Failed to start postponed GC cycle: {:?}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tezos_api::ffi::{ContextKvStoreConfiguration, TezosContextTezEdgeStorageConfiguration};
+
+    use std::convert::TryFrom;
+
+    use super::*;
+    use crate::{initializer::initialize_tezedge_context, IndexApi, ShellContextApi};
+
+    #[test]
+    fn test_read_transaction() {
+        let context = initialize_tezedge_context(&TezosContextTezEdgeStorageConfiguration {
+            backend: ContextKvStoreConfiguration::InMem,
+            ipc_socket_path: None,
+        })
+        .unwrap();
+
+        let context = context.add(&["a", "b"], &[1]).unwrap();
+        let first = context
+            .commit("Tezos".to_string(), "".to_string(), 0)
+            .unwrap();
+        let context = context.add(&["a", "b"], &[2]).unwrap();
+        let context = context.add(&["a", "c"], &[3]).unwrap();
+        let second = context
+            .commit("Tezos".to_string(), "".to_string(), 0)
+            .unwrap();
+
+        let mut index = context.index.clone();
+        let tx = index.begin_read(&first).unwrap().unwrap();
+        assert_eq!(tx.context_hash(), &first);
+        assert_eq!(index.pinned_commits.count(), 1);
+
+        // other checkouts on the same index don't affect the transaction
+        let other = index.checkout(&second).unwrap().unwrap();
+        assert_eq!(other.find(&["a", "b"]).unwrap(), Some(vec![2]));
+
+        assert_eq!(tx.find(&["a", "b"]).unwrap(), Some(vec![1]));
+        assert!(!tx.mem(&["a", "c"]).unwrap());
+        assert_eq!(
+            tx.get_key_values_by_prefix(&["a"]).unwrap(),
+            Some(vec![(vec!["a".to_string(), "b".to_string()], vec![1])])
+        );
+        assert!(tx.is_valid().unwrap());
+
+        // starting a cycle is postponed while the transaction is open
+        assert!(!index.pinned_commits.try_start_cycle());
+        index.cycle_started().unwrap();
+
+        let tx2 = ReadTransaction::begin(&index, &first).unwrap().unwrap();
+        assert_eq!(index.pinned_commits.count(), 2);
+        drop(tx);
+        assert!(index.pinned_commits.is_pinned(tx2.hash_id));
+        drop(tx2);
+        assert_eq!(index.pinned_commits.count(), 0);
+        assert!(index.pinned_commits.try_start_cycle());
+
+        let unknown = ContextHash::try_from(&[0; 32][..]).unwrap();
+        assert!(index.begin_read(&unknown).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_no_compaction_while_open() {
+        let context = initialize_tezedge_context(&TezosContextTezEdgeStorageConfiguration {
+            backend: ContextKvStoreConfiguration::InMem,
+            ipc_socket_path: None,
+        })
+        .unwrap();
+
+        let context = context.add(&["a", "b"], &[1]).unwrap();
+        let commit = context
+            .commit("Tezos".to_string(), "".to_string(), 0)
+            .unwrap();
+        let index = context.index.clone();
+
+        let tx = index.begin_read(&commit).unwrap().unwrap();
+        let generation = index.storage.borrow().strings.generation();
+
+        // enough strings to trigger a compaction at the next checkout
+        {
+            let mut storage = index.storage.borrow_mut();
+            let mut i = 0;
+            while !storage.strings.should_compact() {
+                storage.strings.get_string_id(&format!("{:029}", i));
+                i += 1;
+            }
+        }
+
+        index.checkout(&commit).unwrap().unwrap();
+        assert_eq!(index.storage.borrow().strings.generation(), generation);
+        assert_eq!(tx.find(&["a", "b"]).unwrap(), Some(vec![1]));
+
+        drop(tx);
+        let context = index.checkout(&commit).unwrap().unwrap();
+        assert_ne!(index.storage.borrow().strings.generation(), generation);
+        assert_eq!(context.find(&["a", "b"]).unwrap(), Some(vec![1]));
+    }
+}
@@ -5,22 +5,31 @@
 
 use std::{
     cell::RefCell,
+    collections::{BTreeMap, HashSet},
     convert::TryInto,
-    sync::{Arc, RwLock},
+    sync::{Arc, Mutex, RwLock},
 };
 use std::{convert::TryFrom, rc::Rc};
 
 use crypto::hash::ContextHash;
 use ocaml_interop::BoxRoot;
+use serde::{Deserialize, Serialize};
 use tezos_timing::{BlockMemoryUsage, ContextMemoryUsage};
 
 use crate::{
+    checkpoint::ContextOverlay,
+    gc::{
+        rolling::{collect_reachable, RollingGc},
+        watermark::{MemoryPressure, MemoryWatermarks},
+    },
     hash::ObjectHash,
+    hash::{commit_hash, hash_inlined_blob, recompute_directory_hash},
     kv_store::HashId,
     persistent::DBError,
+    read_transaction::{PinnedCommits, ReadTransaction},
     timings::send_statistics,
     working_tree::{
-        serializer::deserialize_object,
+        serializer::{deserialize_object, directory_shape_id, iter_hash_ids},
         storage::{BlobId, DirEntryId, DirectoryId, Storage},
         working_tree::{MerkleError, PostCommitData},
         Commit, Object,
@@ -37,6 +46,79 @@ use crate::{
     TreeId,
 };
 
+/// Statistics of a subtree of the context, see [`TezedgeIndex::get_tree_stats`].
+///
+/// Objects shared by several paths are counted once per path.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeStats {
+    /// Number of directories, including the root of the subtree
+    pub directories: usize,
+    pub blobs: usize,
+    /// Total size of the blobs, in bytes
+    pub total_bytes: usize,
+    /// Depth of the deepest object, the root of the subtree is at depth 0
+    pub max_depth: usize,
+    /// Number of directories serialized with each shape, by shape id
+    pub shapes: BTreeMap<u32, usize>,
+}
+
+/// Change of a key between two commits, see [`TezedgeIndex::diff`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyChange {
+    Added { size: usize },
+    Removed { size: usize },
+    Modified { old_size: usize, new_size: usize },
+}
+
+/// Key changed between two commits, with the size of its values.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyDiff {
+    /// Full key, including the prefix of the diff
+    pub key: ContextKeyOwned,
+    pub change: KeyChange,
+}
+
+/// How the hashes of the directories are recomputed by [`TezedgeIndex::verify_tree`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VerifyMode {
+    /// From the inodes of the directories, as they are stored in the repository
+    Stored,
+    /// From the entries of the directories only: their inodes are rebuilt the way Irmin
+    /// builds them, so the hashes don't depend on how the directories were stored
+    Irmin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VerifiedObjectKind {
+    Commit,
+    Directory,
+    Blob,
+}
+
+/// Object whose recomputed hash differs from the hash stored in the repository.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HashMismatch {
+    pub kind: VerifiedObjectKind,
+    /// Key of the object, empty for the commit and the root directory
+    pub key: ContextKeyOwned,
+    pub stored: ObjectHash,
+    pub computed: ObjectHash,
+}
+
+/// Result of [`TezedgeIndex::verify_tree`].
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeVerification {
+    /// Number of objects verified, objects shared by several paths are verified once
+    pub objects: usize,
+    pub mismatches: Vec<HashMismatch>,
+}
+
+impl TreeVerification {
+    pub fn is_valid(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
 // Represents the patch_context function passed from the OCaml side
 // It is opaque to rust, we don't care about it's actual type
 // because it is not used on Rust, but we need a type to represent it.
@@ -56,6 +138,12 @@ pub struct TezedgeIndex {
     /// This is where all directories/blobs/strings are allocated.
     /// The `WorkingTree` only has access to ids which refer to data inside `storage`.
     pub storage: Rc<RefCell<Storage>>,
+    /// Commits pinned by the open read transactions.
+    pub pinned_commits: Arc<PinnedCommits>,
+    /// Last commits, when only their trees are kept (rolling mode).
+    pub rolling_gc: Option<Arc<Mutex<RollingGc>>>,
+    /// Memory limits triggering the collections in rolling mode.
+    pub memory_watermarks: Option<MemoryWatermarks>,
 }
 
 // TODO: some of the utility methods here (and in `WorkingTree`) should probably be
@@ -68,11 +156,602 @@ impl TezedgeIndex {
         patch_context: Option<BoxRoot<PatchContextFunction>>,
     ) -> Self {
         let patch_context = Rc::new(patch_context);
+
+        let mut storage = Storage::default();
+        if let Some(strings) = repository
+            .read()
+            .ok()
+            .and_then(|repository| repository.interned_strings())
+        {
+            storage.strings = strings;
+        }
+
         Self {
             patch_context,
             repository,
-            storage: Default::default(),
+            storage: Rc::new(RefCell::new(storage)),
+            pinned_commits: Default::default(),
+            rolling_gc: None,
+            memory_watermarks: None,
+        }
+    }
+
+    /// Enables the rolling mode: only the trees of the last `keep` commits are kept.
+    ///
+    /// See [`crate::gc::rolling`].
+    pub fn with_rolling_gc(mut self, keep: usize) -> Self {
+        self.rolling_gc = Some(Arc::new(Mutex::new(RollingGc::new(keep))));
+        self
+    }
+
+    /// Triggers the collections of the rolling mode on the memory used by the process.
+    ///
+    /// See [`crate::gc::watermark`].
+    pub fn with_memory_watermarks(mut self, watermarks: MemoryWatermarks) -> Self {
+        self.memory_watermarks = Some(watermarks);
+        self
+    }
+
+    /// Drops from the repository every object not reachable from the commits `commits`,
+    /// or from a commit pinned by a read transaction.
+    ///
+    /// Returns the number of objects kept.
+    pub fn retain_commits(&self, commits: &[HashId]) -> Result<usize, DBError> {
+        let mut roots = commits.to_vec();
+        roots.extend(self.pinned_commits.pinned());
+
+        let mut repository = self.repository.write()?;
+        let reachable = collect_reachable(&*repository, &roots)?;
+        let nreachable = reachable.objects.len();
+
+        repository.retain(
+            &mut reachable.objects.into_iter(),
+            &mut reachable.tombstones.into_iter(),
+        )?;
+
+        Ok(nreachable)
+    }
+
+    /// Fetches in advance the objects of the tree at `prefix`, in the commit `context_hash`.
+    ///
+    /// This is meant for repositories accessing their objects remotely (the readonly
+    /// IPC backend): the objects of each level of the tree are requested at once,
+    /// instead of one request per object.
+    /// Returns the number of objects of the tree.
+    pub fn prefetch_subtree(
+        &self,
+        context_hash: &ContextHash,
+        prefix: &ContextKey,
+    ) -> Result<usize, ContextError> {
+        let hash_id = match self.fetch_context_hash_id(context_hash)? {
+            Some(hash_id) => hash_id,
+            None => return Ok(0),
+        };
+
+        // Private storage, the working tree of the index is left untouched
+        let mut storage = Storage::default();
+        storage.strings = self.storage.borrow().strings.clone();
+
+        let commit = self.get_commit(hash_id, &mut storage)?;
+        let subtree_hash_id = if prefix.is_empty() {
+            commit.root_hash
+        } else {
+            let root = self.get_directory(commit.root_hash, &mut storage)?;
+            let dir_entry_id = match self.find_dir_entry(root, prefix, &mut storage)? {
+                Some(dir_entry_id) => dir_entry_id,
+                None => return Ok(0),
+            };
+            let dir_entry = storage
+                .get_dir_entry(dir_entry_id)
+                .map_err(MerkleError::from)?;
+            match dir_entry.hash_id() {
+                Some(hash_id) => hash_id,
+                None => return Ok(1), // Inlined blob
+            }
+        };
+
+        let repository = self.repository.read()?;
+        let mut visited = HashSet::new();
+        let mut level = vec![subtree_hash_id];
+
+        while !level.is_empty() {
+            repository.prefetch(&level)?;
+
+            let mut next_level = Vec::new();
+            for hash_id in level {
+                if !visited.insert(hash_id) {
+                    continue;
+                }
+                if let Some(value) = repository.get_value(hash_id)? {
+                    next_level.extend(iter_hash_ids(&value).filter(|id| !visited.contains(id)));
+                }
+            }
+            level = next_level;
+        }
+
+        Ok(visited.len())
+    }
+
+    /// Returns the statistics of the tree under `prefix`, in the commit `context_hash`.
+    ///
+    /// Returns `None` if the commit or the prefix doesn't exist.
+    /// The whole subtree is loaded in a private storage, the working tree of the index
+    /// is left untouched.
+    pub fn get_tree_stats(
+        &self,
+        context_hash: &ContextHash,
+        prefix: &ContextKey,
+    ) -> Result<Option<TreeStats>, ContextError> {
+        let hash_id = match self.fetch_context_hash_id(context_hash)? {
+            Some(hash_id) => hash_id,
+            None => return Ok(None),
+        };
+
+        let mut storage = Storage::default();
+        storage.strings = self.storage.borrow().strings.clone();
+
+        let subtree = match self.find_subtree(hash_id, prefix, &mut storage)? {
+            Some(subtree) => subtree,
+            None => return Ok(None),
+        };
+
+        let mut stats = TreeStats::default();
+        let mut stack = vec![(subtree, 0)];
+
+        while let Some(((object, hash_id), depth)) = stack.pop() {
+            stats.max_depth = stats.max_depth.max(depth);
+
+            match object {
+                Object::Blob(blob_id) => {
+                    let blob = storage.get_blob(blob_id).map_err(MerkleError::from)?;
+                    stats.blobs += 1;
+                    stats.total_bytes += blob.len();
+                }
+                Object::Directory(dir_id) => {
+                    stats.directories += 1;
+
+                    if let Some(hash_id) = hash_id {
+                        // The shape is not kept once deserialized, look at the raw object
+                        let shape_id = self
+                            .repository
+                            .read()?
+                            .get_value(hash_id)?
+                            .and_then(|value| directory_shape_id(&value));
+
+                        if let Some(shape_id) = shape_id {
+                            *stats.shapes.entry(shape_id.as_u32()).or_insert(0) += 1;
+                        }
+                    }
+
+                    for (_, dir_entry_id) in storage.dir_to_vec_unsorted(dir_id)? {
+                        let hash_id = storage
+                            .get_dir_entry(dir_entry_id)
+                            .map_err(MerkleError::from)?
+                            .hash_id();
+                        let object = self.dir_entry_object(dir_entry_id, &mut storage)?;
+                        stack.push(((object, hash_id), depth + 1));
+                    }
+                }
+                Object::Commit(_) => {
+                    return Err(MerkleError::FoundUnexpectedStructure {
+                        sought: "Directory/Blob".to_string(),
+                        found: "Commit".to_string(),
+                    }
+                    .into())
+                }
+            }
+        }
+
+        Ok(Some(stats))
+    }
+
+    /// Returns the keys added, removed or modified under `prefix` between the commits
+    /// `from` and `to`, sorted by key.
+    ///
+    /// Subtrees with the same hash in both commits are skipped without being loaded.
+    /// Returns `None` if one of the commits doesn't exist, a prefix missing from a commit
+    /// is considered empty.
+    pub fn diff(
+        &self,
+        from: &ContextHash,
+        to: &ContextHash,
+        prefix: &ContextKey,
+    ) -> Result<Option<Vec<KeyDiff>>, ContextError> {
+        let (from, to) = match (
+            self.fetch_context_hash_id(from)?,
+            self.fetch_context_hash_id(to)?,
+        ) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return Ok(None),
+        };
+
+        let mut storage = Storage::default();
+        storage.strings = self.storage.borrow().strings.clone();
+
+        let from = self.find_subtree(from, prefix, &mut storage)?;
+        let to = self.find_subtree(to, prefix, &mut storage)?;
+
+        if let (Some(from), Some(to)) = (&from, &to) {
+            if self.same_hash(from.1, to.1)? {
+                return Ok(Some(Vec::new()));
+            }
+        }
+
+        let mut diffs = Vec::new();
+        let prefix: ContextKeyOwned = prefix.iter().map(|s| s.to_string()).collect();
+        let mut stack = vec![(prefix, from, to)];
+
+        while let Some((key, from, to)) = stack.pop() {
+            let (from, to) = match (from, to) {
+                (None, None) => continue,
+                (Some(from), None) => {
+                    for (key, size) in self.collect_blobs(key, from, &mut storage)? {
+                        diffs.push(KeyDiff {
+                            key,
+                            change: KeyChange::Removed { size },
+                        });
+                    }
+                    continue;
+                }
+                (None, Some(to)) => {
+                    for (key, size) in self.collect_blobs(key, to, &mut storage)? {
+                        diffs.push(KeyDiff {
+                            key,
+                            change: KeyChange::Added { size },
+                        });
+                    }
+                    continue;
+                }
+                (Some(from), Some(to)) => (from, to),
+            };
+
+            match (&from.0, &to.0) {
+                (Object::Blob(from_blob), Object::Blob(to_blob)) => {
+                    let from_blob = storage.get_blob(*from_blob).map_err(MerkleError::from)?;
+                    let to_blob = storage.get_blob(*to_blob).map_err(MerkleError::from)?;
+
+                    if from_blob.as_ref() != to_blob.as_ref() {
+                        diffs.push(KeyDiff {
+                            key,
+                            change: KeyChange::Modified {
+                                old_size: from_blob.len(),
+                                new_size: to_blob.len(),
+                            },
+                        });
+                    }
+                }
+                (Object::Directory(from_dir), Object::Directory(to_dir)) => {
+                    let from_entries = storage.dir_to_vec_sorted(*from_dir)?;
+                    let to_entries = storage.dir_to_vec_sorted(*to_dir)?;
+                    let mut from_entries = from_entries.into_iter().peekable();
+                    let mut to_entries = to_entries.into_iter().peekable();
+
+                    loop {
+                        let ordering = match (from_entries.peek(), to_entries.peek()) {
+                            (None, None) => break,
+                            (Some(_), None) => std::cmp::Ordering::Less,
+                            (None, Some(_)) => std::cmp::Ordering::Greater,
+                            (Some((from_name, _)), Some((to_name, _))) => storage
+                                .get_str(*from_name)
+                                .map_err(MerkleError::from)?
+                                .cmp(storage.get_str(*to_name).map_err(MerkleError::from)?),
+                        };
+
+                        let (name, from_entry, to_entry) = match ordering {
+                            std::cmp::Ordering::Less => {
+                                let (name, entry) = from_entries.next().unwrap();
+                                (name, Some(entry), None)
+                            }
+                            std::cmp::Ordering::Greater => {
+                                let (name, entry) = to_entries.next().unwrap();
+                                (name, None, Some(entry))
+                            }
+                            std::cmp::Ordering::Equal => {
+                                let (name, from_entry) = from_entries.next().unwrap();
+                                let (_, to_entry) = to_entries.next().unwrap();
+                                (name, Some(from_entry), Some(to_entry))
+                            }
+                        };
+
+                        // Unchanged subtrees are skipped before their objects are loaded
+                        if let (Some(from_entry), Some(to_entry)) = (from_entry, to_entry) {
+                            if self.same_hash(
+                                self.dir_entry_hash_id(from_entry, &storage)?,
+                                self.dir_entry_hash_id(to_entry, &storage)?,
+                            )? {
+                                continue;
+                            }
+                        }
+
+                        let mut key = key.clone();
+                        key.push(
+                            storage
+                                .get_str(name)
+                                .map_err(MerkleError::from)?
+                                .to_string(),
+                        );
+
+                        let from = from_entry
+                            .map(|entry| self.subtree_object(entry, &mut storage))
+                            .transpose()?;
+                        let to = to_entry
+                            .map(|entry| self.subtree_object(entry, &mut storage))
+                            .transpose()?;
+                        stack.push((key, from, to));
+                    }
+                }
+                _ => {
+                    // A blob replaced by a directory, or the opposite
+                    stack.push((key.clone(), Some(from), None));
+                    stack.push((key, None, Some(to)));
+                }
+            }
+        }
+
+        diffs.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(Some(diffs))
+    }
+
+    /// Returns the object at `prefix` in the commit `hash_id`, with its `HashId`
+    /// (`None` for inlined blobs).
+    fn find_subtree(
+        &self,
+        hash_id: HashId,
+        prefix: &ContextKey,
+        storage: &mut Storage,
+    ) -> Result<Option<(Object, Option<HashId>)>, ContextError> {
+        let commit = self.get_commit(hash_id, storage)?;
+        let root = self.get_directory(commit.root_hash, storage)?;
+
+        if prefix.is_empty() {
+            return Ok(Some((Object::Directory(root), Some(commit.root_hash))));
+        }
+
+        match self.find_dir_entry(root, prefix, storage)? {
+            Some(dir_entry_id) => Ok(Some(self.subtree_object(dir_entry_id, storage)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn subtree_object(
+        &self,
+        dir_entry_id: DirEntryId,
+        storage: &mut Storage,
+    ) -> Result<(Object, Option<HashId>), ContextError> {
+        let hash_id = self.dir_entry_hash_id(dir_entry_id, storage)?;
+        Ok((self.dir_entry_object(dir_entry_id, storage)?, hash_id))
+    }
+
+    /// Returns the `HashId` of the dir entry, without loading its object.
+    fn dir_entry_hash_id(
+        &self,
+        dir_entry_id: DirEntryId,
+        storage: &Storage,
+    ) -> Result<Option<HashId>, ContextError> {
+        Ok(storage
+            .get_dir_entry(dir_entry_id)
+            .map_err(MerkleError::from)?
+            .hash_id())
+    }
+
+    /// Returns `true` when both objects are stored and have the same hash.
+    fn same_hash(&self, a: Option<HashId>, b: Option<HashId>) -> Result<bool, ContextError> {
+        match (a, b) {
+            (Some(a), Some(b)) if a == b => Ok(true),
+            (Some(a), Some(b)) => {
+                let hash = self.fetch_hash(a)?;
+                Ok(hash.is_some() && hash == self.fetch_hash(b)?)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Returns the keys and sizes of the blobs of `subtree`, located at `key`.
+    fn collect_blobs(
+        &self,
+        key: ContextKeyOwned,
+        subtree: (Object, Option<HashId>),
+        storage: &mut Storage,
+    ) -> Result<Vec<(ContextKeyOwned, usize)>, ContextError> {
+        let mut blobs = Vec::new();
+        let mut stack = vec![(key, subtree.0)];
+
+        while let Some((key, object)) = stack.pop() {
+            match object {
+                Object::Blob(blob_id) => {
+                    let blob = storage.get_blob(blob_id).map_err(MerkleError::from)?;
+                    blobs.push((key, blob.len()));
+                }
+                Object::Directory(dir_id) => {
+                    for (name, dir_entry_id) in storage.dir_to_vec_unsorted(dir_id)? {
+                        let mut key = key.clone();
+                        key.push(
+                            storage
+                                .get_str(name)
+                                .map_err(MerkleError::from)?
+                                .to_string(),
+                        );
+                        stack.push((key, self.dir_entry_object(dir_entry_id, storage)?));
+                    }
+                }
+                Object::Commit(_) => {
+                    return Err(MerkleError::FoundUnexpectedStructure {
+                        sought: "Directory/Blob".to_string(),
+                        found: "Commit".to_string(),
+                    }
+                    .into())
+                }
+            }
+        }
+
+        Ok(blobs)
+    }
+
+    /// Recomputes the hashes of the commit `context_hash` and of all the objects of its tree,
+    /// and compares them with the hashes stored in the repository.
+    ///
+    /// The hash of each object is computed from the hashes stored for its children, so
+    /// a tree without mismatch proves that `context_hash` is the Irmin hash of its content.
+    /// Inlined blobs have no stored hash, they are verified with their directory.
+    /// Returns `None` if the commit doesn't exist.
+    pub fn verify_tree(
+        &self,
+        context_hash: &ContextHash,
+        mode: VerifyMode,
+    ) -> Result<Option<TreeVerification>, ContextError> {
+        let hash_id = match self.fetch_context_hash_id(context_hash)? {
+            Some(hash_id) => hash_id,
+            None => return Ok(None),
+        };
+
+        let mut storage = Storage::default();
+        storage.strings = self.storage.borrow().strings.clone();
+
+        let commit = self.get_commit(hash_id, &mut storage)?;
+        let computed =
+            commit_hash(&commit, &*self.repository.read()?).map_err(MerkleError::from)?;
+
+        let mut verification = TreeVerification::default();
+        self.verify_hash(
+            &mut verification,
+            VerifiedObjectKind::Commit,
+            Vec::new(),
+            hash_id,
+            computed,
+        )?;
+
+        let root = self.get_directory(commit.root_hash, &mut storage)?;
+        let mut visited = HashSet::new();
+        let mut stack = vec![(Vec::new(), Object::Directory(root), commit.root_hash)];
+
+        while let Some((key, object, hash_id)) = stack.pop() {
+            if !visited.insert(hash_id) {
+                continue;
+            }
+
+            let (kind, computed) = match object {
+                Object::Blob(blob_id) => {
+                    let blob = storage.get_blob(blob_id).map_err(MerkleError::from)?;
+                    let computed = hash_inlined_blob(blob).map_err(MerkleError::from)?;
+                    (VerifiedObjectKind::Blob, computed)
+                }
+                Object::Directory(dir_id) => {
+                    for (name, dir_entry_id) in storage.dir_to_vec_unsorted(dir_id)? {
+                        let dir_entry = storage
+                            .get_dir_entry(dir_entry_id)
+                            .map_err(MerkleError::from)?;
+
+                        if let Some(hash_id) = dir_entry.hash_id() {
+                            let mut key = key.clone();
+                            key.push(
+                                storage
+                                    .get_str(name)
+                                    .map_err(MerkleError::from)?
+                                    .to_string(),
+                            );
+                            let object = self.dir_entry_object(dir_entry_id, &mut storage)?;
+                            stack.push((key, object, hash_id));
+                        }
+                    }
+
+                    let dir_id = match mode {
+                        VerifyMode::Stored => dir_id,
+                        VerifyMode::Irmin => rebuild_directory(dir_id, &mut storage)?,
+                    };
+                    let computed =
+                        recompute_directory_hash(dir_id, &*self.repository.read()?, &storage)
+                            .map_err(MerkleError::from)?;
+                    (VerifiedObjectKind::Directory, computed)
+                }
+                Object::Commit(_) => {
+                    return Err(MerkleError::FoundUnexpectedStructure {
+                        sought: "Directory/Blob".to_string(),
+                        found: "Commit".to_string(),
+                    }
+                    .into())
+                }
+            };
+
+            self.verify_hash(&mut verification, kind, key, hash_id, computed)?;
         }
+
+        Ok(Some(verification))
+    }
+
+    fn verify_hash(
+        &self,
+        verification: &mut TreeVerification,
+        kind: VerifiedObjectKind,
+        key: ContextKeyOwned,
+        hash_id: HashId,
+        computed: ObjectHash,
+    ) -> Result<(), ContextError> {
+        let stored = self
+            .fetch_hash(hash_id)?
+            .ok_or(MerkleError::ObjectNotFound { hash_id })?;
+
+        verification.objects += 1;
+        if stored != computed {
+            verification.mismatches.push(HashMismatch {
+                kind,
+                key,
+                stored,
+                computed,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Records the commit in rolling mode, and drops the old trees when it's due.
+    ///
+    /// The collection is due earlier when the memory watermarks are exceeded.
+    fn rolling_commit_applied(&self, commit_hash_id: HashId) -> Result<(), ContextError> {
+        let rolling_gc = match self.rolling_gc.as_ref() {
+            Some(rolling_gc) => rolling_gc,
+            None => return Ok(()),
+        };
+
+        let pressure = self
+            .memory_watermarks
+            .map(|watermarks| watermarks.current_pressure())
+            .unwrap_or(MemoryPressure::Normal);
+
+        let roots = {
+            let mut rolling_gc = rolling_gc.lock()?;
+            if !rolling_gc.commit_applied_under(commit_hash_id, pressure) {
+                return Ok(());
+            }
+            rolling_gc.take_roots_under(pressure)
+        };
+
+        self.retain_commits(&roots)?;
+        Ok(())
+    }
+
+    /// Opens a read transaction on `context_hash`, see [`ReadTransaction`].
+    ///
+    /// Returns `None` if the commit doesn't exist.
+    pub fn begin_read(
+        &self,
+        context_hash: &ContextHash,
+    ) -> Result<Option<ReadTransaction>, ContextError> {
+        ReadTransaction::begin(self, context_hash)
+    }
+
+    /// Forks the commit `context_hash` into a speculative overlay, see [`ContextOverlay`].
+    ///
+    /// Returns `None` if the commit doesn't exist.
+    pub fn checkpoint(
+        &self,
+        context_hash: &ContextHash,
+    ) -> Result<Option<ContextOverlay>, ContextError> {
+        let hash_id = match self.repository.read()?.get_context_hash(context_hash)? {
+            Some(hash_id) => hash_id,
+            None => return Ok(None),
+        };
+
+        ContextOverlay::fork(self, hash_id)
     }
 
     /// Fetches object from the repository associated to this `hash_id`.
@@ -219,6 +898,16 @@ impl TezedgeIndex {
         Ok(db.get_context_hash(context_hash)?)
     }
 
+    /// Same as `Self::fetch_context_hash_id`, with the number of `HashId`s reused by the
+    /// repository so far, see `KeyValueStoreBackend::reused_hash_ids`.
+    pub fn fetch_context_hash_id_and_reuses(
+        &self,
+        context_hash: &ContextHash,
+    ) -> Result<(Option<HashId>, u64), MerkleError> {
+        let db = self.repository.read()?;
+        Ok((db.get_context_hash(context_hash)?, db.reused_hash_ids()))
+    }
+
     /// Convert key in array form to string form
     pub fn key_to_string(&self, key: &ContextKey) -> String {
         key.join("/")
@@ -574,11 +1263,50 @@ impl TezedgeIndex {
     ///   read only protocol runner.
     fn synchronize_interned_strings_to_repository(&self) -> Result<(), MerkleError> {
         let storage = self.storage.borrow();
+        if storage.strings.is_private() {
+            // The repository doesn't reference the strings of a private interner
+            return Ok(());
+        }
+
         let mut repository = self.repository.write()?;
         repository.synchronize_strings(&storage.strings)?;
 
         Ok(())
     }
+
+    /// Checks out the commit `hash_id` without clearing `Self::storage`.
+    pub(crate) fn checkout_commit(
+        &self,
+        hash_id: HashId,
+    ) -> Result<Option<TezedgeContext>, ContextError> {
+        let mut storage = self.storage.borrow_mut();
+
+        let commit = match self.fetch_commit(hash_id, &mut storage)? {
+            Some(commit) => commit,
+            None => return Ok(None),
+        };
+
+        let dir_id = match self.fetch_directory(commit.root_hash, &mut storage)? {
+            Some(dir_id) => dir_id,
+            None => return Ok(None),
+        };
+
+        let tree = WorkingTree::new_with_directory(self.clone(), dir_id);
+
+        Ok(Some(TezedgeContext::new(
+            self.clone(),
+            Some(hash_id),
+            Some(Rc::new(tree)),
+        )))
+    }
+
+    /// Compacts the `StringInterner` of the working tree, and synchronizes the repository.
+    ///
+    /// `storage` must have been cleared: the `StringId`s of the working tree are invalidated.
+    fn compact_interned_strings(&self, storage: &mut Storage) -> Result<(), DBError> {
+        let mut repository = self.repository.write()?;
+        repository.compact_strings(&mut storage.strings)
+    }
 }
 
 impl IndexApi<TezedgeContext> for TezedgeIndex {
@@ -612,26 +1340,20 @@ impl IndexApi<TezedgeContext> for TezedgeIndex {
             }
         };
 
-        let mut storage = self.storage.borrow_mut();
-        storage.clear();
-
-        let commit = match self.fetch_commit(hash_id, &mut storage)? {
-            Some(commit) => commit,
-            None => return Ok(None),
-        };
-
-        let dir_id = match self.fetch_directory(commit.root_hash, &mut storage)? {
-            Some(dir_id) => dir_id,
-            None => return Ok(None),
-        };
+        {
+            let mut storage = self.storage.borrow_mut();
+            storage.clear();
 
-        let tree = WorkingTree::new_with_directory(self.clone(), dir_id);
+            // No `StringId` of the working tree remains after a checkout. The copies of the
+            // interner made by the read transactions would resolve the remapped shapes of the
+            // repository against their stale generation, the compaction waits until the pinned
+            // commits are all released.
+            if storage.strings.should_compact() && self.pinned_commits.count() == 0 {
+                self.compact_interned_strings(&mut storage)?;
+            }
+        }
 
-        Ok(Some(TezedgeContext::new(
-            self.clone(),
-            Some(hash_id),
-            Some(Rc::new(tree)),
-        )))
+        self.checkout_commit(hash_id)
     }
 
     fn block_applied(&self, referenced_older_objects: Vec<HashId>) -> Result<(), ContextError> {
@@ -642,6 +1364,10 @@ impl IndexApi<TezedgeContext> for TezedgeIndex {
     }
 
     fn cycle_started(&mut self) -> Result<(), ContextError> {
+        // Postponed until the last read transaction is released
+        if !self.pinned_commits.try_start_cycle() {
+            return Ok(());
+        }
         Ok(self.repository.write()?.new_cycle_started()?)
     }
 
@@ -827,6 +1553,7 @@ impl ShellContextApi for TezedgeContext {
         repository.clear_objects()?;
 
         std::mem::drop(repository);
+        self.index.rolling_commit_applied(commit_hash_id)?;
         send_statistics(BlockMemoryUsage {
             context: Box::new(self.get_memory_usage()?),
             serialize: serialize_stats,
@@ -927,6 +1654,18 @@ impl TezedgeContext {
         }
     }
 
+    /// Forks the last commit of this context into a speculative overlay, see [`ContextOverlay`].
+    ///
+    /// The changes made to the tree since that commit are not in the overlay.
+    pub fn checkpoint(&self) -> Result<ContextOverlay, ContextError> {
+        let hash_id = self
+            .parent_commit_hash
+            .ok_or(ContextError::CheckpointWithoutCommit)?;
+
+        ContextOverlay::fork(&self.index, hash_id)?
+            .ok_or_else(|| MerkleError::ObjectNotFound { hash_id }.into())
+    }
+
     /// Produce a new copy of the context, replacing the tree (and if different, with a new tree id)
     pub fn with_tree(&self, tree: WorkingTree) -> Self {
         // TODO: only generate a new id if tree changes? Either that
@@ -961,6 +1700,31 @@ impl TezedgeContext {
     }
 }
 
+/// Inserts the entries of `dir_id` one by one in a new directory, so that its inodes are
+/// rebuilt from its entries only.
+fn rebuild_directory(
+    dir_id: DirectoryId,
+    storage: &mut Storage,
+) -> Result<DirectoryId, ContextError> {
+    let mut new_dir_id = DirectoryId::empty();
+
+    for (name, dir_entry_id) in storage.dir_to_vec_unsorted(dir_id)? {
+        let name = storage
+            .get_str(name)
+            .map_err(MerkleError::from)?
+            .to_string();
+        let dir_entry = storage
+            .get_dir_entry(dir_entry_id)
+            .map_err(MerkleError::from)?
+            .clone();
+        new_dir_id = storage
+            .dir_insert(new_dir_id, &name, dir_entry)
+            .map_err(MerkleError::from)?;
+    }
+
+    Ok(new_dir_id)
+}
+
 #[cfg(test)]
 mod tests {
     use tezos_api::ffi::{ContextKvStoreConfiguration, TezosContextTezEdgeStorageConfiguration};
@@ -968,6 +1732,257 @@ mod tests {
     use super::*;
     use crate::initializer::initialize_tezedge_context;
 
+    #[test]
+    fn test_prefetch_subtree() {
+        let context = initialize_tezedge_context(&TezosContextTezEdgeStorageConfiguration {
+            backend: ContextKvStoreConfiguration::InMem,
+            ipc_socket_path: None,
+        })
+        .unwrap();
+
+        let context = context.add(&["a", "b", "c"], &[1; 40]).unwrap();
+        let context = context.add(&["a", "b", "d"], &[2; 40]).unwrap();
+        let context = context.add(&["a", "e"], &[3]).unwrap();
+        let context = context.add(&["f"], &[4; 40]).unwrap();
+        let commit = context
+            .commit("Tezos".to_string(), "".to_string(), 0)
+            .unwrap();
+        let index = &context.index;
+
+        // directory "a/b" and its 2 blobs
+        assert_eq!(index.prefetch_subtree(&commit, &["a", "b"]).unwrap(), 3);
+        // root, "a", "a/b", 3 blobs not inlined
+        assert_eq!(index.prefetch_subtree(&commit, &[]).unwrap(), 6);
+        // inlined blob
+        assert_eq!(index.prefetch_subtree(&commit, &["a", "e"]).unwrap(), 1);
+        assert_eq!(index.prefetch_subtree(&commit, &["x"]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_get_tree_stats() {
+        let context = initialize_tezedge_context(&TezosContextTezEdgeStorageConfiguration {
+            backend: ContextKvStoreConfiguration::InMem,
+            ipc_socket_path: None,
+        })
+        .unwrap();
+
+        let context = context.add(&["a", "b", "c"], &[1; 40]).unwrap();
+        let context = context.add(&["a", "b", "d"], &[2; 40]).unwrap();
+        let context = context.add(&["a", "e"], &[3]).unwrap();
+        let context = context.add(&["g", "b", "c"], &[4; 40]).unwrap();
+        let context = context.add(&["g", "b", "d"], &[5]).unwrap();
+        let commit = context
+            .commit("Tezos".to_string(), "".to_string(), 0)
+            .unwrap();
+        let index = &context.index;
+
+        let stats = index.get_tree_stats(&commit, &[]).unwrap().unwrap();
+        assert_eq!(stats.directories, 5);
+        assert_eq!(stats.blobs, 5);
+        assert_eq!(stats.total_bytes, 40 * 3 + 2);
+        assert_eq!(stats.max_depth, 3);
+        // "a/b" and "g/b" have the same keys
+        assert_eq!(stats.shapes.values().sum::<usize>(), 5);
+        assert_eq!(stats.shapes.values().max(), Some(&2));
+
+        let stats = index.get_tree_stats(&commit, &["a"]).unwrap().unwrap();
+        assert_eq!(stats.directories, 2);
+        assert_eq!(stats.blobs, 3);
+        assert_eq!(stats.total_bytes, 40 * 2 + 1);
+        assert_eq!(stats.max_depth, 2);
+
+        // inlined blob
+        let stats = index.get_tree_stats(&commit, &["a", "e"]).unwrap().unwrap();
+        assert_eq!(stats.directories, 0);
+        assert_eq!(stats.blobs, 1);
+        assert_eq!(stats.max_depth, 0);
+
+        assert!(index.get_tree_stats(&commit, &["x"]).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_compact_interned_strings() {
+        let context = initialize_tezedge_context(&TezosContextTezEdgeStorageConfiguration {
+            backend: ContextKvStoreConfiguration::InMem,
+            ipc_socket_path: None,
+        })
+        .unwrap();
+
+        let context = context.add(&["a", "b", "c"], &[1; 40]).unwrap();
+        let context = context.add(&["a", "b", "d"], &[2]).unwrap();
+        let context = context.add(&["a", "e"], &[3]).unwrap();
+        let commit = context
+            .commit("Tezos".to_string(), "".to_string(), 0)
+            .unwrap();
+        let index = &context.index;
+
+        let unused = index.storage.borrow_mut().strings.get_string_id("unused");
+        for _ in 0..10 {
+            index.checkout(&commit).unwrap().unwrap();
+        }
+
+        {
+            let mut storage = index.storage.borrow_mut();
+            let generation = storage.strings.generation();
+            storage.clear();
+            index.compact_interned_strings(&mut storage).unwrap();
+
+            assert_ne!(storage.strings.generation(), generation);
+            assert!(storage.strings.get(unused).is_none());
+
+            let repository = index.repository.read().unwrap();
+            let update = repository.get_strings_update(generation, 0).unwrap();
+            assert_eq!(update.generation, storage.strings.generation());
+        }
+
+        // The shapes of the repository were remapped
+        let context = index.checkout(&commit).unwrap().unwrap();
+        assert_eq!(context.find(&["a", "b", "c"]).unwrap(), Some(vec![1; 40]));
+        assert_eq!(context.find(&["a", "b", "d"]).unwrap(), Some(vec![2]));
+        assert_eq!(context.find(&["a", "e"]).unwrap(), Some(vec![3]));
+    }
+
+    #[test]
+    fn test_diff() {
+        let context = initialize_tezedge_context(&TezosContextTezEdgeStorageConfiguration {
+            backend: ContextKvStoreConfiguration::InMem,
+            ipc_socket_path: None,
+        })
+        .unwrap();
+
+        let context = context.add(&["a", "b", "c"], &[1; 40]).unwrap();
+        let context = context.add(&["a", "b", "d"], &[2; 40]).unwrap();
+        let context = context.add(&["a", "e"], &[3]).unwrap();
+        let context = context.add(&["g", "h"], &[4; 40]).unwrap();
+        let from = context
+            .commit("Tezos".to_string(), "".to_string(), 0)
+            .unwrap();
+
+        let context = context.add(&["a", "b", "c"], &[5; 20]).unwrap();
+        let context = context.delete(&["a", "e"]).unwrap();
+        let context = context.add(&["a", "f"], &[6]).unwrap();
+        let context = context.delete(&["g", "h"]).unwrap();
+        let context = context.add(&["g", "h", "i"], &[7; 10]).unwrap();
+        let to = context
+            .commit("Tezos".to_string(), "".to_string(), 1)
+            .unwrap();
+        let index = &context.index;
+
+        let key = |key: &[&str]| key.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let diff = index.diff(&from, &to, &[]).unwrap().unwrap();
+        assert_eq!(
+            diff,
+            vec![
+                KeyDiff {
+                    key: key(&["a", "b", "c"]),
+                    change: KeyChange::Modified {
+                        old_size: 40,
+                        new_size: 20
+                    },
+                },
+                KeyDiff {
+                    key: key(&["a", "e"]),
+                    change: KeyChange::Removed { size: 1 },
+                },
+                KeyDiff {
+                    key: key(&["a", "f"]),
+                    change: KeyChange::Added { size: 1 },
+                },
+                KeyDiff {
+                    key: key(&["g", "h"]),
+                    change: KeyChange::Removed { size: 40 },
+                },
+                KeyDiff {
+                    key: key(&["g", "h", "i"]),
+                    change: KeyChange::Added { size: 10 },
+                },
+            ]
+        );
+
+        let diff = index.diff(&from, &to, &["a", "b"]).unwrap().unwrap();
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff[0].key, key(&["a", "b", "c"]));
+
+        let diff = index.diff(&to, &from, &["g"]).unwrap().unwrap();
+        assert_eq!(
+            diff.iter().map(|d| &d.change).collect::<Vec<_>>(),
+            vec![
+                &KeyChange::Added { size: 40 },
+                &KeyChange::Removed { size: 10 }
+            ]
+        );
+
+        assert!(index.diff(&from, &from, &[]).unwrap().unwrap().is_empty());
+        assert!(index.diff(&from, &to, &["x"]).unwrap().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_verify_tree() {
+        let context = initialize_tezedge_context(&TezosContextTezEdgeStorageConfiguration {
+            backend: ContextKvStoreConfiguration::InMem,
+            ipc_socket_path: None,
+        })
+        .unwrap();
+
+        let mut context = context.add(&["a", "b"], &[1; 40]).unwrap();
+        // big enough to be stored with inodes
+        for i in 0..1000 {
+            context = context
+                .add(
+                    &["big", &i.to_string()],
+                    format!("value-{:04}", i).as_bytes(),
+                )
+                .unwrap();
+        }
+        let commit = context
+            .commit("Tezos".to_string(), "".to_string(), 0)
+            .unwrap();
+        let index = &context.index;
+
+        for mode in [VerifyMode::Stored, VerifyMode::Irmin] {
+            let verification = index.verify_tree(&commit, mode).unwrap().unwrap();
+            assert!(verification.is_valid(), "{:?}", verification.mismatches);
+            // commit, root, "a", "a/b", "big" and its blobs
+            assert_eq!(verification.objects, 5 + 1000);
+        }
+
+        // corrupt the content of "a/b"
+        let blob_hash_id = {
+            let mut storage = Storage::default();
+            storage.strings = index.storage.borrow().strings.clone();
+            let commit_hash_id = index.fetch_context_hash_id(&commit).unwrap().unwrap();
+            let blob = index.find_subtree(commit_hash_id, &["a", "b"], &mut storage);
+            blob.unwrap().unwrap().1.unwrap()
+        };
+        let mut value = index.fetch_object_bytes(blob_hash_id).unwrap().unwrap();
+        *value.last_mut().unwrap() = 2;
+        index
+            .repository
+            .write()
+            .unwrap()
+            .write_batch(vec![(blob_hash_id, Arc::from(value))])
+            .unwrap();
+
+        for mode in [VerifyMode::Stored, VerifyMode::Irmin] {
+            let verification = index.verify_tree(&commit, mode).unwrap().unwrap();
+            let mismatches: Vec<_> = verification
+                .mismatches
+                .iter()
+                .map(|mismatch| (mismatch.kind, mismatch.key.join("/")))
+                .collect();
+            assert_eq!(
+                mismatches,
+                vec![(VerifiedObjectKind::Blob, "a/b".to_string())]
+            );
+        }
+
+        let unknown = ContextHash::try_from(&[1; 32][..]).unwrap();
+        assert!(index
+            .verify_tree(&unknown, VerifyMode::Stored)
+            .unwrap()
+            .is_none());
+    }
+
     #[test]
     fn init_context() {
         let context = initialize_tezedge_context(&TezosContextTezEdgeStorageConfiguration {
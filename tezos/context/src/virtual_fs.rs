@@ -0,0 +1,270 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Read-only, filesystem-like view of a context commit.
+//!
+//! Directories of the context tree are exposed as directories and values (blobs)
+//! as files, paths being the context keys joined with `/`:
+//!
+//! ```no_compile
+//! let fs = ContextFs::checkout(&index, &context_hash)?.unwrap();
+//! for (name, entry) in fs.read_dir("/data/votes")? { ... }
+//! let value = fs.read("/data/votes/current_period")?;
+//! ```
+//!
+//! The whole tree can also be exported into a real directory with [`ContextFs::export_to`],
+//! so that it can be inspected and diffed with standard tools.
+
+use std::{
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use crypto::hash::ContextHash;
+use thiserror::Error;
+
+use crate::{
+    working_tree::{working_tree::WorkingTree, DirEntryKind},
+    ContextError, ContextKeyOwned, ContextValue, IndexApi, ProtocolContextApi, TezedgeContext,
+    TezedgeIndex,
+};
+
+/// Errors produced by [`ContextFs`]
+#[derive(Debug, Error)]
+pub enum ContextFsError {
+    #[error("Context error: {error}")]
+    ContextError { error: ContextError },
+    #[error("I/O error: {error}")]
+    IOError { error: io::Error },
+    #[error("Key {key:?} cannot be used as a path component")]
+    InvalidPathComponent { key: String },
+}
+
+impl From<ContextError> for ContextFsError {
+    fn from(error: ContextError) -> Self {
+        Self::ContextError { error }
+    }
+}
+
+impl From<io::Error> for ContextFsError {
+    fn from(error: io::Error) -> Self {
+        Self::IOError { error }
+    }
+}
+
+/// Kind of an entry of the [`ContextFs`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsEntry {
+    Directory,
+    /// A value, `size` is its length in bytes
+    File {
+        size: usize,
+    },
+}
+
+impl FsEntry {
+    fn of_tree(tree: &WorkingTree) -> Self {
+        match tree.kind() {
+            DirEntryKind::Directory => FsEntry::Directory,
+            DirEntryKind::Blob => FsEntry::File {
+                size: tree.get_value().map(|v| v.len()).unwrap_or(0),
+            },
+        }
+    }
+}
+
+/// Read-only view of a context tree with a filesystem-like API.
+pub struct ContextFs {
+    context: TezedgeContext,
+}
+
+impl ContextFs {
+    /// Creates a view of the working tree of `context`.
+    pub fn new(context: TezedgeContext) -> Self {
+        Self { context }
+    }
+
+    /// Checkouts `context_hash` and returns a view of its tree.
+    ///
+    /// Returns `None` if the commit doesn't exist.
+    pub fn checkout(
+        index: &TezedgeIndex,
+        context_hash: &ContextHash,
+    ) -> Result<Option<Self>, ContextFsError> {
+        Ok(index.checkout(context_hash)?.map(Self::new))
+    }
+
+    /// Returns the kind of the entry at `path`, or `None` if it doesn't exist.
+    pub fn metadata(&self, path: &str) -> Result<Option<FsEntry>, ContextFsError> {
+        let key = path_to_key(path);
+        let key: Vec<&str> = key.iter().map(String::as_str).collect();
+
+        Ok(self
+            .context
+            .find_tree(&key)?
+            .map(|tree| FsEntry::of_tree(&tree)))
+    }
+
+    /// Lists the entries of the directory at `path`, sorted by name.
+    ///
+    /// Returns an empty list if `path` doesn't exist or is not a directory.
+    pub fn read_dir(&self, path: &str) -> Result<Vec<(String, FsEntry)>, ContextFsError> {
+        let key = path_to_key(path);
+        let key: Vec<&str> = key.iter().map(String::as_str).collect();
+
+        Ok(self
+            .context
+            .list(None, None, &key)?
+            .iter()
+            .map(|(name, tree)| (name.clone(), FsEntry::of_tree(tree)))
+            .collect())
+    }
+
+    /// Returns the content of the file (value) at `path`.
+    ///
+    /// Returns `None` if `path` doesn't exist or is a directory.
+    pub fn read(&self, path: &str) -> Result<Option<ContextValue>, ContextFsError> {
+        let key = path_to_key(path);
+        let key: Vec<&str> = key.iter().map(String::as_str).collect();
+
+        if key.is_empty() {
+            return Ok(None);
+        }
+        Ok(self.context.find(&key)?)
+    }
+
+    /// Writes the whole tree into the directory `target`, creating it when missing.
+    ///
+    /// Returns the number of files written.
+    pub fn export_to<P: AsRef<Path>>(&self, target: P) -> Result<usize, ContextFsError> {
+        let target = target.as_ref();
+        let mut files = 0;
+
+        fs::create_dir_all(target)?;
+
+        for (key, tree) in self.context.fold_iter(None, &[])? {
+            let path = key_to_path(target, &key)?;
+
+            match tree.kind() {
+                DirEntryKind::Directory => fs::create_dir_all(&path)?,
+                DirEntryKind::Blob => {
+                    fs::write(&path, tree.get_value().unwrap_or_default())?;
+                    files += 1;
+                }
+            }
+        }
+
+        Ok(files)
+    }
+}
+
+/// Converts a `/` separated path into a context key, ignoring empty components.
+fn path_to_key(path: &str) -> ContextKeyOwned {
+    path.split('/')
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Builds the path of `key` inside `root`, refusing components that would escape it.
+///
+/// Each component of the key must be a single normal component of a path: not empty, not
+/// `.` or `..`, without separator (which would also make it absolute) and without NUL.
+fn key_to_path(root: &Path, key: &[String]) -> Result<PathBuf, ContextFsError> {
+    let mut path = root.to_path_buf();
+
+    for component in key {
+        if !is_normal_component(component) {
+            return Err(ContextFsError::InvalidPathComponent {
+                key: component.clone(),
+            });
+        }
+        path.push(component);
+    }
+
+    Ok(path)
+}
+
+fn is_normal_component(component: &str) -> bool {
+    if component.contains(|c| c == '\0' || std::path::is_separator(c)) {
+        return false;
+    }
+
+    let mut components = Path::new(component).components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(normal)), None) if normal == component
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use tezos_api::ffi::{ContextKvStoreConfiguration, TezosContextTezEdgeStorageConfiguration};
+
+    use super::*;
+    use crate::{initializer::initialize_tezedge_context, ShellContextApi};
+
+    #[test]
+    fn test_context_fs() {
+        let context = initialize_tezedge_context(&TezosContextTezEdgeStorageConfiguration {
+            backend: ContextKvStoreConfiguration::InMem,
+            ipc_socket_path: None,
+        })
+        .unwrap();
+
+        let context = context.add(&["a", "b", "c"], &[1, 2, 3]).unwrap();
+        let context = context.add(&["a", "d"], &[4]).unwrap();
+        let context = context.add(&["e"], &[5, 6]).unwrap();
+        let context_hash = context
+            .commit("Tezos".to_string(), "".to_string(), 0)
+            .unwrap();
+
+        let fs = ContextFs::checkout(&context.index, &context_hash)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(fs.metadata("/").unwrap(), Some(FsEntry::Directory));
+        assert_eq!(fs.metadata("/a/b").unwrap(), Some(FsEntry::Directory));
+        assert_eq!(
+            fs.metadata("/a/b/c").unwrap(),
+            Some(FsEntry::File { size: 3 })
+        );
+        assert_eq!(fs.metadata("/a/x").unwrap(), None);
+
+        assert_eq!(
+            fs.read_dir("/a").unwrap(),
+            vec![
+                ("b".to_string(), FsEntry::Directory),
+                ("d".to_string(), FsEntry::File { size: 1 }),
+            ]
+        );
+        assert!(fs.read_dir("/e").unwrap().is_empty());
+
+        assert_eq!(fs.read("a/b/c").unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(fs.read("/a").unwrap(), None);
+        assert_eq!(fs.read("/").unwrap(), None);
+
+        let target = std::env::temp_dir().join(format!("context_fs_{}", std::process::id()));
+        assert_eq!(fs.export_to(&target).unwrap(), 3);
+        assert_eq!(std::fs::read(target.join("a/b/c")).unwrap(), vec![1, 2, 3]);
+        assert_eq!(std::fs::read(target.join("e")).unwrap(), vec![5, 6]);
+        std::fs::remove_dir_all(&target).unwrap();
+    }
+
+    #[test]
+    fn test_key_to_path() {
+        let root = Path::new("/tmp/ctx");
+
+        assert_eq!(
+            key_to_path(root, &["a".to_string(), "b".to_string()]).unwrap(),
+            Path::new("/tmp/ctx/a/b")
+        );
+        for component in &["", ".", "..", "a/../../etc", "/etc", "a\0b"] {
+            assert!(key_to_path(root, &["a".to_string(), component.to_string()]).is_err());
+        }
+        assert_eq!(
+            path_to_key("//a/b/"),
+            vec!["a".to_string(), "b".to_string()]
+        );
+    }
+}
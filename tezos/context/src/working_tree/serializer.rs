@@ -576,6 +576,20 @@ fn deserialize_shaped_directory(
     let shape_id = DirectoryShapeId::from(shape_id);
 
     let directory_shape = match repository.get_shape(shape_id).map_err(Box::new)? {
+        ShapeStrings::SliceIds(slice_ids) if storage.strings.is_private() => {
+            // The `StringId`s of the repository are not valid in a private `StringInterner`,
+            // resolve them with the strings of the repository.
+            let string_ids = slice_ids
+                .iter()
+                .map(|string_id| {
+                    repository
+                        .get_str(*string_id)
+                        .map(|s| storage.get_string_id(s))
+                        .ok_or(StorageError::StringNotFound)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Cow::Owned(string_ids)
+        }
         ShapeStrings::SliceIds(slice_ids) => Cow::Borrowed(slice_ids),
         ShapeStrings::Owned(strings) => {
             // We are in the readonly protocol runner.
@@ -860,6 +874,28 @@ pub fn deserialize_inode(
     }
 }
 
+/// Returns the shape of a serialized directory, if it was serialized with one
+pub fn directory_shape_id(data: &[u8]) -> Option<DirectoryShapeId> {
+    if data.get(0).copied()? != ID_SHAPED_DIRECTORY {
+        return None;
+    }
+
+    let shape_id = data.get(1..SHAPED_DIRECTORY_NBYTES_TO_HASHES)?;
+    let shape_id = u32::from_ne_bytes(shape_id.try_into().ok()?);
+
+    Some(DirectoryShapeId::from(shape_id))
+}
+
+/// Returns the `HashId` of the parent of a serialized commit.
+///
+/// Returns `None` for the other objects, and for a commit without parent.
+pub fn commit_parent_hash_id(data: &[u8]) -> Option<HashId> {
+    if data.first().copied()? != ID_COMMIT {
+        return None;
+    }
+    deserialize_hash_id(data.get(1..)?).ok()?.0
+}
+
 /// Iterate HashIds in the serialized data
 pub fn iter_hash_ids(data: &[u8]) -> HashIdIterator {
     HashIdIterator { data, pos: 0 }
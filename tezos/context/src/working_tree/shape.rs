@@ -9,13 +9,17 @@ use std::{
     },
     convert::{TryFrom, TryInto},
     hash::Hasher,
+    mem::size_of,
 };
 
 use crate::kv_store::index_map::IndexMap;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use super::{storage::DirEntryId, string_interner::StringId};
+use super::{
+    storage::DirEntryId,
+    string_interner::{StringId, StringsRemap},
+};
 
 #[derive(Debug, Error)]
 pub enum DirectoryShapeError {
@@ -74,6 +78,8 @@ pub struct DirectoryShapes {
     id_to_hash: IndexMap<DirectoryShapeId, DirectoryShapeHash>,
     /// Temporary vector used to collect the `StringId` when creating/retrieving a shape.
     temp: Vec<StringId>,
+    /// Total number of `StringId` in the shapes.
+    nstrings: usize,
 }
 
 impl Default for DirectoryShapes {
@@ -93,6 +99,7 @@ impl DirectoryShapes {
             hash_to_strings: BTreeMap::default(),
             id_to_hash: IndexMap::with_capacity(1024),
             temp: Vec::with_capacity(256),
+            nstrings: 0,
         }
     }
 
@@ -100,6 +107,16 @@ impl DirectoryShapes {
         self.id_to_hash.len()
     }
 
+    /// Approximate number of bytes occupied by the shapes.
+    pub fn total_bytes(&self) -> usize {
+        let entry_size = size_of::<(DirectoryShapeHash, (DirectoryShapeId, Box<[StringId]>))>();
+
+        (self.hash_to_strings.len() * entry_size)
+            .saturating_add(self.nstrings * size_of::<StringId>())
+            .saturating_add(self.id_to_hash.capacity() * size_of::<DirectoryShapeHash>())
+            .saturating_add(self.temp.capacity() * size_of::<StringId>())
+    }
+
     pub fn get_shape(
         &self,
         shape_id: DirectoryShapeId,
@@ -121,27 +138,66 @@ impl DirectoryShapes {
     ) -> Result<Option<DirectoryShapeId>, DirectoryShapeError> {
         self.temp.clear();
 
-        let mut hasher = DefaultHasher::new();
-        hasher.write_usize(dir.len());
-
         for (key_id, _) in dir {
             if key_id.is_big() {
                 return Ok(None);
             }
 
-            hasher.write_u32(key_id.as_u32());
             self.temp.push(*key_id);
         }
 
-        let shape_hash = DirectoryShapeHash(hasher.finish());
+        let shape_hash = hash_shape(&self.temp);
 
         match self.hash_to_strings.entry(shape_hash) {
             Occupied(entry) => Ok(Some(entry.get().0)),
             Vacant(entry) => {
                 let shape_id = self.id_to_hash.push(shape_hash)?;
+                self.nstrings += self.temp.len();
                 entry.insert((shape_id, Box::from(self.temp.as_slice())));
                 Ok(Some(shape_id))
             }
         }
     }
+
+    /// Returns the `StringId`s of all the shapes.
+    pub fn string_ids(&self) -> impl Iterator<Item = StringId> + '_ {
+        self.hash_to_strings
+            .values()
+            .flat_map(|(_, strings)| strings.iter().copied())
+    }
+
+    /// Replaces the `StringId`s of the shapes, after a compaction of the `StringInterner`.
+    ///
+    /// The `DirectoryShapeId`s are unchanged, they are referenced by the serialized directories.
+    pub fn remap_strings(&mut self, remap: &StringsRemap) -> Result<(), DirectoryShapeError> {
+        let mut hash_to_strings = BTreeMap::default();
+
+        for (shape_id, strings) in self.hash_to_strings.values() {
+            let strings = strings
+                .iter()
+                .map(|string_id| remap.get(*string_id))
+                .collect::<Option<Box<[StringId]>>>()
+                .ok_or(DirectoryShapeError::CannotFindKey)?;
+
+            hash_to_strings.insert(hash_shape(&strings), (*shape_id, strings));
+        }
+
+        for (shape_hash, (shape_id, _)) in hash_to_strings.iter() {
+            self.id_to_hash.set(*shape_id, *shape_hash)?;
+        }
+
+        self.hash_to_strings = hash_to_strings;
+        Ok(())
+    }
+}
+
+fn hash_shape(strings: &[StringId]) -> DirectoryShapeHash {
+    let mut hasher = DefaultHasher::new();
+    hasher.write_usize(strings.len());
+
+    for string_id in strings {
+        hasher.write_u32(string_id.as_u32());
+    }
+
+    DirectoryShapeHash(hasher.finish())
 }
@@ -3,8 +3,25 @@
 
 //! Implementation of string interning used to implement hash-consing for context path fragments.
 //! This avoids un-necessary duplication of strings, saving memory.
-
-use std::{collections::hash_map::DefaultHasher, hash::Hasher};
+//!
+//! The small strings are never released individually, a long running node would accumulate
+//! all the path fragments it ever saw. Instead, the interner is compacted by epochs: an epoch
+//! ends at every checkout, when the working tree is cleared and its `StringId`s are dropped.
+//! [`StringInterner::compact`] then keeps the strings used during the last epochs, and the ones
+//! still referenced by the repository (the directory shapes).
+//!
+//! A compaction starts a new generation of the interner. The copies of the interner
+//! (in the repository, or in a read only protocol runner) are updated incrementally with a
+//! [`StringsUpdate`] while the generation is unchanged, and replaced otherwise.
+//!
+//! A private interner, see [`StringInterner::new_private`], hands out `StringId`s that never
+//! collide with the ones of another interner, the strings of the repository are resolved
+//! through the repository instead of being copied.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet},
+    hash::Hasher,
+};
 
 use serde::{Deserialize, Serialize};
 use static_assertions::const_assert;
@@ -17,6 +34,20 @@ pub(crate) const STRING_INTERN_THRESHOLD: usize = 30;
 const FULL_31_BITS: usize = 0x7FFFFFFF;
 const FULL_5_BITS: usize = 0x1F;
 
+/// Number of epochs a string is kept by [`StringInterner::compact`] after its last use.
+const MAX_UNUSED_EPOCHS: u32 = 4;
+
+/// Minimum length of `StringInterner::all_strings` to compact it.
+///
+/// The offsets in `StringId` are 26 bits, `all_strings` cannot exceed 64MB.
+const COMPACTION_MIN_LENGTH: usize = 32 * 1024 * 1024;
+
+/// Length of `StringInterner::all_strings` from which it is always compacted.
+///
+/// Below the 64MB limit of the offsets, with room for the strings interned until the next
+/// compaction, even when more than half of the strings survived the previous one.
+const COMPACTION_MAX_LENGTH: usize = 48 * 1024 * 1024;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct StringId {
     /// | 1 bit  |  31 bits |
@@ -97,39 +128,133 @@ impl BigStrings {
     }
 }
 
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+struct InternedString {
+    string_id: StringId,
+    /// Last epoch the string was interned in
+    epoch: u32,
+}
+
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct StringInterner {
     /// `Map` of hash of the string to their `StringId`
     /// We don't use `HashMap<String, StringId>` because the map would
     /// keep a copy of the string
-    string_to_offset: Map<u64, StringId>,
-    /// Concatenation of all strings < STRING_INTERN_THRESHOLD.
-    /// This is never cleared/deallocated
+    string_to_offset: Map<u64, InternedString>,
+    /// Concatenation of all strings < STRING_INTERN_THRESHOLD, each one
+    /// prefixed by its length (a single byte).
+    /// This is only deallocated by `Self::compact`
     all_strings: String,
     /// Concatenation of big strings. This is cleared/deallocated
     /// before every checkouts
     big_strings: BigStrings,
+    /// Incremented by `Self::compact`, the `StringId`s of 2 generations are unrelated
+    generation: u32,
+    /// Incremented by `Self::clear`, before every checkouts
+    epoch: u32,
+    /// Length of `all_strings` after the last compaction
+    compacted_length: usize,
+    /// All the strings are in `big_strings`, see `Self::new_private`
+    private: bool,
+}
+
+/// Strings interned since a cursor of the `StringInterner`, see [`StringInterner::update_since`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct StringsUpdate {
+    pub generation: u32,
+    /// Position of `strings` in the interner, `0` when the interner is sent entirely
+    pub offset: usize,
+    strings: String,
+}
+
+impl StringsUpdate {
+    pub fn as_str(&self) -> &str {
+        &self.strings
+    }
+}
+
+/// The new `StringId` of the strings kept by [`StringInterner::compact`].
+#[derive(Debug, Default)]
+pub struct StringsRemap {
+    ids: Map<u32, StringId>,
+}
+
+impl StringsRemap {
+    /// Returns `None` when the string was dropped
+    pub fn get(&self, string_id: StringId) -> Option<StringId> {
+        self.ids.get(&string_id.as_u32()).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
 }
 
 impl PartialEq for StringInterner {
     fn eq(&self, other: &Self) -> bool {
-        self.all_strings.len() == other.all_strings.len()
+        self.generation == other.generation && self.all_strings.len() == other.all_strings.len()
     }
 }
 
 impl Eq for StringInterner {}
 
 impl StringInterner {
+    /// Rebuilds an interner from the content of `Self::all_strings`, as written by a previous run.
+    ///
+    /// Returns the interner and the number of bytes used in `all_strings`, a record truncated at
+    /// the end is ignored.
+    pub fn from_all_strings(all_strings: &str) -> (Self, usize) {
+        let mut interner = Self::default();
+        let bytes = all_strings.as_bytes();
+        let mut offset = 0;
+
+        while let Some(length) = bytes.get(offset).map(|length| *length as usize) {
+            let start = offset + 1;
+            let end = start + length;
+
+            let s = match all_strings.get(start..end) {
+                Some(s) if length < STRING_INTERN_THRESHOLD => s,
+                _ => break,
+            };
+
+            let string_id = push_record(&mut interner.all_strings, s);
+            interner.string_to_offset.insert(
+                hash_string(s),
+                InternedString {
+                    string_id,
+                    epoch: 0,
+                },
+            );
+            offset = end;
+        }
+
+        interner.compacted_length = offset;
+        (interner, offset)
+    }
+
     /// This extends `Self::all_strings` from `other`.
     ///
     /// The other fields (`string_to_offset` and `big_strings`) are not considered
     /// because this method is used to update the repository:
     /// The repository doesn't need those 2 fields.
+    ///
+    /// When `other` was compacted, `Self::all_strings` is replaced entirely.
     pub fn extend_from(&mut self, other: &Self) {
         if self == other {
             return;
         }
 
+        if self.generation != other.generation {
+            self.all_strings = other.all_strings.clone();
+            self.string_to_offset.clear();
+            self.generation = other.generation;
+            return;
+        }
+
         debug_assert!(self.all_strings.len() < other.all_strings.len());
 
         // Append the missing chunk into Self
@@ -139,6 +264,67 @@ impl StringInterner {
         debug_assert_eq!(self.all_strings, other.all_strings);
     }
 
+    /// Creates an interner whose `StringId`s are not shared with the repository.
+    ///
+    /// The strings, small ones included, are stored in `big_strings`: their `StringId`s are
+    /// never valid in another interner, and never make a directory shape. Such an interner is
+    /// never synchronized with the repository nor compacted.
+    pub fn new_private() -> Self {
+        Self {
+            private: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_private(&self) -> bool {
+        self.private
+    }
+
+    /// Returns the position of the next strings, to use with `Self::update_since`.
+    pub fn cursor(&self) -> (u32, usize) {
+        (self.generation, self.all_strings.len())
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Returns the strings interned since the cursor `(generation, offset)`.
+    ///
+    /// The whole interner is returned when the cursor belongs to another generation.
+    pub fn update_since(&self, generation: u32, offset: usize) -> StringsUpdate {
+        let offset = if generation == self.generation && self.all_strings.is_char_boundary(offset) {
+            offset
+        } else {
+            0
+        };
+
+        StringsUpdate {
+            generation: self.generation,
+            offset,
+            strings: self.all_strings[offset..].to_string(),
+        }
+    }
+
+    /// Applies an update made by `Self::update_since` on another interner.
+    ///
+    /// Returns `false`, and ignores the update, when it doesn't start at the cursor of `self`.
+    pub fn apply_update(&mut self, update: StringsUpdate) -> bool {
+        if update.offset == 0 {
+            self.all_strings = update.strings;
+            self.string_to_offset.clear();
+            self.generation = update.generation;
+            return true;
+        }
+
+        if self.cursor() != (update.generation, update.offset) {
+            return false;
+        }
+
+        self.all_strings.push_str(&update.strings);
+        true
+    }
+
     pub fn get_string_id(&mut self, s: &str) -> StringId {
         if s.len() >= STRING_INTERN_THRESHOLD {
             let index = self.big_strings.push_str(s);
@@ -148,26 +334,28 @@ impl StringInterner {
             };
         }
 
-        let mut hasher = DefaultHasher::new();
-        hasher.write(s.as_bytes());
-        let hashed = hasher.finish();
+        let hashed = hash_string(s);
 
-        if let Some(string_id) = self.string_to_offset.get(&hashed) {
-            return *string_id;
+        if let Some(interned) = self.string_to_offset.get_mut(&hashed) {
+            interned.epoch = self.epoch;
+            return interned.string_id;
         }
 
-        let index: u32 = self.all_strings.len() as u32;
-        let length: u32 = s.len() as u32;
-
-        assert_eq!(index & !0x3FFFFFF, 0);
-
-        self.all_strings.push_str(s);
-
-        let string_id = StringId {
-            bits: index << 5 | length,
+        let string_id = if self.private {
+            StringId {
+                bits: 1 << 31 | self.big_strings.push_str(s),
+            }
+        } else {
+            push_record(&mut self.all_strings, s)
         };
 
-        self.string_to_offset.insert(hashed, string_id);
+        self.string_to_offset.insert(
+            hashed,
+            InternedString {
+                string_id,
+                epoch: self.epoch,
+            },
+        );
 
         debug_assert_eq!(s, self.get(string_id).unwrap());
 
@@ -183,8 +371,74 @@ impl StringInterner {
         self.all_strings.get(start..end)
     }
 
+    /// Clears the big strings, and starts a new epoch.
     pub fn clear(&mut self) {
         self.big_strings.clear();
+        if self.private {
+            self.string_to_offset.clear();
+        }
+        self.epoch = self.epoch.wrapping_add(1);
+    }
+
+    /// Returns `true` when `Self::all_strings` grew enough since the last compaction.
+    pub fn should_compact(&self) -> bool {
+        let threshold = COMPACTION_MIN_LENGTH
+            .max(self.compacted_length * 2)
+            .min(COMPACTION_MAX_LENGTH);
+
+        self.all_strings.len() >= threshold
+    }
+
+    /// Drops the strings not used during the last epochs, except the ones in `roots`.
+    ///
+    /// This must be called right after `Self::clear`: the `StringId`s handed out before
+    /// are invalidated, the ones of `roots` are remapped with the returned `StringsRemap`.
+    pub fn compact(&mut self, roots: &mut dyn Iterator<Item = StringId>) -> StringsRemap {
+        let min_epoch = self.epoch.saturating_sub(MAX_UNUSED_EPOCHS);
+        let roots: HashSet<u32> = roots
+            .filter(|string_id| !string_id.is_big())
+            .map(StringId::as_u32)
+            .collect();
+
+        let mut kept: Vec<(u64, InternedString)> = self
+            .string_to_offset
+            .iter()
+            .filter(|(_, interned)| {
+                interned.epoch >= min_epoch || roots.contains(&interned.string_id.as_u32())
+            })
+            .map(|(hashed, interned)| (*hashed, *interned))
+            .collect();
+
+        // Keep the order of the strings
+        kept.sort_unstable_by_key(|(_, interned)| interned.string_id.as_u32());
+
+        let mut remap = StringsRemap::default();
+        let mut all_strings = String::with_capacity(self.all_strings.len() / 2);
+        let mut string_to_offset = Map::default();
+
+        for (hashed, interned) in kept {
+            let s = match self.get(interned.string_id) {
+                Some(s) => s,
+                None => continue,
+            };
+
+            let string_id = push_record(&mut all_strings, s);
+            remap.ids.insert(interned.string_id.as_u32(), string_id);
+            string_to_offset.insert(
+                hashed,
+                InternedString {
+                    string_id,
+                    epoch: interned.epoch,
+                },
+            );
+        }
+
+        self.all_strings = all_strings;
+        self.string_to_offset = string_to_offset;
+        self.generation = self.generation.wrapping_add(1);
+        self.compacted_length = self.all_strings.len();
+
+        remap
     }
 
     pub fn memory_usage(&self) -> StringsMemoryUsage {
@@ -205,6 +459,31 @@ impl StringInterner {
     }
 }
 
+fn hash_string(s: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(s.as_bytes());
+    hasher.finish()
+}
+
+/// Appends the small string `s`, prefixed by its length, to `all_strings`.
+fn push_record(all_strings: &mut String, s: &str) -> StringId {
+    debug_assert!(s.len() < STRING_INTERN_THRESHOLD);
+
+    // The length is < 32, it is a single byte in UTF-8
+    all_strings.push(char::from(s.len() as u8));
+
+    let index: u32 = all_strings.len() as u32;
+    let length: u32 = s.len() as u32;
+
+    assert_eq!(index & !0x3FFFFFF, 0);
+
+    all_strings.push_str(s);
+
+    StringId {
+        bits: index << 5 | length,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -232,4 +511,118 @@ mod tests {
         assert_eq!(interner.get(a).unwrap(), long_str);
         assert_eq!(interner.get(b).unwrap(), long_str);
     }
+
+    #[test]
+    fn test_string_interner_compact() {
+        let mut interner = StringInterner::default();
+
+        let a = interner.get_string_id("a");
+        let b = interner.get_string_id("b");
+        let c = interner.get_string_id("c");
+
+        for _ in 0..MAX_UNUSED_EPOCHS + 1 {
+            interner.clear();
+        }
+        interner.get_string_id("c");
+        let generation = interner.generation();
+
+        // "a" is not used anymore, "b" is still referenced
+        let remap = interner.compact(&mut std::iter::once(b));
+
+        assert_ne!(interner.generation(), generation);
+        assert_eq!(remap.len(), 2);
+        assert!(remap.get(a).is_none());
+
+        let b = remap.get(b).unwrap();
+        let c = remap.get(c).unwrap();
+        assert_eq!(interner.get(b), Some("b"));
+        assert_eq!(interner.get(c), Some("c"));
+        assert_eq!(interner.get_string_id("c"), c);
+        assert_ne!(interner.get_string_id("a"), a);
+    }
+
+    #[test]
+    fn test_string_interner_should_compact() {
+        let mut interner = StringInterner::default();
+        interner.all_strings = "a".repeat(COMPACTION_MIN_LENGTH - 1);
+        assert!(!interner.should_compact());
+        interner.all_strings.push('a');
+        assert!(interner.should_compact());
+
+        // More than 32MB survived the last compaction, the threshold stays below 64MB
+        interner.compacted_length = 40 * 1024 * 1024;
+        assert!(!interner.should_compact());
+        interner.all_strings = "a".repeat(COMPACTION_MAX_LENGTH);
+        assert!(interner.should_compact());
+    }
+
+    #[test]
+    fn test_string_interner_private() {
+        let mut shared = StringInterner::default();
+        let a = shared.get_string_id("a");
+
+        let mut private = StringInterner::new_private();
+        let b = private.get_string_id("b");
+        assert!(b.is_big());
+        assert_eq!(private.get_string_id("b"), b);
+        assert_eq!(private.get(b), Some("b"));
+        assert_eq!(private.cursor(), (0, 0));
+
+        // the ids of both interners are distinct
+        assert_ne!(private.get_string_id("a"), a);
+        assert_eq!(shared.get(b), None);
+
+        private.clear();
+        assert_eq!(private.get(b), None);
+        assert!(private.get_string_id("b").is_big());
+    }
+
+    #[test]
+    fn test_string_interner_update() {
+        let mut interner = StringInterner::default();
+        let mut copy = StringInterner::default();
+
+        let a = interner.get_string_id("a");
+        assert!(copy.apply_update(interner.update_since(0, 0)));
+        assert_eq!(copy.get(a), Some("a"));
+
+        let (generation, offset) = copy.cursor();
+        let b = interner.get_string_id("bb");
+        let update = interner.update_since(generation, offset);
+        assert_eq!(update.offset, offset);
+        assert_eq!(update.as_str().len(), 3);
+
+        // The update doesn't follow an empty interner
+        assert!(!StringInterner::default().apply_update(update.clone()));
+        assert!(copy.apply_update(update));
+        assert_eq!(copy.get(b), Some("bb"));
+        assert_eq!(copy, interner);
+
+        // The whole interner is sent after a compaction
+        interner.clear();
+        let remap = interner.compact(&mut std::iter::empty());
+        let update = interner.update_since(generation, copy.cursor().1);
+        assert_eq!(update.offset, 0);
+        assert!(copy.apply_update(update));
+        assert_eq!(copy.get(remap.get(b).unwrap()), Some("bb"));
+        assert_eq!(copy, interner);
+    }
+
+    #[test]
+    fn test_string_interner_from_all_strings() {
+        let mut interner = StringInterner::default();
+        let a = interner.get_string_id("a");
+        let b = interner.get_string_id("bcd");
+
+        let mut all_strings = interner.update_since(0, 0).as_str().to_string();
+        let length = all_strings.len();
+        // truncated record
+        all_strings.push_str("\u{5}ab");
+
+        let (mut loaded, loaded_length) = StringInterner::from_all_strings(&all_strings);
+        assert_eq!(loaded_length, length);
+        assert_eq!(loaded.get(a), Some("a"));
+        assert_eq!(loaded.get(b), Some("bcd"));
+        assert_eq!(loaded.get_string_id("bcd"), b);
+    }
 }
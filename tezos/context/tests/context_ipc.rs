@@ -0,0 +1,76 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+use tezos_context::ffi::TEZEDGE_CONTEXT_REPOSITORY;
+use tezos_context::kv_store::in_memory::InMemory;
+use tezos_context::kv_store::readonly_ipc::{IpcContextClient, IpcContextListener};
+use tezos_context::tezedge_context::VerifyMode;
+use tezos_context::{context_key, ContextKeyValueStore, TezedgeContext, TezedgeIndex};
+use tezos_context::{ProtocolContextApi, ShellContextApi};
+
+/// The tree statistics, diff and verification are computed by the server from the
+/// repository shared with the writable context, through the IPC.
+#[test]
+fn test_context_ipc_tree_requests() {
+    let repository: Arc<RwLock<ContextKeyValueStore>> =
+        Arc::new(RwLock::new(InMemory::try_new_with_gc(false).unwrap()));
+    let index = TezedgeIndex::new(Arc::clone(&repository), None);
+    TEZEDGE_CONTEXT_REPOSITORY
+        .write()
+        .unwrap()
+        .replace(repository);
+
+    let mut context = TezedgeContext::new(index.clone(), None, None);
+    for key in &["a", "b", "c", "d"] {
+        context = context
+            .add(&context_key!("data/contracts/{}/balance", key), &[1, 2, 3])
+            .unwrap();
+        context = context
+            .add(&context_key!("data/contracts/{}/counter", key), &[4])
+            .unwrap();
+    }
+    let first = context
+        .commit("Tezos".to_string(), "".to_string(), 0)
+        .unwrap();
+
+    let context = context
+        .add(&context_key!("data/contracts/b/balance"), &[5; 10])
+        .unwrap();
+    let context = context
+        .delete(&context_key!("data/contracts/c/counter"))
+        .unwrap();
+    let second = context
+        .commit("Tezos".to_string(), "".to_string(), 1)
+        .unwrap();
+
+    let path = std::env::temp_dir().join(format!(
+        "tezedge_ctx_tree_requests_{}.sock",
+        std::process::id()
+    ));
+    let mut listener = IpcContextListener::try_new(&path).unwrap();
+    thread::spawn(move || {
+        let server = listener.accept().unwrap();
+        let log = slog::Logger::root(slog::Discard, slog::o!());
+        let _ = server.process_context_requests(&log);
+    });
+    let client = IpcContextClient::try_connect(&path).unwrap();
+
+    let prefix = context_key!("data/contracts");
+    let stats = client.get_tree_stats(&second, &prefix).unwrap().unwrap();
+    assert_eq!(Some(stats), index.get_tree_stats(&second, &prefix).unwrap());
+
+    let diff = client.diff(&first, &second, &prefix).unwrap().unwrap();
+    assert_eq!(diff.len(), 2);
+    assert_eq!(Some(diff), index.diff(&first, &second, &prefix).unwrap());
+
+    for mode in &[VerifyMode::Stored, VerifyMode::Irmin] {
+        let verification = client.verify_tree(&second, *mode).unwrap().unwrap();
+        assert!(verification.is_valid(), "{:?}", verification.mismatches);
+        assert!(verification.objects > 0);
+    }
+
+    std::fs::remove_file(&path).ok();
+}
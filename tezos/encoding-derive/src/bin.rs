@@ -9,6 +9,7 @@ use syn::spanned::Spanned;
 pub fn generate_bin_write_for_data(data: &DataWithEncoding) -> TokenStream {
     let name = data.name;
     let bin_write = generate_bin_write(&data.encoding);
+    let bin_size = generate_bin_size(&data.encoding);
     quote_spanned! {
         data.name.span()=>
         #[allow(unused_parens)]
@@ -17,6 +18,10 @@ pub fn generate_bin_write_for_data(data: &DataWithEncoding) -> TokenStream {
             fn bin_write(&self, out: &mut Vec<u8>) -> tezos_encoding::enc::BinResult {
                 #bin_write(self, out)
             }
+
+            fn bin_size(&self) -> usize {
+                #bin_size(self)
+            }
         }
     }
 }
@@ -181,3 +186,75 @@ fn generate_dynamic_bin_write(
         |size| quote_spanned!(span=> tezos_encoding::enc::bounded_dynamic(#size, #bin_write)),
     )
 }
+
+fn generate_bin_size(encoding: &Encoding) -> TokenStream {
+    match encoding {
+        Encoding::Unit => unreachable!(),
+        Encoding::Primitive(primitive, span) => generate_primitive_bin_size(*primitive, *span),
+        Encoding::Bytes(span) => quote_spanned!(*span=> tezos_encoding::enc::size::bytes),
+        Encoding::Path(path) => {
+            quote_spanned!(path.span()=> <#path as tezos_encoding::enc::BinWriter>::bin_size)
+        }
+        Encoding::Struct(encoding) => generate_struct_bin_size(encoding),
+        Encoding::Enum(encoding) => generate_enum_bin_size(encoding),
+        Encoding::String(_, span) => quote_spanned!(*span=> tezos_encoding::enc::size::string),
+        Encoding::OptionField(encoding, span) => {
+            let bin_size = generate_bin_size(encoding);
+            quote_spanned!(*span=> tezos_encoding::enc::size::optional_field(#bin_size))
+        }
+        Encoding::List(_, encoding, span) => {
+            let bin_size = generate_bin_size(encoding);
+            quote_spanned!(*span=> tezos_encoding::enc::size::list(#bin_size))
+        }
+        Encoding::Sized(size, _, span) => {
+            quote_spanned!(*span=> tezos_encoding::enc::size::fixed(#size as usize))
+        }
+        Encoding::Bounded(_, encoding, _) => generate_bin_size(encoding),
+        Encoding::Dynamic(_, encoding, span) => {
+            let bin_size = generate_bin_size(encoding);
+            quote_spanned!(*span=> tezos_encoding::enc::size::dynamic(#bin_size))
+        }
+        Encoding::Zarith(span) => quote_spanned!(*span=> tezos_encoding::enc::size::zarith),
+        Encoding::MuTez(span) => quote_spanned!(*span=> tezos_encoding::enc::size::mutez),
+    }
+}
+
+fn generate_primitive_bin_size(kind: PrimitiveEncoding, span: Span) -> TokenStream {
+    let ty = syn::Ident::new(get_primitive_number_mapping(kind).unwrap(), span);
+    quote_spanned!(span=> tezos_encoding::enc::size::fixed(std::mem::size_of::<#ty>()))
+}
+
+fn generate_struct_bin_size(encoding: &StructEncoding) -> TokenStream {
+    let fields_with_encoding = encoding.fields.iter().filter(|f| f.encoding().is_some());
+    let field = fields_with_encoding.clone().map(|f| f.name);
+    let field_bin_size = fields_with_encoding.map(|f| generate_bin_size(f.encoding().unwrap()));
+    quote_spanned! {
+        encoding.name.span()=>
+            (|data: &Self| -> usize {
+                0 #(+ #field_bin_size(&data.#field))*
+            })
+    }
+}
+
+fn generate_enum_bin_size(encoding: &EnumEncoding) -> TokenStream {
+    let tag_type = &encoding.tag_type;
+    let enum_name = encoding.name;
+    let tags_bin_size = encoding.tags.iter().map(|tag| {
+        let tag_name = tag.name;
+        match &tag.encoding {
+            Encoding::Unit => quote_spanned!(tag_name.span()=> #enum_name::#tag_name => 0),
+            encoding => {
+                let bin_size = generate_bin_size(encoding);
+                quote_spanned!(tag_name.span()=> #enum_name::#tag_name(inner) => #bin_size(inner))
+            }
+        }
+    });
+    quote_spanned! {
+        tag_type.span()=>
+            (|data: &Self| -> usize {
+                std::mem::size_of::<#tag_type>() + match data {
+                    #(#tags_bin_size),*
+                }
+            })
+    }
+}
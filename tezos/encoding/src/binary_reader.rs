@@ -10,15 +10,50 @@ use thiserror::Error;
 /// Error produced by a [BinaryReader].
 #[derive(Debug, Clone, Error)]
 pub enum BinaryReaderError {
-    Error(String),
+    /// Decoding failed, `path` is the field that failed to decode, e.g.
+    /// `operations[3].contents[0].slot`, empty if the error is not inside a field.
+    Error {
+        path: String,
+        message: String,
+    },
     UnknownTag(String),
+    /// Encoded message exceeds the maximal size allowed for its type.
+    TooLarge {
+        message: &'static str,
+        size: usize,
+        max_size: usize,
+    },
 }
 
 impl fmt::Display for BinaryReaderError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            BinaryReaderError::Error(error) => write!(f, "{}", error),
+            BinaryReaderError::Error { path, message } if path.is_empty() => {
+                write!(f, "{}", message)
+            }
+            BinaryReaderError::Error { path, message } => {
+                write!(f, "Failed to decode `{}`: {}", path, message)
+            }
             BinaryReaderError::UnknownTag(tag) => write!(f, "Unknown tag: {}", tag),
+            BinaryReaderError::TooLarge {
+                message,
+                size,
+                max_size,
+            } => write!(
+                f,
+                "Message {} is too large: {} bytes, at most {} bytes allowed",
+                message, size, max_size
+            ),
+        }
+    }
+}
+
+impl BinaryReaderError {
+    /// Path of the field that failed to decode, if known.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            BinaryReaderError::Error { path, .. } if !path.is_empty() => Some(path),
+            _ => None,
         }
     }
 }
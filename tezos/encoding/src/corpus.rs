@@ -0,0 +1,232 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Corpus of encoded data, for testing the decoders with external fuzzers.
+//!
+//! A corpus directory contains:
+//! - `cases/`, one file per encoded value, usable as the corpus of `cargo fuzz` or the
+//!   input directory of AFL,
+//! - `manifest`, one line per case with the file name and its expected validity
+//!   (`valid` or `invalid`), separated by a tab.
+//!
+//! Values exceeding the bounds of their encoding are exported as invalid cases,
+//! they are encoded with `enc::with_unchecked_bounds`. The export needs the `fuzzing` feature.
+
+#[cfg(any(test, feature = "fuzzing"))]
+use std::io::{BufWriter, Write};
+use std::{
+    fs,
+    io::{self, BufRead, BufReader},
+    path::{Component, Path, PathBuf},
+};
+
+use thiserror::Error;
+
+#[cfg(any(test, feature = "fuzzing"))]
+use crate::{
+    enc::{with_unchecked_bounds, BinWriter},
+    generator::{Generated, Generator, GeneratorFactory},
+};
+
+const CASES_DIR: &str = "cases";
+const MANIFEST_FILE: &str = "manifest";
+
+#[derive(Debug, Error)]
+pub enum CorpusError {
+    #[error("I/O error: {0}")]
+    IOError(#[from] io::Error),
+    #[error("Invalid manifest line {line}: {reason}")]
+    ManifestError { line: usize, reason: String },
+}
+
+/// Encoded value of a corpus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorpusCase {
+    /// File of the case, in the `cases` directory
+    pub name: String,
+    pub bytes: Vec<u8>,
+    /// `false` if the value exceeds the bounds of its encoding
+    pub valid: bool,
+}
+
+/// Number of cases exported by [export].
+#[cfg(any(test, feature = "fuzzing"))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CorpusStats {
+    pub valid: usize,
+    pub invalid: usize,
+    /// Values that cannot be encoded, even without the bounds
+    pub skipped: usize,
+}
+
+/// Encodes `values` into the corpus directory `dir`, replacing its manifest.
+#[cfg(any(test, feature = "fuzzing"))]
+pub fn export<T, I>(dir: impl AsRef<Path>, values: I) -> Result<CorpusStats, CorpusError>
+where
+    T: BinWriter,
+    I: IntoIterator<Item = T>,
+{
+    let cases_dir = dir.as_ref().join(CASES_DIR);
+    fs::create_dir_all(&cases_dir)?;
+    let mut manifest = BufWriter::new(fs::File::create(dir.as_ref().join(MANIFEST_FILE))?);
+    let mut stats = CorpusStats::default();
+
+    for (index, value) in values.into_iter().enumerate() {
+        let (bytes, valid) = match encode(&value) {
+            Some(encoded) => encoded,
+            None => {
+                stats.skipped += 1;
+                continue;
+            }
+        };
+
+        let name = format!("{:06}", index);
+        fs::write(cases_dir.join(&name), &bytes)?;
+        writeln!(
+            manifest,
+            "{}\t{}",
+            name,
+            if valid { "valid" } else { "invalid" }
+        )?;
+
+        if valid {
+            stats.valid += 1;
+        } else {
+            stats.invalid += 1;
+        }
+    }
+
+    manifest.flush()?;
+    Ok(stats)
+}
+
+/// Exports all the values of `T` generated by `factory`, see [export].
+#[cfg(any(test, feature = "fuzzing"))]
+pub fn export_generated<T, F>(
+    dir: impl AsRef<Path>,
+    factory: &mut F,
+) -> Result<CorpusStats, CorpusError>
+where
+    T: Generated + BinWriter,
+    F: GeneratorFactory,
+{
+    export(dir, T::generator("", factory).iter())
+}
+
+/// Reads the cases of the corpus directory `dir`.
+///
+/// The case files must be in the `cases` directory, the manifest cannot name a file outside of it.
+pub fn read(dir: impl AsRef<Path>) -> Result<Vec<CorpusCase>, CorpusError> {
+    let cases_dir: PathBuf = dir.as_ref().join(CASES_DIR);
+    let manifest = BufReader::new(fs::File::open(dir.as_ref().join(MANIFEST_FILE))?);
+    let mut cases = Vec::new();
+
+    for (index, line) in manifest.lines().enumerate() {
+        let line = line?;
+        let manifest_error = |reason: &str| CorpusError::ManifestError {
+            line: index + 1,
+            reason: reason.to_string(),
+        };
+
+        let (name, valid) = line
+            .split_once('\t')
+            .ok_or_else(|| manifest_error("missing validity"))?;
+        let valid = match valid {
+            "valid" => true,
+            "invalid" => false,
+            _ => return Err(manifest_error("unknown validity")),
+        };
+        let inside_cases_dir = !name.is_empty()
+            && Path::new(name)
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+        if !inside_cases_dir {
+            return Err(manifest_error("case file outside of the cases directory"));
+        }
+
+        cases.push(CorpusCase {
+            name: name.to_string(),
+            bytes: fs::read(cases_dir.join(name))?,
+            valid,
+        });
+    }
+
+    Ok(cases)
+}
+
+/// Returns the encoding of `value` and whether it is within the bounds of the encoding.
+#[cfg(any(test, feature = "fuzzing"))]
+fn encode<T: BinWriter>(value: &T) -> Option<(Vec<u8>, bool)> {
+    let mut bytes = Vec::new();
+    match value.bin_write(&mut bytes) {
+        Ok(()) => Some((bytes, true)),
+        Err(e) if e.is_bound_error() => {
+            let mut bytes = Vec::new();
+            with_unchecked_bounds(|| value.bin_write(&mut bytes))
+                .ok()
+                .map(|_| (bytes, false))
+        }
+        Err(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enc;
+
+    struct Strings(Vec<String>);
+
+    impl BinWriter for Strings {
+        fn bin_write(&self, out: &mut Vec<u8>) -> enc::BinResult {
+            enc::bounded_list(2, enc::bounded_string(3))(&self.0, out)
+        }
+    }
+
+    #[test]
+    fn test_export_and_read() {
+        let dir = std::env::temp_dir().join(format!("corpus-test-{}", std::process::id()));
+        let values = vec![
+            Strings(vec!["a".to_string()]),
+            Strings(vec!["a".to_string(), "b".to_string(), "c".to_string()]),
+            Strings(vec!["abcd".to_string()]),
+        ];
+
+        let stats = export(&dir, values).unwrap();
+        assert_eq!(
+            stats,
+            CorpusStats {
+                valid: 1,
+                invalid: 2,
+                skipped: 0
+            }
+        );
+
+        let cases = read(&dir).unwrap();
+        assert_eq!(cases.len(), 3);
+        assert!(cases[0].valid);
+        assert_eq!(cases[0].bytes, vec![0, 0, 0, 1, b'a']);
+        assert!(!cases[1].valid);
+        assert_eq!(cases[1].bytes.len(), 15);
+        assert!(!cases[2].valid);
+        assert_eq!(cases[2].bytes, vec![0, 0, 0, 4, b'a', b'b', b'c', b'd']);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_rejects_outside_files() {
+        let dir = std::env::temp_dir().join(format!("corpus-outside-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join(CASES_DIR)).unwrap();
+
+        for name in &["../manifest", "/etc/hostname", "a/../../manifest", "./", ""] {
+            fs::write(dir.join(MANIFEST_FILE), format!("{}\tvalid\n", name)).unwrap();
+            match read(&dir) {
+                Err(CorpusError::ManifestError { line: 1, .. }) => (),
+                result => panic!("Unexpected result for {:?}: {:?}", name, result),
+            }
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,6 +1,8 @@
 //! Copyright (c) SimpleStaking and Tezedge Contributors
 //! SPDX-License-Identifier: MIT
 
+#[cfg(any(test, feature = "fuzzing"))]
+use std::cell::Cell;
 use std::fmt;
 
 pub use tezos_encoding_derive::BinWriter;
@@ -16,6 +18,9 @@ pub enum BinErrorKind {
     /// Boundary violation error, contains expected and actual sizes.
     #[error("Boundary violation: expected {0}, got {1}")]
     SizeError(usize, usize),
+    /// Bounded encoding exceeding its limit, contains maximal and actual sizes.
+    #[error("Bound exceeded: at most {0} allowed, got {1}")]
+    BoundError(usize, usize),
     /// Field which encoding caused an error.
     #[error("Error encoding field: {0}")]
     FieldError(&'static str),
@@ -47,6 +52,10 @@ impl BinError {
         BinErrorKind::SizeError(expected, actual).into()
     }
 
+    pub fn bound_error(max: usize, actual: usize) -> Self {
+        BinErrorKind::BoundError(max, actual).into()
+    }
+
     pub fn custom(message: String) -> Self {
         BinErrorKind::CustomError(message).into()
     }
@@ -64,6 +73,29 @@ impl BinError {
     pub fn iter(&self) -> impl Iterator<Item = &BinErrorKind> {
         self.0.iter()
     }
+
+    /// Error that caused the failure, without the fields and variants context.
+    pub fn kind(&self) -> &BinErrorKind {
+        &self.0[0]
+    }
+
+    /// Returns `true` if a bounded encoding exceeds its limit.
+    pub fn is_bound_error(&self) -> bool {
+        matches!(self.kind(), BinErrorKind::BoundError(..))
+    }
+
+    /// Fields and enum variants containing the failing value, outermost first,
+    /// e.g. `["PeerMessageResponse::message", "PeerMessage::Advertise", "AdvertiseMessage::id"]`.
+    pub fn path(&self) -> Vec<&'static str> {
+        self.0
+            .iter()
+            .rev()
+            .filter_map(|kind| match kind {
+                BinErrorKind::FieldError(name) | BinErrorKind::VariantError(name) => Some(*name),
+                _ => None,
+            })
+            .collect()
+    }
 }
 
 impl fmt::Display for BinError {
@@ -86,6 +118,41 @@ impl From<std::io::Error> for BinError {
     }
 }
 
+#[cfg(any(test, feature = "fuzzing"))]
+thread_local! {
+    static CHECK_BOUNDS: Cell<bool> = Cell::new(true);
+}
+
+/// Runs `f` without checking the bounds of the encodings, to produce data exceeding them.
+///
+/// Meant for testing the decoders with invalid data, see [crate::corpus].
+#[cfg(any(test, feature = "fuzzing"))]
+pub fn with_unchecked_bounds<T>(f: impl FnOnce() -> T) -> T {
+    struct Restore(bool);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CHECK_BOUNDS.with(|check| check.set(self.0));
+        }
+    }
+
+    let _restore = Restore(CHECK_BOUNDS.with(|check| check.replace(false)));
+    f()
+}
+
+/// Returns `false` when called from [with_unchecked_bounds], for types checking their own bounds.
+#[cfg(any(test, feature = "fuzzing"))]
+pub fn check_bounds() -> bool {
+    CHECK_BOUNDS.with(|check| check.get())
+}
+
+/// Bounds are always checked, unless built with the `fuzzing` feature.
+#[cfg(not(any(test, feature = "fuzzing")))]
+#[inline(always)]
+pub fn check_bounds() -> bool {
+    true
+}
+
 pub struct AndThen<F, G, D1, D2> {
     f: F,
     g: G,
@@ -160,6 +227,17 @@ pub type BinResult = Result<(), BinError>;
 
 pub trait BinWriter {
     fn bin_write(&self, bytes: &mut Vec<u8>) -> BinResult;
+
+    /// Size of the binary encoding.
+    ///
+    /// Derived implementations compute it from the encoding without writing the data,
+    /// and without checking the bounds. The default implementation writes it, so it stops
+    /// at the first bound exceeded.
+    fn bin_size(&self) -> usize {
+        let mut out = Vec::new();
+        let _ = self.bin_write(&mut out);
+        out.len()
+    }
 }
 
 impl BinWriter for u16 {
@@ -167,6 +245,10 @@ impl BinWriter for u16 {
         put_bytes(&self.to_be_bytes(), out);
         Ok(())
     }
+
+    fn bin_size(&self) -> usize {
+        std::mem::size_of::<u16>()
+    }
 }
 
 pub fn put_bytes(bytes: &[u8], out: &mut Vec<u8>) {
@@ -228,6 +310,10 @@ macro_rules! encode_hash {
                 put_bytes(self.as_ref(), out);
                 Ok(())
             }
+
+            fn bin_size(&self) -> usize {
+                self.as_ref().len()
+            }
         }
     };
 }
@@ -273,10 +359,10 @@ pub fn string(data: impl AsRef<str>, out: &mut Vec<u8>) -> BinResult {
 
 pub fn bounded_string<S: AsRef<str>>(max_len: usize) -> impl FnMut(S, &mut Vec<u8>) -> BinResult {
     move |data, out| {
-        if data.as_ref().len() <= max_len {
+        if data.as_ref().len() <= max_len || !check_bounds() {
             string(data, out)
         } else {
-            Err(BinError::size_error(max_len, data.as_ref().len()))
+            Err(BinError::bound_error(max_len, data.as_ref().len()))
         }
     }
 }
@@ -296,12 +382,13 @@ pub fn bounded_list<T: IntoIterator>(
 ) -> impl FnMut(T, &mut Vec<u8>) -> BinResult {
     move |data, out| {
         let iter = data.into_iter();
+        let max_len = if check_bounds() { max_len } else { usize::MAX };
         if iter.size_hint().0 > max_len {
-            return Err(BinError::size_error(max_len, iter.size_hint().0));
+            return Err(BinError::bound_error(max_len, iter.size_hint().0));
         }
         iter.enumerate().try_for_each(|(i, item)| {
-            if i > max_len {
-                Err(BinError::size_error(max_len, i))
+            if i >= max_len {
+                Err(BinError::bound_error(max_len, i + 1))
             } else {
                 serializer.serialize(item, out)
             }
@@ -316,8 +403,8 @@ pub fn bounded<T>(
     move |data, out| {
         let size = out.len();
         serializer.serialize(data, out)?;
-        if out.len() - size > max_size {
-            Err(BinError::size_error(max_size, out.len() - size))
+        if out.len() - size > max_size && check_bounds() {
+            Err(BinError::bound_error(max_size, out.len() - size))
         } else {
             Ok(())
         }
@@ -343,8 +430,8 @@ pub fn bounded_dynamic<T>(
     move |data, out| {
         let mut tmp_out = Vec::new();
         serializer.serialize(data, &mut tmp_out)?;
-        if tmp_out.len() > max_size {
-            Err(BinError::size_error(max_size, tmp_out.len()))
+        if tmp_out.len() > max_size && check_bounds() {
+            Err(BinError::bound_error(max_size, tmp_out.len()))
         } else {
             put_size(tmp_out.len(), out);
             out.extend(tmp_out);
@@ -395,6 +482,51 @@ pub fn optional_field<'a, T: 'a>(
     }
 }
 
+/// Sizes of the encodings, used by the derived [BinWriter::bin_size].
+pub mod size {
+    use num_bigint::BigInt;
+
+    /// Size of the length prefix of dynamic data and strings.
+    const SIZE_BYTES: usize = 4;
+
+    pub fn fixed<T>(size: usize) -> impl Fn(T) -> usize {
+        move |_| size
+    }
+
+    pub fn bytes(bytes: &[u8]) -> usize {
+        bytes.len()
+    }
+
+    pub fn string(data: impl AsRef<str>) -> usize {
+        SIZE_BYTES + data.as_ref().len()
+    }
+
+    pub fn list<T: IntoIterator>(item: impl Fn(T::Item) -> usize) -> impl Fn(T) -> usize {
+        move |data| data.into_iter().map(&item).sum()
+    }
+
+    pub fn dynamic<T>(size: impl Fn(T) -> usize) -> impl Fn(T) -> usize {
+        move |data| SIZE_BYTES + size(data)
+    }
+
+    pub fn optional_field<'a, T: 'a>(
+        size: impl Fn(&'a T) -> usize,
+    ) -> impl Fn(&'a Option<T>) -> usize {
+        move |opt| 1 + opt.as_ref().map_or(0, &size)
+    }
+
+    /// Zarith numbers hold 6 bits in the first byte (with the sign), and 7 bits in the next ones.
+    pub fn zarith(n: &BigInt) -> usize {
+        let bits = n.magnitude().bits() as usize;
+        1 + (bits.saturating_sub(6) + 6) / 7
+    }
+
+    /// Mutez amounts hold 7 bits in each byte.
+    pub fn mutez(n: &BigInt) -> usize {
+        std::cmp::max(1, (n.magnitude().bits() as usize + 6) / 7)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::BinResult;
@@ -472,7 +604,14 @@ mod test {
         assert_eq!(&out, &[0, 1, 0, 2, 0, 3]);
 
         let mut out = Vec::new();
-        super::bounded_list(2, serialize_u16)(&[1, 2, 3], &mut out).expect_err("Should fail");
+        let err =
+            super::bounded_list(2, serialize_u16)(&[1, 2, 3], &mut out).expect_err("Should fail");
+        assert!(err.is_bound_error());
+
+        // no lower bound known in advance
+        let mut out = Vec::new();
+        let items = [1, 2, 3].iter().filter(|_| true);
+        super::bounded_list(2, serialize_u16)(items, &mut out).expect_err("Should fail");
     }
 
     #[test]
@@ -485,6 +624,44 @@ mod test {
         super::bounded(2, serialize_slice)(&[1, 2, 3], &mut out).expect_err("Should fail");
     }
 
+    #[test]
+    fn unchecked_bounds() {
+        let mut out = Vec::new();
+        super::with_unchecked_bounds(|| {
+            super::bounded_list(2, serialize_u16)(&[1, 2, 3], &mut out)
+        })
+        .expect("Should not fail");
+        assert_eq!(&out, &[0, 1, 0, 2, 0, 3]);
+
+        let mut out = Vec::new();
+        super::bounded_list(2, serialize_u16)(&[1, 2, 3], &mut out).expect_err("Should fail");
+    }
+
+    #[test]
+    fn sizes() {
+        use num_bigint::BigInt;
+
+        assert_eq!(super::size::string("abc"), 7);
+        assert_eq!(super::size::list(super::size::fixed(2))(&[1u16, 2, 3]), 6);
+        assert_eq!(super::size::dynamic(super::size::bytes)(&[1, 2, 3]), 7);
+        assert_eq!(
+            super::size::optional_field(super::size::fixed(2))(&Some(1u16)),
+            3
+        );
+        assert_eq!(
+            super::size::optional_field(super::size::fixed(2))(&None::<u16>),
+            1
+        );
+
+        for (n, size) in [(0i64, 1), (63, 1), (64, 2), (-64, 2), (8191, 2), (8192, 3)] {
+            assert_eq!(super::size::zarith(&BigInt::from(n)), size, "{}", n);
+        }
+        for (n, size) in [(0i64, 1), (127, 1), (128, 2), (16384, 3)] {
+            assert_eq!(super::size::mutez(&BigInt::from(n)), size, "{}", n);
+        }
+        assert_eq!(super::BinWriter::bin_size(&1u16), 2);
+    }
+
     #[test]
     fn dynamic() {
         let mut out = Vec::new();
@@ -518,6 +695,18 @@ mod test {
         ));
     }
 
+    #[test]
+    fn error_path() {
+        let mut out = Vec::new();
+        let field_ser = super::field("Inner::data", super::bounded_string(2));
+        let err =
+            super::variant_with_field("Message::Inner", super::u8, field_ser)(&0, &"abc", &mut out)
+                .expect_err("Should fail");
+        assert!(err.is_bound_error());
+        assert!(matches!(err.kind(), super::BinErrorKind::BoundError(2, 3)));
+        assert_eq!(err.path(), vec!["Message::Inner", "Inner::data"]);
+    }
+
     #[test]
     fn variant() {
         let mut out = Vec::new();
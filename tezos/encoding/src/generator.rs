@@ -8,7 +8,12 @@ use std::{
     ops::{Add, Bound, Div, Mul, RangeBounds, Rem, Shl, Shr, Sub},
 };
 
-use crate::encoding::Encoding;
+use num_bigint::BigInt;
+
+use crate::{
+    encoding::Encoding,
+    types::{Mutez, Zarith},
+};
 
 pub trait GeneratorFactory {
     /// Generator for [bool] data
@@ -47,7 +52,48 @@ pub trait GeneratorFactory {
     ) -> Box<dyn Generator<Item = usize>>;
 
     /// Generator for string data.
-    fn string(&mut self, field: &str, encoding: Encoding) -> Box<dyn Generator<Item = String>>;
+    ///
+    /// By default, strings of the lengths generated by [string_lengths] are generated.
+    fn string(&mut self, _field: &str, encoding: Encoding) -> Box<dyn Generator<Item = String>> {
+        Box::new(string_lengths(&encoding).map(|len| "a".repeat(len)))
+    }
+
+    /// Generator for the depth of recursive data, limited to `max_depth`.
+    ///
+    /// By default, all the depths up to one over the limit are generated.
+    fn depth(&mut self, _field: &str, max_depth: usize) -> Box<dyn Generator<Item = usize>> {
+        Box::new(full_range(0..=max_depth + 1))
+    }
+
+    /// Generator for [Zarith] numbers.
+    fn zarith(&mut self, _field: &str) -> Box<dyn Generator<Item = BigInt>> {
+        Box::new(values(
+            [
+                0i64,
+                1,
+                -1,
+                63,
+                64,
+                -64,
+                i32::MAX as i64,
+                i64::MIN,
+                i64::MAX,
+            ]
+            .iter()
+            .map(|n| BigInt::from(*n))
+            .collect::<Vec<_>>(),
+        ))
+    }
+
+    /// Generator for [Mutez] amounts.
+    fn mutez(&mut self, _field: &str) -> Box<dyn Generator<Item = BigInt>> {
+        Box::new(values(
+            [0i64, 1, 127, 128, i32::MAX as i64, i64::MAX]
+                .iter()
+                .map(|n| BigInt::from(*n))
+                .collect::<Vec<_>>(),
+        ))
+    }
 
     fn hash_bytes(
         &mut self,
@@ -527,18 +573,129 @@ where
     compose(len, item, |len, item| vec![item; len])
 }
 
-/// Helper composer for producing [std::opt::Option] generator out of element generator
+/// Generator of vectors containing different combinations of the items.
+#[derive(Debug, Clone)]
+pub struct MixedVecOfItems<T, G> {
+    items: Vec<T>,
+    len: G,
+    pattern: usize,
+}
+
+impl<T, G> MixedVecOfItems<T, G>
+where
+    G: Generator<Item = usize>,
+{
+    /// Number of patterns for the current length.
+    fn patterns(&self) -> usize {
+        match (self.len.value(), self.items.len()) {
+            (0, _) | (_, 0) => 1,
+            (1, items) => items,
+            (_, items) => items * 2,
+        }
+    }
+}
+
+impl<T, G> Generator for MixedVecOfItems<T, G>
+where
+    T: Clone,
+    G: Generator<Item = usize>,
+{
+    type Item = Vec<T>;
+
+    fn next(&mut self) -> bool {
+        self.pattern += 1;
+        if self.pattern < self.patterns() {
+            return true;
+        }
+        self.pattern = 0;
+        self.len.next()
+    }
+
+    fn value(&self) -> Self::Item {
+        let items = self.items.len();
+        if items == 0 {
+            return Vec::new();
+        }
+        (0..self.len.value())
+            .map(|i| {
+                if self.pattern < items {
+                    self.items[self.pattern].clone()
+                } else {
+                    self.items[(self.pattern - items + i) % items].clone()
+                }
+            })
+            .collect()
+    }
+}
+
+/// Helper composer for producing [std::vec::Vec] generator out of length generator and element
+/// generator, mixing the different elements.
+///
+/// For each length, vectors of a single element are generated for each element, then vectors
+/// cycling through all the elements, starting from each of them.
+/// Meant for recursive data like paths, where the order of the elements matters.
+pub fn mixed_vec_of_items<G1, G2>(item: G1, len: G2) -> MixedVecOfItems<G1::Item, G2>
+where
+    G1: Generator,
+    G2: Generator<Item = usize>,
+{
+    MixedVecOfItems {
+        items: item.iter().collect(),
+        len,
+        pattern: 0,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OptionGenerator<G1, G2> {
+    item: G1,
+    presence: G2,
+}
+
+impl<G1, G2> Generator for OptionGenerator<G1, G2>
+where
+    G1: Generator,
+    G2: Generator<Item = bool>,
+{
+    type Item = Option<G1::Item>;
+
+    fn next(&mut self) -> bool {
+        if self.presence.value() && self.item.next() {
+            return true;
+        }
+        self.presence.next()
+    }
+
+    fn value(&self) -> Self::Item {
+        if self.presence.value() {
+            Some(self.item.value())
+        } else {
+            None
+        }
+    }
+}
+
+/// Helper composer for producing [std::opt::Option] generator out of element generator.
+///
+/// [None] is generated once for each absent value of `presence`.
 pub fn option<G1, G2>(item: G1, presence: G2) -> impl Generator<Item = Option<G1::Item>>
 where
     G1: Generator,
     G2: Generator<Item = bool>,
 {
-    // TODO use optimized version that does not issue [None] multiple times.
-    compose(
-        presence,
-        item,
-        |presence, item| if presence { Some(item) } else { None },
-    )
+    OptionGenerator { item, presence }
+}
+
+/// Lengths of the strings generated by default for `encoding`, reaching its bound and one over.
+pub fn string_lengths(encoding: &Encoding) -> Box<dyn Generator<Item = usize>> {
+    match encoding {
+        Encoding::BoundedString(max_len) => Box::new(values([0, 1, *max_len, *max_len + 1])),
+        // the size of the string is included in the bound
+        Encoding::Bounded(max_size, _) if *max_size >= 4 => {
+            Box::new(values([0, 1, *max_size - 4, *max_size - 3]))
+        }
+        _ => Box::new(values([0, 1, 255, 256])),
+    }
 }
 
 /// Generates all integers in the specified range.
@@ -572,6 +729,18 @@ macro_rules! generated_hash {
     };
 }
 
+impl Generated for Zarith {
+    fn generator<F: GeneratorFactory>(field: &str, f: &mut F) -> Box<dyn Generator<Item = Self>> {
+        Box::new(f.zarith(field).map(Zarith))
+    }
+}
+
+impl Generated for Mutez {
+    fn generator<F: GeneratorFactory>(field: &str, f: &mut F) -> Box<dyn Generator<Item = Self>> {
+        Box::new(f.mutez(field).map(Mutez))
+    }
+}
+
 use crypto::hash::*;
 
 generated_hash!(ChainId);
@@ -674,6 +843,22 @@ mod test {
         assert_eq!(g.iter().collect::<Vec<u16>>(), vec![0, 100, 200, 300]);
     }
 
+    #[test]
+    fn option() {
+        let g = super::option(super::values([1, 2]), super::values([false, true]));
+        assert_eq!(g.iter().collect::<Vec<_>>(), vec![None, Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn mixed_vec_of_items() {
+        let g = super::mixed_vec_of_items(super::values(['l', 'r']), super::values([0, 1, 3]));
+        let values = g
+            .iter()
+            .map(|v| v.into_iter().collect::<String>())
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec!["", "l", "r", "lll", "rrr", "lrl", "rlr"]);
+    }
+
     #[test]
     fn compose() {
         let g1 = super::values([1, 2]);
@@ -10,6 +10,7 @@ pub mod types;
 pub mod binary_reader;
 pub mod binary_writer;
 
+pub mod corpus;
 pub mod enc;
 pub mod encoding;
 pub mod generator;
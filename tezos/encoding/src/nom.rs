@@ -62,6 +62,8 @@ pub mod error {
         Field(&'static str),
         /// Field name
         Variant(&'static str),
+        /// Index of a list element
+        Index(usize),
         /// Unknown/unsupported tag
         UnknownTag(String),
         /// Invalid tag
@@ -96,6 +98,14 @@ pub mod error {
             }
         }
 
+        pub(crate) fn add_index(self, index: usize) -> Self {
+            Self {
+                input: <&[u8]>::clone(&self.input),
+                kind: DecodeErrorKind::Index(index),
+                other: Some(Box::new(self)),
+            }
+        }
+
         pub(crate) fn limit(input: NomInput<'a>, kind: BoundedEncodingKind) -> Self {
             Self {
                 input,
@@ -136,6 +146,32 @@ pub mod error {
         }
     }
 
+    impl<I> DecodeError<I> {
+        /// Path of the field that failed to decode, e.g. `operations[3].contents[0].slot`,
+        /// empty if the error is not inside a field or a list.
+        pub fn path(&self) -> String {
+            let mut path = String::new();
+            let mut error = Some(self);
+            while let Some(current) = error {
+                match current.kind {
+                    DecodeErrorKind::Field(name) => {
+                        if !path.is_empty() {
+                            path.push('.');
+                        }
+                        // derived readers name the fields `Struct::field`
+                        path.push_str(name.rsplit("::").next().unwrap_or(name));
+                    }
+                    DecodeErrorKind::Index(index) => {
+                        let _ = write!(path, "[{}]", index);
+                    }
+                    _ => (),
+                }
+                error = current.other.as_deref();
+            }
+            path
+        }
+    }
+
     impl<I> nom::error::ParseError<I> for DecodeError<I> {
         fn from_error_kind(input: I, kind: ErrorKind) -> Self {
             Self {
@@ -185,6 +221,9 @@ pub mod error {
             DecodeErrorKind::Variant(name) => {
                 write!(res, " while decoding variant `{}`", name)
             }
+            DecodeErrorKind::Index(index) => {
+                write!(res, " while decoding list element {}", index)
+            }
             DecodeErrorKind::Bits(e) => write!(res, " while performing bits operation: {}", e),
             DecodeErrorKind::UnknownTag(tag) => write!(res, " caused by unsupported tag `{}`", tag),
             DecodeErrorKind::InvalidTag(tag) => write!(res, " caused by invalid tag `{}`", tag),
@@ -335,20 +374,21 @@ where
     ))
 }
 
-/// Parses input by applying parser `f` to it.
+/// Parses input by applying parser `f` to it until the input is consumed.
+///
+/// Errors are reported with the index of the list element that failed to decode.
 #[inline(always)]
 pub fn list<'a, O, F>(f: F) -> impl FnMut(NomInput<'a>) -> NomResult<'a, Vec<O>>
 where
     F: FnMut(NomInput<'a>) -> NomResult<'a, O>,
     O: Clone,
 {
-    fold_many0(f, Vec::new(), |mut list, item| {
-        list.push(item);
-        list
-    })
+    bounded_list(usize::MAX, f)
 }
 
 /// Parses input by applying parser `f` to it no more than `max` times.
+///
+/// Errors are reported with the index of the list element that failed to decode.
 #[inline(always)]
 pub fn bounded_list<'a, O, F>(
     max: usize,
@@ -358,26 +398,31 @@ where
     F: FnMut(NomInput<'a>) -> NomResult<'a, O>,
     O: Clone,
 {
-    move |input| {
-        let (input, list) = fold_many_m_n(
-            0,
-            max,
-            |i| f.parse(i),
-            Vec::new(),
-            |mut list, item| {
-                list.push(item);
-                list
-            },
-        )(input)?;
-        if input.input_len() > 0 {
-            Err(Err::Error(DecodeError {
-                input,
-                kind: DecodeErrorKind::Boundary(BoundedEncodingKind::List),
-                other: None,
-            }))
-        } else {
-            Ok((input, list))
+    move |mut input: NomInput<'a>| {
+        let mut list = Vec::new();
+        while input.input_len() > 0 {
+            if list.len() == max {
+                return Err(Err::Error(DecodeError {
+                    input,
+                    kind: DecodeErrorKind::Boundary(BoundedEncodingKind::List),
+                    other: None,
+                }));
+            }
+            let (rest, item) = f
+                .parse(input)
+                .map_err(|e| e.map(|e| e.add_index(list.len())))?;
+            if rest.input_len() == input.input_len() {
+                // an element consuming nothing would be parsed forever
+                return Err(Err::Error(DecodeError {
+                    input,
+                    kind: DecodeErrorKind::Nom(ErrorKind::Many0),
+                    other: None,
+                }));
+            }
+            list.push(item);
+            input = rest;
         }
+        Ok((input, list))
     }
 }
 
@@ -657,6 +702,30 @@ mod test {
         assert_eq!(res, Ok((&[][..], vec![0x0001, 0x0203, 0x0405])));
     }
 
+    #[test]
+    fn test_list_error_path() {
+        fn contents(input: NomInput) -> NomResult<Vec<u16>> {
+            list(field("Contents::slot", u16(Endianness::Big)))(input)
+        }
+        fn operations(input: NomInput) -> NomResult<Vec<Vec<u16>>> {
+            list(field("Operation::contents", dynamic(contents)))(input)
+        }
+        let mut message = field("Message::operations", operations);
+
+        // second element of the contents of the second operation is truncated
+        let input = &[0, 0, 0, 2, 0, 1, 0, 0, 0, 3, 0, 1, 2][..];
+        let err = match message(input) {
+            Err(Err::Error(err)) => err,
+            res => panic!("Error is expected: {:?}", res),
+        };
+        assert_eq!(err.path(), "operations[1].contents[1].slot");
+        assert!(convert_error(input, err).contains("while decoding list element 1"));
+
+        let res: NomResult<u8> = u8(&[][..]);
+        let err = res.expect_err("Error is expected");
+        assert!(matches!(err, Err::Error(err) if err.path().is_empty()));
+    }
+
     #[test]
     fn test_bounded_list() {
         let input = &[0, 1, 2, 3, 4, 5];
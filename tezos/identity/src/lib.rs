@@ -13,9 +13,12 @@ use thiserror::Error;
 use crypto::{crypto_box::PublicKeyError, hash::CryptoboxPublicKeyHash};
 use crypto::{
     crypto_box::{random_keypair, PublicKey, SecretKey},
-    proof_of_work::ProofOfWork,
+    proof_of_work::{PowError, ProofOfWork},
 };
 
+/// Proof-of-work difficulty expected by the peers of mainnet.
+pub const MAINNET_EXPECTED_POW: f64 = 26.0;
+
 #[derive(Error, Debug)]
 pub enum IdentityError {
     #[error("I/O error: {reason}")]
@@ -35,6 +38,12 @@ pub enum IdentityError {
 
     #[error("Public key error: {0}")]
     PublicKeyError(PublicKeyError),
+
+    #[error("Identity proof of work difficulty {actual:.2} is below the expected {expected}")]
+    InsufficientPowError { expected: f64, actual: f64 },
+
+    #[error("Proof of work error: {reason}")]
+    PowError { reason: PowError },
 }
 
 impl From<PublicKeyError> for IdentityError {
@@ -69,6 +78,18 @@ impl Identity {
         })
     }
 
+    /// Generates the identity, searching for the proof of work with `threads` threads.
+    pub fn generate_parallel(expected_pow: f64, threads: usize) -> Result<Self, PublicKeyError> {
+        let (sk, pk, peer_id) = random_keypair()?;
+        let pow = ProofOfWork::generate_parallel(&pk, expected_pow, threads);
+        Ok(Identity {
+            peer_id,
+            public_key: pk,
+            secret_key: sk,
+            proof_of_work_stamp: pow,
+        })
+    }
+
     pub fn check_peer_id(&self) -> Result<(), IdentityError> {
         if self.peer_id == self.public_key.public_key_hash()? {
             Ok(())
@@ -77,6 +98,29 @@ impl Identity {
         }
     }
 
+    /// Returns the difficulty reached by the proof of work stamp.
+    pub fn pow_difficulty(&self) -> Result<f64, IdentityError> {
+        self.proof_of_work_stamp
+            .difficulty(&self.public_key)
+            .map_err(|reason| IdentityError::PowError { reason })
+    }
+
+    /// Checks that the proof of work stamp meets the difficulty `expected_pow`,
+    /// e.g. [`MAINNET_EXPECTED_POW`].
+    pub fn check_pow(&self, expected_pow: f64) -> Result<(), IdentityError> {
+        match self
+            .proof_of_work_stamp
+            .check(&self.public_key, expected_pow)
+        {
+            Ok(()) => Ok(()),
+            Err(PowError::CheckFailed) => Err(IdentityError::InsufficientPowError {
+                expected: expected_pow,
+                actual: self.pow_difficulty()?,
+            }),
+            Err(reason) => Err(IdentityError::PowError { reason }),
+        }
+    }
+
     pub fn from_json(json: &str) -> Result<Identity, IdentityError> {
         let identity: HashMap<String, Value> = serde_json::from_str(json)
             .map_err(|e| IdentityError::IdentitySerdeError { reason: e })?;
@@ -206,6 +250,37 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_identity_generate_parallel() -> Result<(), anyhow::Error> {
+        let identity = Identity::generate_parallel(16f64, 4)?;
+
+        assert!(identity.check_peer_id().is_ok());
+        assert!(identity
+            .proof_of_work_stamp
+            .check(&identity.public_key, 16f64)
+            .is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_identity_check_pow() -> Result<(), anyhow::Error> {
+        let identity = Identity::generate(4f64)?;
+
+        let difficulty = identity.pow_difficulty()?;
+        assert!(difficulty > 4f64);
+        assert!(identity.check_pow(4f64).is_ok());
+
+        match identity.check_pow(difficulty + 1f64) {
+            Err(IdentityError::InsufficientPowError { actual, .. }) => {
+                assert_eq!(actual, difficulty)
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_identity_json_serde_generated_by_tezos() -> Result<(), anyhow::Error> {
         let expected_json = serde_json::json!(
@@ -32,6 +32,12 @@ pub trait BinaryRead: Sized {
 pub trait BinaryWrite {
     /// Produce bytes from the struct.
     fn as_bytes(&self) -> Result<Vec<u8>, BinaryWriterError>;
+
+    /// Size of the bytes produced by [Self::as_bytes], computed from the encoding
+    /// without serializing the struct.
+    ///
+    /// Bounds are not checked, a message exceeding them fails to serialize.
+    fn estimated_binary_size(&self) -> usize;
 }
 
 /// Message that can be both encoded and decoded into binary format.
@@ -49,6 +55,11 @@ where
         self.bin_write(&mut res)?;
         Ok(res)
     }
+
+    #[inline]
+    fn estimated_binary_size(&self) -> usize {
+        self.bin_size()
+    }
 }
 
 impl<T> BinaryRead for T
@@ -109,7 +120,10 @@ pub(crate) fn map_nom_error(input: NomInput, error: NomError) -> BinaryReaderErr
     if let Some(unknown_tag) = error.get_unknown_tag() {
         BinaryReaderError::UnknownTag(unknown_tag.clone())
     } else {
-        BinaryReaderError::Error(convert_error(input, error))
+        BinaryReaderError::Error {
+            path: error.path(),
+            message: convert_error(input, error),
+        }
     }
 }
 
@@ -0,0 +1,237 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Encryption of the chunks exchanged once the connection is established.
+//!
+//! Each chunk is encrypted with the precomputed key of the connection and the next nonce of its
+//! direction: the local nonce for the chunks sent, the remote one for the chunks received.
+//! [`ChunkEncrypter`] and [`ChunkDecrypter`] own the nonce of their direction, and move it
+//! forward only when a chunk was successfully encrypted or decrypted, so that a failure cannot
+//! silently shift the nonces of the following chunks. [`ChunkCrypto`] holds both directions.
+
+use thiserror::Error;
+
+use crypto::{
+    crypto_box::{PrecomputedKey, BOX_ZERO_BYTES},
+    nonce::{Nonce, NoncePair},
+    CryptoError,
+};
+
+use super::binary_message::{BinaryChunk, BinaryChunkError, CONTENT_LENGTH_MAX};
+
+/// Max size of the content of a chunk before its encryption, leaving room for the MAC.
+pub const CHUNK_CONTENT_MAX: usize = CONTENT_LENGTH_MAX - BOX_ZERO_BYTES;
+
+#[derive(Debug, Error)]
+pub enum ChunkCryptoError {
+    #[error("Chunk content too large: {size} > {max}")]
+    ContentTooLarge { size: usize, max: usize },
+    #[error("Chunk cannot be decrypted after {decrypted} chunks, the nonces are out of sync")]
+    NonceDesync { decrypted: u64 },
+    #[error("Crypto error: {0}")]
+    CryptoError(#[from] CryptoError),
+    #[error("Chunk error: {0}")]
+    ChunkError(#[from] BinaryChunkError),
+}
+
+/// Encrypts the chunks sent on a connection, e.g. by the writing half of the stream.
+pub struct ChunkEncrypter {
+    precomputed_key: PrecomputedKey,
+    /// Nonce of the next chunk sent
+    nonce: Nonce,
+}
+
+impl ChunkEncrypter {
+    pub fn new(precomputed_key: PrecomputedKey, local_nonce: Nonce) -> Self {
+        Self {
+            precomputed_key,
+            nonce: local_nonce,
+        }
+    }
+
+    /// Encrypts `content` into the next chunk to send.
+    pub fn encrypt_chunk(&mut self, content: &[u8]) -> Result<BinaryChunk, ChunkCryptoError> {
+        if content.len() > CHUNK_CONTENT_MAX {
+            return Err(ChunkCryptoError::ContentTooLarge {
+                size: content.len(),
+                max: CHUNK_CONTENT_MAX,
+            });
+        }
+
+        let encrypted = self.precomputed_key.encrypt(content, &self.nonce)?;
+        let chunk = BinaryChunk::from_content(&encrypted)?;
+        self.nonce = self.nonce.increment();
+
+        Ok(chunk)
+    }
+
+    /// Encrypts `bytes` into as many chunks as needed.
+    pub fn encrypt_message(&mut self, bytes: &[u8]) -> Result<Vec<BinaryChunk>, ChunkCryptoError> {
+        bytes
+            .chunks(CHUNK_CONTENT_MAX)
+            .map(|content| self.encrypt_chunk(content))
+            .collect()
+    }
+}
+
+/// Decrypts the chunks received on a connection, e.g. by the reading half of the stream.
+pub struct ChunkDecrypter {
+    precomputed_key: PrecomputedKey,
+    /// Nonce of the next chunk received
+    nonce: Nonce,
+    /// Number of chunks decrypted
+    decrypted: u64,
+}
+
+impl ChunkDecrypter {
+    pub fn new(precomputed_key: PrecomputedKey, remote_nonce: Nonce) -> Self {
+        Self {
+            precomputed_key,
+            nonce: remote_nonce,
+            decrypted: 0,
+        }
+    }
+
+    /// Decrypts the content of the next chunk received.
+    pub fn decrypt_chunk(&mut self, chunk: &BinaryChunk) -> Result<Vec<u8>, ChunkCryptoError> {
+        let content = match self.precomputed_key.decrypt(chunk.content(), &self.nonce) {
+            Ok(content) => content,
+            Err(CryptoError::FailedToDecrypt) => {
+                return Err(ChunkCryptoError::NonceDesync {
+                    decrypted: self.decrypted,
+                })
+            }
+            Err(error) => return Err(error.into()),
+        };
+        self.nonce = self.nonce.increment();
+        self.decrypted += 1;
+
+        Ok(content)
+    }
+}
+
+/// Encrypts the chunks sent and decrypts the chunks received on a connection.
+pub struct ChunkCrypto {
+    encrypter: ChunkEncrypter,
+    decrypter: ChunkDecrypter,
+}
+
+impl ChunkCrypto {
+    pub fn new(precomputed_key: PrecomputedKey, nonces: NoncePair) -> Self {
+        Self {
+            encrypter: ChunkEncrypter::new(precomputed_key.clone(), nonces.local),
+            decrypter: ChunkDecrypter::new(precomputed_key, nonces.remote),
+        }
+    }
+
+    /// Splits into the encryption of the chunks sent and the decryption of the chunks received.
+    pub fn split(self) -> (ChunkEncrypter, ChunkDecrypter) {
+        (self.encrypter, self.decrypter)
+    }
+
+    /// Encrypts `content` into the next chunk to send.
+    pub fn encrypt_chunk(&mut self, content: &[u8]) -> Result<BinaryChunk, ChunkCryptoError> {
+        self.encrypter.encrypt_chunk(content)
+    }
+
+    /// Encrypts `bytes` into as many chunks as needed.
+    pub fn encrypt_message(&mut self, bytes: &[u8]) -> Result<Vec<BinaryChunk>, ChunkCryptoError> {
+        self.encrypter.encrypt_message(bytes)
+    }
+
+    /// Decrypts the content of the next chunk received.
+    pub fn decrypt_chunk(&mut self, chunk: &BinaryChunk) -> Result<Vec<u8>, ChunkCryptoError> {
+        self.decrypter.decrypt_chunk(chunk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crypto::crypto_box::random_keypair;
+
+    use super::*;
+
+    fn pair() -> (ChunkCrypto, ChunkCrypto) {
+        let (sk_a, pk_a, _) = random_keypair().unwrap();
+        let (sk_b, pk_b, _) = random_keypair().unwrap();
+        let nonce_a = Nonce::random();
+        let nonce_b = Nonce::random();
+
+        let a = ChunkCrypto::new(
+            PrecomputedKey::precompute(&pk_b, &sk_a),
+            NoncePair {
+                local: nonce_a.clone(),
+                remote: nonce_b.clone(),
+            },
+        );
+        let b = ChunkCrypto::new(
+            PrecomputedKey::precompute(&pk_a, &sk_b),
+            NoncePair {
+                local: nonce_b,
+                remote: nonce_a,
+            },
+        );
+        (a, b)
+    }
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let (mut a, mut b) = pair();
+
+        for content in [&b"hello"[..], &b"world"[..]].iter() {
+            let chunk = a.encrypt_chunk(content).unwrap();
+            assert_eq!(b.decrypt_chunk(&chunk).unwrap(), content.to_vec());
+        }
+
+        let message = vec![7; CHUNK_CONTENT_MAX + 10];
+        let chunks = b.encrypt_message(&message).unwrap();
+        assert_eq!(chunks.len(), 2);
+        let decrypted: Vec<u8> = chunks
+            .iter()
+            .flat_map(|chunk| a.decrypt_chunk(chunk).unwrap())
+            .collect();
+        assert_eq!(decrypted, message);
+    }
+
+    #[test]
+    fn test_content_too_large() {
+        let (mut a, mut b) = pair();
+
+        assert!(matches!(
+            a.encrypt_chunk(&[0; CHUNK_CONTENT_MAX + 1]),
+            Err(ChunkCryptoError::ContentTooLarge { .. })
+        ));
+
+        // the nonce was not used
+        let chunk = a.encrypt_chunk(&[0; CHUNK_CONTENT_MAX]).unwrap();
+        assert!(b.decrypt_chunk(&chunk).is_ok());
+    }
+
+    #[test]
+    fn test_nonce_desync() {
+        let (mut a, mut b) = pair();
+
+        let first = a.encrypt_chunk(b"first").unwrap();
+        let second = a.encrypt_chunk(b"second").unwrap();
+
+        assert!(matches!(
+            b.decrypt_chunk(&second),
+            Err(ChunkCryptoError::NonceDesync { decrypted: 0 })
+        ));
+        // the failure did not move the nonce
+        assert_eq!(b.decrypt_chunk(&first).unwrap(), b"first".to_vec());
+        assert_eq!(b.decrypt_chunk(&second).unwrap(), b"second".to_vec());
+    }
+
+    #[test]
+    fn test_split() {
+        let (a, b) = pair();
+        let (mut a_encrypter, mut a_decrypter) = a.split();
+        let (mut b_encrypter, mut b_decrypter) = b.split();
+
+        let chunk = a_encrypter.encrypt_chunk(b"ping").unwrap();
+        assert_eq!(b_decrypter.decrypt_chunk(&chunk).unwrap(), b"ping".to_vec());
+        let chunk = b_encrypter.encrypt_chunk(b"pong").unwrap();
+        assert_eq!(a_decrypter.decrypt_chunk(&chunk).unwrap(), b"pong".to_vec());
+    }
+}
@@ -0,0 +1,45 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Replay of the corpora exported with [tezos_encoding::corpus].
+
+use std::path::Path;
+
+use tezos_encoding::corpus::{self, CorpusCase, CorpusError};
+
+use super::binary_message::BinaryMessage;
+
+/// Case of a corpus not decoded as expected.
+#[derive(Debug)]
+pub struct CorpusMismatch {
+    pub case: CorpusCase,
+    pub reason: String,
+}
+
+/// Decodes all the cases of the corpus directory `dir` as `M`, and returns the ones where
+/// the decoder does not behave as expected.
+///
+/// Valid cases must be decoded, and encoded back into the same bytes.
+/// Invalid cases must be rejected.
+pub fn replay_corpus<M: BinaryMessage>(
+    dir: impl AsRef<Path>,
+) -> Result<Vec<CorpusMismatch>, CorpusError> {
+    let mismatches = corpus::read(dir)?
+        .into_iter()
+        .filter_map(|case| {
+            let reason = match (M::from_bytes(&case.bytes), case.valid) {
+                (Ok(message), true) => match message.as_bytes() {
+                    Ok(bytes) if bytes == case.bytes => return None,
+                    Ok(_) => "encoded back into different bytes".to_string(),
+                    Err(e) => format!("cannot be encoded back: {}", e),
+                },
+                (Err(e), true) => format!("rejected: {}", e),
+                (Ok(_), false) => "accepted".to_string(),
+                (Err(_), false) => return None,
+            };
+            Some(CorpusMismatch { case, reason })
+        })
+        .collect();
+
+    Ok(mismatches)
+}
@@ -0,0 +1,96 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Incremental decoding of messages received in several chunks.
+
+use std::{marker::PhantomData, task::Poll};
+
+use tezos_encoding::binary_reader::BinaryReaderError;
+
+use super::{
+    binary_message::{BinaryRead, SizeFromChunk, CONTENT_LENGTH_MAX},
+    encoding::peer::PeerMessageResponse,
+};
+
+/// Decoder fed with the decrypted content of the chunks as they arrive.
+///
+/// The size of the message is read from the first chunk, and the message is accumulated in a
+/// buffer until that size is reached. The size is announced by the peer: it is bounded by
+/// `SizeFromChunk::size_from_chunk`, which refuses the messages too large before anything is
+/// buffered, and the buffer is only preallocated for one chunk and grows with the chunks actually
+/// received. A message contained in a single chunk is decoded directly from that chunk, without
+/// being copied.
+///
+/// Once a message is decoded (or fails to decode), the decoder is ready for the next one.
+pub struct MessageDecoder<M> {
+    /// Expected size of the message being received
+    size: Option<usize>,
+    buffer: Vec<u8>,
+    _phantom: PhantomData<M>,
+}
+
+/// Decoder of the messages exchanged once the connection is established.
+pub type PeerMessageDecoder = MessageDecoder<PeerMessageResponse>;
+
+impl<M> Default for MessageDecoder<M> {
+    fn default() -> Self {
+        Self {
+            size: None,
+            buffer: Vec::new(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<M> MessageDecoder<M>
+where
+    M: BinaryRead + SizeFromChunk,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds the content of the next chunk.
+    ///
+    /// Returns `Poll::Pending` until the chunks of a complete message have been fed.
+    pub fn feed(&mut self, chunk: &[u8]) -> Poll<Result<M, BinaryReaderError>> {
+        let size = match self.size {
+            Some(size) => size,
+            None => match M::size_from_chunk(chunk) {
+                Ok(size) if size <= chunk.len() => return Poll::Ready(M::from_bytes(chunk)),
+                Ok(size) => {
+                    self.buffer = Vec::with_capacity(size.min(CONTENT_LENGTH_MAX));
+                    self.size = Some(size);
+                    size
+                }
+                Err(e) => return Poll::Ready(Err(e)),
+            },
+        };
+
+        self.buffer.extend_from_slice(chunk);
+
+        if self.buffer.len() < size {
+            return Poll::Pending;
+        }
+
+        self.size = None;
+        let buffer = std::mem::take(&mut self.buffer);
+        Poll::Ready(M::from_bytes(buffer))
+    }
+
+    /// Returns `true` when the chunks of a message have been partially fed.
+    pub fn is_partial(&self) -> bool {
+        self.size.is_some()
+    }
+
+    /// Number of bytes fed for the message being received.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Discards the message being received.
+    pub fn reset(&mut self) {
+        self.size = None;
+        self.buffer = Vec::new();
+    }
+}
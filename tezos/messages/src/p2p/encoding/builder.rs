@@ -0,0 +1,91 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Validation of the messages constructed by the builders, so that the limits enforced
+//! by the peers are checked before sending them.
+
+use thiserror::Error;
+
+use crypto::hash::ChainId;
+use tezos_encoding::enc::BinWriter;
+
+use super::{
+    block_header::BlockHeader,
+    limits::{BLOCK_HEADER_MAX_SIZE, MEMPOOL_MAX_OPERATIONS, MEMPOOL_MAX_SIZE},
+    mempool::Mempool,
+};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum MessageBuilderError {
+    #[error("Missing field: {field}")]
+    MissingField { field: &'static str },
+    #[error("Chain id {actual} does not match the expected chain id {expected}")]
+    ChainIdMismatch { expected: String, actual: String },
+    #[error("History is too long: {len} blocks, at most {max} allowed")]
+    HistoryTooLong { len: usize, max: usize },
+    #[error("Too many {list} operations in mempool: {len}, at most {max} allowed")]
+    TooManyOperations {
+        list: &'static str,
+        len: usize,
+        max: usize,
+    },
+    #[error("Encoded {what} is too large: {size} bytes, at most {max_size} allowed")]
+    TooLarge {
+        what: &'static str,
+        size: usize,
+        max_size: usize,
+    },
+}
+
+pub(crate) fn required<T>(value: Option<T>, field: &'static str) -> Result<T, MessageBuilderError> {
+    value.ok_or(MessageBuilderError::MissingField { field })
+}
+
+pub(crate) fn check_chain_id(
+    chain_id: &ChainId,
+    expected: Option<&ChainId>,
+) -> Result<(), MessageBuilderError> {
+    match expected {
+        Some(expected) if expected != chain_id => Err(MessageBuilderError::ChainIdMismatch {
+            expected: expected.to_base58_check(),
+            actual: chain_id.to_base58_check(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn check_block_header(header: &BlockHeader) -> Result<(), MessageBuilderError> {
+    check_size("block header", header.bin_size(), BLOCK_HEADER_MAX_SIZE)
+}
+
+pub(crate) fn check_mempool(mempool: &Mempool) -> Result<(), MessageBuilderError> {
+    for (list, operations) in [
+        ("known valid", mempool.known_valid()),
+        ("pending", mempool.pending()),
+    ] {
+        if operations.len() > MEMPOOL_MAX_OPERATIONS {
+            return Err(MessageBuilderError::TooManyOperations {
+                list,
+                len: operations.len(),
+                max: MEMPOOL_MAX_OPERATIONS,
+            });
+        }
+    }
+    check_size("mempool", mempool.bin_size(), MEMPOOL_MAX_SIZE)
+}
+
+pub(crate) fn check_size(
+    what: &'static str,
+    size: usize,
+    max_size: usize,
+) -> Result<(), MessageBuilderError> {
+    if size > max_size {
+        Err(MessageBuilderError::TooLarge {
+            what,
+            size,
+            max_size,
+        })
+    } else {
+        Ok(())
+    }
+}
@@ -11,6 +11,7 @@ use tezos_encoding::nom::NomReader;
 
 use crate::p2p::encoding::block_header::BlockHeader;
 
+use super::builder::{self, MessageBuilderError};
 use super::limits::CURRENT_BRANCH_HISTORY_MAX_LENGTH;
 
 #[derive(
@@ -71,6 +72,62 @@ impl CurrentBranch {
     }
 }
 
+/// Builder of [CurrentBranchMessage], checking the limits of the encoding before the message
+/// is sent, instead of failing when it is serialized.
+#[derive(Debug, Clone, Default)]
+pub struct CurrentBranchMessageBuilder {
+    chain_id: Option<ChainId>,
+    expected_chain_id: Option<ChainId>,
+    current_head: Option<BlockHeader>,
+    history: Vec<BlockHash>,
+}
+
+impl CurrentBranchMessageBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn chain_id(mut self, chain_id: ChainId) -> Self {
+        self.chain_id = Some(chain_id);
+        self
+    }
+
+    /// Chain id of the node, the message is rejected if built for another chain.
+    pub fn expected_chain_id(mut self, chain_id: ChainId) -> Self {
+        self.expected_chain_id = Some(chain_id);
+        self
+    }
+
+    pub fn current_head(mut self, current_head: BlockHeader) -> Self {
+        self.current_head = Some(current_head);
+        self
+    }
+
+    /// Hashes from the top of the chain to the bottom, empty if not set.
+    pub fn history(mut self, history: Vec<BlockHash>) -> Self {
+        self.history = history;
+        self
+    }
+
+    pub fn build(self) -> Result<CurrentBranchMessage, MessageBuilderError> {
+        let chain_id = builder::required(self.chain_id, "chain_id")?;
+        builder::check_chain_id(&chain_id, self.expected_chain_id.as_ref())?;
+        let current_head = builder::required(self.current_head, "current_head")?;
+        builder::check_block_header(&current_head)?;
+        if self.history.len() > CURRENT_BRANCH_HISTORY_MAX_LENGTH {
+            return Err(MessageBuilderError::HistoryTooLong {
+                len: self.history.len(),
+                max: CURRENT_BRANCH_HISTORY_MAX_LENGTH,
+            });
+        }
+
+        Ok(CurrentBranchMessage::new(
+            chain_id,
+            CurrentBranch::new(current_head, self.history),
+        ))
+    }
+}
+
 // -----------------------------------------------------------------------------------------------
 #[derive(
     Serialize, Debug, Clone, HasEncoding, NomReader, BinWriter, tezos_encoding::generator::Generated,
@@ -10,6 +10,7 @@ use tezos_encoding::encoding::HasEncoding;
 use tezos_encoding::nom::NomReader;
 
 use super::block_header::BlockHeader;
+use super::builder::{self, MessageBuilderError};
 use super::limits::BLOCK_HEADER_MAX_SIZE;
 use super::mempool::Mempool;
 
@@ -48,6 +49,59 @@ impl CurrentHeadMessage {
     }
 }
 
+/// Builder of [CurrentHeadMessage], checking the limits of the encoding before the message
+/// is sent, instead of failing when it is serialized.
+#[derive(Debug, Clone, Default)]
+pub struct CurrentHeadMessageBuilder {
+    chain_id: Option<ChainId>,
+    expected_chain_id: Option<ChainId>,
+    current_block_header: Option<BlockHeader>,
+    current_mempool: Mempool,
+}
+
+impl CurrentHeadMessageBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn chain_id(mut self, chain_id: ChainId) -> Self {
+        self.chain_id = Some(chain_id);
+        self
+    }
+
+    /// Chain id of the node, the message is rejected if built for another chain.
+    pub fn expected_chain_id(mut self, chain_id: ChainId) -> Self {
+        self.expected_chain_id = Some(chain_id);
+        self
+    }
+
+    pub fn current_block_header(mut self, current_block_header: BlockHeader) -> Self {
+        self.current_block_header = Some(current_block_header);
+        self
+    }
+
+    /// Mempool of the message, empty if not set.
+    pub fn current_mempool(mut self, current_mempool: Mempool) -> Self {
+        self.current_mempool = current_mempool;
+        self
+    }
+
+    pub fn build(self) -> Result<CurrentHeadMessage, MessageBuilderError> {
+        let chain_id = builder::required(self.chain_id, "chain_id")?;
+        builder::check_chain_id(&chain_id, self.expected_chain_id.as_ref())?;
+        let current_block_header =
+            builder::required(self.current_block_header, "current_block_header")?;
+        builder::check_block_header(&current_block_header)?;
+        builder::check_mempool(&self.current_mempool)?;
+
+        Ok(CurrentHeadMessage::new(
+            chain_id,
+            current_block_header,
+            self.current_mempool,
+        ))
+    }
+}
+
 // -----------------------------------------------------------------------------------------------
 #[derive(
     Serialize,
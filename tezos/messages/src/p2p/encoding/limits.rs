@@ -280,3 +280,68 @@ pub const BLOCK_HEADER_PROTOCOL_DATA_MAX_SIZE: usize = BLOCK_HEADER_MAX_SIZE
         + 4
         + BLOCK_HEADER_FITNESS_MAX_SIZE
         + HashType::ContextHash.size());
+
+/// Maximal size of the `Advertise` message body.
+///
+/// OCaml ref: tezos/src/lib_p2p/p2p_message.ml:64
+/// ```ocaml
+///                 (req "id" (Variable.list ~max_length:100 P2p_point.Id.encoding))
+/// ```
+pub const ADVERTISE_MESSAGE_MAX_SIZE: usize = ADVERTISE_ID_LIST_MAX_LENGTH * P2P_POINT_MAX_SIZE;
+
+/// Maximal size of the `SwapRequest`/`SwapAck` message body.
+pub const SWAP_MESSAGE_MAX_SIZE: usize =
+    P2P_POINT_MAX_SIZE + HashType::CryptoboxPublicKeyHash.size();
+
+/// Maximal size of messages consisting only of a chain id,
+/// i.e. `GetCurrentBranch`, `GetCurrentHead` and `Deactivate`.
+pub const CHAIN_ID_MESSAGE_MAX_SIZE: usize = HashType::ChainId.size();
+
+/// Maximal size of the `CurrentBranch` message body.
+pub const CURRENT_BRANCH_MESSAGE_MAX_SIZE: usize = HashType::ChainId.size()
+    + 4
+    + BLOCK_HEADER_MAX_SIZE
+    + CURRENT_BRANCH_HISTORY_MAX_LENGTH * HashType::BlockHash.size();
+
+/// Maximal size of the `CurrentHead` message body.
+pub const CURRENT_HEAD_MESSAGE_MAX_SIZE: usize =
+    HashType::ChainId.size() + 4 + BLOCK_HEADER_MAX_SIZE + MEMPOOL_MAX_SIZE;
+
+/// Maximal size of the `GetBlockHeaders` message body.
+pub const GET_BLOCK_HEADERS_MESSAGE_MAX_SIZE: usize =
+    4 + GET_BLOCK_HEADERS_MAX_LENGTH * HashType::BlockHash.size();
+
+/// Maximal size of the `BlockHeader` message body.
+pub const BLOCK_HEADER_MESSAGE_MAX_SIZE: usize = BLOCK_HEADER_MAX_SIZE;
+
+/// Maximal size of the `GetOperations` message body.
+pub const GET_OPERATIONS_MESSAGE_MAX_SIZE: usize =
+    4 + GET_OPERATIONS_MAX_LENGTH * HashType::OperationHash.size();
+
+/// Maximal size of the `Operation` message body.
+pub const OPERATION_MESSAGE_MAX_SIZE: usize = HashType::BlockHash.size() + OPERATION_MAX_SIZE;
+
+/// Maximal size of the `GetProtocols` message body.
+pub const GET_PROTOCOLS_MESSAGE_MAX_SIZE: usize =
+    4 + GET_PROTOCOLS_MAX_LENGTH * HashType::ProtocolHash.size();
+
+/// Maximal size of the `Protocol` message body.
+pub const PROTOCOL_MESSAGE_MAX_SIZE: usize = PROTOCOL_MAX_SIZE;
+
+/// Maximal size of the `GetOperationsForBlocks` message body.
+pub const GET_OPERATIONS_FOR_BLOCKS_MESSAGE_MAX_SIZE: usize =
+    4 + GET_OPERATIONS_FOR_BLOCKS_MAX_LENGTH * (HashType::BlockHash.size() + 1);
+
+/// Maximal size of the `OperationsForBlocks` message body.
+///
+/// The operation hashes path has at most [MAX_PASS_MERKLE_DEPTH] steps, each one
+/// being a one byte tag followed by a hash, and is terminated by a one byte tag.
+///
+/// [MAX_PASS_MERKLE_DEPTH]: super::operations_for_blocks::MAX_PASS_MERKLE_DEPTH
+pub const OPERATIONS_FOR_BLOCKS_MESSAGE_MAX_SIZE: usize = HashType::BlockHash.size()
+    + 1
+    + super::operations_for_blocks::MAX_PASS_MERKLE_DEPTH
+        * (1 + HashType::OperationListListHash.size())
+    + 1
+    + 4
+    + OPERATION_LIST_MAX_SIZE;
@@ -6,6 +6,7 @@
 pub mod ack;
 pub mod advertise;
 pub mod block_header;
+pub mod builder;
 pub mod connection;
 pub mod current_branch;
 pub mod current_head;
@@ -26,9 +27,14 @@ pub mod prelude {
     pub use super::block_header::{
         BlockHeader, BlockHeaderBuilder, BlockHeaderMessage, GetBlockHeadersMessage,
     };
+    pub use super::builder::MessageBuilderError;
     pub use super::connection::ConnectionMessage;
-    pub use super::current_branch::{CurrentBranch, CurrentBranchMessage, GetCurrentBranchMessage};
-    pub use super::current_head::{CurrentHeadMessage, GetCurrentHeadMessage};
+    pub use super::current_branch::{
+        CurrentBranch, CurrentBranchMessage, CurrentBranchMessageBuilder, GetCurrentBranchMessage,
+    };
+    pub use super::current_head::{
+        CurrentHeadMessage, CurrentHeadMessageBuilder, GetCurrentHeadMessage,
+    };
     pub use super::deactivate::DeactivateMessage;
     pub use super::mempool::Mempool;
     pub use super::metadata::MetadataMessage;
@@ -38,7 +44,9 @@ pub mod prelude {
         GetOperationsForBlocksMessage, OperationsForBlock, OperationsForBlocksMessage, Path,
         PathLeft, PathRight,
     };
-    pub use super::peer::{PeerMessage, PeerMessageResponse};
+    pub use super::peer::{
+        PeerMessage, PeerMessageKind, PeerMessageResponse, PeerMessageWeightClass,
+    };
     pub use super::protocol::{Component, GetProtocolsMessage, Protocol, ProtocolMessage};
     pub use super::swap::SwapMessage;
     pub use super::version::NetworkVersion;
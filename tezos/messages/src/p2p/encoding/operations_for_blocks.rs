@@ -306,15 +306,16 @@ fn bin_write_path_items(
 
 impl BinWriter for Path {
     fn bin_write(&self, out: &mut Vec<u8>) -> tezos_encoding::enc::BinResult {
-        if self.0.len() > MAX_PASS_MERKLE_DEPTH {
-            return Err(BinError::custom(format!(
-                "Merkle path size {} is greater than max {}",
-                self.0.len(),
-                MAX_PASS_MERKLE_DEPTH
-            )));
+        if self.0.len() > MAX_PASS_MERKLE_DEPTH && tezos_encoding::enc::check_bounds() {
+            return Err(BinError::bound_error(MAX_PASS_MERKLE_DEPTH, self.0.len()));
         }
         bin_write_path_items(self.0.as_slice(), out)
     }
+
+    fn bin_size(&self) -> usize {
+        // a tag for each item and the terminating one, plus the hash of each item
+        self.0.len() * (1 + HashType::OperationListListHash.size()) + 1
+    }
 }
 
 impl tezos_encoding::generator::Generated for Path {
@@ -323,11 +324,11 @@ impl tezos_encoding::generator::Generated for Path {
         f: &mut F,
     ) -> Box<dyn tezos_encoding::generator::Generator<Item = Self>> {
         Box::new(
-            generator::vec_of_items(
+            generator::mixed_vec_of_items(
                 PathItem::generator(&(prefix.to_string() + "[]"), f),
-                f.size(prefix, Path::encoding().clone(), Encoding::Unit), //generator::values(&[0, 1, MAX_PASS_MERKLE_DEPTH, MAX_PASS_MERKLE_DEPTH+1, MAX_PASS_MERKLE_DEPTH * 2])
+                f.depth(prefix, MAX_PASS_MERKLE_DEPTH),
             )
-            .map(|vec| Self(vec)),
+            .map(Self),
         )
     }
 }
@@ -5,11 +5,12 @@ use getset::Getters;
 use serde::Serialize;
 
 use crate::p2p::{binary_message::SizeFromChunk, encoding::prelude::*, peer_message_size};
+use tezos_encoding::binary_reader::BinaryReaderError;
 use tezos_encoding::enc::BinWriter;
 use tezos_encoding::encoding::HasEncoding;
 use tezos_encoding::nom::NomReader;
 
-use super::limits::MESSAGE_MAX_SIZE;
+use super::limits::*;
 
 #[derive(Serialize, Debug, Clone, HasEncoding, NomReader, BinWriter)]
 #[encoding(tags = "u16")]
@@ -52,6 +53,208 @@ pub enum PeerMessage {
     OperationsForBlocks(OperationsForBlocksMessage),
 }
 
+impl PeerMessage {
+    /// Returns the name and the maximal encoded size (without the tag) of the message
+    /// identified by `tag`, or `None` if the tag is unknown.
+    ///
+    /// These are the per-message limits enforced by OCaml node in `distributed_db_message.ml`,
+    /// on top of the overall [MESSAGE_MAX_SIZE].
+    pub fn max_size_for_tag(tag: u16) -> Option<(&'static str, usize)> {
+        PeerMessageKind::from_tag(tag).map(|kind| (kind.as_str(), kind.max_size()))
+    }
+
+    pub fn kind(&self) -> PeerMessageKind {
+        match self {
+            PeerMessage::Disconnect => PeerMessageKind::Disconnect,
+            PeerMessage::Advertise(_) => PeerMessageKind::Advertise,
+            PeerMessage::SwapRequest(_) => PeerMessageKind::SwapRequest,
+            PeerMessage::SwapAck(_) => PeerMessageKind::SwapAck,
+            PeerMessage::Bootstrap => PeerMessageKind::Bootstrap,
+            PeerMessage::GetCurrentBranch(_) => PeerMessageKind::GetCurrentBranch,
+            PeerMessage::CurrentBranch(_) => PeerMessageKind::CurrentBranch,
+            PeerMessage::Deactivate(_) => PeerMessageKind::Deactivate,
+            PeerMessage::GetCurrentHead(_) => PeerMessageKind::GetCurrentHead,
+            PeerMessage::CurrentHead(_) => PeerMessageKind::CurrentHead,
+            PeerMessage::GetBlockHeaders(_) => PeerMessageKind::GetBlockHeaders,
+            PeerMessage::BlockHeader(_) => PeerMessageKind::BlockHeader,
+            PeerMessage::GetOperations(_) => PeerMessageKind::GetOperations,
+            PeerMessage::Operation(_) => PeerMessageKind::Operation,
+            PeerMessage::GetProtocols(_) => PeerMessageKind::GetProtocols,
+            PeerMessage::Protocol(_) => PeerMessageKind::Protocol,
+            PeerMessage::GetOperationsForBlocks(_) => PeerMessageKind::GetOperationsForBlocks,
+            PeerMessage::OperationsForBlocks(_) => PeerMessageKind::OperationsForBlocks,
+        }
+    }
+
+    pub fn weight_class(&self) -> PeerMessageWeightClass {
+        self.kind().weight_class()
+    }
+}
+
+/// Kind of a [PeerMessage], without its content.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PeerMessageKind {
+    Disconnect,
+    Advertise,
+    SwapRequest,
+    SwapAck,
+    Bootstrap,
+    GetCurrentBranch,
+    CurrentBranch,
+    Deactivate,
+    GetCurrentHead,
+    CurrentHead,
+    GetBlockHeaders,
+    BlockHeader,
+    GetOperations,
+    Operation,
+    GetProtocols,
+    Protocol,
+    GetOperationsForBlocks,
+    OperationsForBlocks,
+}
+
+/// Class of a message, for prioritization and rate limiting.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PeerMessageWeightClass {
+    /// Management of the connection and of the known peers
+    Control,
+    /// Propagation of the current heads and of the mempool operations
+    Consensus,
+    /// Download of the history, potentially large
+    Bulk,
+}
+
+impl PeerMessageKind {
+    /// All the kinds, in the order of their indexes.
+    pub const ALL: [PeerMessageKind; 18] = [
+        PeerMessageKind::Disconnect,
+        PeerMessageKind::Advertise,
+        PeerMessageKind::SwapRequest,
+        PeerMessageKind::SwapAck,
+        PeerMessageKind::Bootstrap,
+        PeerMessageKind::GetCurrentBranch,
+        PeerMessageKind::CurrentBranch,
+        PeerMessageKind::Deactivate,
+        PeerMessageKind::GetCurrentHead,
+        PeerMessageKind::CurrentHead,
+        PeerMessageKind::GetBlockHeaders,
+        PeerMessageKind::BlockHeader,
+        PeerMessageKind::GetOperations,
+        PeerMessageKind::Operation,
+        PeerMessageKind::GetProtocols,
+        PeerMessageKind::Protocol,
+        PeerMessageKind::GetOperationsForBlocks,
+        PeerMessageKind::OperationsForBlocks,
+    ];
+
+    /// Index of the kind, from `0` to `PeerMessageKind::ALL.len() - 1`.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn from_tag(tag: u16) -> Option<Self> {
+        let kind = match tag {
+            0x01 => PeerMessageKind::Disconnect,
+            0x02 => PeerMessageKind::Bootstrap,
+            0x03 => PeerMessageKind::Advertise,
+            0x04 => PeerMessageKind::SwapRequest,
+            0x05 => PeerMessageKind::SwapAck,
+            0x10 => PeerMessageKind::GetCurrentBranch,
+            0x11 => PeerMessageKind::CurrentBranch,
+            0x12 => PeerMessageKind::Deactivate,
+            0x13 => PeerMessageKind::GetCurrentHead,
+            0x14 => PeerMessageKind::CurrentHead,
+            0x20 => PeerMessageKind::GetBlockHeaders,
+            0x21 => PeerMessageKind::BlockHeader,
+            0x30 => PeerMessageKind::GetOperations,
+            0x31 => PeerMessageKind::Operation,
+            0x40 => PeerMessageKind::GetProtocols,
+            0x41 => PeerMessageKind::Protocol,
+            0x60 => PeerMessageKind::GetOperationsForBlocks,
+            0x61 => PeerMessageKind::OperationsForBlocks,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PeerMessageKind::Disconnect => "Disconnect",
+            PeerMessageKind::Advertise => "Advertise",
+            PeerMessageKind::SwapRequest => "SwapRequest",
+            PeerMessageKind::SwapAck => "SwapAck",
+            PeerMessageKind::Bootstrap => "Bootstrap",
+            PeerMessageKind::GetCurrentBranch => "GetCurrentBranch",
+            PeerMessageKind::CurrentBranch => "CurrentBranch",
+            PeerMessageKind::Deactivate => "Deactivate",
+            PeerMessageKind::GetCurrentHead => "GetCurrentHead",
+            PeerMessageKind::CurrentHead => "CurrentHead",
+            PeerMessageKind::GetBlockHeaders => "GetBlockHeaders",
+            PeerMessageKind::BlockHeader => "BlockHeader",
+            PeerMessageKind::GetOperations => "GetOperations",
+            PeerMessageKind::Operation => "Operation",
+            PeerMessageKind::GetProtocols => "GetProtocols",
+            PeerMessageKind::Protocol => "Protocol",
+            PeerMessageKind::GetOperationsForBlocks => "GetOperationsForBlocks",
+            PeerMessageKind::OperationsForBlocks => "OperationsForBlocks",
+        }
+    }
+
+    /// Maximal encoded size of the message, without the tag.
+    pub fn max_size(self) -> usize {
+        match self {
+            PeerMessageKind::Disconnect => 0,
+            PeerMessageKind::Advertise => ADVERTISE_MESSAGE_MAX_SIZE,
+            PeerMessageKind::SwapRequest => SWAP_MESSAGE_MAX_SIZE,
+            PeerMessageKind::SwapAck => SWAP_MESSAGE_MAX_SIZE,
+            PeerMessageKind::Bootstrap => 0,
+            PeerMessageKind::GetCurrentBranch => CHAIN_ID_MESSAGE_MAX_SIZE,
+            PeerMessageKind::CurrentBranch => CURRENT_BRANCH_MESSAGE_MAX_SIZE,
+            PeerMessageKind::Deactivate => CHAIN_ID_MESSAGE_MAX_SIZE,
+            PeerMessageKind::GetCurrentHead => CHAIN_ID_MESSAGE_MAX_SIZE,
+            PeerMessageKind::CurrentHead => CURRENT_HEAD_MESSAGE_MAX_SIZE,
+            PeerMessageKind::GetBlockHeaders => GET_BLOCK_HEADERS_MESSAGE_MAX_SIZE,
+            PeerMessageKind::BlockHeader => BLOCK_HEADER_MESSAGE_MAX_SIZE,
+            PeerMessageKind::GetOperations => GET_OPERATIONS_MESSAGE_MAX_SIZE,
+            PeerMessageKind::Operation => OPERATION_MESSAGE_MAX_SIZE,
+            PeerMessageKind::GetProtocols => GET_PROTOCOLS_MESSAGE_MAX_SIZE,
+            PeerMessageKind::Protocol => PROTOCOL_MESSAGE_MAX_SIZE,
+            PeerMessageKind::GetOperationsForBlocks => GET_OPERATIONS_FOR_BLOCKS_MESSAGE_MAX_SIZE,
+            PeerMessageKind::OperationsForBlocks => OPERATIONS_FOR_BLOCKS_MESSAGE_MAX_SIZE,
+        }
+    }
+
+    pub fn weight_class(self) -> PeerMessageWeightClass {
+        match self {
+            PeerMessageKind::Disconnect
+            | PeerMessageKind::Advertise
+            | PeerMessageKind::SwapRequest
+            | PeerMessageKind::SwapAck
+            | PeerMessageKind::Bootstrap
+            | PeerMessageKind::Deactivate => PeerMessageWeightClass::Control,
+            PeerMessageKind::GetCurrentBranch
+            | PeerMessageKind::CurrentBranch
+            | PeerMessageKind::GetCurrentHead
+            | PeerMessageKind::CurrentHead
+            | PeerMessageKind::GetOperations
+            | PeerMessageKind::Operation => PeerMessageWeightClass::Consensus,
+            PeerMessageKind::GetBlockHeaders
+            | PeerMessageKind::BlockHeader
+            | PeerMessageKind::GetProtocols
+            | PeerMessageKind::Protocol
+            | PeerMessageKind::GetOperationsForBlocks
+            | PeerMessageKind::OperationsForBlocks => PeerMessageWeightClass::Bulk,
+        }
+    }
+}
+
+impl std::fmt::Display for PeerMessageKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 #[derive(Clone, Serialize, Debug, Getters, HasEncoding, NomReader, BinWriter)]
 #[encoding(dynamic = "MESSAGE_MAX_SIZE")]
 pub struct PeerMessageResponse {
@@ -101,10 +304,45 @@ into_peer_message!(ProtocolMessage, Protocol);
 into_peer_message!(GetOperationsMessage, GetOperations);
 into_peer_message!(OperationMessage, Operation);
 
+/// Size of the dynamic size prefix of [PeerMessageResponse].
+const SIZE_FIELD_BYTES: usize = 4;
+
+/// Size of the [PeerMessage] tag.
+const TAG_FIELD_BYTES: usize = 2;
+
 impl SizeFromChunk for PeerMessageResponse {
-    fn size_from_chunk(
-        bytes: impl AsRef<[u8]>,
-    ) -> Result<usize, tezos_encoding::binary_reader::BinaryReaderError> {
-        peer_message_size(bytes.as_ref()).map(|s| s + 4)
+    /// Returns the size of the message, failing with [BinaryReaderError::TooLarge] if
+    /// it exceeds [MESSAGE_MAX_SIZE], or the limit for the message type (see
+    /// [PeerMessage::max_size_for_tag]), so oversized messages are rejected before they
+    /// are buffered and decoded.
+    fn size_from_chunk(bytes: impl AsRef<[u8]>) -> Result<usize, BinaryReaderError> {
+        let bytes = bytes.as_ref();
+        let size = peer_message_size(bytes)?;
+
+        // Checked first, the tag may be unknown or not received yet
+        let max_size = MESSAGE_MAX_SIZE - SIZE_FIELD_BYTES;
+        if size > max_size {
+            return Err(BinaryReaderError::TooLarge {
+                message: "PeerMessage",
+                size,
+                max_size,
+            });
+        }
+
+        if let Some(tag) = bytes.get(SIZE_FIELD_BYTES..SIZE_FIELD_BYTES + TAG_FIELD_BYTES) {
+            let tag = u16::from_be_bytes([tag[0], tag[1]]);
+            if let Some((message, max_size)) = PeerMessage::max_size_for_tag(tag) {
+                let size = size.saturating_sub(TAG_FIELD_BYTES);
+                if size > max_size {
+                    return Err(BinaryReaderError::TooLarge {
+                        message,
+                        size,
+                        max_size,
+                    });
+                }
+            }
+        }
+
+        Ok(size + SIZE_FIELD_BYTES)
     }
 }
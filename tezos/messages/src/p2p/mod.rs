@@ -10,6 +10,9 @@ use self::binary_message::complete_input;
 #[macro_use]
 pub mod encoding;
 pub mod binary_message;
+pub mod chunk_crypto;
+pub mod corpus;
+pub mod decoder;
 
 pub fn peer_message_size(bytes: impl AsRef<[u8]>) -> Result<usize, BinaryReaderError> {
     let size = complete_input(size, bytes.as_ref())?;
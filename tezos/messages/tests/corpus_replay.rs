@@ -0,0 +1,85 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use tezos_encoding::{
+    corpus,
+    encoding::Encoding,
+    generator::{value, values, Generated, Generator, GeneratorFactory},
+};
+use tezos_messages::p2p::{corpus::replay_corpus, encoding::prelude::*};
+
+/// Generates bounded lists up to one element over their bounds, default generators otherwise.
+struct BoundsFactory;
+
+macro_rules! int_factory {
+    ($ty:ident) => {
+        fn $ty(&mut self, _field: &str) -> Box<dyn Generator<Item = $ty>> {
+            Box::new(value(0))
+        }
+    };
+}
+
+impl GeneratorFactory for BoundsFactory {
+    fn bool(&mut self, _field: &str) -> Box<dyn Generator<Item = bool>> {
+        Box::new(value(false))
+    }
+
+    int_factory!(u8);
+    int_factory!(u16);
+    int_factory!(u32);
+    int_factory!(u64);
+    int_factory!(i8);
+    int_factory!(i16);
+    int_factory!(i32);
+    int_factory!(i64);
+
+    fn size(
+        &mut self,
+        _field: &str,
+        list_encoding: Encoding,
+        _item_encoding: Encoding,
+    ) -> Box<dyn Generator<Item = usize>> {
+        match list_encoding {
+            Encoding::BoundedList(max, _) => Box::new(values([0, 1, max, max + 1])),
+            _ => Box::new(value(1)),
+        }
+    }
+}
+
+#[test]
+fn advertise_corpus() {
+    let dir = std::env::temp_dir().join(format!("advertise-corpus-{}", std::process::id()));
+
+    let messages = AdvertiseMessage::generator("", &mut BoundsFactory)
+        .iter()
+        .map(PeerMessageResponse::from);
+    let stats = corpus::export(&dir, messages).unwrap();
+    assert!(stats.valid > 0);
+    assert!(stats.invalid > 0);
+    assert_eq!(stats.skipped, 0);
+
+    let mismatches = replay_corpus::<PeerMessageResponse>(&dir).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(mismatches.is_empty(), "{:?}", mismatches);
+}
+
+#[test]
+fn operations_for_blocks_corpus() {
+    let dir = std::env::temp_dir().join(format!(
+        "operations-for-blocks-corpus-{}",
+        std::process::id()
+    ));
+
+    let messages = OperationsForBlocksMessage::generator("", &mut BoundsFactory)
+        .iter()
+        .map(PeerMessageResponse::from);
+    let stats = corpus::export(&dir, messages).unwrap();
+    // paths deeper than the limit and too long operations
+    assert!(stats.valid > 0);
+    assert!(stats.invalid > 0);
+    assert_eq!(stats.skipped, 0);
+
+    let mismatches = replay_corpus::<PeerMessageResponse>(&dir).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(mismatches.is_empty(), "{:?}", mismatches);
+}
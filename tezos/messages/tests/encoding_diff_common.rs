@@ -7,7 +7,9 @@ use tezos_encoding::enc::{BinError, BinWriter};
 
 pub fn encode_bin<T: BinWriter>(msg: &T) -> Result<Vec<u8>, BinError> {
     let mut result = Vec::new();
-    msg.bin_write(&mut result).map(|_| result)
+    msg.bin_write(&mut result)?;
+    assert_eq!(msg.bin_size(), result.len(), "size computed from the encoding");
+    Ok(result)
 }
 
 pub fn diff_encodings<T>(msg: &T)
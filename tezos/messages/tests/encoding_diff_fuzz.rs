@@ -440,7 +440,7 @@ impl TestFeedback for LimitCoverageFeedback {
         if let Some(err) = errors.next() {
             if let Some(BinErrorKind::FieldError(field_name)) = errors.next() {
                 match err {
-                    BinErrorKind::SizeError(_exp, _act) => {
+                    BinErrorKind::SizeError(_exp, _act) | BinErrorKind::BoundError(_exp, _act) => {
                         self.covered_fields.insert(field_name.to_string());
                     }
                     BinErrorKind::CustomError(_err) => {
@@ -15,3 +15,18 @@ fn can_deserialize() -> Result<(), Error> {
     );
     Ok(assert_eq!("000008c387fa065a181d45d47a9b78ddc77e92a881779ff2cbabbf9646eade4bf1405a08e00b725ed849eea46953b10b5cdebc518e6fd47e69b82d2ca18c4cf6d2f312dd08", &hex::encode(&operation.data())))
 }
+
+#[test]
+fn can_report_error_path() -> Result<(), Error> {
+    // the second operation hash is truncated
+    let mut message_bytes = vec![0, 0, 0, 40];
+    message_bytes.extend_from_slice(&[1; 40]);
+
+    let error = GetOperationsMessage::from_bytes(message_bytes)
+        .expect_err("Truncated operation hash is expected to fail");
+    assert_eq!(error.path(), Some("get_operations[1]"));
+    assert!(error
+        .to_string()
+        .starts_with("Failed to decode `get_operations[1]`: "));
+    Ok(())
+}
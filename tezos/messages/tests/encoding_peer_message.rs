@@ -2,7 +2,10 @@
 // SPDX-License-Identifier: MIT
 
 use anyhow::Error;
-use tezos_messages::p2p::{binary_message::BinaryRead, encoding::prelude::*};
+use tezos_messages::p2p::{
+    binary_message::{BinaryRead, BinaryWrite},
+    encoding::prelude::*,
+};
 
 #[test]
 fn can_t_deserialize_empty_message() -> Result<(), Error> {
@@ -19,3 +22,14 @@ fn can_t_deserialize_message_list() -> Result<(), Error> {
     let _err = PeerMessageResponse::from_bytes(bytes).expect_err("Error is expected");
     Ok(())
 }
+
+#[test]
+fn estimated_binary_size() -> Result<(), Error> {
+    let message: PeerMessageResponse = PeerMessage::Bootstrap.into();
+    assert_eq!(message.estimated_binary_size(), message.as_bytes()?.len());
+
+    let message: PeerMessageResponse =
+        AdvertiseMessage::new(&["127.0.0.1:9732".parse()?, "[::1]:9732".parse()?]).into();
+    assert_eq!(message.estimated_binary_size(), message.as_bytes()?.len());
+    Ok(())
+}
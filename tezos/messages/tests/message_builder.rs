@@ -0,0 +1,131 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::convert::TryInto;
+
+use crypto::hash::{BlockHash, ChainId, OperationHash};
+use tezos_messages::p2p::encoding::{
+    limits::{CURRENT_BRANCH_HISTORY_MAX_LENGTH, MEMPOOL_MAX_OPERATIONS},
+    prelude::*,
+};
+
+fn chain_id(byte: u8) -> ChainId {
+    vec![byte; 4].try_into().unwrap()
+}
+
+fn block_header() -> BlockHeader {
+    BlockHeaderBuilder::default()
+        .level(1)
+        .proto(1)
+        .predecessor(vec![0; 32].try_into().unwrap())
+        .timestamp(0)
+        .validation_pass(4)
+        .operations_hash(vec![0; 32].try_into().unwrap())
+        .fitness(vec![])
+        .context(vec![0; 32].try_into().unwrap())
+        .protocol_data(vec![])
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn current_head_builder() {
+    let message = CurrentHeadMessageBuilder::new()
+        .chain_id(chain_id(1))
+        .expected_chain_id(chain_id(1))
+        .current_block_header(block_header())
+        .build()
+        .unwrap();
+    assert_eq!(message.chain_id(), &chain_id(1));
+    assert!(message.current_mempool().is_empty());
+
+    assert_eq!(
+        CurrentHeadMessageBuilder::new()
+            .chain_id(chain_id(1))
+            .build()
+            .unwrap_err(),
+        MessageBuilderError::MissingField {
+            field: "current_block_header"
+        }
+    );
+
+    assert!(matches!(
+        CurrentHeadMessageBuilder::new()
+            .chain_id(chain_id(1))
+            .expected_chain_id(chain_id(2))
+            .current_block_header(block_header())
+            .build(),
+        Err(MessageBuilderError::ChainIdMismatch { .. })
+    ));
+}
+
+#[test]
+fn current_head_builder_mempool_limits() {
+    let operation: OperationHash = vec![0; 32].try_into().unwrap();
+    let operations = vec![operation; MEMPOOL_MAX_OPERATIONS + 1];
+
+    assert_eq!(
+        CurrentHeadMessageBuilder::new()
+            .chain_id(chain_id(1))
+            .current_block_header(block_header())
+            .current_mempool(Mempool::new(vec![], operations.clone()))
+            .build()
+            .unwrap_err(),
+        MessageBuilderError::TooManyOperations {
+            list: "pending",
+            len: MEMPOOL_MAX_OPERATIONS + 1,
+            max: MEMPOOL_MAX_OPERATIONS,
+        }
+    );
+
+    // each list is within its limit, but not the whole mempool
+    let half = operations[..MEMPOOL_MAX_OPERATIONS / 2 + 1].to_vec();
+    assert!(matches!(
+        CurrentHeadMessageBuilder::new()
+            .chain_id(chain_id(1))
+            .current_block_header(block_header())
+            .current_mempool(Mempool::new(half.clone(), half))
+            .build(),
+        Err(MessageBuilderError::TooLarge {
+            what: "mempool",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn current_branch_builder() {
+    let block_hash: BlockHash = vec![0; 32].try_into().unwrap();
+
+    let message = CurrentBranchMessageBuilder::new()
+        .chain_id(chain_id(1))
+        .current_head(block_header())
+        .history(vec![block_hash.clone(); CURRENT_BRANCH_HISTORY_MAX_LENGTH])
+        .build()
+        .unwrap();
+    assert_eq!(
+        message.current_branch().history().len(),
+        CURRENT_BRANCH_HISTORY_MAX_LENGTH
+    );
+
+    assert_eq!(
+        CurrentBranchMessageBuilder::new()
+            .chain_id(chain_id(1))
+            .current_head(block_header())
+            .history(vec![block_hash; CURRENT_BRANCH_HISTORY_MAX_LENGTH + 1])
+            .build()
+            .unwrap_err(),
+        MessageBuilderError::HistoryTooLong {
+            len: CURRENT_BRANCH_HISTORY_MAX_LENGTH + 1,
+            max: CURRENT_BRANCH_HISTORY_MAX_LENGTH,
+        }
+    );
+
+    assert_eq!(
+        CurrentBranchMessageBuilder::new()
+            .current_head(block_header())
+            .build()
+            .unwrap_err(),
+        MessageBuilderError::MissingField { field: "chain_id" }
+    );
+}
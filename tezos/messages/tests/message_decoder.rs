@@ -0,0 +1,113 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::{net::SocketAddr, task::Poll};
+
+use tezos_encoding::binary_reader::BinaryReaderError;
+use tezos_messages::p2p::{
+    binary_message::BinaryWrite,
+    decoder::PeerMessageDecoder,
+    encoding::{advertise::AdvertiseMessage, peer::PeerMessageResponse},
+};
+
+fn advertise_bytes(count: u16) -> Vec<u8> {
+    let addresses: Vec<SocketAddr> = (0..count)
+        .map(|port| SocketAddr::from(([127, 0, 0, 1], port)))
+        .collect();
+    PeerMessageResponse::from(AdvertiseMessage::new(&addresses))
+        .as_bytes()
+        .unwrap()
+}
+
+#[test]
+fn decode_single_chunk() {
+    let bytes = advertise_bytes(2);
+    let mut decoder = PeerMessageDecoder::new();
+
+    match decoder.feed(&bytes) {
+        Poll::Ready(Ok(message)) => assert_eq!(message.as_bytes().unwrap(), bytes),
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert!(!decoder.is_partial());
+}
+
+#[test]
+fn decode_multiple_chunks() {
+    let bytes = advertise_bytes(50);
+    let mut decoder = PeerMessageDecoder::new();
+
+    // two messages in a row, the decoder is reused
+    for _ in 0..2 {
+        let mut chunks = bytes.chunks(100).peekable();
+        while let Some(chunk) = chunks.next() {
+            match decoder.feed(chunk) {
+                Poll::Pending => {
+                    assert!(chunks.peek().is_some());
+                    assert!(decoder.is_partial());
+                }
+                Poll::Ready(Ok(message)) => {
+                    assert!(chunks.peek().is_none());
+                    assert_eq!(message.as_bytes().unwrap(), bytes);
+                }
+                Poll::Ready(Err(e)) => panic!("unexpected error: {}", e),
+            }
+        }
+        assert!(!decoder.is_partial());
+        assert_eq!(decoder.buffered(), 0);
+    }
+}
+
+#[test]
+fn decode_trailing_bytes() {
+    let mut bytes = advertise_bytes(50);
+    bytes.push(0);
+    let mut decoder = PeerMessageDecoder::new();
+
+    assert!(decoder.feed(&bytes[..100]).is_pending());
+    assert!(matches!(decoder.feed(&bytes[100..]), Poll::Ready(Err(_))));
+    assert!(!decoder.is_partial());
+}
+
+#[test]
+fn decode_too_large() {
+    // advertise message (tag 0x03) announcing 1MB
+    let mut bytes = 1_000_000u32.to_be_bytes().to_vec();
+    bytes.extend_from_slice(&[0x00, 0x03]);
+    let mut decoder = PeerMessageDecoder::new();
+
+    assert!(matches!(
+        decoder.feed(&bytes),
+        Poll::Ready(Err(BinaryReaderError::TooLarge { .. }))
+    ));
+    assert!(!decoder.is_partial());
+}
+
+#[test]
+fn decode_unknown_tag() {
+    // unknown tag, without its own size limit, announcing 1MB
+    let mut bytes = 1_000_000u32.to_be_bytes().to_vec();
+    bytes.extend_from_slice(&[0xff, 0xff]);
+    let mut decoder = PeerMessageDecoder::new();
+
+    assert!(decoder.feed(&bytes).is_pending());
+    assert!(decoder.is_partial());
+    assert_eq!(decoder.buffered(), bytes.len());
+
+    decoder.reset();
+    assert!(!decoder.is_partial());
+}
+
+#[test]
+fn decode_unknown_tag_too_large() {
+    // unknown tag announcing 4GB, refused before anything is buffered
+    let mut bytes = u32::MAX.to_be_bytes().to_vec();
+    bytes.extend_from_slice(&[0xff, 0xff]);
+    let mut decoder = PeerMessageDecoder::new();
+
+    assert!(matches!(
+        decoder.feed(&bytes),
+        Poll::Ready(Err(BinaryReaderError::TooLarge { .. }))
+    ));
+    assert!(!decoder.is_partial());
+    assert_eq!(decoder.buffered(), 0);
+}
@@ -0,0 +1,128 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use tezos_encoding::{
+    binary_reader::BinaryReaderError,
+    encoding::{Encoding, HasEncoding},
+};
+use tezos_messages::p2p::{
+    binary_message::SizeFromChunk,
+    encoding::{
+        limits::MESSAGE_MAX_SIZE,
+        peer::{PeerMessage, PeerMessageKind, PeerMessageResponse},
+    },
+};
+
+mod message_limit;
+use message_limit::*;
+
+#[test]
+fn per_message_limits_cover_encodings() {
+    let tags = match PeerMessage::encoding() {
+        Encoding::Tags(_, tags) => tags,
+        encoding => panic!("unexpected encoding for PeerMessage: {:?}", encoding),
+    };
+
+    for tag in tags.tags() {
+        let (name, max_size) = PeerMessage::max_size_for_tag(tag.get_id())
+            .unwrap_or_else(|| panic!("no size limit for {}", tag.get_variant()));
+        assert_eq!(name, tag.get_variant());
+
+        match get_max_size(tag.get_encoding()) {
+            Limit::Fixed(size) | Limit::UpTo(size) => assert!(
+                size <= max_size,
+                "{}: encoding allows {} bytes, limit is {}",
+                name,
+                size,
+                max_size
+            ),
+            Limit::Var => panic!("{}: encoding is not limited", name),
+        }
+    }
+}
+
+#[test]
+fn kinds_cover_tags() {
+    let tags = match PeerMessage::encoding() {
+        Encoding::Tags(_, tags) => tags,
+        encoding => panic!("unexpected encoding for PeerMessage: {:?}", encoding),
+    };
+
+    assert_eq!(tags.tags().count(), PeerMessageKind::ALL.len());
+    for tag in tags.tags() {
+        let kind = PeerMessageKind::from_tag(tag.get_id())
+            .unwrap_or_else(|| panic!("no kind for {}", tag.get_variant()));
+        assert_eq!(kind.as_str(), tag.get_variant());
+        assert_eq!(PeerMessageKind::ALL[kind.index()], kind);
+    }
+}
+
+#[test]
+fn size_from_chunk_rejects_too_large() {
+    // GetCurrentHead, claiming a body of 1000 bytes
+    let mut chunk = (1000u32 + 2).to_be_bytes().to_vec();
+    chunk.extend_from_slice(&0x13u16.to_be_bytes());
+    chunk.extend_from_slice(&[0; 4]);
+
+    match PeerMessageResponse::size_from_chunk(&chunk) {
+        Err(BinaryReaderError::TooLarge {
+            message,
+            size,
+            max_size,
+        }) => {
+            assert_eq!(message, "GetCurrentHead");
+            assert_eq!(size, 1000);
+            assert_eq!(max_size, 4);
+        }
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
+
+#[test]
+fn size_from_chunk_accepts_within_limit() {
+    // GetCurrentHead with a chain id
+    let mut chunk = (4u32 + 2).to_be_bytes().to_vec();
+    chunk.extend_from_slice(&0x13u16.to_be_bytes());
+    chunk.extend_from_slice(&[0; 4]);
+
+    assert_eq!(PeerMessageResponse::size_from_chunk(&chunk).unwrap(), 10);
+}
+
+#[test]
+fn size_from_chunk_ignores_unknown_tags() {
+    let mut chunk = (1000u32 + 2).to_be_bytes().to_vec();
+    chunk.extend_from_slice(&0xffu16.to_be_bytes());
+
+    assert_eq!(PeerMessageResponse::size_from_chunk(&chunk).unwrap(), 1006);
+}
+
+#[test]
+fn size_from_chunk_rejects_above_message_max_size() {
+    // unknown tag, announcing 4GB
+    let mut chunk = u32::MAX.to_be_bytes().to_vec();
+    chunk.extend_from_slice(&0xffu16.to_be_bytes());
+    assert!(matches!(
+        PeerMessageResponse::size_from_chunk(&chunk),
+        Err(BinaryReaderError::TooLarge {
+            message: "PeerMessage",
+            ..
+        })
+    ));
+
+    // the tag is not in the chunk
+    let chunk = u32::MAX.to_be_bytes();
+    assert!(matches!(
+        PeerMessageResponse::size_from_chunk(&chunk),
+        Err(BinaryReaderError::TooLarge { .. })
+    ));
+
+    let max_size = (MESSAGE_MAX_SIZE - 4) as u32;
+    let mut chunk = max_size.to_be_bytes().to_vec();
+    chunk.extend_from_slice(&0xffu16.to_be_bytes());
+    assert_eq!(
+        PeerMessageResponse::size_from_chunk(&chunk).unwrap(),
+        MESSAGE_MAX_SIZE
+    );
+    let chunk = (max_size + 1).to_be_bytes();
+    assert!(PeerMessageResponse::size_from_chunk(&chunk).is_err());
+}
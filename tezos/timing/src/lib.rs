@@ -119,6 +119,53 @@ pub struct RepositoryMemoryUsage {
     pub gc_npending_free_ids: usize,
     /// Number of shapes
     pub nshapes: usize,
+    /// Bytes occupied by the hashes store
+    pub hashes_bytes: usize,
+    /// Bytes occupied by the shapes of the directories
+    pub shapes_bytes: usize,
+    /// Bytes occupied by the string interner of the repository
+    pub strings_bytes: usize,
+}
+
+/// Bytes occupied by each subsystem of the context, see [`ContextMemoryUsage::breakdown`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ContextMemoryBreakdown {
+    /// Hashes store of the repository
+    pub hashes: usize,
+    /// Values store of the repository, including its containers
+    pub values: usize,
+    /// Shapes of the directories
+    pub shapes: usize,
+    /// String interners, of the repository and of the working tree
+    pub strings: usize,
+    /// Storage of the working tree, without its strings
+    pub working_tree: usize,
+    pub total: usize,
+}
+
+impl ContextMemoryUsage {
+    /// Returns the bytes occupied by each subsystem of the context.
+    pub fn breakdown(&self) -> ContextMemoryBreakdown {
+        let repo = &self.repo;
+        let storage_strings = self.storage.strings.total_bytes;
+
+        let values = repo
+            .total_bytes
+            .saturating_sub(repo.hashes_bytes)
+            .saturating_sub(repo.shapes_bytes)
+            .saturating_sub(repo.strings_bytes);
+        let strings = repo.strings_bytes.saturating_add(storage_strings);
+        let working_tree = self.storage.total_bytes.saturating_sub(storage_strings);
+
+        ContextMemoryBreakdown {
+            hashes: repo.hashes_bytes,
+            values,
+            shapes: repo.shapes_bytes,
+            strings,
+            working_tree,
+            total: repo.total_bytes.saturating_add(self.storage.total_bytes),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -594,6 +641,9 @@ impl Timing {
               repo_npending_free_ids = :repo_npending_free_ids,
               repo_gc_npending_free_ids = :repo_gc_npending_free_ids,
               repo_nshapes = :repo_nshapes,
+              repo_hashes_bytes = :repo_hashes_bytes,
+              repo_shapes_bytes = :repo_shapes_bytes,
+              repo_strings_bytes = :repo_strings_bytes,
               storage_nodes_capacity = :storage_nodes_capacity,
               storage_nodes_length = :storage_nodes_length,
               storage_trees_capacity = :storage_trees_capacity,
@@ -636,6 +686,9 @@ impl Timing {
             ":repo_npending_free_ids": stats.context.repo.npending_free_ids,
             ":repo_gc_npending_free_ids": stats.context.repo.gc_npending_free_ids,
             ":repo_nshapes": stats.context.repo.nshapes,
+            ":repo_hashes_bytes": stats.context.repo.hashes_bytes,
+            ":repo_shapes_bytes": stats.context.repo.shapes_bytes,
+            ":repo_strings_bytes": stats.context.repo.strings_bytes,
             ":storage_nodes_length": stats.context.storage.nodes_len,
             ":storage_nodes_capacity": stats.context.storage.nodes_cap,
             ":storage_trees_length": stats.context.storage.directories_len,
@@ -22,6 +22,9 @@ CREATE TABLE IF NOT EXISTS blocks (
   repo_npending_free_ids INTEGER,
   repo_gc_npending_free_ids INTEGER,
   repo_nshapes INTEGER,
+  repo_hashes_bytes INTEGER,
+  repo_shapes_bytes INTEGER,
+  repo_strings_bytes INTEGER,
   storage_nodes_length INTEGER,
   storage_nodes_capacity INTEGER,
   storage_trees_length INTEGER,
@@ -14,6 +14,7 @@ use strum_macros::IntoStaticStr;
 use thiserror::Error;
 
 use crypto::hash::{ChainId, ContextHash, ProtocolHash};
+use ipc::version::{IpcProtocol, ProtocolVersion};
 use ipc::*;
 use tezos_api::environment::TezosEnvironmentConfiguration;
 use tezos_api::ffi::*;
@@ -39,6 +40,10 @@ lazy_static! {
     static ref AT_LEAST_ONE_WRITE_PROTOCOL_CONTEXT_WAS_SUCCESS_AT_FIRST_LOCK: Arc<(Mutex<bool>, Condvar)> = Arc::new((Mutex::new(false), Condvar::new()));
 }
 
+/// Protocol of the IPC channel between the node and the protocol runner.
+pub const PROTOCOL_RUNNER_IPC_PROTOCOL: IpcProtocol =
+    IpcProtocol::new("tezedge-protocol-runner", ProtocolVersion::new(1, 0, 0));
+
 /// This command message is generated by tezedge node and is received by the protocol runner.
 #[derive(Serialize, Deserialize, Debug, IntoStaticStr)]
 enum ProtocolMessage {
@@ -151,7 +156,8 @@ pub fn process_protocol_commands<Proto: ProtocolApi, P: AsRef<Path>, SDC: Fn(&Lo
     log: &Logger,
     shutdown_callback: SDC,
 ) -> Result<(), IpcError> {
-    let ipc_client: IpcClient<ProtocolMessage, NodeMessage> = IpcClient::new(socket_path);
+    let ipc_client: IpcClient<ProtocolMessage, NodeMessage> =
+        IpcClient::new(socket_path).with_protocol(PROTOCOL_RUNNER_IPC_PROTOCOL);
     let (mut rx, mut tx) = ipc_client.connect()?;
     loop {
         let cmd = rx.receive()?;
@@ -506,7 +512,7 @@ impl IpcCmdServer {
     /// Create new IPC endpoint
     pub fn try_new(configuration: ProtocolEndpointConfiguration) -> Result<Self, IpcError> {
         Ok(IpcCmdServer(
-            IpcServer::bind_path(&temp_sock())?,
+            IpcServer::bind_path(&temp_sock())?.with_protocol(PROTOCOL_RUNNER_IPC_PROTOCOL),
             configuration,
         ))
     }